@@ -9,15 +9,15 @@ use std::collections::HashMap;
 use makepad_widgets::{makepad_micro_serde::*, *};
 use matrix_sdk::ruma::{OwnedRoomId, RoomId};
 use crate::{
-    avatar_cache::clear_avatar_cache, home::{
-        main_desktop_ui::MainDesktopUiAction, new_message_context_menu::NewMessageContextMenuWidgetRefExt, room_screen::{clear_timeline_states, MessageAction}, rooms_list::{clear_all_invited_rooms, enqueue_rooms_list_update, RoomsListAction, RoomsListRef, RoomsListUpdate}
+    avatar_cache::{self, clear_avatar_cache}, home::{
+        account_switcher::AccountSwitchAction, main_desktop_ui::MainDesktopUiAction, new_message_context_menu::NewMessageContextMenuWidgetRefExt, profile_menu::{ProfileMenuAction, ProfileMenuWidgetRefExt}, room_context_menu::RoomContextMenuWidgetRefExt, room_screen::{clear_timeline_states, MessageAction}, rooms_list::{clear_all_invited_rooms, enqueue_rooms_list_update, RoomsListAction, RoomsListRef, RoomsListUpdate}, unread_totals::UnreadTotals
     }, join_leave_room_modal::{
         JoinLeaveModalKind, JoinLeaveRoomModalAction, JoinLeaveRoomModalWidgetRefExt
-    }, login::login_screen::LoginAction, logout::logout_confirm_modal::{LogoutAction, LogoutConfirmModalAction, LogoutConfirmModalWidgetRefExt}, persistence, profile::user_profile_cache::clear_user_profile_cache, room::BasicRoomDetails, shared::callout_tooltip::{
+    }, login::login_screen::LoginAction, notifications::{self, history::NotificationHistoryModalAction, NotificationAction}, logout::logout_confirm_modal::{LogoutAction, LogoutConfirmModalAction, LogoutConfirmModalWidgetRefExt}, persistence, profile::{presence_cache::clear_presence_cache, user_profile_cache::{self, clear_user_profile_cache}}, room::{room_drafts, BasicRoomDetails}, settings::account_settings::OwnProfileAction, shared::callout_tooltip::{
         CalloutTooltipOptions,
         CalloutTooltipWidgetRefExt,
         TooltipAction,
-    }, sliding_sync::current_user_id, utils::{
+    }, sliding_sync::{current_user_id, submit_async_request, MatrixRequest}, utils::{
         room_name_or_id,
         OwnedRoomIdRon,
     }, verification::VerificationAction, verification_modal::{
@@ -39,6 +39,9 @@ live_design! {
     use crate::logout::logout_confirm_modal::LogoutConfirmModal;
     use crate::shared::popup_list::*;
     use crate::home::new_message_context_menu::*;
+    use crate::home::room_context_menu::*;
+    use crate::home::profile_menu::*;
+    use crate::notifications::history::NotificationHistory;
     use crate::shared::callout_tooltip::CalloutTooltip;
     use link::tsp_link::TspVerificationModal;
 
@@ -101,6 +104,14 @@ live_design! {
                         // Context menus should be shown in front of other UI elements,
                         // but behind verification modals.
                         new_message_context_menu = <NewMessageContextMenu> { }
+                        room_context_menu = <RoomContextMenu> { }
+                        profile_menu = <ProfileMenu> { }
+
+                        notification_history_modal = <Modal> {
+                            content: {
+                                notification_history_inner = <NotificationHistory> {}
+                            }
+                        }
 
                         // Show the logout confirmation modal.
                         logout_confirm_modal = <Modal> {
@@ -175,6 +186,7 @@ impl LiveRegister for App {
         crate::profile::live_design(cx);
         crate::login::live_design(cx);
         crate::logout::live_design(cx);
+        crate::notifications::live_design(cx);
     }
 }
 
@@ -216,8 +228,8 @@ impl MatchEvent for App {
         for action in actions {
             if let Some(logout_modal_action) = action.downcast_ref::<LogoutConfirmModalAction>() {
                 match logout_modal_action {
-                    LogoutConfirmModalAction::Open => {
-                        self.ui.logout_confirm_modal(id!(logout_confirm_modal_inner)).reset_state(cx);
+                    LogoutConfirmModalAction::Open(mode) => {
+                        self.ui.logout_confirm_modal(id!(logout_confirm_modal_inner)).set_mode(cx, mode.clone());
                         self.ui.modal(id!(logout_confirm_modal)).open(cx);
                         continue;
                     },
@@ -239,12 +251,39 @@ impl MatchEvent for App {
                 continue;
             }
 
-            if let Some(LogoutAction::ClearAppState { on_clear_appstate }) = action.downcast_ref() {
-                // Clear user profile cache, invited_rooms timeline states 
-                clear_all_app_state(cx);
-                // Reset all app state to its default.
-                self.app_state = Default::default();
-                on_clear_appstate.notify_one();
+            // Invalidate our own cached profile and avatar such that they're re-fetched.
+            // This must happen here, before the action reaches any widgets that show our profile.
+            if let Some(own_profile_action) = action.downcast_ref::<OwnProfileAction>() {
+                match own_profile_action {
+                    OwnProfileAction::DisplayNameChanged(_) => {}
+                    OwnProfileAction::AvatarChanged { old_avatar_uri, .. } => {
+                        if let Some(old_avatar_uri) = old_avatar_uri {
+                            avatar_cache::invalidate_avatar(cx, old_avatar_uri);
+                        }
+                    }
+                    OwnProfileAction::DisplayNameUpdateFailed(_)
+                    | OwnProfileAction::AvatarUpdateFailed(_) => continue,
+                }
+                if let Some(own_user_id) = current_user_id() {
+                    user_profile_cache::invalidate_user_profile(cx, &own_user_id);
+                }
+                continue;
+            }
+
+            if let Some(switch_action) = action.downcast_ref::<AccountSwitchAction>() {
+                let user_id = match switch_action {
+                    AccountSwitchAction::Switch(user_id) if Some(user_id) == current_user_id().as_ref() => continue,
+                    AccountSwitchAction::Switch(user_id) => Some(user_id.clone()),
+                    AccountSwitchAction::AddAccount => {
+                        self.app_state.logged_in = false;
+                        self.update_login_visibility(cx);
+                        None
+                    }
+                };
+                submit_async_request(MatrixRequest::SwitchAccount {
+                    user_id,
+                    is_desktop: cx.display_context.is_desktop(),
+                });
                 continue;
             }
 
@@ -275,6 +314,7 @@ impl MatchEvent for App {
             if let RoomsListAction::Selected(selected_room) = action.as_widget_action().cast() {
                 // A room has been selected, update the app state and navigate to the main content view.
                 let display_name = room_name_or_id(selected_room.room_name(), selected_room.room_id());
+                notifications::set_selected_room(Some(selected_room.room_id().clone()));
                 self.app_state.selected_room = Some(selected_room);
                 // Set the Stack Navigation header to show the name of the newly-selected room.
                 self.ui
@@ -291,13 +331,52 @@ impl MatchEvent for App {
                 continue;
             }
 
+            // Handle an action requesting to open the context menu for a room in the RoomsList.
+            if let RoomsListAction::OpenRoomContextMenu { details, abs_pos } = action.as_widget_action().cast() {
+                self.ui.callout_tooltip(id!(app_tooltip)).hide(cx);
+                let room_context_menu = self.ui.room_context_menu(id!(room_context_menu));
+                let expected_dimensions = room_context_menu.show(cx, details);
+                // Ensure the context menu does not spill over the window's bounds.
+                let rect = self.ui.window(id!(main_window)).area().rect(cx);
+                let pos_x = min(abs_pos.x, rect.size.x - expected_dimensions.x);
+                let pos_y = min(abs_pos.y, rect.size.y - expected_dimensions.y);
+                room_context_menu.apply_over(cx, live! {
+                    main_content = { margin: { left: (pos_x), top: (pos_y) } }
+                });
+                self.ui.redraw(cx);
+                continue;
+            }
+
+            // Handle an action requesting to open the profile menu from the SpacesDock.
+            if let ProfileMenuAction::Open { abs_pos } = action.as_widget_action().cast() {
+                self.ui.callout_tooltip(id!(app_tooltip)).hide(cx);
+                let profile_menu = self.ui.profile_menu(id!(profile_menu));
+                let expected_dimensions = profile_menu.show(cx);
+                // Ensure the menu does not spill over the window's bounds.
+                let rect = self.ui.window(id!(main_window)).area().rect(cx);
+                let pos_x = min(abs_pos.x, rect.size.x - expected_dimensions.x);
+                let pos_y = min(abs_pos.y, rect.size.y - expected_dimensions.y);
+                profile_menu.apply_over(cx, live! {
+                    main_content = { margin: { left: (pos_x), top: (pos_y) } }
+                });
+                self.ui.redraw(cx);
+                continue;
+            }
+
+            if let RoomsListAction::UnreadTotalsChanged(totals) = action.as_widget_action().cast() {
+                self.update_window_title(cx, &totals);
+                continue;
+            }
+
             // Handle actions that instruct us to update the top-level app state.
             match action.downcast_ref() {
                 Some(AppStateAction::RoomFocused(selected_room)) => {
+                    notifications::set_selected_room(Some(selected_room.room_id().clone()));
                     self.app_state.selected_room = Some(selected_room.clone());
                     continue;
                 }
                 Some(AppStateAction::FocusNone) => {
+                    notifications::set_selected_room(None);
                     self.app_state.selected_room = None;
                     continue;
                 }
@@ -320,6 +399,12 @@ impl MatchEvent for App {
                     cx.action(MainDesktopUiAction::LoadDockFromAppState);
                     continue;
                 }
+                Some(AppStateAction::RestoreRoomDrafts(drafts)) => {
+                    room_drafts::restore_drafts(drafts.clone());
+                    // Redraw the rooms list to show which rooms have drafts.
+                    self.ui.redraw(cx);
+                    continue;
+                }
                 Some(AppStateAction::NavigateToRoom { room_to_close, destination_room }) => {
                     self.navigate_to_room(cx, room_to_close.as_ref(), destination_room);
                     continue;
@@ -346,8 +431,11 @@ impl MatchEvent for App {
                     text_color,
                     bg_color,
                 } => {
-                    // Don't show any tooltips if the message context menu is currently shown.
-                    if self.ui.new_message_context_menu(id!(new_message_context_menu)).is_currently_shown(cx) {
+                    // Don't show any tooltips if a context menu is currently shown.
+                    if self.ui.new_message_context_menu(id!(new_message_context_menu)).is_currently_shown(cx)
+                        || self.ui.room_context_menu(id!(room_context_menu)).is_currently_shown(cx)
+                        || self.ui.profile_menu(id!(profile_menu)).is_currently_shown(cx)
+                    {
                         self.ui.callout_tooltip(id!(app_tooltip)).hide(cx);
                     }
                     else {
@@ -370,6 +458,29 @@ impl MatchEvent for App {
                 _ => {}
             }
 
+            // Handle actions to open/close the notification history modal,
+            // and to jump to the room of a notification that was clicked.
+            match action.downcast_ref() {
+                Some(NotificationHistoryModalAction::Open) => {
+                    self.ui.modal(id!(notification_history_modal)).open(cx);
+                    continue;
+                }
+                Some(NotificationHistoryModalAction::Close) => {
+                    self.ui.modal(id!(notification_history_modal)).close(cx);
+                    continue;
+                }
+                None => {}
+            }
+            if let Some(NotificationAction::Clicked { room_id, room_name }) = action.downcast_ref() {
+                let destination_room = BasicRoomDetails {
+                    room_id: room_id.clone(),
+                    room_name: room_name.clone(),
+                    room_avatar: Default::default(),
+                };
+                self.navigate_to_room(cx, None, &destination_room);
+                continue;
+            }
+
             // Handle actions needed to open/close the join/leave room modal.
             match action.downcast_ref() {
                 Some(JoinLeaveRoomModalAction::Open { kind, show_tip }) => {
@@ -392,7 +503,7 @@ impl MatchEvent for App {
             // Therefore, we cannot use `as_widget_action().cast()` to match them.
             //
             // Note: other verification actions are handled by the verification modal itself.
-            if let Some(VerificationAction::RequestReceived(state)) = action.downcast_ref() {
+            if let Some(VerificationAction::RequestReceived(state) | VerificationAction::RequestSent(state)) = action.downcast_ref() {
                 self.ui.verification_modal(id!(verification_modal_inner))
                     .initialize_with_data(cx, state.clone());
                 self.ui.modal(id!(verification_modal)).open(cx);
@@ -442,6 +553,8 @@ fn clear_all_app_state(cx: &mut Cx) {
     clear_all_invited_rooms(cx);
     clear_timeline_states(cx);
     clear_avatar_cache(cx);
+    clear_presence_cache(cx);
+    room_drafts::clear_drafts();
 }
 
 impl AppMain for App {
@@ -455,12 +568,6 @@ impl AppMain for App {
             if let Err(e) = persistence::save_window_state(window_ref, cx) {
                 error!("Failed to save window state. Error: {e}");
             }
-            if let Some(user_id) = current_user_id() {
-                let app_state = self.app_state.clone();
-                if let Err(e) = persistence::save_app_state(app_state, user_id) {
-                    error!("Failed to save app state. Error: {e}");
-                }
-            }
             #[cfg(feature = "tsp")] {
                 // Save the TSP wallet state, if it exists, with a 3-second timeout.
                 let tsp_state = std::mem::take(&mut *crate::tsp::tsp_state_ref().lock().unwrap());
@@ -482,11 +589,46 @@ impl AppMain for App {
             }
         }
         
+        // Notifications are only suppressed for the selected room while the app has focus.
+        match event {
+            Event::AppGotFocus => notifications::set_app_focused(true),
+            Event::AppLostFocus => notifications::set_app_focused(false),
+            _ => {}
+        }
+
         // Forward events to the MatchEvent trait implementation.
         self.match_event(cx, event);
         let scope = &mut Scope::with_data(&mut self.app_state);
         self.ui.handle_event(cx, event, scope);
 
+        // The account state must be saved after the above, since each RoomScreen
+        // records its current draft upon receiving the shutdown event
+        // or an action to switch to a different account.
+        let is_switching_accounts = match event {
+            Event::Actions(actions) => actions.iter().any(|a| a.downcast_ref::<AccountSwitchAction>().is_some()),
+            _ => false,
+        };
+        if is_switching_accounts || matches!(event, Event::Shutdown) {
+            self.save_account_state();
+        }
+
+        // Likewise, app state must be cleared after each open RoomScreen has recorded its draft
+        // upon receiving the `ClearAppState` action, such that those drafts can be saved first.
+        if let Event::Actions(actions) = event {
+            for action in actions {
+                if let Some(LogoutAction::ClearAppState { user_id, on_clear_appstate }) = action.downcast_ref() {
+                    if let Some(user_id) = user_id {
+                        room_drafts::save_and_clear_drafts(user_id);
+                    }
+                    // Clear user profile cache, invited_rooms timeline states
+                    clear_all_app_state(cx);
+                    // Reset all app state to its default.
+                    self.app_state = Default::default();
+                    on_clear_appstate.notify_one();
+                }
+            }
+        }
+
         /*
          * TODO: I'd like for this to work, but it doesn't behave as expected.
          *       The context menu fails to draw properly when a draw event is passed to it.
@@ -522,6 +664,15 @@ impl AppMain for App {
 }
 
 impl App {
+    /// Saves the app state and room drafts of the currently-active account.
+    fn save_account_state(&self) {
+        let Some(user_id) = current_user_id() else { return };
+        room_drafts::save_drafts();
+        if let Err(e) = persistence::save_app_state(self.app_state.clone(), user_id) {
+            error!("Failed to save app state. Error: {e}");
+        }
+    }
+
     fn update_login_visibility(&self, cx: &mut Cx) {
         let show_login = !self.app_state.logged_in;
         if !show_login {
@@ -533,6 +684,20 @@ impl App {
         self.ui.view(id!(home_screen_view)).set_visible(cx, !show_login);
     }
 
+    /// Updates the window title (and its caption bar label) to show the number
+    /// of unread messages and pending invites, e.g., "Robrix (3)".
+    fn update_window_title(&self, cx: &mut Cx, totals: &UnreadTotals) {
+        let title = match totals.num_notifications() {
+            0 => String::from("Robrix"),
+            n => format!("Robrix ({n})"),
+        };
+        let window = self.ui.window(id!(main_window));
+        window.apply_over(cx, live! {
+            window: { title: (&title) }
+        });
+        self.ui.label(id!(caption_bar.caption_label.label)).set_text(cx, &title);
+    }
+
     /// Navigates to the given `destination_room`, optionally closing the `room_to_close`.
     fn navigate_to_room(
         &mut self,
@@ -688,6 +853,8 @@ pub enum AppStateAction {
     UpgradedInviteToJoinedRoom(OwnedRoomId),
     /// The app state was restored from persistent storage.
     RestoreAppStateFromPersistentState(AppState),
+    /// The unsent message drafts for all rooms were loaded from persistent storage.
+    RestoreRoomDrafts(persistence::RoomDrafts),
     /// The given room was successfully loaded from the homeserver
     /// and is now known to our client.
     ///
@@ -1,7 +1,7 @@
 use std::{cell::RefCell, collections::{btree_map::Entry, BTreeMap}, sync::Arc};
 use crossbeam_queue::SegQueue;
 use makepad_widgets::{Cx, SignalToUI};
-use matrix_sdk::ruma::OwnedMxcUri;
+use matrix_sdk::ruma::{MxcUri, OwnedMxcUri};
 
 use crate::sliding_sync::{submit_async_request, MatrixRequest};
 
@@ -83,6 +83,18 @@ pub fn get_or_fetch_avatar(
     })
 }
 
+/// Removes the cached avatar for the given Matrix URI, if any,
+/// such that it will be re-fetched the next time it's requested.
+///
+/// This function requires passing in a reference to `Cx`,
+/// which isn't used, but acts as a guarantee that this function
+/// must only be called by the main UI thread.
+pub fn invalidate_avatar(_cx: &mut Cx, mxc_uri: &MxcUri) {
+    AVATAR_NEW_CACHE.with_borrow_mut(|cache| {
+        cache.remove(mxc_uri);
+    });
+}
+
 /// Clears cached avatars.
 /// This function requires passing in a reference to `Cx`,
 /// which acts as a guarantee that this function must only be called by the main UI thread.
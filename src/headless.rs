@@ -0,0 +1,449 @@
+//! A headless command-line mode for scripting Robrix from a terminal.
+//!
+//! Headless commands restore a previously-saved login session, sync with the homeserver,
+//! and print their results to stdout as JSON lines, all without starting the Makepad UI.
+//! Errors are printed to stderr, also as JSON lines.
+//!
+//! The subcommand must be the first argument, e.g., `robrix rooms list --user @me:matrix.org`.
+
+use std::{collections::HashSet, io::Write, process::ExitCode, sync::atomic::{AtomicBool, Ordering}, time::Duration};
+
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use eyeball_im::VectorDiff;
+use futures_util::{pin_mut, StreamExt};
+use makepad_widgets::warning;
+use matrix_sdk::{
+    config::SyncSettings,
+    ruma::{events::room::message::RoomMessageEventContent, OwnedEventId, OwnedRoomId, OwnedUserId, RoomOrAliasId},
+    send_queue::RoomSendQueueUpdate,
+    Client, Room, SessionChange,
+};
+use matrix_sdk_ui::{
+    room_list_service::RoomListLoadingState,
+    sync_service::SyncService,
+    timeline::{EventTimelineItem, RoomExt, Timeline},
+};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use crate::{
+    event_preview::plaintext_body_of_timeline_item,
+    home::invite_screen::{JoinRoomResultAction, LeaveRoomResultAction},
+    persistence::{self, SlidingSyncVersion},
+    sliding_sync::{join_room, leave_room, send_message},
+    utils,
+};
+
+/// Whether Robrix is running in headless mode, i.e., without the Makepad UI.
+static IS_HEADLESS: AtomicBool = AtomicBool::new(false);
+
+/// Returns whether Robrix is running in headless mode.
+///
+/// In headless mode, no actions may be posted to the UI, as it doesn't exist.
+pub fn is_headless() -> bool {
+    IS_HEADLESS.load(Ordering::Relaxed)
+}
+
+/// How long to wait for the room list to be loaded before using the locally-cached rooms.
+const ROOM_LIST_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The number of events requested per back-pagination when exporting a room.
+const EXPORT_PAGE_SIZE: u16 = 100;
+
+/// Run Robrix commands from the terminal without starting the GUI.
+///
+/// All commands use the saved login session of the most recently logged-in user,
+/// so you must have logged in using the Robrix GUI at least once.
+#[derive(Parser, Debug)]
+#[command(name = "robrix")]
+pub struct HeadlessCli {
+    /// The user ID of the saved session to use, which defaults to the most recently logged-in user.
+    #[clap(long, global = true)]
+    user: Option<OwnedUserId>,
+
+    #[command(subcommand)]
+    command: HeadlessCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum HeadlessCommand {
+    /// Operations on the list of rooms.
+    Rooms {
+        #[command(subcommand)]
+        command: RoomsCommand,
+    },
+    /// Send a text message (formatted as Markdown) to a room.
+    Send {
+        /// The room ID or alias of the room.
+        room: String,
+        /// The text of the message.
+        text: String,
+    },
+    /// Print a room's recent messages, and then new messages as they arrive, until interrupted.
+    Tail {
+        /// The room ID or alias of the room.
+        room: String,
+        /// The number of recent messages to print before waiting for new messages.
+        #[clap(short = 'n', long, default_value_t = 20)]
+        lines: u16,
+    },
+    /// Join a room.
+    Join {
+        /// The room ID or alias of the room.
+        room: String,
+    },
+    /// Leave a room.
+    Leave {
+        /// The room ID or alias of the room.
+        room: String,
+    },
+    /// Export a room's history, oldest events first.
+    Export {
+        /// The room ID or alias of the room.
+        room: String,
+        /// The maximum number of events to export, which defaults to the room's full history.
+        #[clap(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RoomsCommand {
+    /// List all rooms that the user has joined, been invited to, or left.
+    List,
+}
+
+/// Returns the headless CLI arguments if the first argument is a headless subcommand,
+/// or `None` if Robrix should start its GUI as usual.
+pub fn headless_cli_from_args() -> Option<HeadlessCli> {
+    let first_arg = std::env::args().nth(1)?;
+    HeadlessCommand::has_subcommand(&first_arg).then(HeadlessCli::parse)
+}
+
+/// Runs the given headless command to completion.
+pub fn run(cli: HeadlessCli) -> ExitCode {
+    IS_HEADLESS.store(true, Ordering::Relaxed);
+    // Log to stderr such that stdout only contains JSON lines.
+    let _ = tracing_subscriber::fmt().with_writer(std::io::stderr).try_init();
+
+    let result = tokio::runtime::Runtime::new()
+        .map_err(anyhow::Error::from)
+        .and_then(|runtime| runtime.block_on(run_command(cli)));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let _ = writeln!(std::io::stderr().lock(), "{}", json!({ "type": "error", "error": format!("{e:#}") }));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_command(cli: HeadlessCli) -> Result<()> {
+    let (client, _sync_token) = persistence::restore_session(cli.user).await
+        .map_err(|e| anyhow!("Could not restore a saved login session; please log in using the Robrix GUI first. {e}"))?;
+    let session_changes_task = tokio::spawn(save_session_changes(client.clone()));
+
+    // Homeservers without native sliding sync can only be synced with classic sync.
+    // Either way, we keep syncing for the whole command, e.g., such that `tail` receives new messages.
+    let (sync_service, classic_sync_task) = match SlidingSyncVersion::from(client.sliding_sync_version()) {
+        SlidingSyncVersion::Native => {
+            let sync_service = SyncService::builder(client.clone()).build().await?;
+            sync_service.start().await;
+            wait_for_room_list(&sync_service).await?;
+            (Some(sync_service), None)
+        }
+        SlidingSyncVersion::None => {
+            classic_sync_once(&client).await;
+            (None, Some(tokio::spawn(classic_sync_forever(client.clone()))))
+        }
+    };
+
+    let result = match cli.command {
+        HeadlessCommand::Rooms { command: RoomsCommand::List } => list_rooms(&client).await,
+        HeadlessCommand::Send { room, text } => {
+            let room = resolve_room(&client, &room).await?;
+            let event_id = send_message_and_wait(&room, RoomMessageEventContent::text_markdown(text)).await?;
+            print_json_line(&json!({
+                "type": "sent",
+                "room_id": room.room_id(),
+                "event_id": event_id,
+            }));
+            Ok(())
+        }
+        HeadlessCommand::Tail { room, lines } => {
+            let room = resolve_room(&client, &room).await?;
+            tail_room(&room, lines).await
+        }
+        HeadlessCommand::Join { room } => {
+            let room_id = resolve_room_id(&client, &room).await?;
+            match join_room(&client, room_id).await {
+                JoinRoomResultAction::Joined { room_id } => {
+                    print_json_line(&json!({ "type": "joined", "room_id": room_id }));
+                    Ok(())
+                }
+                JoinRoomResultAction::Failed { room_id, error } => bail!("Failed to join room {room_id}: {error}"),
+            }
+        }
+        HeadlessCommand::Leave { room } => {
+            let room_id = resolve_room_id(&client, &room).await?;
+            match leave_room(&client, room_id).await {
+                LeaveRoomResultAction::Left { room_id } => {
+                    print_json_line(&json!({ "type": "left", "room_id": room_id }));
+                    Ok(())
+                }
+                LeaveRoomResultAction::Failed { room_id, error } => bail!("Failed to leave room {room_id}: {error}"),
+            }
+        }
+        HeadlessCommand::Export { room, limit } => {
+            let room = resolve_room(&client, &room).await?;
+            export_room(&room, limit).await
+        }
+    };
+
+    if let Some(sync_service) = sync_service {
+        sync_service.stop().await;
+    }
+    if let Some(classic_sync_task) = classic_sync_task {
+        classic_sync_task.abort();
+    }
+    session_changes_task.abort();
+    result
+}
+
+/// Saves the session's tokens whenever the client refreshes them,
+/// such that the GUI and later headless invocations can still restore the session.
+async fn save_session_changes(client: Client) {
+    let mut session_changes = client.subscribe_to_session_changes();
+    loop {
+        match session_changes.recv().await {
+            Ok(SessionChange::TokensRefreshed) => {
+                if let Err(e) = persistence::save_session_tokens(&client).await {
+                    warning!("Failed to save the refreshed session tokens: {e}");
+                }
+            }
+            Ok(SessionChange::UnknownToken { .. }) => {
+                warning!("The homeserver rejected the session's access token; please log in again using the Robrix GUI.");
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => { }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Sends the given message the same way the GUI does, i.e., via the room's timeline and send queue,
+/// and waits until the homeserver has accepted it, returning the ID of the sent event.
+async fn send_message_and_wait(room: &Room, message: RoomMessageEventContent) -> Result<OwnedEventId> {
+    let timeline = room.timeline().await?;
+    let (_local_echoes, mut send_queue_updates) = room.send_queue().subscribe().await?;
+    send_message(&timeline, message, None, #[cfg(feature = "tsp")] false).await?;
+
+    let mut transaction_id = None;
+    loop {
+        match send_queue_updates.recv().await? {
+            RoomSendQueueUpdate::NewLocalEvent(local_echo) if transaction_id.is_none() => {
+                transaction_id = Some(local_echo.transaction_id);
+            }
+            RoomSendQueueUpdate::SentEvent { transaction_id: sent_id, event_id }
+                if transaction_id.as_ref() == Some(&sent_id) => return Ok(event_id),
+            RoomSendQueueUpdate::SendError { transaction_id: failed_id, error, .. }
+                if transaction_id.as_ref() == Some(&failed_id) => bail!("Failed to send message: {error}"),
+            _ => { }
+        }
+    }
+}
+
+/// Performs a single classic sync, falling back to the locally-cached rooms
+/// if that fails or takes too long (e.g., if the homeserver is unreachable).
+async fn classic_sync_once(client: &Client) {
+    let sync_settings = SyncSettings::default().timeout(Duration::ZERO);
+    match tokio::time::timeout(ROOM_LIST_LOAD_TIMEOUT, client.sync_once(sync_settings)).await {
+        Ok(Ok(_)) => { }
+        Ok(Err(e)) => warning!("Classic sync failed ({e}); using locally-cached rooms."),
+        Err(_) => warning!("Timed out waiting for classic sync; using locally-cached rooms."),
+    }
+}
+
+/// Continuously syncs with classic sync, continuing from the sync token of the previous sync.
+///
+/// This runs in the background until the headless command finishes and aborts it.
+async fn classic_sync_forever(client: Client) {
+    if let Err(e) = client.sync(SyncSettings::default()).await {
+        warning!("Classic sync stopped: {e}");
+    }
+}
+
+/// Waits for the sync service to load the room list, falling back to the locally-cached rooms
+/// if that takes too long (e.g., if the homeserver is unreachable).
+async fn wait_for_room_list(sync_service: &SyncService) -> Result<()> {
+    let all_rooms = sync_service.room_list_service().all_rooms().await?;
+    let mut loading_state = all_rooms.loading_state();
+    let loaded = tokio::time::timeout(ROOM_LIST_LOAD_TIMEOUT, async {
+        while !matches!(loading_state.get(), RoomListLoadingState::Loaded { .. }) {
+            if loading_state.next().await.is_none() {
+                break;
+            }
+        }
+    }).await;
+    if loaded.is_err() {
+        warning!("Timed out waiting for the room list to load; using locally-cached rooms.");
+    }
+    Ok(())
+}
+
+/// Resolves the given room ID or alias into a room ID.
+async fn resolve_room_id(client: &Client, room: &str) -> Result<OwnedRoomId> {
+    let room_or_alias_id = <&RoomOrAliasId>::try_from(room)
+        .map_err(|e| anyhow!("\"{room}\" is not a valid room ID or alias: {e}"))?;
+    match OwnedRoomId::try_from(room_or_alias_id.to_owned()) {
+        Ok(room_id) => Ok(room_id),
+        Err(alias_id) => Ok(client.resolve_room_alias(&alias_id).await?.room_id),
+    }
+}
+
+/// Resolves the given room ID or alias into a room that is known to the client.
+async fn resolve_room(client: &Client, room: &str) -> Result<Room> {
+    let room_id = resolve_room_id(client, room).await?;
+    client.get_room(&room_id)
+        .ok_or_else(|| anyhow!("Room {room_id} is not known; you may need to join it first."))
+}
+
+async fn list_rooms(client: &Client) -> Result<()> {
+    for room in client.rooms() {
+        print_json_line(&json!({
+            "type": "room",
+            "room_id": room.room_id(),
+            "name": room.display_name().await.ok().map(|name| name.to_string()),
+            "canonical_alias": room.canonical_alias(),
+            "state": format!("{:?}", room.state()).to_lowercase(),
+            "is_direct": room.is_direct().await.unwrap_or(false),
+            "unread_messages": room.num_unread_messages(),
+            "unread_mentions": room.num_unread_mentions(),
+        }));
+    }
+    Ok(())
+}
+
+/// Prints the given room's recent events, and then its new events as they arrive.
+///
+/// New events only arrive while the client keeps syncing in the background,
+/// which [`run_command()`] ensures for both sliding sync and classic sync.
+async fn tail_room(room: &Room, lines: u16) -> Result<()> {
+    let timeline = room.timeline().await?;
+    timeline.paginate_backwards(lines).await?;
+    let (initial_items, stream) = timeline.subscribe().await;
+    pin_mut!(stream);
+
+    let mut printed_events = HashSet::new();
+    let recent_events: Vec<_> = initial_items.iter().filter_map(|item| item.as_event()).collect();
+    for event in recent_events.iter().skip(recent_events.len().saturating_sub(lines as usize)) {
+        print_event(room, event, &mut printed_events);
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            batch = stream.next() => {
+                let Some(diffs) = batch else { break };
+                for diff in diffs {
+                    // We only print newly-added remote events, so edits and redactions are ignored.
+                    let new_items = match diff {
+                        VectorDiff::PushBack { value } | VectorDiff::Insert { value, .. } => vec![value],
+                        VectorDiff::Append { values } => values.into_iter().collect(),
+                        VectorDiff::Set { value, .. } => vec![value],
+                        _ => continue,
+                    };
+                    for item in new_items {
+                        if let Some(event) = item.as_event() {
+                            print_event(room, event, &mut printed_events);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn export_room(room: &Room, limit: Option<usize>) -> Result<()> {
+    let timeline = room.timeline().await?;
+    loop {
+        let reached_start = timeline.paginate_backwards(EXPORT_PAGE_SIZE).await?;
+        if reached_start {
+            break;
+        }
+        if let Some(limit) = limit {
+            if num_events(&timeline).await >= limit {
+                break;
+            }
+        }
+    }
+
+    let items = timeline.items().await;
+    let events: Vec<_> = items.iter().filter_map(|item| item.as_event()).collect();
+    let skip = limit.map_or(0, |limit| events.len().saturating_sub(limit));
+    let mut printed_events = HashSet::new();
+    for event in events.into_iter().skip(skip) {
+        print_event(room, event, &mut printed_events);
+    }
+    Ok(())
+}
+
+/// Returns the number of events (not virtual items) in the given timeline.
+async fn num_events(timeline: &Timeline) -> usize {
+    timeline.items().await.iter().filter(|item| item.as_event().is_some()).count()
+}
+
+/// Prints the given event as a JSON line, unless it was already printed
+/// or hasn't yet been sent to the homeserver (i.e., it's a local echo).
+fn print_event(room: &Room, event: &EventTimelineItem, printed_events: &mut HashSet<String>) {
+    let Some(event_id) = event.event_id() else { return };
+    if !printed_events.insert(event_id.to_string()) {
+        return;
+    }
+    print_json_line(&event_to_json(room, event));
+}
+
+/// Converts the given timeline event into a JSON object, using the same plaintext
+/// rendering of the event's content as is used for previews in the Robrix UI.
+fn event_to_json(room: &Room, event: &EventTimelineItem) -> Value {
+    json!({
+        "type": "event",
+        "room_id": room.room_id(),
+        "event_id": event.event_id(),
+        "sender": event.sender(),
+        "sender_name": utils::get_or_fetch_event_sender(event, None),
+        "timestamp": u64::from(event.timestamp().get()),
+        "body": plaintext_body_of_timeline_item(event),
+    })
+}
+
+/// Prints the given JSON value to stdout on a single line.
+fn print_json_line(value: &Value) {
+    // Ignore errors, e.g., if stdout was closed because it was piped into `head`.
+    let _ = writeln!(std::io::stdout().lock(), "{value}");
+}
+
+#[cfg(test)]
+mod tests_headless {
+    use super::*;
+
+    #[test]
+    fn parses_headless_subcommands() {
+        let cli = HeadlessCli::try_parse_from(["robrix", "rooms", "list", "--user", "@alice:example.org"]).unwrap();
+        assert!(matches!(cli.command, HeadlessCommand::Rooms { command: RoomsCommand::List }));
+        assert_eq!(cli.user.unwrap().as_str(), "@alice:example.org");
+
+        let cli = HeadlessCli::try_parse_from(["robrix", "tail", "#room:example.org", "-n", "5"]).unwrap();
+        assert!(matches!(cli.command, HeadlessCommand::Tail { ref room, lines: 5 } if room == "#room:example.org"));
+    }
+
+    #[test]
+    fn gui_arguments_are_not_headless_subcommands() {
+        // The GUI accepts a user ID, password, and homeserver as positional arguments.
+        for arg in ["@alice:example.org", "alice", "--login-screen"] {
+            assert!(!HeadlessCommand::has_subcommand(arg));
+        }
+        assert!(HeadlessCommand::has_subcommand("export"));
+    }
+}
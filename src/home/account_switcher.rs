@@ -0,0 +1,242 @@
+//! The account switcher in the SpacesDock, which lists the other logged-in accounts
+//! and lets the user switch between them or add another account.
+
+use std::collections::{HashMap, HashSet};
+
+use makepad_widgets::*;
+use matrix_sdk::ruma::OwnedUserId;
+
+use crate::{
+    home::unread_totals::UnreadCounts,
+    login::login_screen::LoginAction,
+    logout::logout_confirm_modal::LogoutAction,
+    persistence::logged_in_accounts,
+    shared::{
+        avatar::AvatarWidgetExt,
+        callout_tooltip::TooltipAction,
+        popup_list::{enqueue_popup_notification, PopupItem, PopupKind},
+        styles::*,
+        unread_badge::UnreadBadgeWidgetExt,
+    },
+    sliding_sync::current_user_id,
+};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::styles::*;
+    use crate::shared::helpers::*;
+    use crate::shared::avatar::*;
+    use crate::shared::unread_badge::*;
+    use crate::shared::icon_button::*;
+
+    // An entry for another logged-in account, showing its initial and its unread badge.
+    AccountEntry = <View> {
+        flow: Overlay
+        width: 50, height: 50
+        align: { x: 1.0, y: 0.0 }
+        cursor: Hand,
+
+        <View> {
+            width: Fill, height: Fill
+            align: { x: 0.5, y: 0.5 }
+            account_avatar = <Avatar> {
+                width: 38, height: 38
+                text_view = {
+                    text = { draw_text: {
+                        text_style: { font_size: 13.0 },
+                    } }
+                }
+            }
+        }
+        unread_badge = <UnreadBadge> {}
+    }
+
+    pub AccountSwitcher = {{AccountSwitcher}} {
+        width: Fit, height: Fit
+        align: { x: 0.5, y: 0.5 }
+        spacing: 8
+
+        account_entry: <AccountEntry> {}
+
+        add_account_button: <RobrixIconButton> {
+            width: Fit, height: Fit
+            padding: 10
+            spacing: 0
+            draw_bg: {
+                color: (COLOR_PRIMARY_DARKER)
+            }
+            draw_icon: {
+                svg_file: (ICON_ADD),
+                color: (COLOR_TEXT),
+            }
+            icon_walk: {width: 16, height: 16, margin: 0}
+        }
+    }
+}
+
+/// Actions emitted by the [`AccountSwitcher`] widget.
+#[derive(Clone, Debug)]
+pub enum AccountSwitchAction {
+    /// The user chose to switch to the given logged-in account.
+    Switch(OwnedUserId),
+    /// The user chose to log in to an additional account.
+    AddAccount,
+}
+
+/// Actions sent from the background tasks that keep inactive accounts syncing.
+#[derive(Clone, Debug)]
+pub enum BackgroundAccountAction {
+    /// The given inactive account's Home unread counts have changed.
+    UnreadCountsChanged {
+        user_id: OwnedUserId,
+        counts: UnreadCounts,
+    },
+    /// The homeserver rejected the given inactive account's access token,
+    /// so it has stopped syncing in the background.
+    SessionExpired {
+        user_id: OwnedUserId,
+    },
+}
+
+/// A list of the other logged-in accounts, plus a button to add another account.
+///
+/// The active account is shown separately by the `ProfileIcon` in the SpacesDock.
+/// Each inactive account keeps syncing in the background,
+/// such that its entry shows that account's live unread counts.
+#[derive(Live, Widget)]
+pub struct AccountSwitcher {
+    #[redraw] #[rust] area: Area,
+    #[walk] walk: Walk,
+    #[layout] layout: Layout,
+
+    #[live] account_entry: Option<LivePtr>,
+    #[live] add_account_button: Option<LivePtr>,
+
+    /// The other (inactive) logged-in accounts and their entry widgets.
+    #[rust] entries: Vec<(OwnedUserId, WidgetRef)>,
+    #[rust] add_button: WidgetRef,
+    /// The latest unread counts of each inactive account.
+    #[rust] unread_counts: HashMap<OwnedUserId, UnreadCounts>,
+    /// The inactive accounts whose login sessions have expired or were revoked.
+    #[rust] expired_accounts: HashSet<OwnedUserId>,
+}
+
+impl LiveHook for AccountSwitcher {
+    fn after_new_from_doc(&mut self, cx: &mut Cx) {
+        self.add_button = WidgetRef::new_from_ptr(cx, self.add_account_button);
+        self.refresh_accounts(cx);
+    }
+}
+
+impl Widget for AccountSwitcher {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        if let Event::Actions(actions) = event {
+            for action in actions {
+                if let Some(BackgroundAccountAction::UnreadCountsChanged { user_id, counts }) = action.downcast_ref() {
+                    self.unread_counts.insert(user_id.clone(), *counts);
+                    if let Some((_, entry)) = self.entries.iter().find(|(id, _)| id == user_id) {
+                        entry.unread_badge(id!(unread_badge)).update_counts(counts.mentions, counts.messages);
+                        self.redraw(cx);
+                    }
+                }
+                if let Some(BackgroundAccountAction::SessionExpired { user_id }) = action.downcast_ref() {
+                    self.expired_accounts.insert(user_id.clone());
+                    enqueue_popup_notification(PopupItem {
+                        message: format!("Your login session for {user_id} has expired or was revoked. \
+                            Switch to that account to log out and log in again."),
+                        kind: PopupKind::Error,
+                        auto_dismissal_duration: None,
+                    });
+                }
+                match action.downcast_ref() {
+                    Some(LoginAction::LoginSuccess) | Some(LogoutAction::LogoutSuccess) => {
+                        // Logging in to an account again renews its expired session.
+                        if let Some(user_id) = current_user_id() {
+                            self.expired_accounts.remove(&user_id);
+                        }
+                        self.refresh_accounts(cx);
+                    }
+                    _ => { }
+                }
+            }
+            if self.add_button.as_button().clicked(actions) {
+                cx.action(AccountSwitchAction::AddAccount);
+            }
+        }
+
+        let mut switch_to = None;
+        for (user_id, entry) in &self.entries {
+            entry.handle_event(cx, event, scope);
+            let area = entry.area();
+            match event.hits(cx, area) {
+                Hit::FingerLongPress(_)
+                | Hit::FingerHoverOver(_) // TODO: remove once CalloutTooltip bug is fixed
+                | Hit::FingerHoverIn(_) => {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        TooltipAction::HoverIn {
+                            widget_rect: area.rect(cx),
+                            text: if self.expired_accounts.contains(user_id) {
+                                format!("Switch to {user_id} (login session expired)")
+                            } else {
+                                format!("Switch to {user_id}")
+                            },
+                            bg_color: None,
+                            text_color: None,
+                        },
+                    );
+                }
+                Hit::FingerHoverOut(_) => {
+                    cx.widget_action(self.widget_uid(), &scope.path, TooltipAction::HoverOut);
+                }
+                Hit::FingerUp(fue) if fue.is_over && fue.is_primary_hit() && fue.was_tap() => {
+                    switch_to = Some(user_id.clone());
+                }
+                _ => { }
+            }
+        }
+        if let Some(user_id) = switch_to {
+            cx.widget_action(self.widget_uid(), &scope.path, TooltipAction::HoverOut);
+            cx.action(AccountSwitchAction::Switch(user_id));
+        }
+        self.add_button.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        cx.begin_turtle(walk, self.layout);
+        for (_, entry) in &self.entries {
+            entry.draw_all(cx, scope);
+        }
+        self.add_button.draw_all(cx, scope);
+        cx.end_turtle_with_area(&mut self.area);
+        DrawStep::done()
+    }
+}
+
+impl AccountSwitcher {
+    /// Rebuilds the list of entries from the set of other logged-in accounts.
+    fn refresh_accounts(&mut self, cx: &mut Cx) {
+        let current = current_user_id();
+        self.entries = logged_in_accounts()
+            .into_iter()
+            .filter(|user_id| Some(user_id) != current.as_ref())
+            .map(|user_id| {
+                let entry = WidgetRef::new_from_ptr(cx, self.account_entry);
+                entry.avatar(id!(account_avatar)).show_text(
+                    cx,
+                    Some(COLOR_FG_DISABLED),
+                    None, // don't make this avatar clickable; we handle clicks on the entry directly.
+                    user_id.localpart(),
+                );
+                let counts = self.unread_counts.get(&user_id).copied().unwrap_or_default();
+                entry.unread_badge(id!(unread_badge)).update_counts(counts.mentions, counts.messages);
+                (user_id, entry)
+            })
+            .collect();
+        self.redraw(cx);
+    }
+}
@@ -13,7 +13,7 @@ use matrix_sdk_ui::timeline::{EventTimelineItem, MsgLikeKind, TimelineEventItemI
 
 use crate::shared::mentionable_text_input::MentionableTextInputWidgetExt;
 use crate::{
-    shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, sliding_sync::{submit_async_request, MatrixRequest}
+    persistence::EditDraft, shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, sliding_sync::{submit_async_request, MatrixRequest}
 };
 
 live_design! {
@@ -468,6 +468,15 @@ impl EditingPane {
         })
     }
 
+    /// Returns the in-progress edit in this `EditingPane` as a persistable draft, if any.
+    pub fn edit_draft(&self) -> Option<EditDraft> {
+        let info = self.info.as_ref()?;
+        Some(EditDraft {
+            event_id: info.event_tl_item.event_id()?.to_string(),
+            text: self.mentionable_text_input(id!(editing_content.edit_text_input)).text(),
+        })
+    }
+
     /// Restores the state of this `EditingPane` from the given `editing_pane_state`.
     pub fn restore_state(
         &mut self,
@@ -535,6 +544,11 @@ impl EditingPaneRef {
         self.borrow()?.save_state()
     }
 
+    /// See [`EditingPane::edit_draft()`].
+    pub fn edit_draft(&self) -> Option<EditDraft> {
+        self.borrow()?.edit_draft()
+    }
+
     /// Restores the state of this `EditingPane` from the given `event_tl_item` and `text_input_state`.
     ///
     /// The arguments should be the result of a previous call to [`Self::save_state()`].
@@ -167,18 +167,23 @@ impl Widget for HomeScreen {
             for action in actions {
                 // Handle the settings screen being opened or closed.
                 match action.downcast_ref() {
-                    Some(SettingsAction::OpenSettings) => {
-                        if !matches!(self.selection, SelectedSpace::Settings) {
+                    Some(open_action @ (SettingsAction::OpenSettings | SettingsAction::OpenSettingsSection(_))) => {
+                        let was_open = matches!(self.selection, SelectedSpace::Settings);
+                        if !was_open {
                             self.previous_selection = self.selection.clone();
                             self.selection = SelectedSpace::Settings;
-                            if let Some(settings_page) = self.update_active_page_from_selection(cx) {
-                                settings_page
-                                    .settings_screen(id!(settings_screen))
-                                    .populate(cx, None);
-                                self.view.redraw(cx);
-                            } else {
-                                error!("BUG: failed to set active page to show settings screen.");
+                        }
+                        if let Some(settings_page) = self.update_active_page_from_selection(cx) {
+                            let settings_screen = settings_page.settings_screen(id!(settings_screen));
+                            if !was_open {
+                                settings_screen.populate(cx, None);
+                            }
+                            if let SettingsAction::OpenSettingsSection(section) = open_action {
+                                settings_screen.show_section(cx, *section);
                             }
+                            self.view.redraw(cx);
+                        } else {
+                            error!("BUG: failed to set active page to show settings screen.");
                         }
                     }
                     Some(SettingsAction::CloseSettings) => {
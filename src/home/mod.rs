@@ -1,5 +1,6 @@
 use makepad_widgets::Cx;
 
+pub mod account_switcher;
 pub mod edited_indicator;
 pub mod editing_pane;
 pub mod home_screen;
@@ -10,6 +11,7 @@ pub mod loading_pane;
 pub mod location_preview;
 pub mod main_desktop_ui;
 pub mod main_mobile_ui;
+pub mod profile_menu;
 pub mod room_preview;
 pub mod room_screen;
 pub mod room_read_receipt;
@@ -20,7 +22,9 @@ pub mod spaces_dock;
 pub mod welcome_screen;
 pub mod event_reaction_list;
 pub mod new_message_context_menu;
+pub mod room_context_menu;
 pub mod link_preview;
+pub mod unread_totals;
 
 pub fn live_design(cx: &mut Cx) {
     home_screen::live_design(cx);
@@ -32,6 +36,8 @@ pub fn live_design(cx: &mut Cx) {
     edited_indicator::live_design(cx);
     editing_pane::live_design(cx);
     new_message_context_menu::live_design(cx);
+    room_context_menu::live_design(cx);
+    profile_menu::live_design(cx);
     invite_screen::live_design(cx);
     tombstone_footer::live_design(cx);
     room_screen::live_design(cx);
@@ -39,6 +45,7 @@ pub fn live_design(cx: &mut Cx) {
     rooms_sidebar::live_design(cx);
     main_mobile_ui::live_design(cx);
     main_desktop_ui::live_design(cx);
+    account_switcher::live_design(cx);
     spaces_dock::live_design(cx);
     welcome_screen::live_design(cx);
     light_themed_dock::live_design(cx);
@@ -0,0 +1,374 @@
+//! A menu that appears when the user right-clicks or long-presses
+//! on their own avatar (the `ProfileIcon`) in the SpacesDock.
+//!
+//! This menu lets the user set their presence and status message,
+//! and choose whether to share their presence with other users at all.
+
+use makepad_widgets::*;
+use matrix_sdk::ruma::presence::PresenceState;
+
+use crate::{
+    persistence::PresenceSettings,
+    settings::SettingsAction,
+    shared::styles::COLOR_ACTIVE_PRIMARY,
+    sliding_sync::{presence_settings, submit_async_request, MatrixRequest},
+};
+
+const BUTTON_HEIGHT: f64 = 35.0;      // KEEP IN SYNC WITH BUTTON_HEIGHT BELOW
+const MENU_WIDTH: f64 = 250.0;        // KEEP IN SYNC WITH MENU_WIDTH BELOW
+const HEADER_HEIGHT: f64 = 30.0;      // KEEP IN SYNC WITH HEADER_HEIGHT BELOW
+const STATUS_INPUT_HEIGHT: f64 = 45.0; // KEEP IN SYNC WITH STATUS_INPUT_HEIGHT BELOW
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    BUTTON_HEIGHT = 35        // KEEP IN SYNC WITH BUTTON_HEIGHT ABOVE
+    MENU_WIDTH = 250          // KEEP IN SYNC WITH MENU_WIDTH ABOVE
+    HEADER_HEIGHT = 30        // KEEP IN SYNC WITH HEADER_HEIGHT ABOVE
+    STATUS_INPUT_HEIGHT = 45  // KEEP IN SYNC WITH STATUS_INPUT_HEIGHT ABOVE
+
+    ICON_SETTINGS = dep("crate://self/resources/icons/settings.svg")
+
+    ProfileMenuButton = <RobrixIconButton> {
+        height: (BUTTON_HEIGHT)
+        width: Fill,
+        margin: 0,
+        icon_walk: {width: 16, height: 16, margin: {right: 3}}
+    }
+
+    // A button to set a presence state, with a dot showing that state's color.
+    PresenceButton = <View> {
+        width: Fill, height: (BUTTON_HEIGHT)
+        flow: Overlay,
+        align: {x: 0, y: 0.5}
+
+        button = <ProfileMenuButton> {
+            padding: {left: 32}
+        }
+        dot = <View> {
+            width: 10, height: 10,
+            margin: {left: 12}
+            show_bg: true
+            draw_bg: {
+                fn pixel(self) -> vec4 {
+                    let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                    sdf.circle(5.0, 5.0, 5.0);
+                    sdf.fill(self.color);
+                    return sdf.result;
+                }
+            }
+        }
+    }
+
+    ProfileMenuDivider = <LineH> {
+        margin: {top: 3, bottom: 3}
+        width: Fill,
+    }
+
+    pub ProfileMenu = {{ProfileMenu}} {
+        visible: false,
+        flow: Overlay,
+        width: Fill,
+        height: Fill,
+        cursor: Default,
+        // Align to top-left such that our coordinate adjustment
+        // when showing this menu pane will work correctly.
+        align: {x: 0, y: 0}
+
+        // Show a slightly darkened translucent background to make the menu stand out.
+        show_bg: true
+        draw_bg: {
+            fn pixel(self) -> vec4 {
+                return vec4(0., 0., 0., 0.3)
+            }
+        }
+
+        main_content = <RoundedView> {
+            flow: Down
+            width: (MENU_WIDTH),
+            height: Fit,
+            padding: 10
+            spacing: 0,
+            align: {x: 0, y: 0}
+
+            show_bg: true
+            draw_bg: {
+                color: #fff
+                border_radius: 5.0
+                border_size: 0.5
+                border_color: #888
+            }
+
+            <Label> {
+                width: Fill, height: (HEADER_HEIGHT)
+                padding: {left: 10, top: 5}
+                draw_text: {
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <USERNAME_TEXT_STYLE>{ font_size: 10.5 },
+                }
+                text: "Set your status"
+            }
+
+            <ProfileMenuDivider> {}
+
+            online_button = <PresenceButton> {
+                button = { text: "Online" }
+                dot = { draw_bg: { color: (COLOR_FG_ACCEPT_GREEN) } }
+            }
+            unavailable_button = <PresenceButton> {
+                button = { text: "Away" }
+                dot = { draw_bg: { color: (COLOR_PRESENCE_AWAY) } }
+            }
+            offline_button = <PresenceButton> {
+                button = { text: "Appear Offline" }
+                dot = { draw_bg: { color: (COLOR_FG_DISABLED) } }
+            }
+
+            status_input = <SimpleTextInput> {
+                width: Fill, height: (STATUS_INPUT_HEIGHT)
+                margin: {top: 5, bottom: 5}
+                empty_text: "What's your status?"
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: Right,
+                spacing: 5,
+                set_status_button = <ProfileMenuButton> {
+                    draw_icon: { svg_file: (ICON_CHECKMARK) }
+                    text: "Set Status"
+                }
+                clear_status_button = <ProfileMenuButton> {
+                    draw_icon: { svg_file: (ICON_CLOSE) }
+                    text: "Clear"
+                }
+            }
+
+            <ProfileMenuDivider> {}
+
+            share_presence_checkbox = <CheckBoxFlat> {
+                height: (BUTTON_HEIGHT)
+                margin: {left: 5}
+                draw_text: {
+                    color: (COLOR_TEXT),
+                    text_style: <REGULAR_TEXT>{ font_size: 10 },
+                }
+                text: "Share my online status"
+            }
+
+            <ProfileMenuDivider> {}
+
+            open_settings_button = <ProfileMenuButton> {
+                draw_icon: { svg_file: (ICON_SETTINGS) }
+                text: "Profile & Settings"
+            }
+        }
+    }
+}
+
+/// Actions related to the profile menu.
+#[derive(Clone, DefaultNone, Debug)]
+pub enum ProfileMenuAction {
+    /// The user right-clicked or long-pressed on their avatar in the SpacesDock,
+    /// requesting that the profile menu be shown.
+    Open {
+        /// The absolute position where we should show the profile menu,
+        /// in which the (0,0) origin coordinate is the top left corner of the app window.
+        abs_pos: DVec2,
+    },
+    None,
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct ProfileMenu {
+    #[deref] view: View,
+    /// The current user's presence settings at the time this menu was shown.
+    #[rust] settings: Option<PresenceSettings>,
+}
+
+impl Widget for ProfileMenu {
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        if self.settings.is_none() {
+            self.visible = false;
+        };
+
+        self.view.draw_walk(cx, scope, walk)
+    }
+
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        if !self.visible { return; }
+        self.view.handle_event(cx, event, scope);
+
+        let area = self.view.area();
+
+        // Close the menu if:
+        // 1. The back navigational gesture/action occurs (e.g., Back on Android),
+        // 2. The escape key is pressed if this menu has key focus,
+        // 3. The user clicks/touches outside the main_content view area.
+        // 4. The user scrolls anywhere.
+        let close_menu = {
+            event.back_pressed()
+            || match event.hits_with_capture_overload(cx, area, true) {
+                Hit::KeyUp(key) => key.key_code == KeyCode::Escape,
+                Hit::FingerDown(_fde) => {
+                    cx.set_key_focus(area);
+                    false
+                }
+                Hit::FingerUp(fue) if fue.is_over => {
+                    !self.view(id!(main_content)).area().rect(cx).contains(fue.abs)
+                }
+                Hit::FingerScroll(_) => true,
+                _ => false,
+            }
+        };
+        if close_menu {
+            self.close(cx);
+            return;
+        }
+
+        self.widget_match_event(cx, event, scope);
+    }
+}
+
+impl WidgetMatchEvent for ProfileMenu {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        let Some(settings) = self.settings.as_mut() else { return };
+        let status_input = self.view.text_input(id!(status_input));
+
+        if let Some(share_presence) = self.view.check_box(id!(share_presence_checkbox)).changed(actions) {
+            settings.share_presence = share_presence;
+            submit_async_request(MatrixRequest::SetPresenceSharing { share_presence });
+            self.update_buttons(cx);
+            return;
+        }
+
+        let new_presence = if self.view.button(id!(online_button.button)).clicked(actions) {
+            Some(PresenceState::Online)
+        } else if self.view.button(id!(unavailable_button.button)).clicked(actions) {
+            Some(PresenceState::Unavailable)
+        } else if self.view.button(id!(offline_button.button)).clicked(actions) {
+            Some(PresenceState::Offline)
+        } else {
+            None
+        };
+        if let Some(presence) = new_presence {
+            submit_async_request(MatrixRequest::SetPresence {
+                presence,
+                status_msg: settings.status_msg.clone(),
+            });
+            self.close(cx);
+            return;
+        }
+
+        let new_status_msg = if self.view.button(id!(set_status_button)).clicked(actions)
+            || status_input.returned(actions).is_some()
+        {
+            Some(Some(status_input.text()))
+        } else if self.view.button(id!(clear_status_button)).clicked(actions) {
+            Some(None)
+        } else {
+            None
+        };
+        if let Some(status_msg) = new_status_msg {
+            submit_async_request(MatrixRequest::SetPresence {
+                presence: settings.presence.clone(),
+                status_msg,
+            });
+            self.close(cx);
+            return;
+        }
+
+        if self.view.button(id!(open_settings_button)).clicked(actions) {
+            cx.action(SettingsAction::OpenSettings);
+            self.close(cx);
+        }
+    }
+}
+
+impl ProfileMenu {
+    /// Returns `true` if this menu is currently being shown.
+    pub fn is_currently_shown(&self, _cx: &mut Cx) -> bool {
+        self.visible
+    }
+
+    /// Shows this menu with the current user's presence settings.
+    ///
+    /// Returns the expected (approximate) dimensions of the menu,
+    /// which can be used to proactively reposition it such that it fits on screen.
+    pub fn show(&mut self, cx: &mut Cx) -> DVec2 {
+        let settings = presence_settings();
+        self.view.text_input(id!(status_input))
+            .set_text(cx, settings.status_msg.as_deref().unwrap_or_default());
+        self.view.check_box(id!(share_presence_checkbox)).set_active(cx, settings.share_presence);
+        self.settings = Some(settings);
+        self.update_buttons(cx);
+        for button_id in [id!(set_status_button), id!(clear_status_button), id!(open_settings_button)] {
+            self.view.button(button_id).reset_hover(cx);
+        }
+
+        self.visible = true;
+        cx.set_key_focus(self.view.area());
+        self.redraw(cx);
+
+        let height = (6.0 * BUTTON_HEIGHT)
+            + HEADER_HEIGHT
+            + STATUS_INPUT_HEIGHT + 10.0 // status input and its margins
+            + (3.0 * 7.0) // dividers
+            + 20.0  // top and bottom padding
+            + 1.0;  // top and bottom border
+        dvec2(MENU_WIDTH, height)
+    }
+
+    /// Highlights the button for the user's current presence, and disables
+    /// the presence and status buttons if the user doesn't share their presence.
+    fn update_buttons(&mut self, cx: &mut Cx) {
+        let Some(settings) = self.settings.as_ref() else { return };
+        for (button_id, presence) in [
+            (id!(online_button.button), PresenceState::Online),
+            (id!(unavailable_button.button), PresenceState::Unavailable),
+            (id!(offline_button.button), PresenceState::Offline),
+        ] {
+            let button = self.view.button(button_id);
+            let color = if presence == settings.presence {
+                COLOR_ACTIVE_PRIMARY
+            } else {
+                vec4(0.0, 0.0, 0.0, 1.0)
+            };
+            button.apply_over(cx, live!(
+                draw_text: { color: (color) }
+            ));
+            button.set_enabled(cx, settings.share_presence);
+            button.reset_hover(cx);
+        }
+        self.view.button(id!(set_status_button)).set_enabled(cx, settings.share_presence);
+        self.view.button(id!(clear_status_button)).set_enabled(cx, settings.share_presence);
+        self.redraw(cx);
+    }
+
+    fn close(&mut self, cx: &mut Cx) {
+        self.visible = false;
+        self.settings = None;
+        cx.revert_key_focus();
+        self.redraw(cx);
+    }
+}
+
+impl ProfileMenuRef {
+    /// See [`ProfileMenu::is_currently_shown()`].
+    pub fn is_currently_shown(&self, cx: &mut Cx) -> bool {
+        let Some(inner) = self.borrow() else { return false };
+        inner.is_currently_shown(cx)
+    }
+
+    /// See [`ProfileMenu::show()`].
+    pub fn show(&self, cx: &mut Cx) -> DVec2 {
+        let Some(mut inner) = self.borrow_mut() else { return DVec2::default() };
+        inner.show(cx)
+    }
+}
@@ -0,0 +1,257 @@
+//! A context menu that appears when the user right-clicks
+//! or long-presses on a room preview in the RoomsList.
+
+use makepad_widgets::*;
+use matrix_sdk::{notification_settings::RoomNotificationMode, ruma::OwnedRoomId};
+
+use crate::{shared::styles::COLOR_ACTIVE_PRIMARY, sliding_sync::{submit_async_request, MatrixRequest}, utils::room_name_or_id};
+
+const BUTTON_HEIGHT: f64 = 35.0; // KEEP IN SYNC WITH BUTTON_HEIGHT BELOW
+const MENU_WIDTH: f64 = 250.0;   // KEEP IN SYNC WITH MENU_WIDTH BELOW
+const HEADER_HEIGHT: f64 = 30.0; // KEEP IN SYNC WITH HEADER_HEIGHT BELOW
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    BUTTON_HEIGHT = 35  // KEEP IN SYNC WITH BUTTON_HEIGHT ABOVE
+    MENU_WIDTH = 250    // KEEP IN SYNC WITH MENU_WIDTH ABOVE
+    HEADER_HEIGHT = 30  // KEEP IN SYNC WITH HEADER_HEIGHT ABOVE
+
+    ContextMenuButton = <RobrixIconButton> {
+        height: (BUTTON_HEIGHT)
+        width: Fill,
+        margin: 0,
+        icon_walk: {width: 16, height: 16, margin: {right: 3}}
+    }
+
+    pub RoomContextMenu = {{RoomContextMenu}} {
+        visible: false,
+        flow: Overlay,
+        width: Fill,
+        height: Fill,
+        cursor: Default,
+        // Align to top-left such that our coordinate adjustment
+        // when showing this menu pane will work correctly.
+        align: {x: 0, y: 0}
+
+        // Show a slightly darkened translucent background to make the menu stand out.
+        show_bg: true
+        draw_bg: {
+            fn pixel(self) -> vec4 {
+                return vec4(0., 0., 0., 0.3)
+            }
+        }
+
+        main_content = <RoundedView> {
+            flow: Down
+            width: (MENU_WIDTH),
+            height: Fit,
+            padding: 10
+            spacing: 0,
+            align: {x: 0, y: 0}
+
+            show_bg: true
+            draw_bg: {
+                color: #fff
+                border_radius: 5.0
+                border_size: 0.5
+                border_color: #888
+            }
+
+            room_name = <Label> {
+                width: Fill, height: (HEADER_HEIGHT)
+                padding: {left: 10, top: 5}
+                flow: Right, // do not wrap
+                draw_text: {
+                    wrap: Ellipsis,
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <USERNAME_TEXT_STYLE>{ font_size: 10.5 },
+                }
+                text: ""
+            }
+
+            <LineH> {
+                margin: {top: 3, bottom: 3}
+                width: Fill,
+            }
+
+            all_messages_button = <ContextMenuButton> {
+                draw_icon: { svg_file: (ICON_BELL) }
+                text: "All Messages"
+            }
+
+            mentions_and_keywords_button = <ContextMenuButton> {
+                draw_icon: { svg_file: (ICON_BELL) }
+                text: "Mentions and Keywords Only"
+            }
+
+            mute_button = <ContextMenuButton> {
+                draw_icon: { svg_file: (ICON_BELL_OFF) }
+                text: "Mute"
+            }
+        }
+    }
+}
+
+/// Details about the room that define its context menu content.
+#[derive(Clone, Debug)]
+pub struct RoomContextMenuDetails {
+    /// The ID of the room that this context menu is for.
+    pub room_id: OwnedRoomId,
+    /// The displayable name of the room, if known.
+    pub room_name: Option<String>,
+    /// The room's current notification mode.
+    pub notification_mode: RoomNotificationMode,
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct RoomContextMenu {
+    #[deref] view: View,
+    #[rust] details: Option<RoomContextMenuDetails>,
+}
+
+impl Widget for RoomContextMenu {
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        if self.details.is_none() {
+            self.visible = false;
+        };
+
+        self.view.draw_walk(cx, scope, walk)
+    }
+
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        if !self.visible { return; }
+        self.view.handle_event(cx, event, scope);
+
+        let area = self.view.area();
+
+        // Close the menu if:
+        // 1. The back navigational gesture/action occurs (e.g., Back on Android),
+        // 2. The escape key is pressed if this menu has key focus,
+        // 3. The user clicks/touches outside the main_content view area.
+        // 4. The user scrolls anywhere.
+        let close_menu = {
+            event.back_pressed()
+            || match event.hits_with_capture_overload(cx, area, true) {
+                Hit::KeyUp(key) => key.key_code == KeyCode::Escape,
+                Hit::FingerDown(_fde) => {
+                    cx.set_key_focus(area);
+                    false
+                }
+                Hit::FingerUp(fue) if fue.is_over => {
+                    !self.view(id!(main_content)).area().rect(cx).contains(fue.abs)
+                }
+                Hit::FingerScroll(_) => true,
+                _ => false,
+            }
+        };
+        if close_menu {
+            self.close(cx);
+            return;
+        }
+
+        self.widget_match_event(cx, event, scope);
+    }
+}
+
+impl WidgetMatchEvent for RoomContextMenu {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        let Some(details) = self.details.as_ref() else { return };
+
+        let new_mode = if self.button(id!(all_messages_button)).clicked(actions) {
+            Some(RoomNotificationMode::AllMessages)
+        } else if self.button(id!(mentions_and_keywords_button)).clicked(actions) {
+            Some(RoomNotificationMode::MentionsAndKeywordsOnly)
+        } else if self.button(id!(mute_button)).clicked(actions) {
+            Some(RoomNotificationMode::Mute)
+        } else {
+            None
+        };
+
+        if let Some(mode) = new_mode {
+            if mode != details.notification_mode {
+                submit_async_request(MatrixRequest::SetRoomNotificationMode {
+                    room_id: details.room_id.clone(),
+                    mode,
+                });
+            }
+            self.close(cx);
+        }
+    }
+}
+
+impl RoomContextMenu {
+    /// Returns `true` if this menu is currently being shown.
+    pub fn is_currently_shown(&self, _cx: &mut Cx) -> bool {
+        self.visible
+    }
+
+    /// Shows this context menu with the given room details.
+    ///
+    /// Returns the expected (approximate) dimensions of the context menu,
+    /// which can be used to proactively reposition it such that it fits on screen.
+    pub fn show(&mut self, cx: &mut Cx, details: RoomContextMenuDetails) -> DVec2 {
+        self.view.label(id!(room_name)).set_text(
+            cx,
+            &room_name_or_id(details.room_name.as_ref(), &details.room_id),
+        );
+
+        // Highlight the button for the room's current notification mode.
+        for (button_id, mode) in [
+            (id!(all_messages_button), RoomNotificationMode::AllMessages),
+            (id!(mentions_and_keywords_button), RoomNotificationMode::MentionsAndKeywordsOnly),
+            (id!(mute_button), RoomNotificationMode::Mute),
+        ] {
+            let button = self.view.button(button_id);
+            let color = if mode == details.notification_mode {
+                COLOR_ACTIVE_PRIMARY
+            } else {
+                vec4(0.0, 0.0, 0.0, 1.0)
+            };
+            button.apply_over(cx, live!(
+                draw_icon: { color: (color) }
+                draw_text: { color: (color) }
+            ));
+            button.reset_hover(cx);
+        }
+
+        self.details = Some(details);
+        self.visible = true;
+        cx.set_key_focus(self.view.area());
+        self.redraw(cx);
+
+        let height = (3.0 * BUTTON_HEIGHT)
+            + HEADER_HEIGHT
+            + 7.0   // divider
+            + 20.0  // top and bottom padding
+            + 1.0;  // top and bottom border
+        dvec2(MENU_WIDTH, height)
+    }
+
+    fn close(&mut self, cx: &mut Cx) {
+        self.visible = false;
+        self.details = None;
+        cx.revert_key_focus();
+        self.redraw(cx);
+    }
+}
+
+impl RoomContextMenuRef {
+    /// See [`RoomContextMenu::is_currently_shown()`].
+    pub fn is_currently_shown(&self, cx: &mut Cx) -> bool {
+        let Some(inner) = self.borrow() else { return false };
+        inner.is_currently_shown(cx)
+    }
+
+    /// See [`RoomContextMenu::show()`].
+    pub fn show(&self, cx: &mut Cx, details: RoomContextMenuDetails) -> DVec2 {
+        let Some(mut inner) = self.borrow_mut() else { return DVec2::default() };
+        inner.show(cx, details)
+    }
+}
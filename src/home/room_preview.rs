@@ -1,10 +1,12 @@
 use makepad_widgets::*;
-use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::{notification_settings::RoomNotificationMode, ruma::OwnedRoomId};
 
 use crate::{
-    room::RoomPreviewAvatar, shared::{
+    room::{room_drafts, RoomPreviewAvatar}, shared::{
         avatar::AvatarWidgetExt,
-        html_or_plaintext::HtmlOrPlaintextWidgetExt, unread_badge::UnreadBadgeWidgetExt as _,
+        html_or_plaintext::HtmlOrPlaintextWidgetExt,
+        presence_indicator::PresenceIndicatorWidgetExt,
+        unread_badge::UnreadBadgeWidgetExt as _,
     }, utils::{self, relative_format}
 };
 
@@ -18,6 +20,7 @@ live_design! {
     use crate::shared::helpers::*;
     use crate::shared::avatar::Avatar;
     use crate::shared::html_or_plaintext::HtmlOrPlaintext;
+    use crate::shared::presence_indicator::PresenceIndicator;
     use crate::shared::unread_badge::UnreadBadge;
 
     // A cancel icon to be displayed in the room preview when the room is tombstoned.
@@ -36,6 +39,47 @@ live_design! {
         }
     }
 
+    // A crossed-out bell icon to be displayed in the room preview when the room is muted.
+    MutedIcon = <View> {
+        width: Fit, height: Fit,
+        visible: false,
+
+        <Icon> {
+            width: 14, height: 14,
+            align: {x: 0.5, y: 0.5}
+            draw_icon: {
+                svg_file: (ICON_BELL_OFF)
+                color: (TIMESTAMP_TEXT_COLOR)
+            }
+            icon_walk: { width: 12, height: 12 }
+        }
+    }
+
+    // A pencil icon to be displayed in the room preview when the room has an unsent draft.
+    DraftIcon = <View> {
+        width: Fit, height: Fit,
+        visible: false,
+
+        <Icon> {
+            width: 14, height: 14,
+            align: {x: 0.5, y: 0.5}
+            draw_icon: {
+                svg_file: (ICON_EDIT)
+                color: (TIMESTAMP_TEXT_COLOR)
+            }
+            icon_walk: { width: 12, height: 12 }
+        }
+    }
+
+    // The room's avatar, overlaid with the presence of the other user in a direct room.
+    AvatarWithPresence = <View> {
+        width: Fit, height: Fit
+        flow: Overlay
+        align: {x: 1.0, y: 1.0}
+        avatar = <Avatar> {}
+        presence_indicator = <PresenceIndicator> {}
+    }
+
     RoomName = <Label> {
         width: Fill, height: Fit
         flow: Right, // do not wrap
@@ -161,7 +205,7 @@ live_design! {
                     height: Fit
                     flow: Overlay
                     align: { x: 1.0 }
-                    avatar = <Avatar> {}
+                    <AvatarWithPresence> {}
                     unread_badge = <UnreadBadge> {}
                     tombstone_icon = <TombstoneIcon> {}
                 }
@@ -169,14 +213,16 @@ live_design! {
             IconAndName = <RoomPreviewContent> {
                 padding: 5.
                 align: {x: 0.5, y: 0.5}
-                avatar = <Avatar> {}
+                <AvatarWithPresence> {}
                 room_name = <RoomName> {}
+                draft_icon = <DraftIcon> {}
+                muted_icon = <MutedIcon> {}
                 unread_badge = <UnreadBadge>  {}
                 tombstone_icon = <TombstoneIcon> {}
             }
             FullPreview = <RoomPreviewContent> {
                 padding: 10
-                avatar = <Avatar> {}
+                <AvatarWithPresence> {}
                 <View> {
                     flow: Down
                     width: Fill, height: 56
@@ -186,6 +232,8 @@ live_design! {
                         spacing: 3,
                         flow: Right,
                         room_name = <RoomName> {}
+                        draft_icon = <DraftIcon> {}
+                        muted_icon = <MutedIcon> {}
                         timestamp = <Timestamp> { }
                     }
                     bottom = <View> {
@@ -217,6 +265,14 @@ pub struct RoomPreview {
 #[derive(Clone, DefaultNone, Debug)]
 pub enum RoomPreviewAction {
     Clicked(OwnedRoomId),
+    /// The user right-clicked or long-pressed on the room preview,
+    /// requesting that its context menu be shown.
+    OpenContextMenu {
+        room_id: OwnedRoomId,
+        /// The absolute position where we should show the context menu,
+        /// in which the (0,0) origin coordinate is the top left corner of the app window.
+        abs_pos: DVec2,
+    },
     None,
 }
 
@@ -242,8 +298,29 @@ impl Widget for RoomPreview {
         // will just select the room, rather than resulting in a click on any child view
         // within the room preview content itself, such as links or avatars.
         match event.hits(cx, self.view.area()) {
-            Hit::FingerDown(..) => {
+            Hit::FingerDown(fe) => {
                 cx.set_key_focus(self.view.area());
+                // A right click means we should display the context menu.
+                if fe.device.mouse_button().is_some_and(|b| b.is_secondary()) {
+                    cx.widget_action(
+                        uid,
+                        &scope.path,
+                        RoomPreviewAction::OpenContextMenu {
+                            room_id: self.room_id.clone().unwrap(),
+                            abs_pos: fe.abs,
+                        },
+                    );
+                }
+            }
+            Hit::FingerLongPress(lp) => {
+                cx.widget_action(
+                    uid,
+                    &scope.path,
+                    RoomPreviewAction::OpenContextMenu {
+                        room_id: self.room_id.clone().unwrap(),
+                        abs_pos: lp.abs,
+                    },
+                );
             }
             Hit::FingerUp(fe) => {
                 if !rooms_list_props.was_scrolling && fe.is_over && fe.is_primary_hit() && fe.was_tap() {
@@ -310,10 +387,16 @@ impl RoomPreviewContent {
                 .show_html(cx, msg);
         }
 
+        let is_muted = room_info.notification_mode == RoomNotificationMode::Mute;
         self.view
             .unread_badge(id!(unread_badge))
             .update_counts(room_info.num_unread_mentions, room_info.num_unread_messages);
-        self.draw_common(cx, &room_info.avatar, room_info.is_selected);
+        self.view.view(id!(muted_icon)).set_visible(cx, is_muted);
+        self.view.view(id!(draft_icon)).set_visible(cx, room_drafts::has_draft(&room_info.room_id));
+        self.view
+            .presence_indicator(id!(presence_indicator))
+            .set_user(cx, room_info.direct_user_id.as_deref());
+        self.draw_common(cx, &room_info.avatar, room_info.is_selected, is_muted);
         // Show tombstone icon if the room is tombstoned
         self.view.view(id!(tombstone_icon)).set_visible(cx, room_info.is_tombstoned);
     }
@@ -355,7 +438,10 @@ impl RoomPreviewContent {
             .unread_badge(id!(unread_badge))
             .update_counts(1, 0);
 
-        self.draw_common(cx, &room_info.room_avatar, room_info.is_selected);
+        self.view.view(id!(muted_icon)).set_visible(cx, false);
+        self.view.view(id!(draft_icon)).set_visible(cx, false);
+        self.view.presence_indicator(id!(presence_indicator)).set_user(cx, None);
+        self.draw_common(cx, &room_info.room_avatar, room_info.is_selected, false);
     }
 
     /// Populates the widgets common to both invited and joined room previews.
@@ -364,6 +450,7 @@ impl RoomPreviewContent {
         cx: &mut Cx,
         room_avatar: &RoomPreviewAvatar,
         is_selected: bool,
+        is_muted: bool,
     ) {
         match room_avatar {
             RoomPreviewAvatar::Text(text) => {
@@ -379,17 +466,19 @@ impl RoomPreviewContent {
         }
 
         if cx.display_context.is_desktop() {
-            self.update_preview_colors(cx, is_selected);
+            self.update_preview_colors(cx, is_selected, is_muted);
         } else {
             // Mobile doesn't have a selected state. Always use the default colors.
             // We call the update in case the app was resized from desktop to mobile while the room was selected.
             // This can be optimized by only calling this when the app is resized.
-            self.update_preview_colors(cx, false);
+            self.update_preview_colors(cx, false, is_muted);
         }
     }
 
     /// Updates the styling of the preview based on whether the room is selected or not.
-    pub fn update_preview_colors(&mut self, cx: &mut Cx, is_selected: bool) {
+    ///
+    /// Muted rooms that aren't selected are drawn with dimmed text colors.
+    pub fn update_preview_colors(&mut self, cx: &mut Cx, is_selected: bool, is_muted: bool) {
         let bg_color;
         let message_text_color;
         let room_name_color;
@@ -403,6 +492,12 @@ impl RoomPreviewContent {
             room_name_color = vec3(1., 1., 1.); // COLOR_PRIMARY
             timestamp_color = vec3(1., 1., 1.); // COLOR_PRIMARY
             code_bg_color = vec3(0.3, 0.3, 0.3); // a darker gray, used for `code_color` and `quote_bg_color`
+        } else if is_muted {
+            bg_color = vec3(1., 1., 1.); // COLOR_PRIMARY
+            message_text_color = vec3(0.6, 0.6, 0.6);
+            room_name_color = vec3(0.5, 0.5, 0.5);
+            timestamp_color = vec3(0.7, 0.7, 0.7);
+            code_bg_color = vec3(0.929, 0.929, 0.929); // #EDEDED, see `code_color` and `quote_bg_color`
         } else {
             bg_color = vec3(1., 1., 1.); // COLOR_PRIMARY
             message_text_color = vec3(0.267, 0.267, 0.267); // MESSAGE_TEXT_COLOR
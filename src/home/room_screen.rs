@@ -1,13 +1,13 @@
 //! A room screen is the UI view that displays a single Room's timeline of events/messages
 //! along with a message input bar at the bottom.
 
-use std::{borrow::Cow, cell::RefCell, collections::BTreeMap, ops::{DerefMut, Range}, sync::Arc};
+use std::{borrow::Cow, cell::RefCell, collections::{BTreeMap, HashSet}, ops::{DerefMut, Range}, sync::Arc};
 
 use bytesize::ByteSize;
 use imbl::Vector;
 use makepad_widgets::{image_cache::ImageBuffer, *};
 use matrix_sdk::{
-    room::RoomMember, ruma::{
+    crypto::types::events::UtdCause, room::RoomMember, ruma::{
         events::{
             receipt::Receipt,
             room::{
@@ -30,12 +30,17 @@ use crate::{
         user_profile::{AvatarState, ShowUserProfileAction, UserProfile, UserProfileAndRoomId, UserProfilePaneInfo, UserProfileSlidingPaneRef, UserProfileSlidingPaneWidgetExt},
         user_profile_cache,
     },
-    room::{room_input_bar::RoomInputBarState, typing_notice::TypingNoticeWidgetExt},
+    persistence::RoomDraft,
+    room::{member_list_pane::{MemberListPaneWidgetExt, DISPLAYED_MEMBERSHIPS}, room_drafts, room_input_bar::RoomInputBarState, typing_notice::TypingNoticeWidgetExt},
     shared::{
         avatar::AvatarWidgetRefExt, callout_tooltip::TooltipAction, html_or_plaintext::{HtmlOrPlaintextRef, HtmlOrPlaintextWidgetRefExt, RobrixHtmlLinkAction}, jump_to_bottom_button::{JumpToBottomButtonWidgetExt, UnreadMessageCount}, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, restore_status_view::RestoreStatusViewWidgetExt, styles::*, text_or_image::{TextOrImageRef, TextOrImageWidgetRefExt}, timestamp::TimestampWidgetRefExt
     },
-    sliding_sync::{get_client, submit_async_request, take_timeline_endpoints, BackwardsPaginateUntilEventRequest, MatrixRequest, PaginationDirection, TimelineEndpoints, TimelineRequestSender, UserPowerLevels}, utils::{self, room_name_or_id, unix_time_millis_to_datetime, ImageFormat, MEDIA_THUMBNAIL_FORMAT}
+    sliding_sync::{get_client, is_user_ignored, submit_async_request, take_timeline_endpoints, BackwardsPaginateUntilEventRequest, MatrixRequest, PaginationDirection, TimelineEndpoints, TimelineRequestSender, UserPowerLevels}, utils::{self, room_name_or_id, unix_time_millis_to_datetime, ImageFormat, MEDIA_THUMBNAIL_FORMAT}
 };
+use crate::home::account_switcher::AccountSwitchAction;
+use crate::logout::logout_confirm_modal::LogoutAction;
+use crate::settings::{account_settings::OwnProfileAction, SettingsAction, SettingsSection};
+use crate::verification::VerificationTarget;
 use crate::home::event_reaction_list::ReactionListWidgetRefExt;
 use crate::home::room_read_receipt::AvatarRowWidgetRefExt;
 use crate::room::room_input_bar::RoomInputBarWidgetExt;
@@ -55,6 +60,9 @@ const MAX_ITEMS_TO_SEARCH_THROUGH: usize = 100;
 /// The max size (width or height) of a blurhash image to decode.
 const BLURHASH_IMAGE_MAX_SIZE: u32 = 500;
 
+/// How long to wait after the user stops editing a room's draft before saving it to disk.
+const DRAFT_SAVE_DELAY_SECS: f64 = 2.0;
+
 
 live_design! {
     use link::theme::*;
@@ -70,6 +78,7 @@ live_design! {
     use crate::shared::icon_button::*;
     use crate::shared::jump_to_bottom_button::*;
     use crate::profile::user_profile::UserProfileSlidingPane;
+    use crate::room::member_list_pane::MemberListPane;
     use crate::home::edited_indicator::*;
     use crate::home::event_reaction_list::*;
     use crate::home::loading_pane::*;
@@ -224,7 +233,7 @@ live_design! {
                     }
                 }
 
-                message = <HtmlOrPlaintext> { }
+                message = <HtmlOrPlaintext> { highlight_keywords: true }
                 link_preview_view = <LinkPreview> {}
 
                 // <LineH> {
@@ -269,7 +278,7 @@ live_design! {
                 flow: Down,
                 padding: { left: 10.0 }
 
-                message = <HtmlOrPlaintext> { }
+                message = <HtmlOrPlaintext> { highlight_keywords: true }
                 link_preview_view = <LinkPreview> {}
                 <View> {
                     width: Fill,
@@ -376,6 +385,47 @@ live_design! {
     }
 
 
+    UtdActionButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: {top: 5, bottom: 5, left: 8, right: 8}
+        margin: 0,
+        draw_bg: {
+            color: (COLOR_SECONDARY)
+        }
+        icon_walk: {width: 12, height: 12, margin: 0}
+        draw_text: {
+            text_style: <SMALL_STATE_TEXT_STYLE> {},
+        }
+    }
+
+    // The view used for each event that could not be decrypted.
+    // This is a SmallStateEvent with buttons below it that may help to decrypt the event.
+    UnableToDecryptEvent = <SmallStateEvent> {
+        flow: Down,
+
+        utd_buttons = <View> {
+            width: Fill, height: Fit
+            flow: RightWrap,
+            // Align the buttons with the content label, which is after the timestamp and avatar.
+            margin: {left: 106, top: 3}
+            spacing: 6
+
+            request_keys_button = <UtdActionButton> {
+                draw_icon: { svg_file: (ICON_IMPORT) }
+                text: "Request Keys"
+            }
+            restore_backup_button = <UtdActionButton> {
+                draw_icon: { svg_file: (ICON_CLOUD_CHECKMARK) }
+                text: "Restore from Backup"
+            }
+            verify_session_button = <UtdActionButton> {
+                draw_icon: { svg_file: (ICON_CHECKMARK) }
+                text: "Verify This Session"
+            }
+        }
+    }
+
+
     // The view used for each day divider in a room's timeline.
     // The date text is centered between two horizontal lines.
     DateDivider = <View> {
@@ -467,6 +517,7 @@ live_design! {
             ImageMessage = <ImageMessage> {}
             CondensedImageMessage = <CondensedImageMessage> {}
             SmallStateEvent = <SmallStateEvent> {}
+            UnableToDecryptEvent = <UnableToDecryptEvent> {}
             Empty = <Empty> {}
             DateDivider = <DateDivider> {}
             ReadMarker = <ReadMarker> {}
@@ -478,6 +529,31 @@ live_design! {
     }
 
 
+    // A round button that opens the room's member list.
+    MemberListButton = <View> {
+        width: Fill, height: Fit,
+        align: {x: 1.0, y: 0.0},
+        padding: {top: 8, right: 12},
+
+        member_list_button = <IconButton> {
+            spacing: 0,
+            width: 40, height: 40,
+            draw_icon: {svg_file: (ICON_MEMBERS)},
+            icon_walk: {width: 18, height: 18, margin: {top: 1, left: 1} }
+            // draw a circular background for the button
+            draw_bg: {
+                instance background_color: #edededce,
+                fn pixel(self) -> vec4 {
+                    let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                    let c = self.rect_size * 0.5;
+                    sdf.circle(c.x, c.x, c.x);
+                    sdf.fill_keep(self.background_color);
+                    return sdf.result
+                }
+            }
+        }
+    }
+
     pub RoomScreen = {{RoomScreen}} {
         width: Fill, height: Fill,
         cursor: Default,
@@ -514,6 +590,13 @@ live_design! {
             // The top space should be displayed as an overlay at the top of the timeline.
             top_space = <TopSpace> { }
 
+            // The button that opens the member list is shown in the top-right corner of the timeline.
+            member_list_button_view = <MemberListButton> { }
+
+            // The member list pane slides in from the right, beneath the user profile sliding pane
+            // such that a member's profile can be shown on top of the member list.
+            member_list_pane = <MemberListPane> { }
+
             // The user profile sliding pane should be displayed on top of other "static" subviews
             // (on top of all other views that are always visible).
             user_profile_sliding_pane = <UserProfileSlidingPane> { }
@@ -562,6 +645,8 @@ pub struct RoomScreen {
     #[rust] is_loaded: bool,
     /// Whether or not all rooms have been loaded (received from the homeserver).
     #[rust] all_rooms_loaded: bool,
+    /// The timer used to save this room's draft shortly after the user stops editing it.
+    #[rust] draft_save_timer: Timer,
 }
 impl Drop for RoomScreen {
     fn drop(&mut self) {
@@ -590,15 +675,60 @@ impl Widget for RoomScreen {
         let room_screen_widget_uid = self.widget_uid();
         let portal_list = self.portal_list(id!(timeline.list));
         let user_profile_sliding_pane = self.user_profile_sliding_pane(id!(user_profile_sliding_pane));
+        let member_list_pane = self.member_list_pane(id!(member_list_pane));
         let loading_pane = self.loading_pane(id!(loading_pane));
 
+        // Record this room's unsent draft before the app shuts down or switches accounts,
+        // so that it can be persisted.
+        match event {
+            Event::Shutdown => self.record_draft(),
+            Event::Actions(actions) if actions.iter().any(|a| a.downcast_ref::<AccountSwitchAction>().is_some()) => {
+                self.record_draft();
+            }
+            // The app state is being cleared, e.g., when switching accounts on mobile,
+            // where no tabs are closed beforehand. Thus, we flush this room's draft and state
+            // as if it were being hidden, such that nothing is recorded into the cleared state later.
+            Event::Actions(actions) if self.tl_state.is_some()
+                && actions.iter().any(|a| matches!(a.downcast_ref(), Some(LogoutAction::ClearAppState { .. }))) =>
+            {
+                self.hide_timeline();
+            }
+            _ => { }
+        }
+
+        // Persist this room's draft once the user has stopped typing for a moment,
+        // so that it isn't lost if the app crashes or is killed.
+        if let Event::Actions(actions) = event {
+            if self.view.text_input(id!(room_input_bar.mentionable_text_input.text_input)).changed(actions).is_some() {
+                cx.stop_timer(self.draft_save_timer);
+                self.draft_save_timer = cx.start_timeout(DRAFT_SAVE_DELAY_SECS);
+            }
+        }
+        if self.draft_save_timer.is_event(event).is_some() {
+            self.record_draft();
+            room_drafts::save_drafts();
+        }
+
         // Handle actions here before processing timeline updates.
         // Normally (in most other widgets), the order of event handling doesn't matter much.
         // However, since actions may refer to a specific timeline item's index,
         // we want to handle those before processing any updates that might change
         // the set of timeline indices (which would invalidate the index values in any actions).
         if let Event::Actions(actions) = event {
-            for (_, wr) in portal_list.items_with_actions(actions) {
+            for (item_id, wr) in portal_list.items_with_actions(actions) {
+                // Handle the buttons shown on events that could not be decrypted.
+                if wr.button(id!(request_keys_button)).clicked(actions) {
+                    self.request_keys_for_item(item_id);
+                }
+                if wr.button(id!(restore_backup_button)).clicked(actions) {
+                    cx.action(SettingsAction::OpenSettingsSection(SettingsSection::KeyBackup));
+                }
+                if wr.button(id!(verify_session_button)).clicked(actions) {
+                    submit_async_request(MatrixRequest::RequestVerification {
+                        target: VerificationTarget::OwnSession,
+                    });
+                }
+
                 let reaction_list = wr.reaction_list(id!(reaction_list));
                 if let RoomScreenTooltipActions::HoverInReactionButton {
                     widget_rect,
@@ -671,8 +801,15 @@ impl Widget for RoomScreen {
                         return;
                     }
                 }
-                // Handle the highlight animation.
                 let Some(tl) = self.tl_state.as_mut() else { continue };
+
+                // Redraw all event profiles, since our own name and avatar may be shown in any of them.
+                if let Some(OwnProfileAction::DisplayNameChanged(_) | OwnProfileAction::AvatarChanged { .. }) = action.downcast_ref() {
+                    tl.profile_drawn_since_last_update.clear();
+                    self.view.redraw(cx);
+                }
+
+                // Handle the highlight animation.
                 if let MessageHighlightAnimationState::Pending { item_id } = tl.message_highlight_animation_state {
                     if portal_list.smooth_scroll_reached(actions) {
                         cx.widget_action(
@@ -703,6 +840,18 @@ impl Widget for RoomScreen {
                 }
             }
 
+            // Show the member list pane, and refresh the room's members from the server.
+            if self.button(id!(member_list_button)).clicked(actions) {
+                if let Some(tl) = self.tl_state.as_ref() {
+                    member_list_pane.show(cx, tl.room_id.clone(), tl.room_members.clone());
+                    submit_async_request(MatrixRequest::GetRoomMembers {
+                        room_id: tl.room_id.clone(),
+                        memberships: DISPLAYED_MEMBERSHIPS,
+                        local_only: false,
+                    });
+                }
+            }
+
             /*
             // close message action bar if scrolled.
             if portal_list.scrolled(actions) {
@@ -773,6 +922,12 @@ impl Widget for RoomScreen {
                 user_profile_sliding_pane.handle_event(cx, event, scope);
             }
         }
+        else if member_list_pane.is_currently_shown(cx) {
+            is_pane_shown = true;
+            if is_interactive_hit {
+                member_list_pane.handle_event(cx, event, scope);
+            }
+        }
         else {
             is_pane_shown = false;
         }
@@ -1091,6 +1246,7 @@ impl RoomScreen {
     fn process_timeline_updates(&mut self, cx: &mut Cx, portal_list: &PortalListRef) {
         let top_space = self.view(id!(top_space));
         let jump_to_bottom = self.jump_to_bottom_button(id!(jump_to_bottom));
+        let member_list_pane = self.member_list_pane(id!(member_list_pane));
         let curr_first_id = portal_list.first_id();
         let ui = self.widget_uid();
         let Some(tl) = self.tl_state.as_mut() else { return };
@@ -1118,38 +1274,56 @@ impl RoomScreen {
                     if new_items.is_empty() {
                         if !tl.items.is_empty() {
                             log!("process_timeline_updates(): timeline (had {} items) was cleared for room {}", tl.items.len(), tl.room_id);
-                            // For now, we paginate a cleared timeline in order to be able to show something at least.
-                            // A proper solution would be what's described below, which would be to save a few event IDs
-                            // and then either focus on them (if we're not close to the end of the timeline)
-                            // or paginate backwards until we find them (only if we are close the end of the timeline).
+                            // The Matrix SDK clears all timelines after a user is (un)ignored,
+                            // so we remember the first few events that were visible in the viewport
+                            // in order to scroll back to them once they have been re-paginated.
+                            // Events from ignored users won't come back, so they can't be anchors.
+                            if tl.restore_after_clear.is_none() {
+                                let anchor_event_ids: Vec<OwnedEventId> = tl.items.iter()
+                                    .skip(curr_first_id)
+                                    .filter_map(|item| item.as_event())
+                                    .filter(|ev| !is_user_ignored(ev.sender()))
+                                    .filter_map(|ev| ev.event_id().map(ToOwned::to_owned))
+                                    .take(RESTORE_AFTER_CLEAR_MAX_ANCHORS)
+                                    .collect();
+                                if !anchor_event_ids.is_empty() {
+                                    tl.restore_after_clear = Some(RestoreAfterClear {
+                                        anchor_event_ids,
+                                        remaining_paginations: RESTORE_AFTER_CLEAR_MAX_PAGINATIONS,
+                                    });
+                                }
+                            }
+                            tl.fully_paginated = false;
                             should_continue_backwards_pagination = true;
                         }
-
-                        // If the bottom of the timeline (the last event) is visible, then we should
-                        // set the timeline to live mode.
-                        // If the bottom of the timeline is *not* visible, then we should
-                        // set the timeline to Focused mode.
-
-                        // TODO: Save the event IDs of the top 3 items before we apply this update,
-                        //       which indicates this timeline is in the process of being restored,
-                        //       such that we can jump back to that position later after applying this update.
-
-                        // TODO: here we need to re-build the timeline via TimelineBuilder
-                        //       and set the TimelineFocus to one of the above-saved event IDs.
-
-                        // TODO: the docs for `TimelineBuilder::with_focus()` claim that the timeline's focus mode
-                        //       can be changed after creation, but I do not see any methods to actually do that.
-                        //       <https://matrix-org.github.io/matrix-rust-sdk/matrix_sdk_ui/timeline/struct.TimelineBuilder.html#method.with_focus>
-                        //
-                        //       As such, we probably need to create a new async request enum variant
-                        //       that tells the background async task to build a new timeline
-                        //       (either in live mode or focused mode around one or more events)
-                        //       and then replaces the existing timeline in ALL_ROOMS_INFO with the new one.
                     }
 
                     let prior_items_changed = clear_cache || changed_indices.start <= curr_first_id;
 
-                    if new_items.len() == tl.items.len() {
+                    // If this timeline was previously cleared, restore the viewport to an event
+                    // that was visible before, paginating backwards until one of them is found.
+                    if let Some(restore) = tl.restore_after_clear.as_mut().filter(|_| !new_items.is_empty()) {
+                        let anchor = restore.anchor_event_ids.iter().find_map(|anchor_event_id|
+                            new_items.iter().position(|item|
+                                item.as_event().and_then(|ev| ev.event_id()).is_some_and(|id| id == anchor_event_id)
+                            ).map(|idx| (anchor_event_id, idx))
+                        );
+                        if let Some((anchor_event_id, idx)) = anchor {
+                            log!("process_timeline_updates(): restoring cleared timeline to event {anchor_event_id} at index {idx}");
+                            portal_list.set_first_id_and_scroll(idx, 0.0);
+                            tl.prev_first_index = Some(idx);
+                            tl.restore_after_clear = None;
+                        } else if tl.fully_paginated || restore.remaining_paginations == 0 {
+                            // Give up and show the latest events instead.
+                            portal_list.set_first_id_and_scroll(new_items.len().saturating_sub(1), 0.0);
+                            portal_list.set_tail_range(true);
+                            tl.restore_after_clear = None;
+                        } else {
+                            restore.remaining_paginations -= 1;
+                            should_continue_backwards_pagination = true;
+                        }
+                    }
+                    else if new_items.len() == tl.items.len() {
                         // log!("process_timeline_updates(): no jump necessary for updated timeline of same length: {}", items.len());
                     }
                     else if curr_first_id > new_items.len() {
@@ -1177,9 +1351,6 @@ impl RoomScreen {
                             cx.widget_action(ui, &HeapLiveIdPath::default(), RoomScreenTooltipActions::HoverOut);
                         }
                     }
-                    //
-                    // TODO: after an (un)ignore user event, all timelines are cleared. Handle that here.
-                    //
                     else {
                         // warning!("!!! Couldn't find new event with matching ID for ANY event currently visible in the portal list");
                     }
@@ -1319,12 +1490,20 @@ impl RoomScreen {
                 }
                 TimelineUpdate::RoomMembersSynced => {
                     // log!("process_timeline_updates(): room members fetched for room {}", tl.room_id);
+                    // Now that the members have been synced, get the updated list of members from the local cache.
+                    submit_async_request(MatrixRequest::GetRoomMembers {
+                        room_id: tl.room_id.clone(),
+                        memberships: DISPLAYED_MEMBERSHIPS,
+                        local_only: true,
+                    });
                     // Here, to be most efficient, we could redraw only the user avatars and names in the timeline,
                     // but for now we just fall through and let the final `redraw()` call re-draw the whole timeline view.
                 }
                 TimelineUpdate::RoomMembersListFetched { members } => {
                     // Store room members directly in TimelineUiState
-                    tl.room_members = Some(Arc::new(members));
+                    let members = Arc::new(members);
+                    member_list_pane.set_members(cx, members.clone());
+                    tl.room_members = Some(members);
                 },
                 TimelineUpdate::MediaFetched => {
                     log!("process_timeline_updates(): media fetched for room {}", tl.room_id);
@@ -1389,6 +1568,24 @@ impl RoomScreen {
                 TimelineUpdate::OwnUserReadReceipt(receipt) => {
                     tl.latest_own_user_receipt = Some(receipt);
                 }
+                TimelineUpdate::EventsDecrypted(event_ids) => {
+                    // The decrypted events have already been replaced in place by the `NewItems` update
+                    // sent right before this one, so here we only need to report on the keys we requested.
+                    let num_requested = event_ids.iter()
+                        .filter(|event_id| tl.pending_key_requests.remove(*event_id))
+                        .count();
+                    if num_requested > 0 {
+                        enqueue_popup_notification(PopupItem {
+                            message: if num_requested == 1 {
+                                String::from("Received the keys for a message, which is now decrypted.")
+                            } else {
+                                format!("Received the keys for {num_requested} messages, which are now decrypted.")
+                            },
+                            kind: PopupKind::Success,
+                            auto_dismissal_duration: Some(5.0),
+                        });
+                    }
+                }
                 TimelineUpdate::Tombstoned(successor_room) => {
                     self.view.room_input_bar(id!(room_input_bar))
                         .update_tombstone_footer(cx, &tl.room_id, successor_room.as_ref());
@@ -1415,6 +1612,14 @@ impl RoomScreen {
                 .show_or_hide(cx, &users);
         }
 
+        // Newly-loaded items may include the target events of a draft reply or edit.
+        if num_updates > 0 {
+            if let Some(draft) = tl.pending_draft_target.take() {
+                tl.pending_draft_target = self.view.room_input_bar(id!(room_input_bar))
+                    .restore_draft_target(cx, &tl.room_id, draft, &tl.items);
+            }
+        }
+
         if num_updates > 0 {
             // log!("Applied {} timeline updates for room {}, redrawing with {} items...", num_updates, tl.room_id, tl.items.len());
             self.redraw(cx);
@@ -1538,6 +1743,30 @@ impl RoomScreen {
         }
     }
 
+    /// Re-requests the decryption key for the undecryptable event at the given `item_id`
+    /// and then retries decrypting it.
+    fn request_keys_for_item(&mut self, item_id: usize) {
+        let Some(tl) = self.tl_state.as_mut() else { return };
+        let Some(event_tl_item) = tl.items.get(item_id).and_then(|item| item.as_event()) else { return };
+        let TimelineItemContent::MsgLike(MsgLikeContent {
+            kind: MsgLikeKind::UnableToDecrypt(EncryptedMessage::MegolmV1AesSha2 { session_id, .. }),
+            ..
+        }) = event_tl_item.content() else { return };
+
+        if let Some(event_id) = event_tl_item.event_id() {
+            tl.pending_key_requests.insert(event_id.to_owned());
+        }
+        submit_async_request(MatrixRequest::RetryDecryption {
+            room_id: tl.room_id.clone(),
+            session_id: session_id.clone(),
+        });
+        enqueue_popup_notification(PopupItem {
+            message: String::from("Requested the keys for this message. It will be decrypted once they arrive."),
+            kind: PopupKind::Info,
+            auto_dismissal_duration: Some(4.0),
+        });
+    }
+
     /// Handles any [`MessageAction`]s received by this RoomScreen.
     fn handle_message_actions(
         &mut self,
@@ -1933,6 +2162,10 @@ impl RoomScreen {
             .expect("BUG: Timeline::show_timeline(): no room_id was set.");
 
         let state_opt = TIMELINE_STATES.with_borrow_mut(|ts| ts.remove(&room_id));
+        // If this room hasn't been shown yet in this session, restore its persisted draft (if any).
+        let draft_to_restore = state_opt.is_none()
+            .then(|| room_drafts::get_draft(&room_id))
+            .flatten();
         let (mut tl_state, mut is_first_time_being_loaded) = if let Some(existing) = state_opt {
             (existing, false)
         } else {
@@ -1975,6 +2208,9 @@ impl RoomScreen {
                 scrolled_past_read_marker: false,
                 latest_own_user_receipt: None,
                 tombstone_info: successor_room,
+                pending_draft_target: None,
+                pending_key_requests: HashSet::new(),
+                restore_after_clear: None,
             };
             (tl_state, true)
         };
@@ -2037,7 +2273,8 @@ impl RoomScreen {
             });
             submit_async_request(MatrixRequest::GetRoomMembers {
                 room_id: room_id.clone(),
-                memberships: matrix_sdk::RoomMemberships::JOIN,
+                // Invited and banned users are also included such that they can be shown in the member list.
+                memberships: DISPLAYED_MEMBERSHIPS,
                 // Fetch from the local cache, as we already requested to sync
                 // the room members from the homeserver above.
                 local_only: true,
@@ -2058,6 +2295,11 @@ impl RoomScreen {
 
         // Now, restore the visual state of this timeline from its previously-saved state.
         self.restore_state(cx, &mut tl_state);
+        if let Some(draft) = draft_to_restore {
+            let room_input_bar = self.view.room_input_bar(id!(room_input_bar));
+            room_input_bar.restore_draft_text(cx, &draft);
+            tl_state.pending_draft_target = room_input_bar.restore_draft_target(cx, &room_id, draft, &tl_state.items);
+        }
 
         // Store the tl_state for this room into this RoomScreen widget,
         // such that it can be accessed in future functions like event/draw handlers.
@@ -2074,6 +2316,8 @@ impl RoomScreen {
     fn hide_timeline(&mut self) {
         let Some(room_id) = self.room_id.clone() else { return };
 
+        self.record_draft();
+        room_drafts::save_drafts();
         self.save_state();
 
         // When closing a room view, we do the following with non-persistent states:
@@ -2095,6 +2339,21 @@ impl RoomScreen {
         });
     }
 
+    /// Records the current room's unsent message draft such that it can be persisted.
+    ///
+    /// Does nothing if this room's timeline hasn't been shown yet,
+    /// in order to avoid overwriting its persisted draft with an empty one.
+    fn record_draft(&self) {
+        let Some(tl) = self.tl_state.as_ref() else { return };
+        let mut draft = self.view.room_input_bar(id!(room_input_bar)).draft();
+        // Keep the parts of a restored draft that are still waiting on their target events.
+        if let Some(pending) = tl.pending_draft_target.as_ref() {
+            draft.replying_to = draft.replying_to.or_else(|| pending.replying_to.clone());
+            draft.editing = draft.editing.or_else(|| pending.editing.clone());
+        }
+        room_drafts::set_draft(tl.room_id.clone(), draft);
+    }
+
     /// Removes the current room's visual UI state from this widget
     /// and saves it to the map of `TIMELINE_STATES` such that it can be restored later.
     ///
@@ -2160,6 +2419,7 @@ impl RoomScreen {
         self.hide_timeline();
         // Reset the the state of the inner loading pane.
         self.loading_pane(id!(loading_pane)).take_state();
+        self.member_list_pane(id!(member_list_pane)).reset(cx);
         self.room_name = room_name_or_id(room_name.into(), &room_id);
         self.room_id = Some(room_id.clone());
 
@@ -2414,6 +2674,9 @@ pub enum TimelineUpdate {
     UserPowerLevels(UserPowerLevels),
     /// An update to the currently logged-in user's own read receipt for this room.
     OwnUserReadReceipt(Receipt),
+    /// A notice that the given events, which previously could not be decrypted,
+    /// have now been decrypted in place because their keys have arrived.
+    EventsDecrypted(Vec<OwnedEventId>),
     /// A notice that the given room has been tombstoned,
     /// includes a `SuccessorRoom` that contains the successor room.
     /// If the room is not tombstoned, then the `SuccessorRoom` is `None`.
@@ -2442,7 +2705,7 @@ struct TimelineUiState {
     /// The power levels of the currently logged-in user in this room.
     user_power: UserPowerLevels,
 
-    /// The list of room members for this room.
+    /// The list of room members for this room, including invited and banned users.
     room_members: Option<Arc<Vec<RoomMember>>>,
 
     /// Whether this room's timeline has been fully paginated, which means
@@ -2533,6 +2796,35 @@ struct TimelineUiState {
     /// If this room has been tombstoned, this has details of its successor room.
     /// If the room is not tombstoned, this is `None`.
     tombstone_info: Option<SuccessorRoom>,
+
+    /// The reply and/or edit of a persisted draft whose target events
+    /// have not yet been loaded into this timeline, and thus couldn't yet be restored.
+    pending_draft_target: Option<RoomDraft>,
+
+    /// The undecryptable events for which the user explicitly requested keys,
+    /// which are removed once those events have been decrypted.
+    pending_key_requests: HashSet<OwnedEventId>,
+
+    /// The viewport position to restore once this timeline has been re-paginated after being cleared.
+    restore_after_clear: Option<RestoreAfterClear>,
+}
+
+/// The maximum number of previously-visible events to remember as anchors
+/// for restoring the viewport of a cleared timeline.
+const RESTORE_AFTER_CLEAR_MAX_ANCHORS: usize = 5;
+/// The maximum number of backwards pagination requests to send while looking for
+/// an anchor event of a cleared timeline, after which we jump to the bottom instead.
+const RESTORE_AFTER_CLEAR_MAX_PAGINATIONS: usize = 10;
+
+/// The events that were visible at the top of a timeline's viewport when it was cleared,
+/// one of which we scroll back to once the timeline has been re-paginated.
+#[derive(Debug)]
+struct RestoreAfterClear {
+    /// The IDs of the previously-visible events, from top to bottom,
+    /// excluding events sent by ignored users.
+    anchor_event_ids: Vec<OwnedEventId>,
+    /// How many more backwards pagination requests to send while looking for an anchor event.
+    remaining_paginations: usize,
 }
 
 #[derive(Default, Debug)]
@@ -3690,6 +3982,11 @@ trait SmallStateEventContent {
         item_drawn_status: ItemDrawnStatus,
         new_drawn_status: ItemDrawnStatus,
     ) -> (WidgetRef, ItemDrawnStatus);
+
+    /// Returns the ID of the `PortalList` template used to display this kind of event content.
+    fn template(&self) -> LiveId {
+        live_id!(SmallStateEvent)
+    }
 }
 
 /// An empty marker struct used for populating redacted messages.
@@ -3737,9 +4034,61 @@ impl SmallStateEventContent for EncryptedMessage {
             cx,
             &text_preview_of_encrypted_message(self).format_with(username, false),
         );
+        let actions = UtdRecoveryActions::for_message(self);
+        item.button(id!(request_keys_button)).set_visible(cx, actions.request_keys);
+        item.button(id!(restore_backup_button)).set_visible(cx, actions.restore_backup);
+        item.button(id!(verify_session_button)).set_visible(cx, actions.verify_session);
         new_drawn_status.content_drawn = true;
         (item, new_drawn_status)
     }
+
+    fn template(&self) -> LiveId {
+        live_id!(UnableToDecryptEvent)
+    }
+}
+
+/// Which actions may help the user decrypt a message that could not be decrypted.
+#[derive(Debug, Default)]
+struct UtdRecoveryActions {
+    /// Whether to retry decryption after re-requesting the message's key
+    /// from our other devices and from the key backup.
+    request_keys: bool,
+    /// Whether to open the key backup settings in order to restore keys from the backup.
+    restore_backup: bool,
+    /// Whether to verify this session, after which other devices will share keys with it.
+    verify_session: bool,
+}
+
+impl UtdRecoveryActions {
+    /// Returns the recovery actions that make sense for the cause of the given decryption failure.
+    fn for_message(encrypted_message: &EncryptedMessage) -> Self {
+        let EncryptedMessage::MegolmV1AesSha2 { cause, .. } = encrypted_message else {
+            // We can only request keys for megolm sessions.
+            return Self::default();
+        };
+        match cause {
+            // The key may simply not have arrived yet, or may be stored in the backup.
+            UtdCause::Unknown
+            | UtdCause::SentBeforeWeJoined
+            | UtdCause::HistoricalMessageAndBackupIsDisabled => Self {
+                request_keys: true,
+                restore_backup: true,
+                verify_session: false,
+            },
+            // Other devices won't share keys with us until this session is verified.
+            UtdCause::HistoricalMessageAndDeviceIsUnverified
+            | UtdCause::WithheldForUnverifiedOrInsecureDevice => Self {
+                request_keys: false,
+                restore_backup: true,
+                verify_session: true,
+            },
+            // These are caused by the sender, so there's nothing we can do about them.
+            UtdCause::WithheldBySender
+            | UtdCause::VerificationViolation
+            | UtdCause::UnsignedDevice
+            | UtdCause::UnknownDevice => Self::default(),
+        }
+    }
 }
 
 // For other message-like content (custom message-like events).
@@ -3877,7 +4226,7 @@ fn populate_small_state_event(
     item_drawn_status: ItemDrawnStatus,
 ) -> (WidgetRef, ItemDrawnStatus) {
     let mut new_drawn_status = item_drawn_status;
-    let (item, existed) = list.item_with_existed(cx, item_id, live_id!(SmallStateEvent));
+    let (item, existed) = list.item_with_existed(cx, item_id, event_content.template());
     // The content of a small state event view may depend on the profile info,
     // so we can only mark the content as drawn after the profile has been fully drawn and cached.
     let skip_redrawing_profile = existed && item_drawn_status.profile_drawn;
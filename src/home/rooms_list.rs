@@ -1,14 +1,14 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 use crossbeam_queue::SegQueue;
 use makepad_widgets::*;
-use matrix_sdk::{ruma::{events::tag::Tags, MilliSecondsSinceUnixEpoch, OwnedRoomAliasId, OwnedRoomId, OwnedUserId}, RoomState};
+use matrix_sdk::{notification_settings::RoomNotificationMode, ruma::{events::tag::Tags, MilliSecondsSinceUnixEpoch, OwnedRoomAliasId, OwnedRoomId, OwnedUserId}, RoomState};
 use crate::{
     app::{AppState, SelectedRoom},
     room::{room_display_filter::{RoomDisplayFilter, RoomDisplayFilterBuilder, RoomFilterCriteria, SortFn}, RoomPreviewAvatar},
     shared::{collapsible_header::{CollapsibleHeaderAction, CollapsibleHeaderWidgetRefExt, HeaderCategory}, jump_to_bottom_button::UnreadMessageCount, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, room_filter_input_bar::RoomFilterAction},
     sliding_sync::{submit_async_request, MatrixRequest, PaginationDirection}, utils::room_name_or_id,
 };
-use super::room_preview::RoomPreviewAction;
+use super::{room_context_menu::RoomContextMenuDetails, room_preview::RoomPreviewAction, unread_totals::UnreadTotals};
 
 /// Whether to pre-paginate visible rooms at least once in order to
 /// be able to display the latest message in the room preview,
@@ -134,6 +134,11 @@ pub enum RoomsListUpdate {
         room_id: OwnedRoomId,
         new_room_name: Option<String>,
     },
+    /// The full details of the given room have been requested,
+    /// so they need not be requested again.
+    RoomDetailsRequested {
+        room_id: OwnedRoomId,
+    },
     /// Update the avatar (image) for the given room.
     UpdateRoomAvatar {
         room_id: OwnedRoomId,
@@ -143,6 +148,13 @@ pub enum RoomsListUpdate {
     UpdateIsDirect {
         room_id: OwnedRoomId,
         is_direct: bool,
+        /// The ID of the other user in this room, if it is now a direct room with one other user.
+        direct_user_id: Option<OwnedUserId>,
+    },
+    /// Update the notification mode for the given room.
+    UpdateNotificationMode {
+        room_id: OwnedRoomId,
+        mode: RoomNotificationMode,
     },
     /// Remove the given room from the rooms list
     RemoveRoom {
@@ -188,6 +200,15 @@ pub enum RoomsListAction {
         room_id: OwnedRoomId,
         room_name: Option<String>,
     },
+    /// The running totals of unread messages and mentions have changed.
+    UnreadTotalsChanged(UnreadTotals),
+    /// The user requested that we show a context menu for the given room.
+    OpenRoomContextMenu {
+        details: RoomContextMenuDetails,
+        /// The absolute position where we should show the context menu,
+        /// in which the (0,0) origin coordinate is the top left corner of the app window.
+        abs_pos: DVec2,
+    },
     None,
 }
 
@@ -206,6 +227,9 @@ pub struct JoinedRoomInfo {
     pub num_unread_messages: u64,
     /// The number of unread mentions in this room.
     pub num_unread_mentions: u64,
+    /// The user's notification mode for this room.
+    /// Muted rooms don't contribute to the unread totals.
+    pub notification_mode: RoomNotificationMode,
     /// The canonical alias for this room, if any.
     pub canonical_alias: Option<OwnedRoomAliasId>,
     /// The alternative aliases for this room, if any.
@@ -224,10 +248,20 @@ pub struct JoinedRoomInfo {
     /// be able to display the latest message in the room preview,
     /// and to have something to immediately show when a user first opens a room.
     pub has_been_paginated: bool,
+    /// Whether we have requested this room's full details (e.g., its avatar).
+    /// These are only requested once the room is first visible in the RoomsList,
+    /// such that we don't fetch details for every room in very large accounts.
+    ///
+    /// This is only set once the background worker has accepted the request,
+    /// such that a request that couldn't be handled is retried when the room is drawn again.
+    pub has_requested_details: bool,
     /// Whether this room is currently selected in the UI.
     pub is_selected: bool,
     /// Whether this a direct room.
     pub is_direct: bool,
+    /// The ID of the other user in this direct room, if there is exactly one,
+    /// which is used to show their presence.
+    pub direct_user_id: Option<OwnedUserId>,
     /// Whether this room is tombstoned (shut down and replaced with a successor room).
     pub is_tombstoned: bool,
 }
@@ -347,8 +381,14 @@ pub struct RoomsList {
     #[rust] status: String,
     /// The ID of the currently-selected room.
     #[rust] current_active_room: Option<OwnedRoomId>,
-    /// The maximum number of rooms that will ever be loaded.
+    /// The maximum number of joined and invited rooms that will ever be loaded.
     #[rust] max_known_rooms: Option<u32>,
+    /// Whether we have requested another page of rooms from the room list service
+    /// and are still waiting for those rooms to arrive.
+    #[rust] is_loading_more_rooms: bool,
+    /// The running totals of unread messages and mentions for each category of rooms,
+    /// which are updated incrementally as we handle each `RoomsListUpdate`.
+    #[rust] unread_totals: UnreadTotals,
 }
 
 impl LiveHook for RoomsList {
@@ -361,7 +401,7 @@ impl RoomsList {
     /// Determines if all known rooms have been loaded from the homeserver.
     ///
     /// Returns `true` if the number of rooms in `all_joined_rooms` and `invited_rooms` equals or exceeds
-    /// `max_known_rooms`.
+    /// `max_known_rooms`, which only counts joined and invited rooms.
     /// Returns `false` if `max_known_rooms` is `None`.
     pub fn all_known_rooms_loaded(&self) -> bool {
         self.max_known_rooms.is_some_and(|max_rooms| {
@@ -378,16 +418,19 @@ impl RoomsList {
     /// Handle all pending updates to the list of all rooms.
     fn handle_rooms_list_updates(&mut self, cx: &mut Cx, _event: &Event, scope: &mut Scope) {
         let mut num_updates: usize = 0;
+        let prev_unread_totals = self.unread_totals;
         while let Some(update) = PENDING_ROOM_UPDATES.pop() {
             num_updates += 1;
             match update {
                 RoomsListUpdate::AddInvitedRoom(invited_room) => {
+                    self.is_loading_more_rooms = false;
                     let room_id = invited_room.room_id.clone();
                     let should_display = (self.display_filter)(&invited_room);
                     let _replaced = self.invited_rooms.borrow_mut().insert(room_id.clone(), invited_room);
                     if let Some(_old_room) = _replaced {
                         error!("BUG: Added invited room {room_id} that already existed");
                     } else {
+                        self.unread_totals.add_invited_room();
                         if should_display {
                             self.displayed_invited_rooms.push(room_id);
                         }
@@ -397,11 +440,16 @@ impl RoomsList {
                     SignalToUI::set_ui_signal();
                 }
                 RoomsListUpdate::AddJoinedRoom(joined_room) => {
+                    self.is_loading_more_rooms = false;
                     let room_id = joined_room.room_id.clone();
                     let is_direct = joined_room.is_direct;
                     let room_name = joined_room.room_name.clone();
                     let should_display = (self.display_filter)(&joined_room);
+                    self.unread_totals.add_joined_room(&joined_room);
                     let replaced = self.all_joined_rooms.insert(room_id.clone(), joined_room);
+                    if let Some(replaced_room) = replaced.as_ref() {
+                        self.unread_totals.remove_joined_room(replaced_room);
+                    }
                     if replaced.is_none() {
                         if should_display {
                             if is_direct {
@@ -422,6 +470,7 @@ impl RoomsList {
                     //    RoomScreen displaying the now-joined room.
                     if let Some(_accepted_invite) = self.invited_rooms.borrow_mut().remove(&room_id) {
                         log!("Removed room {room_id} from the list of invited rooms");
+                        self.unread_totals.remove_invited_room();
                         self.displayed_invited_rooms.iter()
                             .position(|r| r == &room_id)
                             .map(|index| self.displayed_invited_rooms.remove(index));
@@ -435,6 +484,11 @@ impl RoomsList {
                     // Signal the UI to update the RoomScreen
                     SignalToUI::set_ui_signal();
                 }
+                RoomsListUpdate::RoomDetailsRequested { room_id } => {
+                    if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
+                        room.has_requested_details = true;
+                    }
+                }
                 RoomsListUpdate::UpdateRoomAvatar { room_id, avatar } => {
                     if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
                         room.avatar = avatar;
@@ -451,10 +505,12 @@ impl RoomsList {
                 }
                 RoomsListUpdate::UpdateNumUnreadMessages { room_id, unread_messages, unread_mentions } => {
                     if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
-                        (room.num_unread_messages, room.num_unread_mentions) = match unread_messages {
-                            UnreadMessageCount::Unknown => (0, 0),
-                            UnreadMessageCount::Known(count) => (count, unread_mentions),
-                        };
+                        self.unread_totals.update_joined_room(room, |room| {
+                            (room.num_unread_messages, room.num_unread_mentions) = match unread_messages {
+                                UnreadMessageCount::Unknown => (0, 0),
+                                UnreadMessageCount::Known(count) => (count, unread_mentions),
+                            };
+                        });
                     } else {
                         warning!("Warning: couldn't find room {} to update unread messages count", room_id);
                     }
@@ -492,8 +548,9 @@ impl RoomsList {
                         error!("Error: couldn't find room {room_id} to update room name");
                     }
                 }
-                RoomsListUpdate::UpdateIsDirect { room_id, is_direct } => {
+                RoomsListUpdate::UpdateIsDirect { room_id, is_direct, direct_user_id } => {
                     if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
+                        room.direct_user_id = direct_user_id;
                         if room.is_direct == is_direct {
                             continue;
                         }
@@ -518,7 +575,7 @@ impl RoomsList {
                                 .map(|index| list_to_remove_from.remove(index));
                         }
                         // Update the room. If it should now be displayed, add it to the correct list.
-                        room.is_direct = is_direct;
+                        self.unread_totals.update_joined_room(room, |room| room.is_direct = is_direct);
                         if (self.display_filter)(room) {
                             if is_direct {
                                 self.displayed_direct_rooms.push(room_id);
@@ -530,9 +587,19 @@ impl RoomsList {
                         error!("Error: couldn't find room {room_id} to update is_direct");
                     }
                 }
+                RoomsListUpdate::UpdateNotificationMode { room_id, mode } => {
+                    if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
+                        if room.notification_mode != mode {
+                            self.unread_totals.update_joined_room(room, |room| room.notification_mode = mode);
+                        }
+                    } else {
+                        warning!("Warning: couldn't find room {room_id} to update its notification mode");
+                    }
+                }
                 RoomsListUpdate::RemoveRoom { room_id, new_state: _ } => {
                     if let Some(removed) = self.all_joined_rooms.remove(&room_id) {
                         log!("Removed room {room_id} from the list of all joined rooms");
+                        self.unread_totals.remove_joined_room(&removed);
                         if removed.is_direct {
                             self.displayed_direct_rooms.iter()
                                 .position(|r| r == &room_id)
@@ -545,6 +612,7 @@ impl RoomsList {
                     }
                     else if let Some(_removed) = self.invited_rooms.borrow_mut().remove(&room_id) {
                         log!("Removed room {room_id} from the list of all invited rooms");
+                        self.unread_totals.remove_invited_room();
                         self.displayed_invited_rooms.iter()
                             .position(|r| r == &room_id)
                             .map(|index| self.displayed_invited_rooms.remove(index));
@@ -558,6 +626,7 @@ impl RoomsList {
                     self.displayed_regular_rooms.clear();
                     self.invited_rooms.borrow_mut().clear();
                     self.displayed_invited_rooms.clear();
+                    self.unread_totals = UnreadTotals::default();
                     self.update_status_rooms_count();
                 }
                 RoomsListUpdate::NotLoaded => {
@@ -565,11 +634,12 @@ impl RoomsList {
                 }
                 RoomsListUpdate::LoadedRooms { max_rooms } => {
                     self.max_known_rooms = max_rooms;
+                    self.is_loading_more_rooms = false;
                     self.update_status_rooms_count();
                 },
                 RoomsListUpdate::Tags { room_id, new_tags } => {
                     if let Some(room) = self.all_joined_rooms.get_mut(&room_id) {
-                        room.tags = new_tags;
+                        self.unread_totals.update_joined_room(room, |room| room.tags = new_tags);
                     } else if let Some(_room) = self.invited_rooms.borrow().get(&room_id) {
                         log!("Ignoring updated tags update for invited room {room_id}");
                     } else {
@@ -633,6 +703,32 @@ impl RoomsList {
             // log!("RoomsList: processed {} updates to the list of all rooms", num_updates);
             self.redraw(cx);
         }
+        if self.unread_totals != prev_unread_totals {
+            cx.widget_action(
+                self.widget_uid(),
+                &scope.path,
+                RoomsListAction::UnreadTotalsChanged(self.unread_totals),
+            );
+        }
+    }
+
+    /// Requests the next page of rooms from the room list service if the user has scrolled
+    /// to the bottom of the list and not all rooms have been loaded yet.
+    ///
+    /// Pages are loaded one at a time: `is_loading_more_rooms` is reset once the room list
+    /// service reports the loaded rooms (via `RoomsListUpdate::LoadedRooms` or a new room).
+    fn load_more_rooms_if_at_end(&mut self, cx: &mut Cx) {
+        if self.is_loading_more_rooms
+            || self.max_known_rooms.is_none()
+            || self.all_known_rooms_loaded()
+            || !self.view.portal_list(id!(list)).is_at_end()
+        {
+            return;
+        }
+        self.is_loading_more_rooms = true;
+        submit_async_request(MatrixRequest::LoadMoreRooms);
+        self.status.push_str(" Loading more rooms...");
+        self.redraw(cx);
     }
 
     /// Updates the status message to show how many rooms have been loaded.
@@ -829,6 +925,11 @@ impl RoomsList {
         )
     }
 
+    /// Returns the running totals of unread messages and mentions for each category of rooms.
+    pub fn unread_totals(&self) -> UnreadTotals {
+        self.unread_totals
+    }
+
     /// Returns a room's avatar and displayable name.
     pub fn get_room_avatar_and_name(&self, room_id: &OwnedRoomId) -> Option<(RoomPreviewAvatar, Option<String>)> {
         self.all_joined_rooms.get(room_id)
@@ -880,6 +981,22 @@ impl Widget for RoomsList {
                 );
                 self.redraw(cx);
             }
+            else if let RoomPreviewAction::OpenContextMenu { room_id, abs_pos } = list_action.as_widget_action().cast() {
+                // Invited rooms don't yet have a context menu.
+                let Some(jr) = self.all_joined_rooms.get(&room_id) else { continue };
+                cx.widget_action(
+                    self.widget_uid(),
+                    &scope.path,
+                    RoomsListAction::OpenRoomContextMenu {
+                        details: RoomContextMenuDetails {
+                            room_id,
+                            room_name: jr.room_name.clone(),
+                            notification_mode: jr.notification_mode,
+                        },
+                        abs_pos,
+                    },
+                );
+            }
             else if let CollapsibleHeaderAction::Toggled { category } = list_action.as_widget_action().cast() {
                 match category {
                     HeaderCategory::Invites => {
@@ -905,6 +1022,11 @@ impl Widget for RoomsList {
                 }
             }
         }
+
+        // If the user has scrolled to the bottom of the list, load the next page of rooms.
+        if matches!(event, Event::Signal | Event::Actions(_)) {
+            self.load_more_rooms_if_at_end(cx);
+        }
     }
 
     fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
@@ -963,7 +1085,7 @@ impl Widget for RoomsList {
                         cx,
                         self.is_invited_rooms_header_expanded,
                         HeaderCategory::Invites,
-                        self.displayed_invited_rooms.len() as u64,
+                        self.unread_totals.invites,
                     );
                     item.draw_all(cx, &mut scope);
                 }
@@ -987,9 +1109,7 @@ impl Widget for RoomsList {
                         cx,
                         self.is_direct_rooms_header_expanded,
                         HeaderCategory::DirectRooms,
-                        0,
-                        // TODO: sum up all the unread mentions in rooms
-                        // NOTE: this might be really slow, so we should maintain a running total of mentions in this struct
+                        self.unread_totals.direct_rooms,
                     );
                     item.draw_all(cx, &mut scope);
                 }
@@ -1008,6 +1128,12 @@ impl Widget for RoomsList {
                                 direction: PaginationDirection::Backwards,
                             });
                         }
+                        // Fetch the room's full details (e.g., avatar) now that it's visible.
+                        if !direct_room.has_requested_details {
+                            submit_async_request(MatrixRequest::FetchRoomDetails {
+                                room_id: direct_room.room_id.clone(),
+                            });
+                        }
                         // Pass the room info down to the RoomPreview widget via Scope.
                         scope = Scope::with_props(&*direct_room);
                         item.draw_all(cx, &mut scope);
@@ -1022,9 +1148,7 @@ impl Widget for RoomsList {
                         cx,
                         self.is_regular_rooms_header_expanded,
                         HeaderCategory::RegularRooms,
-                        0,
-                        // TODO: sum up all the unread mentions in rooms.
-                        // NOTE: this might be really slow, so we should maintain a running total of mentions in this struct
+                        self.unread_totals.regular_rooms,
                     );
                     item.draw_all(cx, &mut scope);
                 }
@@ -1043,6 +1167,12 @@ impl Widget for RoomsList {
                                 direction: PaginationDirection::Backwards,
                             });
                         }
+                        // Fetch the room's full details (e.g., avatar) now that it's visible.
+                        if !regular_room.has_requested_details {
+                            submit_async_request(MatrixRequest::FetchRoomDetails {
+                                room_id: regular_room.room_id.clone(),
+                            });
+                        }
                         // Pass the room info down to the RoomPreview widget via Scope.
                         scope = Scope::with_props(&*regular_room);
                         item.draw_all(cx, &mut scope);
@@ -1093,6 +1223,14 @@ impl RoomsListRef {
         let inner = self.borrow()?;
         inner.get_room_avatar_and_name(room_id)
     }
+
+    /// See [`RoomsList::unread_totals()`].
+    pub fn unread_totals(&self) -> UnreadTotals {
+        let Some(inner) = self.borrow() else {
+            return UnreadTotals::default();
+        };
+        inner.unread_totals()
+    }
 }
 pub struct RoomsListScopeProps {
     /// Whether the RoomsList's inner PortalList was scrolling
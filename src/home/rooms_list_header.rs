@@ -1,4 +1,5 @@
-//! The RoomsListHeader contains the title label and loading spinner for rooms list.
+//! The RoomsListHeader contains the title label and loading spinner for rooms list,
+//! plus a button that opens the notification history.
 //!
 //! This widget is designed to be reused across both Desktop and Mobile variants 
 //! of the RoomsSideBar to avoid code duplication.
@@ -8,7 +9,7 @@ use std::mem::discriminant;
 use makepad_widgets::*;
 use matrix_sdk_ui::sync_service::State;
 
-use crate::shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind};
+use crate::{notifications::history::NotificationHistoryModalAction, shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind}};
 
 live_design! {
     use link::theme::*;
@@ -17,6 +18,7 @@ live_design! {
 
     use crate::shared::styles::*;
     use crate::shared::helpers::*;
+    use crate::shared::icon_button::*;
 
     pub RoomsListHeader = {{RoomsListHeader}} {
         width: Fill,
@@ -75,6 +77,22 @@ live_design! {
                 }
             }
         }
+
+        <View> { width: Fill, height: Fit }
+
+        notifications_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 6,
+            spacing: 0,
+            draw_bg: {
+                color: (COLOR_SECONDARY)
+            }
+            draw_icon: {
+                svg_file: (ICON_BELL),
+                color: (COLOR_TEXT),
+            }
+            icon_walk: {width: 16, height: 16, margin: 0}
+        }
     }
 }
 
@@ -121,6 +139,10 @@ impl Widget for RoomsListHeader {
                     _ => {}
                 }
             }
+
+            if self.view.button(id!(notifications_button)).clicked(actions) {
+                cx.action(NotificationHistoryModalAction::Open);
+            }
         }
 
         self.view.handle_event(cx, event, scope);
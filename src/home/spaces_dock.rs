@@ -1,13 +1,15 @@
 use makepad_widgets::*;
 
 use crate::{
-    avatar_cache::{self, AvatarCacheEntry}, login::login_screen::LoginAction, profile::{
+    avatar_cache::{self, AvatarCacheEntry}, home::{profile_menu::ProfileMenuAction, rooms_list::RoomsListAction}, login::login_screen::LoginAction, profile::{
         user_profile::{AvatarState, UserProfile},
         user_profile_cache::{self, UserProfileUpdate},
-    }, settings::SettingsAction, shared::{
+    }, settings::{account_settings::OwnProfileAction, SettingsAction}, shared::{
         avatar::AvatarWidgetExt,
         callout_tooltip::TooltipAction,
+        presence_indicator::PresenceIndicatorWidgetExt,
         styles::*,
+        unread_badge::UnreadBadgeWidgetExt,
         verification_badge::VerificationBadgeWidgetExt,
     }, sliding_sync::current_user_id, utils
 };
@@ -21,6 +23,9 @@ live_design! {
     use crate::shared::helpers::*;
     use crate::shared::verification_badge::*;
     use crate::shared::avatar::*;
+    use crate::shared::unread_badge::*;
+    use crate::shared::presence_indicator::*;
+    use crate::home::account_switcher::*;
 
     SPACES_DOCK_SIZE = 68
 
@@ -51,6 +56,12 @@ live_design! {
             align: { x: 1.0, y: 0.0 }
             verification_badge = <VerificationBadge> {}
         }
+
+        <View> {
+            align: { x: 1.0, y: 1.0 }
+            padding: {right: 6, bottom: 6}
+            presence_indicator = <PresenceIndicator> {}
+        }
     }
 
     Home = <RoundedView> {
@@ -76,6 +87,16 @@ live_design! {
         }
     }
 
+    // The Home space, with a badge showing the unread totals across all rooms.
+    HomeSpace = {{HomeSpace}} {
+        width: Fit, height: Fit
+        flow: Overlay
+        align: {x: 1.0, y: 0.0}
+
+        <Home> { margin: {top: 8, right: 12} }
+        unread_badge = <UnreadBadge> {}
+    }
+
     pub SpacesDock = <AdaptiveView> {
         // TODO: make this vertically scrollable
         Desktop = {
@@ -94,9 +115,15 @@ live_design! {
 
             <LineH> { margin: {left: 15, right: 15} }
 
-            <Home> {}
+            <CachedWidget> {
+                home_space = <HomeSpace> {}
+            }
 
             <Filler> {}
+
+            <CachedWidget> {
+                account_switcher = <AccountSwitcher> { flow: Down }
+            }
         }
 
         // TODO: make this horizontally scrollable via touch
@@ -118,16 +145,25 @@ live_design! {
 
             <Filler> {}
 
-            <Home> {}
+            <CachedWidget> {
+                home_space = <HomeSpace> {}
+            }
+
+            <Filler> {}
+
+            <CachedWidget> {
+                account_switcher = <AccountSwitcher> { flow: Right }
+            }
 
             <Filler> {}
         }
     }
 }
 
-/// The icon in the SpacesDock that show the user's avatar.
+/// The icon in the SpacesDock that show the user's avatar and presence.
 ///
-/// Clicking on this icon will open the settings screen.
+/// Clicking on this icon will open the settings screen,
+/// while right-clicking or long-pressing it will open the profile menu.
 #[derive(Live, Widget)]
 pub struct ProfileIcon {
     #[deref] view: View,
@@ -159,9 +195,7 @@ impl Widget for ProfileIcon {
             }
         }
 
-        // TODO: handle login/logout actions, as well as actions related to
-        //       the currently-logged-in user's account (such as them changing
-        //       their avatar, display name, etc.)
+        // TODO: handle logout actions.
 
         if let Event::Actions(actions) = event {
             for action in actions {
@@ -170,24 +204,42 @@ impl Widget for ProfileIcon {
                     self.view.redraw(cx);
                 }
 
-                if let Some(SettingsAction::CloseSettings) = action.downcast_ref() {
-                    self.is_selected = false;
+                // Our own profile was already invalidated by the App, so this re-fetches it.
+                if let Some(OwnProfileAction::DisplayNameChanged(_) | OwnProfileAction::AvatarChanged { .. }) = action.downcast_ref() {
+                    self.own_profile = get_own_profile(cx);
                     self.view.redraw(cx);
                 }
+
+                match action.downcast_ref() {
+                    Some(SettingsAction::OpenSettingsSection(_)) => {
+                        self.is_selected = true;
+                        self.view.redraw(cx);
+                    }
+                    Some(SettingsAction::CloseSettings) => {
+                        self.is_selected = false;
+                        self.view.redraw(cx);
+                    }
+                    _ => { }
+                }
             }
         }
 
         let area = self.view.area();
         match event.hits(cx, area) {
-            Hit::FingerLongPress(_)
-            | Hit::FingerHoverOver(_) // TODO: remove once CalloutTooltip bug is fixed
+            Hit::FingerDown(fde) if fde.device.mouse_button().is_some_and(|b| b.is_secondary()) => {
+                cx.widget_action(self.widget_uid(), &scope.path, ProfileMenuAction::Open { abs_pos: fde.abs });
+            }
+            Hit::FingerLongPress(lp) => {
+                cx.widget_action(self.widget_uid(), &scope.path, ProfileMenuAction::Open { abs_pos: lp.abs });
+            }
+            Hit::FingerHoverOver(_) // TODO: remove once CalloutTooltip bug is fixed
             | Hit::FingerHoverIn(_) => {
                 let (verification_str, bg_color) = self.view
                     .verification_badge(id!(verification_badge))
                     .tooltip_content();
                 let text = self.own_profile.as_ref().map_or_else(
                     || format!("Not logged in.\n\n{}\n\nTap to view Profiles & Settings.", verification_str),
-                    |p| format!("Logged in as \"{}\".\n\n{}\n\nTap to view Profile & Settings.", p.displayable_name(), verification_str)
+                    |p| format!("Logged in as \"{}\".\n\n{}\n\nTap to view Profile & Settings.\nRight-click or long-press to set your status.", p.displayable_name(), verification_str)
                 );
                 let rect = area.rect(cx);
                 cx.widget_action(
@@ -201,7 +253,7 @@ impl Widget for ProfileIcon {
                     },
                 );
             }
-            Hit::FingerUp(fue) if fue.is_over && fue.is_primary_hit() => {
+            Hit::FingerUp(fue) if fue.is_over && fue.is_primary_hit() && fue.was_tap() => {
                 cx.action(SettingsAction::OpenSettings);
                 self.is_selected = true;
                 // TODO: actually use the `is_selected` state by showing a
@@ -218,6 +270,9 @@ impl Widget for ProfileIcon {
     }
 
     fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view
+            .presence_indicator(id!(presence_indicator))
+            .set_user(cx, self.own_profile.as_ref().map(|p| &*p.user_id));
         let our_own_avatar = self.view.avatar(id!(our_own_avatar));
         let Some(own_profile) = self.own_profile.as_ref() else {
             // If we don't have a profile, default to an unknown avatar.
@@ -251,6 +306,31 @@ impl Widget for ProfileIcon {
     }
 }
 
+/// The Home space icon in the SpacesDock, which shows the unread totals across all rooms.
+#[derive(Live, LiveHook, Widget)]
+pub struct HomeSpace {
+    #[deref] view: View,
+}
+
+impl Widget for HomeSpace {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        if let Event::Actions(actions) = event {
+            for action in actions {
+                if let RoomsListAction::UnreadTotalsChanged(totals) = action.as_widget_action().cast() {
+                    self.view.unread_badge(id!(unread_badge))
+                        .update_counts(totals.home.mentions, totals.home.messages);
+                    self.view.redraw(cx);
+                }
+            }
+        }
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
 /// Returns the current user's profile and avatar, if available.
 pub fn get_own_profile(cx: &mut Cx) -> Option<UserProfile> {
     let mut own_profile = None;
@@ -0,0 +1,372 @@
+//! Running totals of unread messages and mentions for each category of rooms.
+//!
+//! Instead of summing up the unread counts of every room each time the RoomsList
+//! is drawn (which is slow for accounts with many rooms), we maintain these totals
+//! incrementally as rooms are added, removed, and updated via `RoomsListUpdate`s.
+
+use matrix_sdk::{notification_settings::RoomNotificationMode, ruma::events::tag::TagName};
+
+use crate::shared::collapsible_header::HeaderCategory;
+use super::rooms_list::JoinedRoomInfo;
+
+/// A count of unread messages and unread mentions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnreadCounts {
+    pub messages: u64,
+    pub mentions: u64,
+}
+impl UnreadCounts {
+    pub const fn new(messages: u64, mentions: u64) -> Self {
+        Self { messages, mentions }
+    }
+
+    /// Returns `true` if there are no unread messages or mentions.
+    pub fn is_empty(&self) -> bool {
+        self.messages == 0 && self.mentions == 0
+    }
+
+    fn add(&mut self, other: UnreadCounts) {
+        self.messages += other.messages;
+        self.mentions += other.mentions;
+    }
+
+    fn subtract(&mut self, other: UnreadCounts) {
+        if self.messages < other.messages || self.mentions < other.mentions {
+            makepad_widgets::error!("BUG: unread totals {self:?} would underflow when subtracting {other:?}");
+        }
+        self.messages = self.messages.saturating_sub(other.messages);
+        self.mentions = self.mentions.saturating_sub(other.mentions);
+    }
+}
+
+/// The unread counts that an invite contributes to the totals.
+///
+/// Every pending invite is shown as a single unread mention, just like in its `RoomPreview`.
+const INVITE_COUNTS: UnreadCounts = UnreadCounts::new(0, 1);
+
+/// Running totals of unread messages and mentions for each category of rooms.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnreadTotals {
+    /// Rooms the user has been invited to, each of which counts as one unread mention.
+    pub invites: UnreadCounts,
+    /// Joined direct rooms (DMs).
+    pub direct_rooms: UnreadCounts,
+    /// Joined regular (non-direct) rooms.
+    pub regular_rooms: UnreadCounts,
+    /// Joined rooms that are tagged as a favorite, which can be either direct or regular rooms.
+    pub favorites: UnreadCounts,
+    /// All rooms in the Home space shown in the SpacesDock, i.e., all joined rooms and invites.
+    pub home: UnreadCounts,
+}
+
+impl UnreadTotals {
+    /// Calculates the totals from scratch based on the given set of rooms.
+    ///
+    /// This is slow, so it should only be used to (re-)initialize the totals.
+    pub fn from_rooms<'a>(
+        joined_rooms: impl IntoIterator<Item = &'a JoinedRoomInfo>,
+        num_invited_rooms: usize,
+    ) -> Self {
+        let mut totals = Self::default();
+        for room in joined_rooms {
+            totals.add_joined_room(room);
+        }
+        for _ in 0..num_invited_rooms {
+            totals.add_invited_room();
+        }
+        totals
+    }
+
+    /// Returns the totals for the given header category.
+    pub fn for_category(&self, category: HeaderCategory) -> UnreadCounts {
+        match category {
+            HeaderCategory::Invites => self.invites,
+            HeaderCategory::Favorites => self.favorites,
+            HeaderCategory::DirectRooms => self.direct_rooms,
+            HeaderCategory::RegularRooms => self.regular_rooms,
+            HeaderCategory::LowPriority
+            | HeaderCategory::LeftRooms
+            | HeaderCategory::None => UnreadCounts::default(),
+        }
+    }
+
+    /// Returns the number of things awaiting the user's attention:
+    /// the unread messages in all joined rooms plus all pending invites.
+    ///
+    /// This is what's displayed in the app's window title.
+    pub fn num_notifications(&self) -> u64 {
+        self.home.messages + self.invites.mentions
+    }
+
+    /// Adds the given joined room's unread counts to the totals.
+    pub fn add_joined_room(&mut self, room: &JoinedRoomInfo) {
+        let counts = joined_room_counts(room);
+        if room.is_direct {
+            self.direct_rooms.add(counts);
+        } else {
+            self.regular_rooms.add(counts);
+        }
+        if is_favorite(room) {
+            self.favorites.add(counts);
+        }
+        self.home.add(counts);
+    }
+
+    /// Removes the given joined room's unread counts from the totals.
+    ///
+    /// The given `room` must be in the same state as when it was added,
+    /// otherwise the totals will no longer be consistent.
+    pub fn remove_joined_room(&mut self, room: &JoinedRoomInfo) {
+        let counts = joined_room_counts(room);
+        if room.is_direct {
+            self.direct_rooms.subtract(counts);
+        } else {
+            self.regular_rooms.subtract(counts);
+        }
+        if is_favorite(room) {
+            self.favorites.subtract(counts);
+        }
+        self.home.subtract(counts);
+    }
+
+    /// Applies the given `update` function to the given joined room,
+    /// keeping the totals consistent with any changes it makes to that room.
+    pub fn update_joined_room<R>(
+        &mut self,
+        room: &mut JoinedRoomInfo,
+        update: impl FnOnce(&mut JoinedRoomInfo) -> R,
+    ) -> R {
+        self.remove_joined_room(room);
+        let ret = update(room);
+        self.add_joined_room(room);
+        ret
+    }
+
+    /// Adds a new invite to the totals.
+    pub fn add_invited_room(&mut self) {
+        self.invites.add(INVITE_COUNTS);
+        self.home.add(INVITE_COUNTS);
+    }
+
+    /// Removes an invite from the totals.
+    pub fn remove_invited_room(&mut self) {
+        self.invites.subtract(INVITE_COUNTS);
+        self.home.subtract(INVITE_COUNTS);
+    }
+}
+
+/// Returns the unread counts that the given joined room contributes to the totals.
+///
+/// Muted rooms don't contribute anything, even though their own badges still show their counts.
+fn joined_room_counts(room: &JoinedRoomInfo) -> UnreadCounts {
+    if room.notification_mode == RoomNotificationMode::Mute {
+        return UnreadCounts::default();
+    }
+    UnreadCounts::new(room.num_unread_messages, room.num_unread_mentions)
+}
+
+fn is_favorite(room: &JoinedRoomInfo) -> bool {
+    room.tags.contains_key(&TagName::Favorite)
+}
+
+
+#[cfg(test)]
+mod tests_unread_totals {
+    use std::collections::HashMap;
+    use matrix_sdk::ruma::{events::tag::{TagInfo, Tags}, OwnedRoomId};
+    use crate::room::RoomPreviewAvatar;
+    use super::*;
+
+    fn room_id(n: usize) -> OwnedRoomId {
+        OwnedRoomId::try_from(format!("!room{n}:example.org")).unwrap()
+    }
+
+    fn joined_room(n: usize, is_direct: bool, messages: u64, mentions: u64) -> JoinedRoomInfo {
+        JoinedRoomInfo {
+            room_id: room_id(n),
+            room_name: Some(format!("Room {n}")),
+            num_unread_messages: messages,
+            num_unread_mentions: mentions,
+            notification_mode: RoomNotificationMode::AllMessages,
+            canonical_alias: None,
+            alt_aliases: Vec::new(),
+            tags: Tags::new(),
+            latest: None,
+            avatar: RoomPreviewAvatar::Text(String::from("R")),
+            has_been_paginated: false,
+            has_requested_details: false,
+            is_selected: false,
+            is_direct,
+            direct_user_id: None,
+            is_tombstoned: false,
+        }
+    }
+
+    fn favorite_tags() -> Tags {
+        let mut tags = Tags::new();
+        tags.insert(TagName::Favorite, TagInfo::new());
+        tags
+    }
+
+    /// Asserts that the incrementally-maintained totals match totals recalculated from scratch.
+    fn assert_consistent(totals: &UnreadTotals, rooms: &HashMap<OwnedRoomId, JoinedRoomInfo>, num_invites: usize) {
+        assert_eq!(*totals, UnreadTotals::from_rooms(rooms.values(), num_invites));
+    }
+
+    #[test]
+    fn test_add_rooms() {
+        let mut totals = UnreadTotals::default();
+        let mut rooms = HashMap::new();
+        for room in [
+            joined_room(0, false, 5, 1),
+            joined_room(1, true, 2, 0),
+            joined_room(2, false, 0, 0),
+        ] {
+            totals.add_joined_room(&room);
+            rooms.insert(room.room_id.clone(), room);
+        }
+        totals.add_invited_room();
+        assert_consistent(&totals, &rooms, 1);
+        assert_eq!(totals.regular_rooms, UnreadCounts::new(5, 1));
+        assert_eq!(totals.direct_rooms, UnreadCounts::new(2, 0));
+        assert_eq!(totals.invites, UnreadCounts::new(0, 1));
+        assert_eq!(totals.home, UnreadCounts::new(7, 2));
+        assert_eq!(totals.num_notifications(), 8);
+    }
+
+    #[test]
+    fn test_remove_rooms() {
+        let mut rooms: HashMap<_, _> = [
+            joined_room(0, false, 5, 1),
+            joined_room(1, true, 2, 2),
+        ].into_iter().map(|r| (r.room_id.clone(), r)).collect();
+        let mut totals = UnreadTotals::from_rooms(rooms.values(), 2);
+
+        let removed = rooms.remove(&room_id(1)).unwrap();
+        totals.remove_joined_room(&removed);
+        totals.remove_invited_room();
+        assert_consistent(&totals, &rooms, 1);
+        assert_eq!(totals.direct_rooms, UnreadCounts::default());
+
+        let removed = rooms.remove(&room_id(0)).unwrap();
+        totals.remove_joined_room(&removed);
+        totals.remove_invited_room();
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals, UnreadTotals::default());
+    }
+
+    #[test]
+    fn test_update_unread_counts() {
+        let mut rooms: HashMap<_, _> = [
+            joined_room(0, false, 5, 1),
+            joined_room(1, true, 2, 0),
+        ].into_iter().map(|r| (r.room_id.clone(), r)).collect();
+        let mut totals = UnreadTotals::from_rooms(rooms.values(), 0);
+
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| {
+            r.num_unread_messages = 0;
+            r.num_unread_mentions = 0;
+        });
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.regular_rooms, UnreadCounts::default());
+
+        let room = rooms.get_mut(&room_id(1)).unwrap();
+        totals.update_joined_room(room, |r| r.num_unread_mentions = 3);
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.home, UnreadCounts::new(2, 3));
+    }
+
+    #[test]
+    fn test_update_category() {
+        let mut rooms: HashMap<_, _> = [
+            joined_room(0, false, 4, 1),
+        ].into_iter().map(|r| (r.room_id.clone(), r)).collect();
+        let mut totals = UnreadTotals::from_rooms(rooms.values(), 0);
+
+        // A regular room becomes a direct room.
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.is_direct = true);
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.regular_rooms, UnreadCounts::default());
+        assert_eq!(totals.direct_rooms, UnreadCounts::new(4, 1));
+
+        // The room is tagged as a favorite, and then untagged.
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.tags = favorite_tags());
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.favorites, UnreadCounts::new(4, 1));
+        assert_eq!(totals.home, UnreadCounts::new(4, 1));
+
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.tags = Tags::new());
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.favorites, UnreadCounts::default());
+    }
+
+    #[test]
+    fn test_muted_rooms_are_excluded() {
+        let mut rooms: HashMap<_, _> = [
+            joined_room(0, false, 5, 1),
+            joined_room(1, true, 2, 0),
+        ].into_iter().map(|r| (r.room_id.clone(), r)).collect();
+        let mut totals = UnreadTotals::from_rooms(rooms.values(), 0);
+
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.notification_mode = RoomNotificationMode::Mute);
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.regular_rooms, UnreadCounts::default());
+        assert_eq!(totals.home, UnreadCounts::new(2, 0));
+
+        // New messages in a muted room don't change the totals.
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.num_unread_messages = 9);
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.home, UnreadCounts::new(2, 0));
+
+        // Unmuting the room brings its current counts back into the totals.
+        let room = rooms.get_mut(&room_id(0)).unwrap();
+        totals.update_joined_room(room, |r| r.notification_mode = RoomNotificationMode::MentionsAndKeywordsOnly);
+        assert_consistent(&totals, &rooms, 0);
+        assert_eq!(totals.home, UnreadCounts::new(11, 1));
+    }
+
+    #[test]
+    fn test_mixed_sequence_stays_consistent() {
+        let mut rooms = HashMap::new();
+        let mut totals = UnreadTotals::default();
+        let mut num_invites = 0;
+
+        for n in 0..20 {
+            let room = joined_room(n, n % 3 == 0, (n as u64) * 2, n as u64 % 4);
+            totals.add_joined_room(&room);
+            rooms.insert(room.room_id.clone(), room);
+            if n % 5 == 0 {
+                totals.add_invited_room();
+                num_invites += 1;
+            }
+            assert_consistent(&totals, &rooms, num_invites);
+        }
+        for n in (0..20).step_by(2) {
+            let room = rooms.get_mut(&room_id(n)).unwrap();
+            totals.update_joined_room(room, |r| {
+                r.num_unread_messages += 1;
+                r.is_direct = !r.is_direct;
+                if n % 4 == 0 {
+                    r.tags = favorite_tags();
+                }
+            });
+            assert_consistent(&totals, &rooms, num_invites);
+        }
+        for n in (1..20).step_by(3) {
+            let removed = rooms.remove(&room_id(n)).unwrap();
+            totals.remove_joined_room(&removed);
+            assert_consistent(&totals, &rooms, num_invites);
+        }
+        while num_invites > 0 {
+            totals.remove_invited_room();
+            num_invites -= 1;
+            assert_consistent(&totals, &rooms, num_invites);
+        }
+    }
+}
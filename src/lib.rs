@@ -25,6 +25,8 @@ mod verification_modal;
 mod join_leave_room_modal;
 /// Shared UI components.
 pub mod shared;
+/// Local notifications about new messages, shown via the OS and an in-app history.
+pub mod notifications;
 /// Generating text previews of timeline events/messages.
 mod event_preview;
 pub mod room;
@@ -46,6 +48,8 @@ pub mod verification;
 
 pub mod utils;
 pub mod temp_storage;
+/// A headless command-line mode for scripting Robrix without its UI.
+pub mod headless;
 pub mod location;
 
 
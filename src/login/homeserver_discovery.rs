@@ -0,0 +1,164 @@
+//! Helpers for discovering and validating the homeserver that a user wants to log into.
+//!
+//! Users often enter their server name (e.g., `example.org`) or their full user ID
+//! rather than the homeserver's actual client API base URL (e.g., `https://matrix.example.org`).
+//! The Matrix SDK resolves a server name into that URL via `.well-known/matrix/client` discovery;
+//! this module determines what to discover from, and validates what was discovered.
+
+use std::collections::BTreeMap;
+
+use matrix_sdk::ruma::UserId;
+
+/// The unstable feature that homeservers advertise if they support native sliding sync (MSC4186),
+/// which Robrix prefers over classic sync.
+const NATIVE_SLIDING_SYNC_FEATURE: &str = "org.matrix.simplified_msc3575";
+
+/// The minimum minor version of the stable `v1.x` Matrix spec that Robrix requires.
+const MIN_SPEC_MINOR_VERSION: u32 = 1;
+
+/// A readable summary of a discovered homeserver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HomeserverSummary {
+    /// The server name, or URL, that the homeserver was discovered from.
+    pub server_name: String,
+    /// The homeserver's client API base URL.
+    pub homeserver_url: String,
+    /// The homeserver's software name and version, e.g., "Synapse 1.120.0", if it provided them.
+    pub software: Option<String>,
+    /// The Matrix spec versions that the homeserver supports.
+    pub spec_versions: Vec<String>,
+    /// Whether the homeserver supports native sliding sync.
+    /// If not, Robrix falls back to classic sync.
+    pub supports_native_sliding_sync: bool,
+    /// The ways of logging in that the homeserver supports, e.g., "password".
+    pub login_methods: Vec<String>,
+}
+
+impl HomeserverSummary {
+    /// Returns a multi-line, user-facing description of this homeserver.
+    pub fn to_display_string(&self) -> String {
+        let mut lines = vec![format!("{} ({})", self.server_name, self.homeserver_url)];
+        if let Some(software) = &self.software {
+            lines.push(format!("Server: {software}"));
+        }
+        match (self.spec_versions.first(), self.spec_versions.last()) {
+            (Some(oldest), Some(newest)) if oldest != newest => lines.push(format!("Matrix versions: {oldest} to {newest}")),
+            (Some(version), _) => lines.push(format!("Matrix version: {version}")),
+            _ => { }
+        }
+        lines.push(String::from(if self.supports_native_sliding_sync {
+            "Sync: native sliding sync"
+        } else {
+            "Sync: classic sync only (no native sliding sync, so loading rooms may be slower)"
+        }));
+        if self.login_methods.is_empty() {
+            lines.push(String::from("Login methods: none supported by Robrix"));
+        } else {
+            lines.push(format!("Login methods: {}", self.login_methods.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Determines what to discover the homeserver from, given what the user entered
+/// into the homeserver input (if anything) and the user ID input.
+///
+/// The homeserver input takes precedence; otherwise, the server name of a full
+/// user ID (e.g., `@alice:example.org`) is used. Returns `None` if there's nothing to discover from.
+pub fn discovery_target(homeserver_input: &str, user_id_input: &str) -> Option<String> {
+    let homeserver_input = homeserver_input.trim();
+    if !homeserver_input.is_empty() {
+        return Some(homeserver_input.trim_end_matches('/').to_owned());
+    }
+    <&UserId>::try_from(user_id_input.trim())
+        .ok()
+        .map(|user_id| user_id.server_name().to_string())
+}
+
+/// Checks that the homeserver supports a recent enough version of the Matrix spec,
+/// returning a user-facing error if not.
+pub fn validate_spec_versions(spec_versions: &[String]) -> Result<(), String> {
+    let is_supported = spec_versions.iter().any(|version| {
+        version.strip_prefix("v1.")
+            .and_then(|minor| minor.parse::<u32>().ok())
+            .is_some_and(|minor| minor >= MIN_SPEC_MINOR_VERSION)
+    });
+    if is_supported {
+        return Ok(());
+    }
+    Err(format!(
+        "This homeserver only supports old versions of the Matrix spec ({}), \
+        but Robrix requires version v1.{MIN_SPEC_MINOR_VERSION} or newer. \
+        Please ask your homeserver's administrator to upgrade it.",
+        if spec_versions.is_empty() { String::from("none") } else { spec_versions.join(", ") },
+    ))
+}
+
+/// Returns whether the homeserver supports native sliding sync, based on the unstable features
+/// listed in its `/_matrix/client/versions` response.
+pub fn supports_native_sliding_sync(unstable_features: &BTreeMap<String, bool>) -> bool {
+    unstable_features.get(NATIVE_SLIDING_SYNC_FEATURE).copied().unwrap_or(false)
+}
+
+/// Returns the user-facing error shown when a homeserver doesn't support native sliding sync.
+pub fn no_sliding_sync_error(homeserver_url: &str) -> String {
+    format!(
+        "The homeserver at {homeserver_url} does not support native sliding sync (MSC4186), \
+        which Robrix uses to quickly load your rooms, so only classic sync can be used. \
+        Please ask your homeserver's administrator to upgrade it (e.g., to a recent version of Synapse)."
+    )
+}
+
+#[cfg(test)]
+mod tests_homeserver_discovery {
+    use super::*;
+
+    #[test]
+    fn discovers_from_homeserver_or_user_id() {
+        assert_eq!(discovery_target(" example.org ", "@alice:other.org").as_deref(), Some("example.org"));
+        assert_eq!(discovery_target("https://matrix.example.org/", "").as_deref(), Some("https://matrix.example.org"));
+        assert_eq!(discovery_target("", "@alice:example.org").as_deref(), Some("example.org"));
+        assert_eq!(discovery_target("", "@alice:example.org:8448").as_deref(), Some("example.org:8448"));
+        assert_eq!(discovery_target("", "alice"), None);
+        assert_eq!(discovery_target("", ""), None);
+    }
+
+    #[test]
+    fn validates_spec_versions() {
+        let versions = |versions: &[&str]| versions.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        assert!(validate_spec_versions(&versions(&["r0.6.1", "v1.1", "v1.12"])).is_ok());
+        assert!(validate_spec_versions(&versions(&["r0.5.0", "r0.6.1"])).unwrap_err().contains("r0.6.1"));
+        assert!(validate_spec_versions(&versions(&["v1.0"])).is_err());
+        assert!(validate_spec_versions(&[]).is_err());
+    }
+
+    #[test]
+    fn detects_native_sliding_sync() {
+        let mut features = BTreeMap::new();
+        assert!(!supports_native_sliding_sync(&features));
+        features.insert(NATIVE_SLIDING_SYNC_FEATURE.to_owned(), false);
+        assert!(!supports_native_sliding_sync(&features));
+        features.insert(NATIVE_SLIDING_SYNC_FEATURE.to_owned(), true);
+        assert!(supports_native_sliding_sync(&features));
+    }
+
+    #[test]
+    fn summarizes_homeserver() {
+        let summary = HomeserverSummary {
+            server_name: String::from("example.org"),
+            homeserver_url: String::from("https://matrix.example.org/"),
+            software: Some(String::from("Synapse 1.120.0")),
+            spec_versions: vec![String::from("v1.1"), String::from("v1.12")],
+            supports_native_sliding_sync: true,
+            login_methods: vec![String::from("password"), String::from("SSO (GitHub)")],
+        };
+        assert_eq!(
+            summary.to_display_string(),
+            "example.org (https://matrix.example.org/)\n\
+            Server: Synapse 1.120.0\n\
+            Matrix versions: v1.1 to v1.12\n\
+            Sync: native sliding sync\n\
+            Login methods: password, SSO (GitHub)",
+        );
+    }
+}
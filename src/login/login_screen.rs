@@ -3,8 +3,12 @@ use std::ops::Not;
 use makepad_widgets::*;
 use url::Url;
 
-use crate::sliding_sync::{submit_async_request, LoginByPassword, LoginRequest, MatrixRequest};
+use crate::persistence::{proxy_settings::set_login_proxy_settings, ProxySettings};
+use crate::shared::styles::{COLOR_FG_DANGER_RED, COLOR_MESSAGE_NOTICE_TEXT};
+use crate::sliding_sync::{submit_async_request, LoginByPassword, LoginRequest, MatrixRequest, RegisterAccount};
 
+use super::homeserver_discovery::{discovery_target, HomeserverSummary};
+use super::registration::TermsPolicy;
 use super::login_status_modal::{LoginStatusModalAction, LoginStatusModalWidgetExt};
 
 live_design! {
@@ -117,6 +121,56 @@ live_design! {
                         is_password: true,
                     }
 
+                    // Only shown when creating a new account.
+                    registration_view = <View> {
+                        visible: false,
+                        width: 250, height: Fit,
+                        flow: Down,
+                        spacing: 10,
+
+                        registration_token_input = <RobrixTextInput> {
+                            width: 250, height: Fit
+                            flow: Right, // do not wrap
+                            padding: 10,
+                            empty_text: "Registration token (if required)"
+                        }
+
+                        // Shown once the homeserver asks the user to accept its terms and policies.
+                        terms_view = <View> {
+                            visible: false,
+                            width: Fill, height: Fit,
+                            flow: Down,
+                            spacing: 5,
+
+                            <Label> {
+                                width: Fill, height: Fit
+                                draw_text: {
+                                    wrap: Word,
+                                    color: (COLOR_TEXT)
+                                    text_style: <REGULAR_TEXT>{font_size: 10}
+                                }
+                                text: "This homeserver requires you to accept the following policies:"
+                            }
+
+                            terms_html = <Html> {
+                                width: Fill, height: Fit
+                                padding: {left: 5}
+                                font_size: 10,
+                                font_color: (COLOR_TEXT),
+                                body: ""
+                            }
+
+                            accept_terms_checkbox = <CheckBoxFlat> {
+                                draw_text: {
+                                    color: (COLOR_TEXT),
+                                    text_style: <REGULAR_TEXT>{ font_size: 10 },
+                                }
+                                text: "I accept these terms and policies"
+                                active: false,
+                            }
+                        }
+                    }
+
                     <View> {
                         width: 250, height: Fit,
                         flow: Down,
@@ -157,6 +211,72 @@ live_design! {
                                 draw_bg: { color: #C8C8C8 }
                             }
                         }
+
+                        proxy_input = <RobrixTextInput> {
+                            width: Fill, height: Fit,
+                            flow: Right, // do not wrap
+                            padding: {top: 3, bottom: 3}
+                            margin: {top: 5}
+                            empty_text: "socks5://127.0.0.1:1080"
+                            draw_text: {
+                                text_style: <TITLE_TEXT>{font_size: 10.0}
+                            }
+                        }
+
+                        <View> {
+                            width: 250,
+                            height: Fit,
+                            flow: Right,
+                            padding: {top: 3, left: 2, right: 2}
+                            spacing: 0.0,
+                            align: {x: 0.5, y: 0.5} // center horizontally and vertically
+
+                            <LineH> {
+                                draw_bg: { color: #C8C8C8 }
+                            }
+
+                            <Label> {
+                                width: Fit, height: Fit
+                                padding: 0
+                                draw_text: {
+                                    color: #8C8C8C
+                                    text_style: <REGULAR_TEXT>{font_size: 9}
+                                }
+                                text: "Proxy URL (optional)"
+                            }
+
+                            <LineH> {
+                                draw_bg: { color: #C8C8C8 }
+                            }
+                        }
+
+                        check_homeserver_button = <RobrixIconButton> {
+                            width: Fill, height: Fit
+                            padding: 6
+                            margin: {top: 3}
+                            align: {x: 0.5, y: 0.5}
+                            draw_bg: {
+                                color: (COLOR_SECONDARY)
+                            }
+                            draw_text: {
+                                color: (COLOR_TEXT)
+                                text_style: <REGULAR_TEXT>{font_size: 9}
+                            }
+                            text: "Check homeserver"
+                        }
+
+                        // Shows a summary of the checked homeserver, or why it isn't supported.
+                        homeserver_summary_label = <Label> {
+                            visible: false
+                            width: Fill, height: Fit
+                            padding: {top: 5, left: 2, right: 2}
+                            draw_text: {
+                                wrap: Word
+                                color: (COLOR_MESSAGE_NOTICE_TEXT)
+                                text_style: <REGULAR_TEXT>{font_size: 9}
+                            }
+                            text: ""
+                        }
                     }
                     
 
@@ -176,6 +296,24 @@ live_design! {
                         text: "Login"
                     }
 
+                    // For homeservers that use next-generation auth (OAuth 2.0),
+                    // on which password and SSO logins aren't available.
+                    oauth_login_button = <RobrixIconButton> {
+                        width: 250,
+                        height: 40
+                        padding: 10
+                        margin: {bottom: 10}
+                        align: {x: 0.5, y: 0.5}
+                        draw_bg: {
+                            color: (COLOR_SECONDARY)
+                        }
+                        draw_text: {
+                            color: (COLOR_TEXT)
+                            text_style: <REGULAR_TEXT> {}
+                        }
+                        text: "Login via Browser (OAuth)"
+                    }
+
                     left_line = <LineH> {
                         margin: {bottom: -5}
                         draw_bg: { color: #C8C8C8 }
@@ -239,7 +377,7 @@ live_design! {
                             draw_bg: { color: #C8C8C8 }
                         }
 
-                        <Label> {
+                        signup_prompt = <Label> {
                             width: Fit, height: Fit
                             padding: {left: 1, right: 1, top: 0, bottom: 0}
                             draw_text: {
@@ -267,7 +405,7 @@ live_design! {
                             text_style: <REGULAR_TEXT> {}
                         }
 
-                        text: "Sign up here"
+                        text: "Create an account"
                     }
                 }
 
@@ -286,8 +424,6 @@ live_design! {
     }
 }
 
-static MATRIX_SIGN_UP_URL: &str = "https://matrix.org/docs/chat_basics/matrix-for-im/#creating-a-matrix-account";
-
 #[derive(Live, LiveHook, Widget)]
 pub struct LoginScreen {
     #[deref] view: View,
@@ -295,6 +431,13 @@ pub struct LoginScreen {
     #[rust] sso_pending: bool,
     /// The URL to redirect to after logging in with SSO.
     #[rust] sso_redirect_url: Option<String>,
+    /// Whether this screen is currently being used to create (register) a new account.
+    #[rust] is_registering: bool,
+    /// The URLs of the homeserver's policies shown in the `terms_html` widget.
+    ///
+    /// Link clicks are app-wide actions, so only clicks on these URLs
+    /// while the terms are shown are treated as clicks on a policy link.
+    #[rust] terms_urls: Vec<String>,
 }
 
 
@@ -313,6 +456,8 @@ impl MatchEvent for LoginScreen {
     fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
         let login_button = self.view.button(id!(login_button));
         let signup_button = self.view.button(id!(signup_button));
+        let oauth_login_button = self.view.button(id!(oauth_login_button));
+        let check_homeserver_button = self.view.button(id!(check_homeserver_button));
         let user_id_input = self.view.text_input(id!(user_id_input));
         let password_input = self.view.text_input(id!(password_input));
         let homeserver_input = self.view.text_input(id!(homeserver_input));
@@ -321,19 +466,54 @@ impl MatchEvent for LoginScreen {
         let login_status_modal_inner = self.view.login_status_modal(id!(login_status_modal_inner));
 
         if signup_button.clicked(actions) {
-            log!("Opening URL \"{}\"", MATRIX_SIGN_UP_URL);
-            let _ = robius_open::Uri::new(MATRIX_SIGN_UP_URL).open();
+            self.set_registering(cx, !self.is_registering);
+        }
+
+        // Open any of the homeserver's policy links that were clicked.
+        let terms_visible = self.view.view(id!(terms_view)).visible();
+        for action in actions {
+            if let HtmlLinkAction::Clicked { url, .. } = action.as_widget_action().cast() {
+                if !terms_visible || !self.terms_urls.contains(&url) {
+                    continue;
+                }
+                log!("Opening URL \"{url}\"");
+                if let Err(e) = robius_open::Uri::new(&url).open() {
+                    error!("Failed to open URL {url}: {e:?}");
+                }
+            }
         }
 
         if login_button.clicked(actions)
             || user_id_input.returned(actions).is_some()
             || password_input.returned(actions).is_some()
             || homeserver_input.returned(actions).is_some()
+            || self.view.text_input(id!(proxy_input)).returned(actions).is_some()
         {
             let user_id = user_id_input.text();
             let password = password_input.text();
             let homeserver = homeserver_input.text();
-            if user_id.is_empty() {
+            if let Err(error) = self.apply_login_proxy() {
+                login_status_modal_inner.set_title(cx, "Invalid Proxy");
+                login_status_modal_inner.set_status(cx, &error);
+                login_status_modal_inner.button_ref().set_text(cx, "Okay");
+            } else if self.is_registering && user_id.is_empty() {
+                login_status_modal_inner.set_title(cx, "Missing Username");
+                login_status_modal_inner.set_status(cx, "Please enter a username for your new account.");
+                login_status_modal_inner.button_ref().set_text(cx, "Okay");
+            } else if self.is_registering && !password.is_empty() {
+                let registration_token = self.view.text_input(id!(registration_token_input)).text();
+                let accepted_terms = self.view.check_box(id!(accept_terms_checkbox)).active(cx);
+                login_status_modal_inner.set_title(cx, "Creating account...");
+                login_status_modal_inner.set_status(cx, "Waiting for a response from the homeserver...");
+                login_status_modal_inner.button_ref().set_text(cx, "Cancel");
+                submit_async_request(MatrixRequest::Login(LoginRequest::Register(RegisterAccount {
+                    username: user_id,
+                    password,
+                    homeserver: homeserver.is_empty().not().then_some(homeserver),
+                    registration_token: registration_token.is_empty().not().then_some(registration_token),
+                    accepted_terms,
+                })));
+            } else if user_id.is_empty() {
                 login_status_modal_inner.set_title(cx, "Missing User ID");
                 login_status_modal_inner.set_status(cx, "Please enter a valid User ID.");
                 login_status_modal_inner.button_ref().set_text(cx, "Okay");
@@ -354,7 +534,41 @@ impl MatchEvent for LoginScreen {
             login_status_modal.open(cx);
             self.redraw(cx);
         }
-        
+
+        if oauth_login_button.clicked(actions) && !self.sso_pending {
+            if let Err(error) = self.apply_login_proxy() {
+                login_status_modal_inner.set_title(cx, "Invalid Proxy");
+                login_status_modal_inner.set_status(cx, &error);
+                login_status_modal_inner.button_ref().set_text(cx, "Okay");
+            } else {
+                login_status_modal_inner.set_title(cx, "Logging in via OAuth...");
+                login_status_modal_inner.set_status(cx, "Discovering your homeserver's authentication service...");
+                login_status_modal_inner.button_ref().set_text(cx, "Cancel");
+                submit_async_request(MatrixRequest::SpawnOAuthLogin {
+                    homeserver_url: homeserver_input.text(),
+                });
+            }
+            login_status_modal.open(cx);
+            self.redraw(cx);
+        }
+
+        if check_homeserver_button.clicked(actions) {
+            match discovery_target(&homeserver_input.text(), &user_id_input.text())
+                .map(|target| self.apply_login_proxy().map(|_| target))
+            {
+                Some(Err(error)) => self.set_homeserver_summary(cx, &error, true),
+                Some(Ok(target)) => {
+                    self.set_homeserver_summary(cx, &format!("Checking the homeserver for \"{target}\"..."), false);
+                    submit_async_request(MatrixRequest::Login(LoginRequest::HomeserverLoginTypesQuery(target)));
+                }
+                None => self.set_homeserver_summary(
+                    cx,
+                    "Enter a homeserver, or a full user ID like @alice:example.org, to check it.",
+                    false,
+                ),
+            }
+        }
+
         let provider_brands = ["apple", "facebook", "github", "gitlab", "google", "twitter"];
         let button_set: &[&[LiveId]] = ids!(
             apple_button, 
@@ -400,11 +614,13 @@ impl MatchEvent for LoginScreen {
                     user_id_input.set_text(cx, "");
                     password_input.set_text(cx, "");
                     homeserver_input.set_text(cx, "");
+                    self.set_registering(cx, false);
+                    self.view.label(id!(homeserver_summary_label)).set_visible(cx, false);
                     login_status_modal.close(cx);
                     self.redraw(cx);
                 }
                 Some(LoginAction::LoginFailure(error)) => {
-                    login_status_modal_inner.set_title(cx, "Login Failed.");
+                    login_status_modal_inner.set_title(cx, if self.is_registering { "Registration Failed." } else { "Login Failed." });
                     login_status_modal_inner.set_status(cx, error);
                     let login_status_modal_button = login_status_modal_inner.button_ref();
                     login_status_modal_button.set_text(cx, "Okay");
@@ -433,6 +649,27 @@ impl MatchEvent for LoginScreen {
                 Some(LoginAction::SsoSetRedirectUrl(url)) => {
                     self.sso_redirect_url = Some(url.to_string());
                 }
+                Some(LoginAction::RegistrationTermsRequired(policies)) => {
+                    let terms_html = policies.iter()
+                        .map(|policy| format!(
+                            "• <a href=\"{}\">{}</a>",
+                            htmlize::escape_attribute(&policy.url),
+                            htmlize::escape_text(&policy.name),
+                        ))
+                        .collect::<Vec<_>>()
+                        .join("<br>");
+                    self.terms_urls = policies.iter().map(|policy| policy.url.clone()).collect();
+                    self.view.html(id!(terms_html)).set_text(cx, &terms_html);
+                    self.view.check_box(id!(accept_terms_checkbox)).set_active(cx, false);
+                    self.view.view(id!(terms_view)).set_visible(cx, true);
+                    self.redraw(cx);
+                }
+                Some(LoginAction::HomeserverDiscovered(summary)) => {
+                    self.set_homeserver_summary(cx, &summary.to_display_string(), false);
+                }
+                Some(LoginAction::HomeserverDiscoveryFailed(error)) => {
+                    self.set_homeserver_summary(cx, error, true);
+                }
                 _ => { }
             }
         }
@@ -451,6 +688,14 @@ impl MatchEvent for LoginScreen {
         // Handle any of the SSO login buttons being clicked
         for (view_ref, brand) in self.view_set(button_set).iter().zip(&provider_brands) {
             if view_ref.finger_up(actions).is_some() && !self.sso_pending {
+                if let Err(error) = self.apply_login_proxy() {
+                    login_status_modal_inner.set_title(cx, "Invalid Proxy");
+                    login_status_modal_inner.set_status(cx, &error);
+                    login_status_modal_inner.button_ref().set_text(cx, "Okay");
+                    login_status_modal.open(cx);
+                    self.redraw(cx);
+                    continue;
+                }
                 submit_async_request(MatrixRequest::SpawnSSOServer{
                     identity_provider_id: format!("oidc-{}",brand),
                     brand: brand.to_string(),
@@ -462,6 +707,54 @@ impl MatchEvent for LoginScreen {
 
 }
 
+impl LoginScreen {
+    /// Uses the proxy entered on this screen (if any) for checking the homeserver and logging in,
+    /// returning a user-facing error if its URL is invalid.
+    fn apply_login_proxy(&self) -> Result<(), String> {
+        let proxy = self.view.text_input(id!(proxy_input)).text();
+        let proxy = proxy.trim();
+        if proxy.is_empty() {
+            set_login_proxy_settings(None);
+            return Ok(());
+        }
+        let proxy_settings = ProxySettings::from_cli_proxy(proxy);
+        proxy_settings.validate()?;
+        set_login_proxy_settings(Some(proxy_settings));
+        Ok(())
+    }
+
+    /// Shows the given text in the homeserver summary label, in red if it's an error.
+    fn set_homeserver_summary(&mut self, cx: &mut Cx, text: &str, is_error: bool) {
+        let label = self.view.label(id!(homeserver_summary_label));
+        let color = if is_error { COLOR_FG_DANGER_RED } else { COLOR_MESSAGE_NOTICE_TEXT };
+        label.apply_over(cx, live! { draw_text: { color: (color) } });
+        label.set_text(cx, text);
+        label.set_visible(cx, true);
+        self.redraw(cx);
+    }
+
+    /// Switches this screen between logging into an existing account
+    /// and creating (registering) a new account.
+    fn set_registering(&mut self, cx: &mut Cx, is_registering: bool) {
+        self.is_registering = is_registering;
+        self.view.view(id!(registration_view)).set_visible(cx, is_registering);
+        self.view.view(id!(terms_view)).set_visible(cx, false);
+        self.terms_urls.clear();
+        self.view.check_box(id!(accept_terms_checkbox)).set_active(cx, false);
+        self.view.text_input(id!(registration_token_input)).set_text(cx, "");
+        let (title, login_text, prompt, signup_text) = if is_registering {
+            ("Create a Robrix Account", "Create Account", "Already have an account?", "Back to login")
+        } else {
+            ("Login to Robrix", "Login", "Don't have an account?", "Create an account")
+        };
+        self.view.label(id!(title)).set_text(cx, title);
+        self.view.button(id!(login_button)).set_text(cx, login_text);
+        self.view.label(id!(signup_prompt)).set_text(cx, prompt);
+        self.view.button(id!(signup_button)).set_text(cx, signup_text);
+        self.redraw(cx);
+    }
+}
+
 /// Actions sent to or from the login screen.
 #[derive(Clone, DefaultNone, Debug)]
 pub enum LoginAction {
@@ -493,5 +786,12 @@ pub enum LoginAction {
     /// When an SSO-based login is pendng, pressing the cancel button will send
     /// an HTTP request to this SSO server URL to gracefully shut it down.
     SsoSetRedirectUrl(Url),
+    /// The homeserver requires the user to accept the given policies before registering.
+    RegistrationTermsRequired(Vec<TermsPolicy>),
+    /// The homeserver that the user asked to check was discovered and is supported by Robrix.
+    HomeserverDiscovered(HomeserverSummary),
+    /// The homeserver that the user asked to check couldn't be discovered or isn't supported,
+    /// with a user-facing explanation of why.
+    HomeserverDiscoveryFailed(String),
     None,
 }
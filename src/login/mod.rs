@@ -1,7 +1,10 @@
 use makepad_widgets::*;
 
+pub mod homeserver_discovery;
 pub mod login_screen;
 pub mod login_status_modal;
+pub mod oauth;
+pub mod registration;
 
 pub fn live_design(cx: &mut Cx) {
     login_screen::live_design(cx);
@@ -0,0 +1,309 @@
+//! Helpers for logging in via OAuth 2.0 with homeservers that use next-generation auth
+//! (MSC3861), e.g., those that delegate authentication to the Matrix Authentication Service.
+//!
+//! The Matrix SDK drives the authorization-code flow (including PKCE and dynamic client
+//! registration), whereas this module provides the client metadata that Robrix registers
+//! and the loopback server that receives the authorization server's redirect.
+
+use std::{io, time::Duration};
+
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use matrix_sdk::authentication::oauth::registration::{
+    ApplicationType, ClientMetadata, Localized, OAuthGrantType,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use url::Url;
+
+/// The path on the loopback server to which the authorization server redirects.
+const CALLBACK_PATH: &str = "/callback";
+
+/// The page shown in the browser once the authorization server has redirected back to Robrix.
+const CALLBACK_RESPONSE_HTML: &str = "<!DOCTYPE html><html><head><title>Robrix</title></head>\
+    <body><h2>You may now close this page and return to Robrix.</h2></body></html>";
+
+/// How long to wait for a connection to the loopback server to send its request line,
+/// after which that connection is dropped.
+///
+/// Browsers often open speculative connections that never send a request.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the metadata that Robrix registers with the authorization server
+/// via dynamic client registration, using the given loopback `redirect_uri`.
+pub fn client_metadata(redirect_uri: Url) -> ClientMetadata {
+    let client_uri = Url::parse("https://github.com/project-robius/robrix")
+        .expect("BUG: invalid Robrix client URI");
+    let mut metadata = ClientMetadata::new(
+        ApplicationType::Native,
+        vec![OAuthGrantType::AuthorizationCode { redirect_uris: vec![redirect_uri] }],
+        Localized::new(client_uri, None),
+    );
+    metadata.client_name = Some(Localized::new(String::from("Robrix"), None));
+    metadata
+}
+
+/// A local HTTP server that receives the redirect from the authorization server
+/// once the user has finished logging in via their browser.
+///
+/// Native apps use a loopback redirect URI with an ephemeral port, as per RFC 8252.
+pub struct LoopbackRedirectServer {
+    listener: TcpListener,
+    redirect_uri: Url,
+}
+
+impl LoopbackRedirectServer {
+    /// Binds a new loopback server to an ephemeral port on `127.0.0.1`.
+    pub async fn bind() -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let port = listener.local_addr()?.port();
+        let redirect_uri = Url::parse(&format!("http://127.0.0.1:{port}{CALLBACK_PATH}"))
+            .map_err(io::Error::other)?;
+        Ok(Self { listener, redirect_uri })
+    }
+
+    /// Returns the redirect URI that should be registered with the authorization server.
+    pub fn redirect_uri(&self) -> &Url {
+        &self.redirect_uri
+    }
+
+    /// Waits for the authorization server to redirect the user's browser to this server,
+    /// and returns the full callback URL, which includes the authorization code and state.
+    ///
+    /// Requests for other paths (e.g., the browser's favicon request) are answered with
+    /// `404 Not Found`, and connections are read concurrently such that an idle connection
+    /// can't block the callback.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Interrupted`] if a request for the callback path
+    /// that isn't a valid callback was received, which is how the login screen cancels a pending login.
+    pub async fn wait_for_callback(self) -> io::Result<Url> {
+        let mut pending_requests = FuturesUnordered::new();
+        loop {
+            let (mut stream, request_line) = tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, _addr) = accepted?;
+                    pending_requests.push(read_request_line_with_timeout(stream));
+                    continue;
+                }
+                Some(request) = pending_requests.next(), if !pending_requests.is_empty() => request,
+            };
+            let Some(request_line) = request_line else { continue };
+            let (status, body, result) = match parse_callback_request(&request_line, &self.redirect_uri) {
+                Some(callback_url) => ("200 OK", CALLBACK_RESPONSE_HTML, Some(Ok(callback_url))),
+                None if is_callback_path_request(&request_line) => (
+                    "400 Bad Request",
+                    "Login cancelled.",
+                    Some(Err(io::Error::new(io::ErrorKind::Interrupted, "OAuth login was cancelled"))),
+                ),
+                None => ("404 Not Found", "Not found.", None),
+            };
+            let response = format!(
+                "HTTP/1.1 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len(),
+            );
+            // The browser may have already closed the connection, which doesn't matter.
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+            if let Some(result) = result {
+                return result;
+            }
+        }
+    }
+}
+
+/// Reads the first line of an HTTP request from the given stream,
+/// giving up after [`REQUEST_READ_TIMEOUT`] or if reading fails.
+///
+/// Returns the stream along with the request line, such that the request can be answered.
+async fn read_request_line_with_timeout(mut stream: TcpStream) -> (TcpStream, Option<String>) {
+    let request_line = tokio::time::timeout(REQUEST_READ_TIMEOUT, read_request_line(&mut stream)).await
+        .ok()
+        .and_then(Result::ok)
+        .flatten();
+    (stream, request_line)
+}
+
+/// Reads the first line of an HTTP request from the given stream.
+///
+/// Returns `None` if the connection was closed before a full line was received.
+async fn read_request_line(stream: &mut TcpStream) -> io::Result<Option<String>> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(end) = buf.windows(2).position(|w| w == b"\r\n") {
+            return Ok(Some(String::from_utf8_lossy(&buf[..end]).into_owned()));
+        }
+        // Guard against clients that never send a line ending.
+        if buf.len() > 16 * 1024 {
+            return Ok(None);
+        }
+    }
+}
+
+/// Parses the given HTTP request line (e.g., `GET /callback?code=..&state=.. HTTP/1.1`)
+/// into the full callback URL, if it is a valid redirect from the authorization server.
+///
+/// A valid redirect is a `GET` request for the callback path that contains either
+/// an authorization `code` or an `error`, both of which must be accompanied by a `state`.
+fn parse_callback_request(request_line: &str, redirect_uri: &Url) -> Option<Url> {
+    let mut parts = request_line.split_whitespace();
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let url = redirect_uri.join(parts.next()?).ok()?;
+    if url.path() != CALLBACK_PATH {
+        return None;
+    }
+    let has_param = |name: &str| url.query_pairs().any(|(key, _)| key == name);
+    ((has_param("code") || has_param("error")) && has_param("state")).then_some(url)
+}
+
+/// Returns whether the given HTTP request line is a `GET` request for the callback path,
+/// ignoring its query and any trailing slash.
+fn is_callback_path_request(request_line: &str) -> bool {
+    let mut parts = request_line.split_whitespace();
+    parts.next() == Some("GET") && parts.next().is_some_and(|target| {
+        let path = target.split('?').next().unwrap_or_default();
+        path.trim_end_matches('/') == CALLBACK_PATH
+    })
+}
+
+#[cfg(test)]
+mod tests_oauth_loopback {
+    use super::*;
+
+    fn redirect_uri() -> Url {
+        Url::parse("http://127.0.0.1:4321/callback").unwrap()
+    }
+
+    #[test]
+    fn parses_authorization_code_callback() {
+        let url = parse_callback_request("GET /callback?code=abc&state=xyz HTTP/1.1", &redirect_uri()).unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:4321/callback?code=abc&state=xyz");
+    }
+
+    #[test]
+    fn parses_error_callback() {
+        assert!(parse_callback_request("GET /callback?error=access_denied&state=xyz HTTP/1.1", &redirect_uri()).is_some());
+    }
+
+    #[test]
+    fn rejects_invalid_callbacks() {
+        for request_line in [
+            "POST /callback?code=abc&state=xyz HTTP/1.1",
+            "GET /other?code=abc&state=xyz HTTP/1.1",
+            "GET /callback?code=abc HTTP/1.1",
+            "GET /callback/?login_token= HTTP/1.1",
+            "GET",
+            "",
+        ] {
+            assert!(parse_callback_request(request_line, &redirect_uri()).is_none(), "{request_line:?}");
+        }
+    }
+
+    /// Acts as a mock authorization server redirecting the user's browser back to the loopback server.
+    async fn send_redirect(redirect_uri: &Url, path_and_query: &str) -> String {
+        let addr = format!("{}:{}", redirect_uri.host_str().unwrap(), redirect_uri.port().unwrap());
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(format!("GET {path_and_query} HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n").as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn receives_redirect_from_mock_provider() {
+        let server = LoopbackRedirectServer::bind().await.unwrap();
+        let redirect_uri = server.redirect_uri().clone();
+        assert_eq!(redirect_uri.path(), CALLBACK_PATH);
+
+        let callback = tokio::spawn(server.wait_for_callback());
+        let response = send_redirect(&redirect_uri, "/callback?code=abc&state=xyz").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        let callback_url = callback.await.unwrap().unwrap();
+        assert_eq!(callback_url.query(), Some("code=abc&state=xyz"));
+    }
+
+    #[tokio::test]
+    async fn cancels_on_invalid_request() {
+        let server = LoopbackRedirectServer::bind().await.unwrap();
+        let redirect_uri = server.redirect_uri().clone();
+
+        let callback = tokio::spawn(server.wait_for_callback());
+        let response = send_redirect(&redirect_uri, "/callback/?login_token=").await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+
+        let err = callback.await.unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[tokio::test]
+    async fn ignores_idle_connections_and_other_paths() {
+        let server = LoopbackRedirectServer::bind().await.unwrap();
+        let redirect_uri = server.redirect_uri().clone();
+        let callback = tokio::spawn(server.wait_for_callback());
+
+        // A speculative connection that never sends a request must not block the callback.
+        let addr = format!("{}:{}", redirect_uri.host_str().unwrap(), redirect_uri.port().unwrap());
+        let _idle_connection = TcpStream::connect(addr).await.unwrap();
+
+        let response = send_redirect(&redirect_uri, "/favicon.ico").await;
+        assert!(response.starts_with("HTTP/1.1 404"));
+        let response = send_redirect(&redirect_uri, "/callback?code=abc&state=xyz").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        let callback_url = callback.await.unwrap().unwrap();
+        assert_eq!(callback_url.query(), Some("code=abc&state=xyz"));
+    }
+
+    /// Logs in via a mock OAuth provider, covering server metadata discovery,
+    /// dynamic client registration with our client metadata, the redirect to the
+    /// loopback server, the authorization code exchange, and refreshing the access token.
+    #[tokio::test]
+    async fn logs_in_and_refreshes_with_mock_provider() {
+        use matrix_sdk::{authentication::oauth::UrlOrQuery, ruma::serde::Raw, test_utils::mocks::MatrixMockServer};
+
+        let server = MatrixMockServer::new().await;
+        server.mock_versions().ok().mount().await;
+        server.mock_who_am_i().ok().mount().await;
+        let oauth_server = server.oauth();
+        oauth_server.mock_server_metadata().ok().named("discovery").mount().await;
+        oauth_server.mock_registration().ok().expect(1).named("registration").mount().await;
+        // One request to exchange the authorization code, and another to refresh the access token.
+        oauth_server.mock_token().ok().expect(2).named("token").mount().await;
+
+        let client = server.client_builder().unlogged().build().await;
+        client.oauth().server_metadata().await.unwrap();
+
+        let redirect_server = LoopbackRedirectServer::bind().await.unwrap();
+        let redirect_uri = redirect_server.redirect_uri().clone();
+        let registration_data = Raw::new(&client_metadata(redirect_uri.clone())).unwrap().into();
+        let authorization_data = client.oauth()
+            .login(redirect_uri.clone(), None, Some(registration_data), None)
+            .build()
+            .await
+            .unwrap();
+        let state = authorization_data.url.query_pairs()
+            .find_map(|(key, value)| (key == "state").then(|| value.into_owned()))
+            .unwrap();
+
+        let callback = tokio::spawn(redirect_server.wait_for_callback());
+        let response = send_redirect(&redirect_uri, &format!("/callback?code=mock_code&state={state}")).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        let callback_url = callback.await.unwrap().unwrap();
+
+        client.oauth().finish_login(UrlOrQuery::Url(callback_url)).await.unwrap();
+        assert!(client.user_id().is_some());
+        assert!(client.access_token().is_some());
+
+        client.oauth().refresh_access_token().await.unwrap();
+        assert!(client.access_token().is_some());
+    }
+}
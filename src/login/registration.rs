@@ -0,0 +1,153 @@
+//! Helpers for registering a new account on homeservers that allow it.
+//!
+//! Registration uses user-interactive authentication (UIAA): the homeserver offers
+//! one or more flows, each of which is a list of stages that must all be completed.
+//! Robrix can only complete flows whose stages are all in [`SUPPORTED_STAGES`].
+
+use matrix_sdk::ruma::api::client::uiaa::{AuthFlow, AuthType};
+use serde_json::Value;
+
+/// The registration stages that Robrix knows how to complete.
+pub const SUPPORTED_STAGES: [AuthType; 3] = [
+    AuthType::Dummy,
+    AuthType::Terms,
+    AuthType::RegistrationToken,
+];
+
+/// A policy document (e.g., terms of service) that the user must accept in order to register.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TermsPolicy {
+    pub name: String,
+    pub url: String,
+}
+
+/// Chooses the registration flow that Robrix should attempt to complete.
+///
+/// Flows that require a registration token are only preferred if the user provided one.
+/// If no flow consists solely of supported stages, this returns the unsupported stages.
+pub fn choose_flow(flows: &[AuthFlow], has_registration_token: bool) -> Result<&AuthFlow, Vec<AuthType>> {
+    let mut supported_flows = flows.iter()
+        .filter(|flow| flow.stages.iter().all(|stage| SUPPORTED_STAGES.contains(stage)))
+        .peekable();
+    let first_supported = supported_flows.peek().copied();
+    if let Some(flow) = supported_flows
+        .find(|flow| has_registration_token || !flow.stages.contains(&AuthType::RegistrationToken))
+        .or(first_supported)
+    {
+        return Ok(flow);
+    }
+
+    let mut unsupported = Vec::new();
+    for stage in flows.iter().flat_map(|flow| &flow.stages) {
+        if !SUPPORTED_STAGES.contains(stage) && !unsupported.contains(stage) {
+            unsupported.push(stage.clone());
+        }
+    }
+    Err(unsupported)
+}
+
+/// Returns a user-facing error message explaining that the given stages aren't supported.
+pub fn unsupported_stages_message(stages: &[AuthType]) -> String {
+    if stages.is_empty() {
+        return String::from("This homeserver does not offer any way to create an account.");
+    }
+    let descriptions = stages.iter()
+        .map(|stage| match stage {
+            AuthType::ReCaptcha => "solving a CAPTCHA",
+            AuthType::EmailIdentity => "verifying an email address",
+            AuthType::Msisdn => "verifying a phone number",
+            AuthType::Sso => "single sign-on",
+            other => other.as_str(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("Creating an account on this homeserver requires {descriptions}, \
+        which Robrix does not yet support.\n\n\
+        Please create your account using the homeserver's website or another client, \
+        and then log in here.")
+}
+
+/// Extracts the policies of the `m.login.terms` stage from the given UIAA `params` JSON.
+///
+/// Each policy may be translated into multiple languages; this prefers English,
+/// falling back to whichever translation comes first.
+pub fn terms_policies(params_json: &str) -> Vec<TermsPolicy> {
+    let Ok(params) = serde_json::from_str::<Value>(params_json) else { return Vec::new() };
+    let Some(policies) = params.get(AuthType::Terms.as_str())
+        .and_then(|terms| terms.get("policies"))
+        .and_then(Value::as_object)
+    else {
+        return Vec::new();
+    };
+
+    policies.values()
+        .filter_map(|policy| {
+            let policy = policy.as_object()?;
+            let translation = policy.get("en").or_else(||
+                policy.iter().find(|(key, value)| *key != "version" && value.is_object()).map(|(_, value)| value)
+            )?;
+            Some(TermsPolicy {
+                name: translation.get("name")?.as_str()?.to_owned(),
+                url: translation.get("url")?.as_str()?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests_registration {
+    use super::*;
+
+    fn flow(stages: &[AuthType]) -> AuthFlow {
+        AuthFlow::new(stages.to_vec())
+    }
+
+    #[test]
+    fn prefers_flows_without_token_unless_one_was_given() {
+        let flows = [
+            flow(&[AuthType::RegistrationToken, AuthType::Terms]),
+            flow(&[AuthType::Terms, AuthType::Dummy]),
+        ];
+        assert_eq!(choose_flow(&flows, false).unwrap(), &flows[1]);
+        assert_eq!(choose_flow(&flows, true).unwrap(), &flows[0]);
+
+        let token_only = [flow(&[AuthType::RegistrationToken])];
+        assert_eq!(choose_flow(&token_only, false).unwrap(), &token_only[0]);
+    }
+
+    #[test]
+    fn reports_unsupported_stages() {
+        let flows = [
+            flow(&[AuthType::ReCaptcha, AuthType::Terms]),
+            flow(&[AuthType::EmailIdentity, AuthType::ReCaptcha]),
+        ];
+        let unsupported = choose_flow(&flows, false).unwrap_err();
+        assert_eq!(unsupported, vec![AuthType::ReCaptcha, AuthType::EmailIdentity]);
+        assert!(unsupported_stages_message(&unsupported).contains("CAPTCHA"));
+    }
+
+    #[test]
+    fn parses_terms_policies() {
+        let params = r#"{
+            "m.login.terms": {
+                "policies": {
+                    "privacy_policy": {
+                        "version": "1.0",
+                        "en": { "name": "Privacy Policy", "url": "https://example.org/privacy" },
+                        "fr": { "name": "Politique de confidentialité", "url": "https://example.org/fr/privacy" }
+                    },
+                    "terms_of_service": {
+                        "version": "2.0",
+                        "de": { "name": "Nutzungsbedingungen", "url": "https://example.org/de/terms" }
+                    }
+                }
+            }
+        }"#;
+        let policies = terms_policies(params);
+        assert_eq!(policies, vec![
+            TermsPolicy { name: "Privacy Policy".into(), url: "https://example.org/privacy".into() },
+            TermsPolicy { name: "Nutzungsbedingungen".into(), url: "https://example.org/de/terms".into() },
+        ]);
+        assert!(terms_policies("{}").is_empty());
+    }
+}
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
+use matrix_sdk::ruma::OwnedUserId;
+
 use makepad_widgets::*;
 use tokio::sync::Notify;
 use crate::{shared::styles::COLOR_FG_DANGER_RED, sliding_sync::{submit_async_request, MatrixRequest}};
-use super::logout_state_machine::is_logout_past_point_of_no_return;
+use super::logout_state_machine::{is_logout_past_point_of_no_return, LogoutMode};
 
 live_design! {
     use link::theme::*;
@@ -60,6 +62,36 @@ live_design! {
                 text: "Are you sure you want to logout?"
             }
 
+            // Only shown for operations that may require user-interactive authentication.
+            password_input = <SimpleTextInput> {
+                visible: false,
+                width: Fill, height: Fit,
+                is_password: true,
+                empty_text: "Your password (if required by your homeserver)"
+            }
+
+            // Only shown when deactivating the account.
+            erase_checkbox = <CheckBoxFlat> {
+                visible: false,
+                draw_text: {
+                    color: #000000,
+                    text_style: <REGULAR_TEXT>{ font_size: 11 },
+                }
+                text: "Also erase my messages and other data, as far as possible"
+                active: false,
+            }
+
+            // The explicit confirmation step for destructive operations.
+            confirm_checkbox = <CheckBoxFlat> {
+                visible: false,
+                draw_text: {
+                    color: #000000,
+                    text_style: <REGULAR_TEXT>{ font_size: 11 },
+                }
+                text: ""
+                active: false,
+            }
+
             <View> {
                 width: Fill,
                 height: Fit,
@@ -109,13 +141,15 @@ pub struct LogoutConfirmModal {
     /// * Set to `Some(false)` after a logout error occurs.
     /// * Set to `None` when the user is still able to interact with the modal.
     #[rust] final_success: Option<bool>,
+    /// The logout operation that this modal is confirming.
+    #[rust] mode: LogoutMode,
 }
 
 /// Actions handled by the parent widget of the [`LogoutConfirmModal`].
 #[derive(Clone, Debug, DefaultNone)]
 pub enum LogoutConfirmModalAction {
-    /// The modal should be opened
-    Open,
+    /// The modal should be opened, in order to confirm the given logout operation.
+    Open(LogoutMode),
     /// The modal requested its parent widget to close.
     Close {
         /// `True` if the modal was closed after a successful logout action.
@@ -134,7 +168,12 @@ pub enum LogoutAction {
     /// A negative response to a logout request from the Matrix homeserver.
     LogoutFailure(String),
     /// A request from the background task to the main UI thread to clear all app state.
+    ///
+    /// Open rooms record their unsent drafts upon receiving this action,
+    /// which are then saved for the given account before being cleared.
     ClearAppState {
+        /// The account whose app state is being cleared, if any.
+        user_id: Option<OwnedUserId>,
         on_clear_appstate: Arc<Notify>,
     },
     /// Signal that the application is in an invalid state and needs to be restarted.
@@ -151,6 +190,13 @@ pub enum LogoutAction {
     },
     /// Indicates logout is in progress or not
     InProgress(bool),
+    /// All of the account's other sessions were signed out, keeping this session logged in.
+    OtherSessionsSignedOut {
+        /// The number of sessions that were signed out.
+        count: usize,
+    },
+    /// This session's locally-cached data was cleared, keeping it logged in.
+    LocalDataCleared,
 }
 
 impl std::fmt::Debug for LogoutAction {
@@ -166,6 +212,8 @@ impl std::fmt::Debug for LogoutAction {
                 write!(f, "ProgressUpdate({}, {}%)", message, percentage)
             }
             LogoutAction::InProgress(value) => write!(f, "InProgress({})", value),
+            LogoutAction::OtherSessionsSignedOut { count } => write!(f, "OtherSessionsSignedOut({})", count),
+            LogoutAction::LocalDataCleared => write!(f, "LocalDataCleared"),
         }
     }
 }
@@ -217,6 +265,9 @@ impl WidgetMatchEvent for LogoutConfirmModal {
                 cx.action(LogoutConfirmModalAction::Close { successful, was_internal: true });
                 self.reset_state(cx);
                 return;
+            } else if self.mode.requires_confirmation() && !self.check_box(id!(confirm_checkbox)).active(cx) {
+                self.set_message(cx, "Please check the box below to confirm that you want to continue.");
+                needs_redraw = true;
             } else {
                 self.set_message(cx, "Waiting for logout...");
                 confirm_button.set_enabled(cx, false);
@@ -225,7 +276,19 @@ impl WidgetMatchEvent for LogoutConfirmModal {
                 cancel_button.set_text(cx, "Abort");
                 cancel_button.set_enabled(cx, true);
 
-                submit_async_request(MatrixRequest::Logout { is_desktop: cx.display_context.is_desktop() });
+                let mode = match self.mode {
+                    LogoutMode::DeactivateAccount { .. } => LogoutMode::DeactivateAccount {
+                        erase: self.check_box(id!(erase_checkbox)).active(cx),
+                    },
+                    ref mode => mode.clone(),
+                };
+                let password = self.text_input(id!(password_input)).text();
+                submit_async_request(MatrixRequest::Logout {
+                    is_desktop: cx.display_context.is_desktop(),
+                    mode,
+                    password: (!password.is_empty()).then_some(password),
+                    confirmed: self.mode.requires_confirmation(),
+                });
                 needs_redraw = true;
             }
         }
@@ -243,6 +306,24 @@ impl WidgetMatchEvent for LogoutConfirmModal {
                     needs_redraw = true;
                 }
 
+                Some(LogoutAction::OtherSessionsSignedOut { count }) => {
+                    self.final_success = Some(true);
+                    self.set_message(cx, &format!("Signed out of {count} other session(s)."));
+                    confirm_button.set_text(cx, "Okay");
+                    confirm_button.set_enabled(cx, true);
+                    cancel_button.set_visible(cx, false);
+                    needs_redraw = true;
+                }
+
+                Some(LogoutAction::LocalDataCleared) => {
+                    self.final_success = Some(true);
+                    self.set_message(cx, "Cleared the local data. Your rooms are now being reloaded from your homeserver.");
+                    confirm_button.set_text(cx, "Okay");
+                    confirm_button.set_enabled(cx, true);
+                    cancel_button.set_visible(cx, false);
+                    needs_redraw = true;
+                }
+
                 Some(LogoutAction::LogoutFailure(error)) => {
                     if is_logout_past_point_of_no_return() {
                         self.label(id!(title)).set_text(cx, "Logout error, please restart Robrix.");
@@ -313,13 +394,59 @@ impl LogoutConfirmModal {
         self.label(id!(message)).set_text(cx, message);
     }
 
+    /// Sets the logout operation that this modal is confirming, and resets its state.
+    pub fn set_mode(&mut self, cx: &mut Cx, mode: LogoutMode) {
+        self.mode = mode;
+        self.reset_state(cx);
+    }
+
     fn reset_state(&mut self, cx: &mut Cx) {
         let cancel_button = self.button(id!(cancel_button));
         let confirm_button = self.button(id!(confirm_button));
         self.final_success = None;
-        self.set_message(cx, "Are you sure you want to logout?");
+        let (title, message, confirm_text, confirmation_text) = match self.mode {
+            LogoutMode::ThisSession => (
+                "Confirm Logout",
+                "Are you sure you want to logout?",
+                "Logout Now",
+                "",
+            ),
+            LogoutMode::OtherSessions => (
+                "Sign Out Other Sessions",
+                "This signs out all of your other sessions (devices), keeping only this one logged in. \
+                Those sessions will lose access to encrypted messages that have not been backed up.",
+                "Sign Out Others",
+                "I want to sign out all of my other sessions",
+            ),
+            LogoutMode::DeactivateAccount { .. } => (
+                "Deactivate Account",
+                "This permanently deactivates your account and logs you out everywhere. \
+                You will not be able to reactivate it or re-use your User ID.",
+                "Deactivate",
+                "I understand that this cannot be undone",
+            ),
+            LogoutMode::ClearLocalDataOnly => (
+                "Clear Local Data",
+                "This deletes the data that Robrix has cached for this account, which is then re-fetched \
+                from your homeserver. You will stay logged in, and your encryption keys are kept.",
+                "Clear Data",
+                "",
+            ),
+        };
+        self.label(id!(title)).set_text(cx, title);
+        self.set_message(cx, message);
+        let password_input = self.text_input(id!(password_input));
+        password_input.set_text(cx, "");
+        password_input.set_visible(cx, matches!(self.mode, LogoutMode::OtherSessions | LogoutMode::DeactivateAccount { .. }));
+        let erase_checkbox = self.check_box(id!(erase_checkbox));
+        erase_checkbox.set_active(cx, false);
+        erase_checkbox.set_visible(cx, matches!(self.mode, LogoutMode::DeactivateAccount { .. }));
+        let confirm_checkbox = self.check_box(id!(confirm_checkbox));
+        confirm_checkbox.set_active(cx, false);
+        confirm_checkbox.set_text(cx, confirmation_text);
+        confirm_checkbox.set_visible(cx, self.mode.requires_confirmation());
         confirm_button.set_enabled(cx, true);
-        confirm_button.set_text(cx, "Logout Now");
+        confirm_button.set_text(cx, confirm_text);
         cancel_button.set_visible(cx, true);
         cancel_button.set_enabled(cx, true);
         cancel_button.set_text(cx, "Cancel");
@@ -344,4 +471,11 @@ impl LogoutConfirmModalRef {
         }
     }
 
+    /// See [`LogoutConfirmModal::set_mode()`].
+    pub fn set_mode(&self, cx: &mut Cx, mode: LogoutMode) {
+        if let Some(mut inner) = self.borrow_mut() {
+            inner.set_mode(cx, mode);
+        }
+    }
+
 }
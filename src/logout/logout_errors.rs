@@ -20,6 +20,12 @@ pub enum RecoverableError {
     Timeout(String),
     /// User cancelled the operation
     Cancelled,
+    /// A destructive operation (e.g., deactivating the account) wasn't explicitly confirmed
+    NotConfirmed,
+    /// The homeserver requires the account's password, but none was given
+    AuthenticationRequired,
+    /// Signing out other sessions or deactivating the account failed
+    AccountOperationFailed(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,6 +42,9 @@ pub enum UnrecoverableError {
 impl fmt::Display for LogoutError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            LogoutError::Recoverable(RecoverableError::NotConfirmed) => write!(f, "This action must be confirmed first."),
+            LogoutError::Recoverable(RecoverableError::AuthenticationRequired) => write!(f, "Your homeserver requires your password to do this."),
+            LogoutError::Recoverable(RecoverableError::AccountOperationFailed(e)) => write!(f, "{e}"),
             LogoutError::Recoverable(e) => write!(f, "Recoverable error: {:?}", e),
             LogoutError::Unrecoverable(e) => write!(f, "Unrecoverable error: {:?}", e),
         }
@@ -32,6 +32,21 @@
 //!                                                                        Failed
 //! ```
 //!
+//! ## Logout Modes
+//!
+//! [`LogoutConfig::mode`] selects what the state machine does with the account's sessions:
+//! - [`LogoutMode::ThisSession`]: the flow above, which logs out only this session.
+//! - [`LogoutMode::DeactivateAccount`]: the flow above, but `DeactivatingAccount` replaces
+//!   `LoggingOutFromServer`, which permanently deactivates the account (and optionally erases its data).
+//! - [`LogoutMode::OtherSessions`]: `PreChecking → SigningOutOtherSessions → Completed`,
+//!   which signs out all of the account's other sessions while keeping this one logged in.
+//! - [`LogoutMode::ClearLocalDataOnly`]: the flow above without any server request,
+//!   plus `ClearingLocalData` (85%) after `ShuttingDownTasks`, which deletes the session's
+//!   cached data but keeps its server-side session, such that it is restored once the runtime restarts.
+//!
+//! Signing out other sessions and deactivating the account require [`LogoutConfig::confirmed`]
+//! to be set, and may require the account's password for user-interactive authentication (UIAA).
+//!
 //! ## Critical Design Points
 //!
 //! ### Point of No Return (50% completion)
@@ -63,10 +78,11 @@
 //!
 //! ## State Machine Execution Flow
 //!
-//! 1. **PreChecking**: Validate CLIENT, SYNC_SERVICE, and access_token existence
-//! 2. **StoppingSyncService**: Stop sync service to prevent new data
-//! 3. **LoggingOutFromServer**: Call `client.matrix_auth().logout()` (60s timeout)
-//! 4. **PointOfNoReturn**: Set global flags, delete saved user ID
+//! 1. **PreChecking**: Validate CLIENT and access_token existence, and that destructive modes were confirmed
+//! 2. **StoppingSyncService**: Stop sync service (if any) to prevent new data
+//! 3. **LoggingOutFromServer**: Call `client.logout()` (60s timeout)
+//! 4. **PointOfNoReturn**: Set global flags, forget the logged-out account
+//!    (another logged-in account, if any, becomes the one restored next)
 //! 5. **ClosingTabs**: Close desktop tabs via `MainDesktopUiAction::CloseAllTabs`
 //! 6. **CleaningAppState**: Clear global resources and notify UI cleanup
 //! 7. **ShuttingDownTasks**: Call `shutdown_background_tasks()`
@@ -76,12 +92,16 @@
 //! ## Usage
 //!
 //! ```rust
-//! let result = logout_with_state_machine(is_desktop).await;
+//! let result = logout_with_state_machine(LogoutConfig { is_desktop, ..Default::default() }).await;
 //! ```
 //!
 //! Progress updates are sent via `LogoutAction::ProgressUpdate` for UI feedback.
 //! Errors are classified as `Recoverable` or `Unrecoverable` for appropriate handling.
+//!
+//! The state machine performs all operations via a [`LogoutBackend`], which is the real
+//! Matrix client and Robrix's global state in the app, and a mock client in tests.
 
+use std::future::Future;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
@@ -89,12 +109,17 @@ use tokio::sync::{Mutex, Notify};
 use anyhow::{anyhow, Result};
 use makepad_widgets::{Cx, log};
 
-use crate::persistence::delete_latest_user_id;
+use matrix_sdk::{
+    ruma::{api::client::uiaa::{self, UserIdentifier}, OwnedDeviceId, OwnedUserId, UserId},
+    Client,
+};
+
+use crate::persistence::{clear_session_caches, delete_latest_user_id, delete_persisted_session, delete_session_secrets, remove_logged_in_account, save_latest_user_id};
 use crate::settings::SettingsAction;
 use crate::sliding_sync::clean_app_state;
 use crate::{
     home::main_desktop_ui::MainDesktopUiAction,
-    sliding_sync::{get_client, get_sync_service, shutdown_background_tasks, start_matrix_tokio},
+    sliding_sync::{get_client, get_sync_service, pause_classic_sync, resume_classic_sync, shutdown_background_tasks, start_matrix_tokio},
 };
 use super::logout_confirm_modal::{LogoutAction, ClearedComponentType};
 use super::logout_errors::{LogoutError, RecoverableError, UnrecoverableError};
@@ -104,12 +129,16 @@ use super::logout_errors::{LogoutError, RecoverableError, UnrecoverableError};
 pub enum LogoutState {
     /// Initial state before logout starts
     Idle,
-    /// Checking prerequisites (client, sync service existence)
+    /// Checking prerequisites (client existence, confirmation)
     PreChecking,
     /// Stopping the sync service
     StoppingSyncService,
     /// Performing server-side logout
     LoggingOutFromServer,
+    /// Signing out all of the account's other sessions
+    SigningOutOtherSessions,
+    /// Deactivating the account on the server
+    DeactivatingAccount,
     /// Reached point of no return - session invalidated
     PointOfNoReturn,
     /// Closing UI tabs (desktop only)
@@ -118,6 +147,8 @@ pub enum LogoutState {
     CleaningAppState,
     /// Shutting down background tasks
     ShuttingDownTasks,
+    /// Deleting the session's locally-cached data
+    ClearingLocalData,
     /// Restarting the Matrix runtime
     RestartingRuntime,
     /// Logout completed successfully
@@ -156,8 +187,33 @@ impl LogoutProgress {
     }
 }
 
+/// What the logout process does with the current account's sessions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum LogoutMode {
+    /// Log out of this session only.
+    #[default]
+    ThisSession,
+    /// Sign out all of the account's other sessions, keeping this session logged in.
+    OtherSessions,
+    /// Permanently deactivate the account, which also logs out all of its sessions.
+    DeactivateAccount {
+        /// Whether to ask the homeserver to erase the account's data, e.g., its sent messages.
+        erase: bool,
+    },
+    /// Delete this session's locally-cached data without logging out,
+    /// such that it is re-fetched from the homeserver.
+    ClearLocalDataOnly,
+}
+
+impl LogoutMode {
+    /// Returns whether this mode must be explicitly confirmed via [`LogoutConfig::confirmed`].
+    pub fn requires_confirmation(&self) -> bool {
+        matches!(self, LogoutMode::OtherSessions | LogoutMode::DeactivateAccount { .. })
+    }
+}
+
 /// Configuration for logout process
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LogoutConfig {
     /// Timeout for closing tabs
     pub tab_close_timeout: Duration,
@@ -169,6 +225,12 @@ pub struct LogoutConfig {
     pub allow_cancellation: bool,
     /// Whether this is desktop mode
     pub is_desktop: bool,
+    /// What to do with the account's sessions
+    pub mode: LogoutMode,
+    /// The account's password, used if the homeserver requires user-interactive authentication
+    pub password: Option<String>,
+    /// Whether the user explicitly confirmed a mode that [requires confirmation](LogoutMode::requires_confirmation)
+    pub confirmed: bool,
 }
 
 impl Default for LogoutConfig {
@@ -179,21 +241,235 @@ impl Default for LogoutConfig {
             server_logout_timeout: Duration::from_secs(60),
             allow_cancellation: true,
             is_desktop: true,
+            mode: LogoutMode::default(),
+            password: None,
+            confirmed: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for LogoutConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogoutConfig")
+            .field("tab_close_timeout", &self.tab_close_timeout)
+            .field("app_state_cleanup_timeout", &self.app_state_cleanup_timeout)
+            .field("server_logout_timeout", &self.server_logout_timeout)
+            .field("allow_cancellation", &self.allow_cancellation)
+            .field("is_desktop", &self.is_desktop)
+            .field("mode", &self.mode)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("confirmed", &self.confirmed)
+            .finish()
+    }
+}
+
+/// The operations that the logout state machine performs on the Matrix client and the rest of Robrix.
+///
+/// This allows the state transitions to be tested with a mock client.
+pub trait LogoutBackend: Send + Sync {
+    /// Checks that the client exists and has an access token.
+    fn check_prerequisites(&self) -> Result<(), LogoutError>;
+    /// Returns the user ID of the current account.
+    fn user_id(&self) -> Option<OwnedUserId>;
+    /// Stops the sync service, if there is one.
+    fn stop_sync(&self) -> impl Future<Output = ()> + Send;
+    /// Restarts the sync service after a recoverable failure.
+    fn restart_sync(&self) -> impl Future<Output = ()> + Send;
+    /// Logs this session out from the homeserver.
+    fn logout_from_server(&self) -> impl Future<Output = Result<(), LogoutError>> + Send;
+    /// Signs out all of the account's other sessions, returning how many were signed out.
+    fn sign_out_other_sessions(&self, password: Option<String>) -> impl Future<Output = Result<usize, LogoutError>> + Send;
+    /// Permanently deactivates the account.
+    fn deactivate_account(&self, erase: bool, password: Option<String>) -> impl Future<Output = Result<(), LogoutError>> + Send;
+    /// Forgets the given logged-out account, such that it isn't restored on the next start.
+    ///
+    /// If `delete_session` is `true` (e.g., because the account was deactivated),
+    /// its persisted session is deleted as well.
+    fn forget_account(&self, user_id: Option<OwnedUserId>, delete_session: bool) -> impl Future<Output = ()> + Send;
+    /// Closes all desktop tabs, waiting up to the given timeout.
+    fn close_all_tabs(&self, timeout: Duration) -> impl Future<Output = Result<()>> + Send;
+    /// Clears all global and UI app state.
+    fn clean_app_state(&self, config: &LogoutConfig) -> impl Future<Output = Result<()>> + Send;
+    /// Deletes the given account's locally-cached data.
+    fn clear_local_data(&self, user_id: Option<OwnedUserId>) -> impl Future<Output = Result<()>> + Send;
+    /// Shuts down the Matrix runtime's background tasks.
+    fn shutdown_background_tasks(&self);
+    /// Restarts the Matrix runtime.
+    fn restart_runtime(&self) -> Result<()>;
+    /// Reports the given progress to the UI.
+    fn report_progress(&self, progress: &LogoutProgress);
+    /// Posts the given logout action to the UI.
+    fn post_action(&self, action: LogoutAction);
+    /// Closes the settings screen.
+    fn close_settings(&self);
+}
+
+/// The real [`LogoutBackend`], which uses the current Matrix client and Robrix's global state.
+#[derive(Debug, Default)]
+pub struct MatrixLogoutBackend;
+
+impl MatrixLogoutBackend {
+    fn client(&self) -> Result<Client, LogoutError> {
+        get_client().ok_or(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared))
+    }
+}
+
+impl LogoutBackend for MatrixLogoutBackend {
+    fn check_prerequisites(&self) -> Result<(), LogoutError> {
+        // Note: there is no sync service when using classic sync.
+        let client = self.client().inspect_err(|_| log!("perform_prechecks: client cleared"))?;
+        if client.access_token().is_none() {
+            log!("perform_prechecks: no access token");
+            return Err(LogoutError::Recoverable(RecoverableError::NoAccessToken));
+        }
+        Ok(())
+    }
+
+    fn user_id(&self) -> Option<OwnedUserId> {
+        get_client().and_then(|client| client.user_id().map(ToOwned::to_owned))
+    }
+
+    async fn stop_sync(&self) {
+        if let Some(sync_service) = get_sync_service() {
+            sync_service.stop().await;
+        }
+        // Classic sync has no sync service, so its sync loop must be paused separately.
+        pause_classic_sync();
+    }
+
+    async fn restart_sync(&self) {
+        if let Some(sync_service) = get_sync_service() {
+            sync_service.start().await;
+        }
+        resume_classic_sync();
+    }
+
+    async fn logout_from_server(&self) -> Result<(), LogoutError> {
+        self.client()?.logout().await
+            .map_err(|e| LogoutError::Recoverable(RecoverableError::ServerLogoutFailed(e.to_string())))
+    }
+
+    async fn sign_out_other_sessions(&self, password: Option<String>) -> Result<usize, LogoutError> {
+        let client = self.client()?;
+        let own_device_id = client.device_id().map(ToOwned::to_owned);
+        let device_ids: Vec<OwnedDeviceId> = client.devices().await
+            .map_err(account_operation_failed)?
+            .devices
+            .into_iter()
+            .map(|device| device.device_id)
+            .filter(|device_id| Some(device_id) != own_device_id.as_ref())
+            .collect();
+        if device_ids.is_empty() {
+            return Ok(0);
+        }
+        if let Err(e) = client.delete_devices(&device_ids, None).await {
+            let Some(uiaa_info) = e.as_uiaa_response() else { return Err(account_operation_failed(e)) };
+            let auth = password_auth(&client, uiaa_info.session.clone(), password)?;
+            client.delete_devices(&device_ids, Some(auth)).await.map_err(account_operation_failed)?;
+        }
+        Ok(device_ids.len())
+    }
+
+    async fn deactivate_account(&self, erase: bool, password: Option<String>) -> Result<(), LogoutError> {
+        let client = self.client()?;
+        let account = client.account();
+        if let Err(e) = account.deactivate(None, None, erase).await {
+            let Some(uiaa_info) = e.as_uiaa_response() else { return Err(account_operation_failed(e)) };
+            let auth = password_auth(&client, uiaa_info.session.clone(), password)?;
+            account.deactivate(None, Some(auth), erase).await.map_err(account_operation_failed)?;
+        }
+        Ok(())
+    }
+
+    async fn forget_account(&self, user_id: Option<OwnedUserId>, delete_session: bool) {
+        forget_logged_out_account(user_id.as_deref(), delete_session).await;
+    }
+
+    async fn close_all_tabs(&self, timeout: Duration) -> Result<()> {
+        let on_close_all = Arc::new(Notify::new());
+        Cx::post_action(MainDesktopUiAction::CloseAllTabs { on_close_all: on_close_all.clone() });
+        
+        match tokio::time::timeout(timeout, on_close_all.notified()).await {
+            Ok(_) => {
+                log!("Received signal that all tabs were closed successfully");
+                Ok(())
+            }
+            Err(_) => Err(anyhow!("Timed out waiting for tabs to close")),
         }
     }
+
+    async fn clean_app_state(&self, config: &LogoutConfig) -> Result<()> {
+        // All static resources (CLIENT, SYNC_SERVICE, etc.) are defined in the sliding_sync module,
+        // so the state machine delegates the cleanup operation to sliding_sync's clean_app_state function
+        // rather than accessing these static variables directly from outside the module.
+        clean_app_state(config).await
+    }
+
+    async fn clear_local_data(&self, user_id: Option<OwnedUserId>) -> Result<()> {
+        let user_id = user_id.ok_or_else(|| anyhow!("No account to clear the local data of"))?;
+        clear_session_caches(&user_id).await
+    }
+
+    fn shutdown_background_tasks(&self) {
+        shutdown_background_tasks();
+    }
+
+    fn restart_runtime(&self) -> Result<()> {
+        start_matrix_tokio()
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to restart runtime: {}", e))
+    }
+
+    fn report_progress(&self, progress: &LogoutProgress) {
+        log!("Sending progress update: {} ({}%)", progress.message, progress.percentage);
+        Cx::post_action(LogoutAction::ProgressUpdate {
+            message: progress.message.clone(),
+            percentage: progress.percentage,
+        });
+    }
+
+    fn post_action(&self, action: LogoutAction) {
+        Cx::post_action(action);
+    }
+
+    fn close_settings(&self) {
+        Cx::post_action(SettingsAction::CloseSettings);
+    }
+}
+
+/// Returns the password auth data for completing user-interactive authentication,
+/// or an error if no password was given.
+fn password_auth(client: &Client, session: Option<String>, password: Option<String>) -> Result<uiaa::AuthData, LogoutError> {
+    let password = password.ok_or(LogoutError::Recoverable(RecoverableError::AuthenticationRequired))?;
+    let user_id = client.user_id().ok_or(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared))?;
+    let mut auth = uiaa::Password::new(UserIdentifier::UserIdOrLocalpart(user_id.to_string()), password);
+    auth.session = session;
+    Ok(uiaa::AuthData::Password(auth))
+}
+
+fn account_operation_failed(error: impl std::fmt::Display) -> LogoutError {
+    LogoutError::Recoverable(RecoverableError::AccountOperationFailed(error.to_string()))
 }
 
 /// State machine for managing the logout process
-pub struct LogoutStateMachine {
+pub struct LogoutStateMachine<B: LogoutBackend = MatrixLogoutBackend> {
     current_state: Arc<Mutex<LogoutState>>,
     progress: Arc<Mutex<LogoutProgress>>,
     config: LogoutConfig,
+    backend: B,
     point_of_no_return: Arc<AtomicBool>,
     cancellation_requested: Arc<AtomicBool>,
 }
 
 impl LogoutStateMachine {
     pub fn new(config: LogoutConfig) -> Self {
+        Self::with_backend(config, MatrixLogoutBackend)
+    }
+}
+
+impl<B: LogoutBackend> LogoutStateMachine<B> {
+    /// Creates a state machine that performs its operations via the given backend.
+    pub fn with_backend(config: LogoutConfig, backend: B) -> Self {
         let initial_progress = LogoutProgress::new(
             LogoutState::Idle,
             "Ready to logout".to_string(),
@@ -204,6 +480,7 @@ impl LogoutStateMachine {
             current_state: Arc::new(Mutex::new(LogoutState::Idle)),
             progress: Arc::new(Mutex::new(initial_progress)),
             config,
+            backend,
             point_of_no_return: Arc::new(AtomicBool::new(false)),
             cancellation_requested: Arc::new(AtomicBool::new(false)),
         }
@@ -248,27 +525,56 @@ impl LogoutStateMachine {
         drop(state);
         
         let mut progress = self.progress.lock().await;
-        progress.update(new_state, message.clone(), percentage);
-        let progress_message = progress.message.clone();
-        let progress_percentage = progress.percentage;
-        drop(progress);
+        progress.update(new_state, message, percentage);
+        let progress = progress.clone();
         
         // Send progress update to UI
-        log!("Sending progress update: {} ({}%)", progress_message, progress_percentage);
-        Cx::post_action(LogoutAction::ProgressUpdate { 
-            message: progress_message,
-            percentage: progress_percentage
-        });
+        self.backend.report_progress(&progress);
         
         Ok(())
     }
+
+    /// Transitions to the `Failed` state with the given error and message, and handles the error.
+    async fn fail(&self, error: LogoutError, message: String) -> Result<()> {
+        self.transition_to(LogoutState::Failed(error.clone()), message, 0).await?;
+        self.handle_error(&error).await;
+        Err(anyhow!(error))
+    }
+
+    /// Runs the given server request with the configured server timeout.
+    async fn with_server_timeout<T>(
+        &self,
+        request: impl Future<Output = Result<T, LogoutError>>,
+        timeout_message: &str,
+    ) -> Result<T, LogoutError> {
+        tokio::time::timeout(self.config.server_logout_timeout, request).await
+            .unwrap_or_else(|_| Err(LogoutError::Recoverable(RecoverableError::Timeout(timeout_message.to_string()))))
+    }
+
+    /// Marks the point of no return as reached, after which all failures are unrecoverable.
+    async fn reach_point_of_no_return(&self, message: &str) -> Result<()> {
+        self.point_of_no_return.store(true, Ordering::Release);
+        set_logout_point_of_no_return(true);
+        self.transition_to(
+            LogoutState::PointOfNoReturn,
+            message.to_string(),
+            50
+        ).await
+    }
+
+    /// Sets the global logout-in-progress flag, notifying the UI if it changed.
+    fn set_in_progress(&self, value: bool) {
+        if set_logout_in_progress(value) {
+            self.backend.post_action(LogoutAction::InProgress(value));
+        }
+    }
     
     /// Execute the logout process
     pub async fn execute(&self) -> Result<()> {
-        log!("LogoutStateMachine::execute() started");
+        log!("LogoutStateMachine::execute() started with mode {:?}", self.config.mode);
         
         // Set logout in progress flag
-        set_logout_in_progress(true);
+        self.set_in_progress(true);
         
         // Reset global point of no return flag
         set_logout_point_of_no_return(false);
@@ -281,14 +587,12 @@ impl LogoutStateMachine {
         ).await?;
         
         // Pre-checks
-        if let Err(e) = self.perform_prechecks().await {
-            self.transition_to(
-                LogoutState::Failed(e.clone()),
-                format!("Precheck failed: {}", e),
-                0
-            ).await?;
-            self.handle_error(&e).await;
-            return Err(anyhow!(e));
+        if let Err(e) = self.perform_prechecks() {
+            return self.fail(e.clone(), format!("Precheck failed: {}", e)).await;
+        }
+
+        if self.config.mode == LogoutMode::OtherSessions {
+            return self.sign_out_other_sessions().await;
         }
         
         // Stop sync service
@@ -297,71 +601,53 @@ impl LogoutStateMachine {
             "Stopping sync service...".to_string(),
             20
         ).await?;
+        self.backend.stop_sync().await;
         
-        if let Err(e) = self.stop_sync_service().await {
-            self.transition_to(
-                LogoutState::Failed(e.clone()),
-                format!("Failed to stop sync service: {}", e),
-                0
-            ).await?;
-            self.handle_error(&e).await;
-            return Err(anyhow!(e));
-        }
-        
-        // Server logout
-        self.transition_to(
-            LogoutState::LoggingOutFromServer,
-            "Logging out from server...".to_string(),
-            30
-        ).await?;
-        
-        match self.perform_server_logout().await {
-            Ok(_) => {
-                self.point_of_no_return.store(true, Ordering::Release);
-                set_logout_point_of_no_return(true);
-                self.transition_to(
-                    LogoutState::PointOfNoReturn,
-                    "Point of no return reached".to_string(),
-                    50
-                ).await?;
-                
-                // We delete latest_user_id after reaching LOGOUT_POINT_OF_NO_RETURN:
-                // 1. To prevent auto-login with invalid session on next start
-                // 2. While keeping session file intact for potential future login
-                if let Err(e) = delete_latest_user_id().await {
-                    log!("Warning: Failed to delete latest user ID: {}", e);
-                }
+        // Only the currently-selected account is logged out; other logged-in accounts remain.
+        let logged_out_user_id = self.backend.user_id();
+
+        match &self.config.mode {
+            LogoutMode::ClearLocalDataOnly => {
+                // Nothing changes on the server, but the client is torn down from here on.
+                self.reach_point_of_no_return("Clearing local data...").await?;
             }
-            Err(e) => {
-                // Check if it's an M_UNKNOWN_TOKEN error
-                if matches!(&e, LogoutError::Recoverable(RecoverableError::ServerLogoutFailed(msg)) if msg.contains("M_UNKNOWN_TOKEN")) {
-                    log!("Token already invalidated, continuing with logout");
-                    self.point_of_no_return.store(true, Ordering::Release);
-                    set_logout_point_of_no_return(true);
-                    self.transition_to(
-                        LogoutState::PointOfNoReturn,
-                        "Token already invalidated".to_string(),
-                        50
-                    ).await?;
-                    
-                    // Same delete operation as in the success case above
-                    if let Err(e) = delete_latest_user_id().await {
-                        log!("Warning: Failed to delete latest user ID: {}", e);
+            mode => {
+                let (state, message, failure_message) = match mode {
+                    LogoutMode::DeactivateAccount { .. } => (LogoutState::DeactivatingAccount, "Deactivating account...", "Account deactivation failed"),
+                    _ => (LogoutState::LoggingOutFromServer, "Logging out from server...", "Server logout failed"),
+                };
+                self.transition_to(state, message.to_string(), 30).await?;
+
+                let result = match mode {
+                    LogoutMode::DeactivateAccount { erase } => self.with_server_timeout(
+                        self.backend.deactivate_account(*erase, self.config.password.clone()),
+                        "Account deactivation timed out",
+                    ).await,
+                    _ => self.with_server_timeout(
+                        self.backend.logout_from_server(),
+                        "Server logout timed out",
+                    ).await,
+                };
+                match result {
+                    Ok(()) => self.reach_point_of_no_return("Point of no return reached").await?,
+                    // Check if it's an M_UNKNOWN_TOKEN error
+                    Err(LogoutError::Recoverable(RecoverableError::ServerLogoutFailed(msg))) if msg.contains("M_UNKNOWN_TOKEN") => {
+                        log!("Token already invalidated, continuing with logout");
+                        self.reach_point_of_no_return("Token already invalidated").await?;
                     }
-                } else {
-                    // Restart sync service since we haven't reached point of no return
-                    if let Some(sync_service) = get_sync_service() {
-                        sync_service.start().await;
+                    Err(e) => {
+                        // Restart sync service since we haven't reached point of no return
+                        self.backend.restart_sync().await;
+                        return self.fail(e.clone(), format!("{failure_message}: {e}")).await;
                     }
-                    
-                    self.transition_to(
-                        LogoutState::Failed(e.clone()),
-                        format!("Server logout failed: {}", e),
-                        0
-                    ).await?;
-                    self.handle_error(&e).await;
-                    return Err(anyhow!(e));
                 }
+
+                // We forget the logged-out account after reaching LOGOUT_POINT_OF_NO_RETURN:
+                // 1. To prevent auto-login with invalid session on next start
+                // 2. To delete its now-invalid secrets from the secret store
+                // 3. To delete a deactivated account's session, which can never be restored
+                let delete_session = matches!(mode, LogoutMode::DeactivateAccount { .. });
+                self.backend.forget_account(logged_out_user_id.clone(), delete_session).await;
             }
         }
         
@@ -375,15 +661,9 @@ impl LogoutStateMachine {
                 60
             ).await?;
             
-            if let Err(e) = self.close_all_tabs().await {
+            if let Err(e) = self.backend.close_all_tabs(self.config.tab_close_timeout).await {
                 let error = LogoutError::Unrecoverable(UnrecoverableError::PostPointOfNoReturnFailure(e.to_string()));
-                self.transition_to(
-                    LogoutState::Failed(error.clone()),
-                    "Failed to close tabs".to_string(),
-                    0
-                ).await?;
-                self.handle_error(&error).await;
-                return Err(anyhow!(error));
+                return self.fail(error, "Failed to close tabs".to_string()).await;
             }
         }
         
@@ -394,18 +674,9 @@ impl LogoutStateMachine {
             70
         ).await?;
         
-        // All static resources (CLIENT, SYNC_SERVICE, etc.) are defined in the sliding_sync module,
-        // so the state machine delegates the cleanup operation to sliding_sync's clean_app_state function
-        // rather than accessing these static variables directly from outside the module.
-        if let Err(e) = clean_app_state(&self.config).await {
+        if let Err(e) = self.backend.clean_app_state(&self.config).await {
             let error = LogoutError::Unrecoverable(UnrecoverableError::PostPointOfNoReturnFailure(e.to_string()));
-            self.transition_to(
-                LogoutState::Failed(error.clone()),
-                "Failed to clean app state".to_string(),
-                0
-            ).await?;
-            self.handle_error(&error).await;
-            return Err(anyhow!(error));
+            return self.fail(error, "Failed to clean app state".to_string()).await;
         }
         
         // Shutdown tasks
@@ -415,7 +686,21 @@ impl LogoutStateMachine {
             80
         ).await?;
         
-        self.shutdown_background_tasks();
+        self.backend.shutdown_background_tasks();
+
+        // The session's database is only closed once the background tasks have shut down.
+        if self.config.mode == LogoutMode::ClearLocalDataOnly {
+            self.transition_to(
+                LogoutState::ClearingLocalData,
+                "Deleting locally-cached data...".to_string(),
+                85
+            ).await?;
+
+            if let Err(e) = self.backend.clear_local_data(logged_out_user_id).await {
+                let error = LogoutError::Unrecoverable(UnrecoverableError::PostPointOfNoReturnFailure(e.to_string()));
+                return self.fail(error, "Failed to clear local data".to_string()).await;
+            }
+        }
         
         // Restart runtime
         self.transition_to(
@@ -424,120 +709,85 @@ impl LogoutStateMachine {
             90
         ).await?;
         
-        if let Err(e) = self.restart_runtime(){
+        if let Err(e) = self.backend.restart_runtime() {
             let error = LogoutError::Unrecoverable(UnrecoverableError::RuntimeRestartFailed);
-            self.transition_to(
-                LogoutState::Failed(error.clone()),
-                format!("Failed to restart runtime: {}", e),
-                0
-            ).await?;
-            self.handle_error(&error).await;
-            return Err(anyhow!(error));
+            return self.fail(error, format!("Failed to restart runtime: {}", e)).await;
         }
         
         // Success!
+        let message = match self.config.mode {
+            LogoutMode::DeactivateAccount { .. } => "Account deactivated successfully",
+            LogoutMode::ClearLocalDataOnly => "Local data cleared successfully",
+            _ => "Logout completed successfully",
+        };
         self.transition_to(
             LogoutState::Completed,
-            "Logout completed successfully".to_string(),
+            message.to_string(),
             100
         ).await?;
 
         // CloseSetting after logout
-        Cx::post_action(SettingsAction::CloseSettings);
+        self.backend.close_settings();
 
         // Reset logout in progress flag
-        set_logout_in_progress(false);
-        Cx::post_action(LogoutAction::LogoutSuccess);
+        self.set_in_progress(false);
+        self.backend.post_action(match self.config.mode {
+            // The same session is restored once the runtime restarts, so the user isn't logged out.
+            LogoutMode::ClearLocalDataOnly => LogoutAction::LocalDataCleared,
+            _ => LogoutAction::LogoutSuccess,
+        });
+        Ok(())
+    }
+
+    /// Signs out all of the account's other sessions, which doesn't affect this session.
+    async fn sign_out_other_sessions(&self) -> Result<()> {
+        self.transition_to(
+            LogoutState::SigningOutOtherSessions,
+            "Signing out of all other sessions...".to_string(),
+            50
+        ).await?;
+
+        let result = self.with_server_timeout(
+            self.backend.sign_out_other_sessions(self.config.password.clone()),
+            "Signing out other sessions timed out",
+        ).await;
+        let num_signed_out = match result {
+            Ok(num_signed_out) => num_signed_out,
+            Err(e) => return self.fail(e.clone(), format!("Failed to sign out other sessions: {e}")).await,
+        };
+
+        self.transition_to(
+            LogoutState::Completed,
+            format!("Signed out of {num_signed_out} other session(s)"),
+            100
+        ).await?;
+        self.set_in_progress(false);
+        self.backend.post_action(LogoutAction::OtherSessionsSignedOut { count: num_signed_out });
         Ok(())
     }
     
     // Individual step implementations
-    async fn perform_prechecks(&self) -> Result<(), LogoutError> {
+    fn perform_prechecks(&self) -> Result<(), LogoutError> {
         log!("perform_prechecks started");
-        
-        // Check client existence
-        if get_client().is_none() {
-            log!("perform_prechecks: client cleared");
-            return Err(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared));
-        }
-        
-        // Check sync service
-        if get_sync_service().is_none() {
-            log!("perform_prechecks: sync service cleared");
-            return Err(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared));
+        self.backend.check_prerequisites()?;
+        if self.config.mode.requires_confirmation() && !self.config.confirmed {
+            log!("perform_prechecks: {:?} was not confirmed", self.config.mode);
+            return Err(LogoutError::Recoverable(RecoverableError::NotConfirmed));
         }
-        log!("perform_prechecks: sync service exists");
-        
-        // Check access token
-        if let Some(client) = get_client() {
-            if client.access_token().is_none() {
-                log!("perform_prechecks: no access token");
-                return Err(LogoutError::Recoverable(RecoverableError::NoAccessToken));
-            }
-            log!("perform_prechecks: access token exists");
-        }
-        
         log!("perform_prechecks completed successfully");
         Ok(())
     }
     
-    async fn stop_sync_service(&self) -> Result<(), LogoutError> {
-        if let Some(sync_service) = get_sync_service() {
-            sync_service.stop().await;
-            Ok(())
-        } else {
-            Err(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared))
-        }
-    }
-    
-    async fn perform_server_logout(&self) -> Result<(), LogoutError> {
-        let Some(client) = get_client() else {
-            return Err(LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared));
-        };
-        
-        match tokio::time::timeout(
-            self.config.server_logout_timeout,
-            client.matrix_auth().logout()
-        ).await {
-            Ok(Ok(_)) => Ok(()),
-            Ok(Err(e)) => Err(LogoutError::Recoverable(RecoverableError::ServerLogoutFailed(e.to_string()))),
-            Err(_) => Err(LogoutError::Recoverable(RecoverableError::Timeout("Server logout timed out".to_string()))),
-        }
-    }
-    
-    async fn close_all_tabs(&self) -> Result<()> {
-        let on_close_all = Arc::new(Notify::new());
-        Cx::post_action(MainDesktopUiAction::CloseAllTabs { on_close_all: on_close_all.clone() });
-        
-        match tokio::time::timeout(self.config.tab_close_timeout, on_close_all.notified()).await {
-            Ok(_) => {
-                log!("Received signal that all tabs were closed successfully");
-                Ok(())
-            }
-            Err(_) => Err(anyhow!("Timed out waiting for tabs to close")),
-        }
-    }
-    
-    fn shutdown_background_tasks(&self) {
-        shutdown_background_tasks();
-    }
-    
-    fn restart_runtime(&self) -> Result<()> {
-        start_matrix_tokio()
-            .map(|_| ())
-            .map_err(|e| anyhow!("Failed to restart runtime: {}", e))
-    }
-    
     /// Handle errors by posting appropriate actions
     async fn handle_error(&self, error: &LogoutError) {
         // Reset logout in progress flag on error (unless we've reached point of no return)
-        if !is_logout_past_point_of_no_return() {
-            set_logout_in_progress(false);
+        if !self.point_of_no_return.load(Ordering::Acquire) {
+            self.set_in_progress(false);
         }
         
         match error {
             LogoutError::Unrecoverable(UnrecoverableError::ComponentsCleared) => {
-                Cx::post_action(LogoutAction::ApplicationRequiresRestart { 
+                self.backend.post_action(LogoutAction::ApplicationRequiresRestart { 
                     cleared_component: ClearedComponentType::Client 
                 });
             }
@@ -546,7 +796,7 @@ impl LogoutStateMachine {
                 // Don't post failure action for cancellation
             }
             _ => {
-                Cx::post_action(LogoutAction::LogoutFailure(error.to_string()));
+                self.backend.post_action(LogoutAction::LogoutFailure(error.to_string()));
             }
         }
     }
@@ -571,22 +821,51 @@ fn set_logout_point_of_no_return(value: bool) {
     LOGOUT_POINT_OF_NO_RETURN.store(value, Ordering::Relaxed);
 }
 
-fn set_logout_in_progress(value: bool) {
-    let prev = LOGOUT_IN_PROGRESS.swap(value, Ordering::Relaxed);
-    if prev != value {
-        // Emit the action here (only when the value has changed)
-        Cx::post_action(LogoutAction::InProgress(value));
+/// Sets the logout-in-progress flag, returning whether its value changed.
+fn set_logout_in_progress(value: bool) -> bool {
+    LOGOUT_IN_PROGRESS.swap(value, Ordering::Relaxed) != value
+}
+
+/// Removes the given logged-out account from the list of logged-in accounts,
+/// and deletes its session's secrets, which are no longer valid.
+/// If `delete_session` is `true`, the account's persisted session file is deleted too.
+///
+/// If other accounts remain logged in, the first of them becomes the most recent account,
+/// such that its session is restored once the Matrix runtime restarts.
+/// Otherwise, the latest user ID is deleted such that the login screen is shown.
+async fn forget_logged_out_account(user_id: Option<&UserId>, delete_session: bool) {
+    let remaining_accounts = match user_id {
+        Some(user_id) => {
+            crate::sliding_sync::stop_handling_session_changes(user_id);
+            if delete_session {
+                if let Err(e) = delete_persisted_session(user_id).await {
+                    log!("Warning: Failed to delete the persisted session of {user_id}: {e}");
+                }
+            } else if let Err(e) = delete_session_secrets(user_id).await {
+                log!("Warning: Failed to delete the session secrets of {user_id}: {e}");
+            }
+            remove_logged_in_account(user_id).await.unwrap_or_else(|e| {
+                log!("Warning: Failed to remove {user_id} from logged-in accounts: {e}");
+                Vec::new()
+            })
+        }
+        None => Vec::new(),
+    };
+    let result = match remaining_accounts.first() {
+        Some(next_user_id) => {
+            log!("Other accounts remain logged in; switching to {next_user_id} after logout.");
+            save_latest_user_id(next_user_id).await
+        }
+        None => delete_latest_user_id().await.map(|_| ()),
+    };
+    if let Err(e) = result {
+        log!("Warning: Failed to update latest user ID: {}", e);
     }
 }
 
 /// Execute logout using the state machine
-pub async fn logout_with_state_machine(is_desktop: bool) -> Result<()> {
-    log!("logout_with_state_machine called with is_desktop={}", is_desktop);
-    
-    let config = LogoutConfig {
-        is_desktop,
-        ..Default::default()
-    };
+pub async fn logout_with_state_machine(config: LogoutConfig) -> Result<()> {
+    log!("logout_with_state_machine called with {config:?}");
     
     let state_machine = LogoutStateMachine::new(config);
     let result = state_machine.execute().await;
@@ -594,3 +873,266 @@ pub async fn logout_with_state_machine(is_desktop: bool) -> Result<()> {
     log!("logout_with_state_machine finished with result: {:?}", result.is_ok());
     result
 }
+
+#[cfg(test)]
+mod tests_logout_state_machine {
+    use std::sync::Mutex as StdMutex;
+
+    use matrix_sdk::ruma::owned_user_id;
+
+    use super::*;
+
+    /// A mock client that records the operations that the state machine performs on it.
+    #[derive(Default)]
+    struct MockClient {
+        /// The error returned by the server request, if any.
+        server_error: Option<LogoutError>,
+        /// Whether the server requires a password to complete user-interactive authentication.
+        requires_password: bool,
+        calls: StdMutex<Vec<String>>,
+        states: StdMutex<Vec<LogoutState>>,
+        actions: StdMutex<Vec<String>>,
+    }
+
+    impl MockClient {
+        fn record(&self, call: impl Into<String>) {
+            self.calls.lock().unwrap().push(call.into());
+        }
+
+        fn server_request(&self, call: impl Into<String>, password: Option<String>) -> Result<(), LogoutError> {
+            self.record(call);
+            if let Some(error) = &self.server_error {
+                return Err(error.clone());
+            }
+            if self.requires_password && password.is_none() {
+                return Err(LogoutError::Recoverable(RecoverableError::AuthenticationRequired));
+            }
+            Ok(())
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn states(&self) -> Vec<LogoutState> {
+            self.states.lock().unwrap().clone()
+        }
+
+        /// Returns the posted actions, except for `InProgress` actions,
+        /// which depend on a global flag shared by all tests.
+        fn actions(&self) -> Vec<String> {
+            self.actions.lock().unwrap().iter()
+                .filter(|action| !action.starts_with("InProgress"))
+                .cloned()
+                .collect()
+        }
+    }
+
+    impl LogoutBackend for MockClient {
+        fn check_prerequisites(&self) -> Result<(), LogoutError> {
+            Ok(())
+        }
+        fn user_id(&self) -> Option<OwnedUserId> {
+            Some(owned_user_id!("@alice:example.org"))
+        }
+        async fn stop_sync(&self) {
+            self.record("stop_sync");
+        }
+        async fn restart_sync(&self) {
+            self.record("restart_sync");
+        }
+        async fn logout_from_server(&self) -> Result<(), LogoutError> {
+            self.server_request("logout_from_server", Some(String::new()))
+        }
+        async fn sign_out_other_sessions(&self, password: Option<String>) -> Result<usize, LogoutError> {
+            self.server_request("sign_out_other_sessions", password).map(|()| 2)
+        }
+        async fn deactivate_account(&self, erase: bool, password: Option<String>) -> Result<(), LogoutError> {
+            self.server_request(format!("deactivate_account(erase: {erase})"), password)
+        }
+        async fn forget_account(&self, _user_id: Option<OwnedUserId>, delete_session: bool) {
+            self.record(if delete_session { "forget_account(delete_session)" } else { "forget_account" });
+        }
+        async fn close_all_tabs(&self, _timeout: Duration) -> Result<()> {
+            self.record("close_all_tabs");
+            Ok(())
+        }
+        async fn clean_app_state(&self, _config: &LogoutConfig) -> Result<()> {
+            self.record("clean_app_state");
+            Ok(())
+        }
+        async fn clear_local_data(&self, _user_id: Option<OwnedUserId>) -> Result<()> {
+            self.record("clear_local_data");
+            Ok(())
+        }
+        fn shutdown_background_tasks(&self) {
+            self.record("shutdown_background_tasks");
+        }
+        fn restart_runtime(&self) -> Result<()> {
+            self.record("restart_runtime");
+            Ok(())
+        }
+        fn report_progress(&self, progress: &LogoutProgress) {
+            self.states.lock().unwrap().push(progress.state.clone());
+        }
+        fn post_action(&self, action: LogoutAction) {
+            self.actions.lock().unwrap().push(format!("{action:?}"));
+        }
+        fn close_settings(&self) {
+            self.record("close_settings");
+        }
+    }
+
+    async fn execute(config: LogoutConfig, client: MockClient) -> (Result<()>, MockClient) {
+        let state_machine = LogoutStateMachine::with_backend(config, client);
+        let result = state_machine.execute().await;
+        (result, state_machine.backend)
+    }
+
+    fn deactivation_config(password: Option<&str>) -> LogoutConfig {
+        LogoutConfig {
+            mode: LogoutMode::DeactivateAccount { erase: true },
+            password: password.map(String::from),
+            confirmed: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_out_this_session() {
+        let (result, client) = execute(LogoutConfig::default(), MockClient::default()).await;
+        assert!(result.is_ok());
+        assert_eq!(client.states(), [
+            LogoutState::PreChecking,
+            LogoutState::StoppingSyncService,
+            LogoutState::LoggingOutFromServer,
+            LogoutState::PointOfNoReturn,
+            LogoutState::ClosingTabs,
+            LogoutState::CleaningAppState,
+            LogoutState::ShuttingDownTasks,
+            LogoutState::RestartingRuntime,
+            LogoutState::Completed,
+        ]);
+        assert_eq!(client.calls(), [
+            "stop_sync", "logout_from_server", "forget_account", "close_all_tabs",
+            "clean_app_state", "shutdown_background_tasks", "restart_runtime", "close_settings",
+        ]);
+        assert_eq!(client.actions(), ["LogoutSuccess"]);
+    }
+
+    #[tokio::test]
+    async fn recovers_from_server_logout_failure() {
+        let error = LogoutError::Recoverable(RecoverableError::ServerLogoutFailed("M_LIMIT_EXCEEDED".into()));
+        let client = MockClient { server_error: Some(error.clone()), ..Default::default() };
+        let (result, client) = execute(LogoutConfig::default(), client).await;
+        assert!(result.is_err());
+        assert_eq!(client.states().last(), Some(&LogoutState::Failed(error)));
+        assert_eq!(client.calls(), ["stop_sync", "logout_from_server", "restart_sync"]);
+        assert!(client.actions()[0].starts_with("LogoutFailure"));
+    }
+
+    #[tokio::test]
+    async fn treats_unknown_token_as_logged_out() {
+        let error = LogoutError::Recoverable(RecoverableError::ServerLogoutFailed("M_UNKNOWN_TOKEN".into()));
+        let client = MockClient { server_error: Some(error), ..Default::default() };
+        let (result, client) = execute(LogoutConfig::default(), client).await;
+        assert!(result.is_ok());
+        assert!(client.states().contains(&LogoutState::PointOfNoReturn));
+        assert!(client.calls().contains(&String::from("forget_account")));
+        assert_eq!(client.actions(), ["LogoutSuccess"]);
+    }
+
+    #[tokio::test]
+    async fn deactivation_requires_confirmation() {
+        let config = LogoutConfig { confirmed: false, ..deactivation_config(Some("secret")) };
+        let (result, client) = execute(config, MockClient::default()).await;
+        assert!(result.is_err());
+        assert_eq!(client.states(), [
+            LogoutState::PreChecking,
+            LogoutState::Failed(LogoutError::Recoverable(RecoverableError::NotConfirmed)),
+        ]);
+        assert!(client.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deactivation_requires_password_for_uiaa() {
+        let client = MockClient { requires_password: true, ..Default::default() };
+        let (result, client) = execute(deactivation_config(None), client).await;
+        assert!(result.is_err());
+        assert_eq!(
+            client.states().last(),
+            Some(&LogoutState::Failed(LogoutError::Recoverable(RecoverableError::AuthenticationRequired))),
+        );
+        assert_eq!(client.calls(), ["stop_sync", "deactivate_account(erase: true)", "restart_sync"]);
+
+        let client = MockClient { requires_password: true, ..Default::default() };
+        let (result, client) = execute(deactivation_config(Some("secret")), client).await;
+        assert!(result.is_ok());
+        assert_eq!(client.states()[..4], [
+            LogoutState::PreChecking,
+            LogoutState::StoppingSyncService,
+            LogoutState::DeactivatingAccount,
+            LogoutState::PointOfNoReturn,
+        ]);
+        assert!(client.calls().contains(&String::from("forget_account(delete_session)")));
+        assert_eq!(client.actions(), ["LogoutSuccess"]);
+    }
+
+    #[tokio::test]
+    async fn signs_out_other_sessions_only() {
+        let config = LogoutConfig {
+            mode: LogoutMode::OtherSessions,
+            password: Some(String::from("secret")),
+            confirmed: true,
+            ..Default::default()
+        };
+        let client = MockClient { requires_password: true, ..Default::default() };
+        let (result, client) = execute(config, client).await;
+        assert!(result.is_ok());
+        assert_eq!(client.states(), [
+            LogoutState::PreChecking,
+            LogoutState::SigningOutOtherSessions,
+            LogoutState::Completed,
+        ]);
+        assert_eq!(client.calls(), ["sign_out_other_sessions"]);
+        assert_eq!(client.actions(), ["OtherSessionsSignedOut(2)"]);
+    }
+
+    #[tokio::test]
+    async fn clears_local_data_without_logging_out() {
+        let config = LogoutConfig {
+            mode: LogoutMode::ClearLocalDataOnly,
+            is_desktop: false,
+            ..Default::default()
+        };
+        let (result, client) = execute(config, MockClient::default()).await;
+        assert!(result.is_ok());
+        assert_eq!(client.states(), [
+            LogoutState::PreChecking,
+            LogoutState::StoppingSyncService,
+            LogoutState::PointOfNoReturn,
+            LogoutState::CleaningAppState,
+            LogoutState::ShuttingDownTasks,
+            LogoutState::ClearingLocalData,
+            LogoutState::RestartingRuntime,
+            LogoutState::Completed,
+        ]);
+        assert_eq!(client.calls(), [
+            "stop_sync", "clean_app_state", "shutdown_background_tasks",
+            "clear_local_data", "restart_runtime", "close_settings",
+        ]);
+        assert_eq!(client.actions(), ["LocalDataCleared"]);
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_before_any_step() {
+        let state_machine = LogoutStateMachine::with_backend(LogoutConfig::default(), MockClient::default());
+        state_machine.request_cancellation();
+        assert!(state_machine.execute().await.is_err());
+        assert_eq!(
+            state_machine.current_state().await,
+            LogoutState::Failed(LogoutError::Recoverable(RecoverableError::Cancelled)),
+        );
+        assert!(state_machine.backend.calls().is_empty());
+    }
+}
@@ -6,6 +6,11 @@
 // TODO: move this into Makepad itself as an addition to the `MAKEPAD` env var.
 #![cfg_attr(all(feature = "hide_windows_console", target_os = "windows"), windows_subsystem = "windows")]
 
-fn main() {
-    robrix::app::app_main()
+fn main() -> std::process::ExitCode {
+    // Headless subcommands (e.g., `robrix rooms list`) run without starting the UI.
+    if let Some(headless_cli) = robrix::headless::headless_cli_from_args() {
+        return robrix::headless::run(headless_cli);
+    }
+    robrix::app::app_main();
+    std::process::ExitCode::SUCCESS
 }
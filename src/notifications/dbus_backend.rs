@@ -0,0 +1,129 @@
+//! A notification backend that uses the freedesktop notification service over D-Bus (Linux).
+//!
+//! All notifications are sent over a single session bus connection,
+//! and a single thread listens on that connection for the user clicking on any of them.
+//! Some notification servers only send action signals to the connection
+//! that created the notification, so the same connection must be used for both.
+
+use std::{collections::{BTreeMap, HashMap}, sync::Mutex};
+
+use makepad_widgets::{warning, Cx};
+use matrix_sdk::ruma::OwnedRoomId;
+use zbus::{blocking::{Connection, MessageIterator}, message::Type as MessageType, zvariant::Value, MatchRule};
+
+use super::{LocalNotification, NotificationAction, NotificationBackend};
+
+/// The well-known bus name and interface of the freedesktop notification service.
+const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+/// The object path of the freedesktop notification service.
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+/// The action key that freedesktop notification servers invoke when the notification itself is clicked.
+const DEFAULT_ACTION: &str = "default";
+
+/// The session bus connection used to show notifications, once it has been established.
+static SESSION_CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// The room of each notification that is currently shown, keyed by its notification ID.
+static SHOWN_NOTIFICATIONS: Mutex<BTreeMap<u32, (OwnedRoomId, Option<String>)>> = Mutex::new(BTreeMap::new());
+
+/// Shows notifications via the freedesktop `org.freedesktop.Notifications` D-Bus service.
+pub struct DbusBackend;
+
+impl NotificationBackend for DbusBackend {
+    fn name(&self) -> &'static str {
+        "freedesktop D-Bus"
+    }
+
+    fn notify(&self, notification: &LocalNotification) -> anyhow::Result<()> {
+        let summary = notification.title().to_owned();
+        // Notification servers may interpret a subset of HTML markup in the body.
+        let body = htmlize::escape_text(notification.text()).into_owned();
+        let room_id = notification.room_id.clone();
+        let room_name = notification.room_name.clone();
+        // Connecting to and calling the notification service are blocking operations.
+        tokio::runtime::Handle::try_current()?.spawn_blocking(move || {
+            match show_notification(&summary, &body) {
+                Ok(id) => {
+                    SHOWN_NOTIFICATIONS.lock().unwrap().insert(id, (room_id, room_name));
+                }
+                Err(e) => warning!("Failed to show notification via D-Bus: {e:?}"),
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Shows a notification with the given summary and body, returning its ID.
+fn show_notification(summary: &str, body: &str) -> anyhow::Result<u32> {
+    let connection = session_connection()?;
+    let hints = HashMap::from([("category", Value::from("im.received"))]);
+    let reply = connection.call_method(
+        Some(NOTIFICATIONS_NAME),
+        NOTIFICATIONS_PATH,
+        Some(NOTIFICATIONS_NAME),
+        "Notify",
+        &(
+            "Robrix",                     // app_name
+            0u32,                         // replaces_id
+            "",                           // app_icon
+            summary,
+            body,
+            vec![DEFAULT_ACTION, "Open"], // actions, as pairs of keys and labels
+            hints,
+            -1i32,                        // expire_timeout: the server's default
+        ),
+    )?;
+    Ok(reply.body().deserialize::<u32>()?)
+}
+
+/// Returns the session bus connection used to show notifications,
+/// connecting to the session bus and starting the action listener thread if needed.
+fn session_connection() -> anyhow::Result<Connection> {
+    let mut session_connection = SESSION_CONNECTION.lock().unwrap();
+    if let Some(connection) = session_connection.as_ref() {
+        return Ok(connection.clone());
+    }
+    let connection = Connection::session()?;
+    // Subscribe to the notification signals before any notification is shown,
+    // such that we cannot miss a click on it.
+    let signal_rule = MatchRule::builder()
+        .msg_type(MessageType::Signal)
+        .interface(NOTIFICATIONS_NAME)?
+        .path(NOTIFICATIONS_PATH)?
+        .build();
+    let messages = MessageIterator::for_match_rule(signal_rule, &connection, None)?;
+    std::thread::Builder::new()
+        .name(String::from("notification_action_listener"))
+        .spawn(move || listen_for_actions(messages))?;
+    *session_connection = Some(connection.clone());
+    Ok(connection)
+}
+
+/// Handles the notification service's signals for all notifications shown by Robrix,
+/// posting a `NotificationAction::Clicked` action when one of them is clicked.
+fn listen_for_actions(messages: MessageIterator) {
+    for message in messages {
+        let Ok(message) = message else { continue };
+        match message.header().member().map(|member| member.as_str()) {
+            Some("ActionInvoked") => {
+                let Ok((id, action_key)) = message.body().deserialize::<(u32, String)>() else { continue };
+                if action_key != DEFAULT_ACTION {
+                    continue;
+                }
+                if let Some((room_id, room_name)) = SHOWN_NOTIFICATIONS.lock().unwrap().remove(&id) {
+                    Cx::post_action(NotificationAction::Clicked { room_id, room_name });
+                }
+            }
+            Some("NotificationClosed") => {
+                if let Ok((id, _reason)) = message.body().deserialize::<(u32, u32)>() {
+                    SHOWN_NOTIFICATIONS.lock().unwrap().remove(&id);
+                }
+            }
+            _ => { }
+        }
+    }
+    // The connection was closed, so the next notification must reconnect.
+    warning!("Stopped listening for notification clicks: the D-Bus connection was closed.");
+    SESSION_CONNECTION.lock().unwrap().take();
+    SHOWN_NOTIFICATIONS.lock().unwrap().clear();
+}
@@ -0,0 +1,298 @@
+//! The in-app notification history: a backend that records recent notifications,
+//! plus a modal view that lists them and lets the user jump to their rooms.
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use makepad_widgets::*;
+
+use crate::utils::relative_format;
+
+use super::{LocalNotification, NotificationAction, NotificationBackend};
+
+/// The maximum number of notifications kept in the history.
+const MAX_HISTORY_LEN: usize = 100;
+
+/// The recent notifications, from newest (at the front) to oldest (at the back).
+static NOTIFICATION_HISTORY: Mutex<VecDeque<LocalNotification>> = Mutex::new(VecDeque::new());
+
+/// Clears all notifications from the history.
+pub fn clear_history() {
+    NOTIFICATION_HISTORY.lock().unwrap().clear();
+    Cx::post_action(NotificationAction::HistoryChanged);
+}
+
+/// Records notifications into the in-app notification history.
+pub struct HistoryBackend;
+
+impl NotificationBackend for HistoryBackend {
+    fn name(&self) -> &'static str {
+        "in-app history"
+    }
+
+    fn notify(&self, notification: &LocalNotification) -> anyhow::Result<()> {
+        let mut history = NOTIFICATION_HISTORY.lock().unwrap();
+        history.push_front(notification.clone());
+        history.truncate(MAX_HISTORY_LEN);
+        drop(history);
+        Cx::post_action(NotificationAction::HistoryChanged);
+        Ok(())
+    }
+}
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    // A single notification in the notification history.
+    NotificationHistoryEntry = {{NotificationHistoryEntry}} {
+        width: Fill, height: Fit
+        flow: Down,
+        padding: {top: 8, bottom: 8, left: 10, right: 10}
+        spacing: 3
+        cursor: Hand,
+
+        show_bg: true
+        draw_bg: {
+            instance is_mention: 0.0
+            fn pixel(self) -> vec4 {
+                return mix(#FFFFFF, #FFF5D6, self.is_mention);
+            }
+        }
+
+        <View> {
+            width: Fill, height: Fit
+            flow: Right,
+            spacing: 10
+
+            title = <Label> {
+                width: Fill, height: Fit
+                flow: Right,
+                draw_text: {
+                    wrap: Ellipsis,
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <USERNAME_TEXT_STYLE>{ font_size: 10.5 },
+                }
+                text: ""
+            }
+
+            timestamp = <Label> {
+                width: Fit, height: Fit
+                draw_text: {
+                    color: (TIMESTAMP_TEXT_COLOR),
+                    text_style: <REGULAR_TEXT>{ font_size: 9 },
+                }
+                text: ""
+            }
+        }
+
+        body = <Label> {
+            width: Fill, height: Fit
+            draw_text: {
+                wrap: Word,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <MESSAGE_TEXT_STYLE>{ font_size: 10 },
+            }
+            text: ""
+        }
+
+        <LineH> { margin: {top: 5} }
+    }
+
+    // A modal dialog that lists recent notifications.
+    pub NotificationHistory = {{NotificationHistory}} {
+        width: Fit,
+        height: Fit,
+
+        <RoundedView> {
+            width: 420,
+            height: 520,
+            flow: Down,
+            padding: 20,
+            spacing: 10,
+
+            show_bg: true,
+            draw_bg: {
+                color: #FFFFFF
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: Right,
+                align: {y: 0.5}
+                spacing: 10
+
+                <Label> {
+                    width: Fill, height: Fit
+                    text: "Notifications",
+                    draw_text: {
+                        text_style: <TITLE_TEXT>{font_size: 16},
+                        color: #000000
+                    }
+                }
+
+                clear_button = <RobrixIconButton> {
+                    width: Fit, height: Fit,
+                    padding: 8,
+                    draw_bg: {
+                        color: (COLOR_SECONDARY)
+                    }
+                    draw_text: {
+                        color: (COLOR_TEXT)
+                    }
+                    text: "Clear All"
+                }
+
+                close_button = <RobrixIconButton> {
+                    width: Fit, height: Fit,
+                    padding: 8,
+                    spacing: 0,
+                    draw_bg: {
+                        color: (COLOR_SECONDARY)
+                    }
+                    draw_icon: {
+                        svg_file: (ICON_CLOSE),
+                        color: (COLOR_TEXT),
+                    }
+                    icon_walk: {width: 14, height: 14, margin: 0}
+                }
+            }
+
+            empty_label = <Label> {
+                width: Fill, height: Fit
+                margin: {top: 10}
+                draw_text: {
+                    wrap: Word,
+                    color: (COLOR_MESSAGE_NOTICE_TEXT),
+                    text_style: <REGULAR_TEXT>{ font_size: 11 },
+                }
+                text: "You have no recent notifications."
+            }
+
+            notification_list = <FlatList> {
+                width: Fill,
+                height: Fill,
+                flow: Down,
+                spacing: 0.0
+
+                grab_key_focus: true,
+                drag_scrolling: true,
+                scroll_bars: { show_scroll_x: false, show_scroll_y: true },
+
+                notification_entry = <NotificationHistoryEntry> { }
+            }
+        }
+    }
+}
+
+/// Actions sent to the parent widget of the [`NotificationHistory`] modal.
+#[derive(Clone, Debug)]
+pub enum NotificationHistoryModalAction {
+    /// The notification history modal should be opened.
+    Open,
+    /// The notification history modal requested to be closed.
+    Close,
+}
+
+/// A modal view that lists the notifications in the in-app history.
+#[derive(Live, LiveHook, Widget)]
+pub struct NotificationHistory {
+    #[deref] view: View,
+}
+
+impl Widget for NotificationHistory {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+        self.widget_match_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        let history = NOTIFICATION_HISTORY.lock().unwrap().clone();
+        self.view.label(id!(empty_label)).set_visible(cx, history.is_empty());
+
+        while let Some(subview) = self.view.draw_walk(cx, scope, walk).step() {
+            // Here, we only need to handle drawing the notification list.
+            let flat_list_ref = subview.as_flat_list();
+            let Some(mut list) = flat_list_ref.borrow_mut() else {
+                error!("!!! NotificationHistory::draw_walk(): BUG: expected a FlatList widget, but got something else");
+                continue;
+            };
+            for notification in &history {
+                let item_live_id = LiveId::from_str(&format!("{}_{}", notification.room_id, notification.timestamp.get()));
+                let item = list.item(cx, item_live_id, live_id!(notification_entry)).unwrap();
+                // Pass the notification in through Scope via props.
+                let mut scope = Scope::with_props(notification);
+                item.draw_all(cx, &mut scope);
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl WidgetMatchEvent for NotificationHistory {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        if self.view.button(id!(close_button)).clicked(actions) {
+            cx.action(NotificationHistoryModalAction::Close);
+        }
+        if self.view.button(id!(clear_button)).clicked(actions) {
+            clear_history();
+        }
+        for action in actions {
+            match action.downcast_ref() {
+                Some(NotificationAction::HistoryChanged) => self.view.redraw(cx),
+                // Close this modal once the user has chosen a notification to jump to.
+                Some(NotificationAction::Clicked { .. }) => cx.action(NotificationHistoryModalAction::Close),
+                None => { }
+            }
+        }
+    }
+}
+
+/// A single entry in the notification history list.
+#[derive(Live, LiveHook, Widget)]
+pub struct NotificationHistoryEntry {
+    #[deref] view: View,
+
+    #[rust] notification: Option<LocalNotification>,
+}
+
+impl Widget for NotificationHistoryEntry {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        let Some(notification) = self.notification.as_ref() else { return };
+        if let Hit::FingerUp(fe) = event.hits(cx, self.view.area()) {
+            if fe.is_over && fe.is_primary_hit() && fe.was_tap() {
+                cx.action(NotificationAction::Clicked {
+                    room_id: notification.room_id.clone(),
+                    room_name: notification.room_name.clone(),
+                });
+            }
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        // The notification was passed in through Scope via props.
+        let notification = scope.props.get::<LocalNotification>().unwrap();
+        if self.notification.as_ref() != Some(notification) {
+            self.notification = Some(notification.clone());
+        }
+
+        self.label(id!(title)).set_text(cx, notification.title());
+        self.label(id!(body)).set_text(cx, &notification.text());
+        self.label(id!(timestamp)).set_text(
+            cx,
+            &relative_format(notification.timestamp).unwrap_or_default(),
+        );
+        let is_mention = if notification.is_mention { 1.0 } else { 0.0 };
+        self.view.apply_over(cx, live!(
+            draw_bg: { is_mention: (is_mention) }
+        ));
+
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
@@ -0,0 +1,29 @@
+//! A notification backend that records notifications instead of showing them, for tests.
+
+use std::sync::{Arc, Mutex};
+
+use super::{LocalNotification, NotificationBackend};
+
+/// A backend that records every notification it is asked to show.
+pub struct MockBackend {
+    shown: Arc<Mutex<Vec<LocalNotification>>>,
+}
+
+impl MockBackend {
+    /// Returns a new mock backend and a handle to the list of notifications it has shown.
+    pub fn new() -> (Self, Arc<Mutex<Vec<LocalNotification>>>) {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        (Self { shown: shown.clone() }, shown)
+    }
+}
+
+impl NotificationBackend for MockBackend {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn notify(&self, notification: &LocalNotification) -> anyhow::Result<()> {
+        self.shown.lock().unwrap().push(notification.clone());
+        Ok(())
+    }
+}
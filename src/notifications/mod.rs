@@ -0,0 +1,375 @@
+//! Local notifications about new messages and invites.
+//!
+//! The Matrix SDK evaluates each incoming timeline event against the user's push rules.
+//! Events whose push actions say they should notify are further filtered here
+//! according to the room's notification mode, and are then emitted
+//! through every registered [`NotificationBackend`], e.g., the OS notification service
+//! and the in-app notification history.
+//!
+//! Notifications are never emitted for the room that the user is currently looking at,
+//! i.e., the selected room while the app window has focus.
+
+use std::sync::{LazyLock, Mutex};
+
+use makepad_widgets::{log, warning, Cx};
+use matrix_sdk::{
+    deserialized_responses::RawAnySyncOrStrippedTimelineEvent,
+    notification_settings::RoomNotificationMode,
+    ruma::{
+        events::{
+            room::{member::MembershipState, message::MessageType},
+            AnyStrippedStateEvent, AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncMessageLikeEvent,
+        },
+        push::Action,
+        MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, UserId,
+    },
+    sync::Notification,
+    Client, Room,
+};
+
+use crate::sliding_sync::{current_user_id, room_notification_mode};
+
+pub mod history;
+#[cfg(target_os = "linux")]
+mod dbus_backend;
+#[cfg(test)]
+mod mock_backend;
+
+pub fn live_design(cx: &mut Cx) {
+    history::live_design(cx);
+}
+
+/// A notification about a new event in a room, ready to be shown to the user.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocalNotification {
+    /// The room in which the event occurred.
+    pub room_id: OwnedRoomId,
+    /// The displayable name of the room, if known.
+    pub room_name: Option<String>,
+    /// The ID of the event that triggered this notification, if any.
+    /// Invites (stripped state events) don't have an event ID.
+    pub event_id: Option<OwnedEventId>,
+    /// The displayable name of the event's sender.
+    pub sender_name: String,
+    /// A plaintext description of the event, e.g., the message body.
+    pub body: String,
+    /// Whether the event mentioned the user or contained one of their keywords.
+    pub is_mention: bool,
+    /// When this notification was received.
+    pub timestamp: MilliSecondsSinceUnixEpoch,
+}
+
+impl LocalNotification {
+    /// Returns the title of this notification: the room name, or the sender if the room is unnamed.
+    pub fn title(&self) -> &str {
+        self.room_name.as_deref().unwrap_or(&self.sender_name)
+    }
+
+    /// Returns the text of this notification, prefixed by the sender's name
+    /// if it isn't already shown in the title.
+    pub fn text(&self) -> String {
+        if self.room_name.as_deref().is_some_and(|name| name != self.sender_name) {
+            format!("{}: {}", self.sender_name, self.body)
+        } else {
+            self.body.clone()
+        }
+    }
+}
+
+/// A destination that notifications can be emitted to.
+pub trait NotificationBackend: Send + Sync {
+    /// A short human-readable name for this backend, used for logging.
+    fn name(&self) -> &'static str;
+
+    /// Shows the given notification to the user.
+    fn notify(&self, notification: &LocalNotification) -> anyhow::Result<()>;
+}
+
+/// Actions emitted by notifications or the notification history.
+#[derive(Clone, Debug)]
+pub enum NotificationAction {
+    /// The user clicked on a notification, so its room should be focused.
+    Clicked {
+        room_id: OwnedRoomId,
+        room_name: Option<String>,
+    },
+    /// The in-app notification history was changed.
+    HistoryChanged,
+}
+
+/// Where the user's attention currently is.
+#[derive(Debug)]
+struct FocusState {
+    /// The room that is currently selected in the main UI, if any.
+    selected_room: Option<OwnedRoomId>,
+    /// Whether the app window currently has focus.
+    is_app_focused: bool,
+}
+
+/// Dispatches notifications to a set of backends,
+/// suppressing those for the room that the user is currently looking at.
+pub struct NotificationCenter {
+    backends: Vec<Box<dyn NotificationBackend>>,
+    focus: Mutex<FocusState>,
+}
+
+impl NotificationCenter {
+    /// Creates a new notification center that emits notifications through the given backends.
+    pub fn new(backends: Vec<Box<dyn NotificationBackend>>) -> Self {
+        Self {
+            backends,
+            focus: Mutex::new(FocusState {
+                selected_room: None,
+                is_app_focused: true,
+            }),
+        }
+    }
+
+    /// Sets the room that is currently selected in the main UI.
+    pub fn set_selected_room(&self, room_id: Option<OwnedRoomId>) {
+        self.focus.lock().unwrap().selected_room = room_id;
+    }
+
+    /// Sets whether the app window currently has focus.
+    pub fn set_app_focused(&self, is_focused: bool) {
+        self.focus.lock().unwrap().is_app_focused = is_focused;
+    }
+
+    /// Returns `true` if the given room is currently in front of the user.
+    pub fn is_room_focused(&self, room_id: &OwnedRoomId) -> bool {
+        let focus = self.focus.lock().unwrap();
+        focus.is_app_focused && focus.selected_room.as_ref() == Some(room_id)
+    }
+
+    /// Emits the given notification through all backends,
+    /// unless its room is currently focused.
+    ///
+    /// Returns `true` if the notification was emitted.
+    pub fn dispatch(&self, notification: &LocalNotification) -> bool {
+        if self.is_room_focused(&notification.room_id) {
+            return false;
+        }
+        for backend in &self.backends {
+            if let Err(e) = backend.notify(notification) {
+                warning!("Failed to show notification via the {} backend: {e:?}", backend.name());
+            }
+        }
+        true
+    }
+}
+
+/// The app-wide notification center, which uses the in-app history
+/// plus the OS notification service on supported platforms.
+static NOTIFICATION_CENTER: LazyLock<NotificationCenter> = LazyLock::new(|| {
+    NotificationCenter::new(vec![
+        Box::new(history::HistoryBackend),
+        #[cfg(target_os = "linux")]
+        Box::new(dbus_backend::DbusBackend),
+    ])
+});
+
+/// Sets the room that is currently selected in the main UI,
+/// for which notifications will be suppressed while the app has focus.
+pub fn set_selected_room(room_id: Option<OwnedRoomId>) {
+    NOTIFICATION_CENTER.set_selected_room(room_id);
+}
+
+/// Sets whether the app window currently has focus.
+pub fn set_app_focused(is_focused: bool) {
+    NOTIFICATION_CENTER.set_app_focused(is_focused);
+}
+
+/// Evaluates an event's push actions against its room's notification mode.
+///
+/// Returns `None` if no notification should be shown,
+/// otherwise `Some(is_mention)`, where `is_mention` indicates whether
+/// the push actions highlight the event, i.e., it's a mention or keyword match.
+pub fn evaluate_push_actions(actions: &[Action], mode: RoomNotificationMode) -> Option<bool> {
+    let should_notify = actions.iter().any(Action::should_notify);
+    let is_mention = actions.iter().any(Action::is_highlight);
+    match mode {
+        RoomNotificationMode::Mute => None,
+        RoomNotificationMode::MentionsAndKeywordsOnly => (should_notify && is_mention).then_some(true),
+        RoomNotificationMode::AllMessages => should_notify.then_some(is_mention),
+    }
+}
+
+/// Registers a handler that receives notifications from the Matrix SDK for every event
+/// that the user's push rules say should notify them, and dispatches them
+/// to the app-wide notification center.
+///
+/// Events sent before this handler was registered are ignored, which prevents a flood
+/// of notifications about old messages during the initial sync.
+/// Events received by accounts that sync in the background are also ignored,
+/// as opening their notifications would show a room of a different account.
+pub async fn register_notification_handler(client: &Client) {
+    let registered_at = MilliSecondsSinceUnixEpoch::now();
+    client.register_notification_handler(move |notification: Notification, room: Room, client: Client| async move {
+        if client.user_id() != current_user_id().as_deref() { return }
+        let mode = room_notification_mode(&room).await;
+        let Some(is_mention) = evaluate_push_actions(&notification.actions, mode) else { return };
+        let Some(local_notification) = local_notification_from_event(
+            &notification.event,
+            &room,
+            &client,
+            is_mention,
+            registered_at,
+        ).await else { return };
+
+        if NOTIFICATION_CENTER.dispatch(&local_notification) {
+            log!("Emitted notification for event {:?} in room {}", local_notification.event_id, local_notification.room_id);
+        }
+    }).await;
+}
+
+/// Converts an event from the Matrix SDK into a notification that can be shown to the user.
+///
+/// Returns `None` for events that aren't worth notifying about,
+/// such as the user's own events or those sent before `not_before`.
+async fn local_notification_from_event(
+    event: &RawAnySyncOrStrippedTimelineEvent,
+    room: &Room,
+    client: &Client,
+    is_mention: bool,
+    not_before: MilliSecondsSinceUnixEpoch,
+) -> Option<LocalNotification> {
+    let own_user_id = client.user_id()?;
+    let (sender, event_id, body) = match event {
+        RawAnySyncOrStrippedTimelineEvent::Sync(raw) => {
+            let event = raw.deserialize().ok()?;
+            if event.sender() == own_user_id || event.origin_server_ts() < not_before {
+                return None;
+            }
+            let body = match &event {
+                AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(msg))) => {
+                    plaintext_of_message(&msg.content.msgtype)
+                }
+                AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(_)) => {
+                    String::from("Sent an encrypted message.")
+                }
+                AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::Sticker(_)) => String::from("[Sticker]"),
+                // Other events (e.g., edits, reactions, and redactions) aren't worth notifying about,
+                // even if the push rules say otherwise.
+                _ => return None,
+            };
+            (event.sender().to_owned(), Some(event.event_id().to_owned()), body)
+        }
+        RawAnySyncOrStrippedTimelineEvent::Stripped(raw) => {
+            let AnyStrippedStateEvent::RoomMember(member) = raw.deserialize().ok()? else {
+                return None;
+            };
+            if member.content.membership != MembershipState::Invite || *member.state_key != *own_user_id {
+                return None;
+            }
+            (member.sender, None, String::from("Invited you to join this room."))
+        }
+    };
+
+    Some(LocalNotification {
+        room_id: room.room_id().to_owned(),
+        room_name: room.display_name().await.ok().map(|n| n.to_string()),
+        event_id,
+        sender_name: sender_display_name(room, &sender).await,
+        body,
+        is_mention,
+        timestamp: MilliSecondsSinceUnixEpoch::now(),
+    })
+}
+
+/// Returns the display name of the given sender in the given room, falling back to their user ID.
+async fn sender_display_name(room: &Room, sender: &UserId) -> String {
+    room.get_member_no_sync(sender).await
+        .ok()
+        .flatten()
+        .and_then(|member| member.display_name().map(ToOwned::to_owned))
+        .unwrap_or_else(|| sender.to_string())
+}
+
+/// Returns a short plaintext description of the given message, suitable for a notification.
+fn plaintext_of_message(msgtype: &MessageType) -> String {
+    match msgtype {
+        MessageType::Audio(_) => String::from("[Audio]"),
+        MessageType::Emote(emote) => format!("* {}", emote.body),
+        MessageType::File(file) => format!("[File]: {}", file.body),
+        MessageType::Image(_) => String::from("[Image]"),
+        MessageType::Location(_) => String::from("[Location]"),
+        MessageType::Video(_) => String::from("[Video]"),
+        MessageType::VerificationRequest(_) => String::from("[Verification Request]"),
+        other => other.body().to_owned(),
+    }
+}
+
+/// Clears the in-app notification history, e.g., upon logout.
+pub fn clear_notifications() {
+    history::clear_history();
+}
+
+#[cfg(test)]
+mod tests_notification_center {
+    use matrix_sdk::ruma::{owned_room_id, push::Tweak};
+
+    use super::*;
+    use super::mock_backend::MockBackend;
+
+    fn notification(room_id: OwnedRoomId) -> LocalNotification {
+        LocalNotification {
+            room_id,
+            room_name: Some(String::from("Robrix Dev")),
+            event_id: None,
+            sender_name: String::from("Alice"),
+            body: String::from("Hello!"),
+            is_mention: false,
+            timestamp: MilliSecondsSinceUnixEpoch::now(),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_to_all_backends() {
+        let (first, first_shown) = MockBackend::new();
+        let (second, second_shown) = MockBackend::new();
+        let center = NotificationCenter::new(vec![Box::new(first), Box::new(second)]);
+        let notification = notification(owned_room_id!("!a:example.org"));
+        assert!(center.dispatch(&notification));
+        assert_eq!(*first_shown.lock().unwrap(), vec![notification.clone()]);
+        assert_eq!(*second_shown.lock().unwrap(), vec![notification]);
+    }
+
+    #[test]
+    fn test_suppressed_for_focused_room() {
+        let (backend, shown) = MockBackend::new();
+        let center = NotificationCenter::new(vec![Box::new(backend)]);
+        center.set_selected_room(Some(owned_room_id!("!a:example.org")));
+        assert!(!center.dispatch(&notification(owned_room_id!("!a:example.org"))));
+        assert!(center.dispatch(&notification(owned_room_id!("!b:example.org"))));
+        assert_eq!(shown.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_not_suppressed_while_app_unfocused() {
+        let (backend, shown) = MockBackend::new();
+        let center = NotificationCenter::new(vec![Box::new(backend)]);
+        center.set_selected_room(Some(owned_room_id!("!a:example.org")));
+        center.set_app_focused(false);
+        assert!(center.dispatch(&notification(owned_room_id!("!a:example.org"))));
+        center.set_app_focused(true);
+        assert!(!center.dispatch(&notification(owned_room_id!("!a:example.org"))));
+        assert_eq!(shown.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_evaluate_push_actions() {
+        let notify = [Action::Notify];
+        let highlight = [Action::Notify, Action::SetTweak(Tweak::Highlight(true))];
+        let silent: [Action; 0] = [];
+
+        assert_eq!(evaluate_push_actions(&notify, RoomNotificationMode::AllMessages), Some(false));
+        assert_eq!(evaluate_push_actions(&highlight, RoomNotificationMode::AllMessages), Some(true));
+        assert_eq!(evaluate_push_actions(&silent, RoomNotificationMode::AllMessages), None);
+
+        assert_eq!(evaluate_push_actions(&notify, RoomNotificationMode::MentionsAndKeywordsOnly), None);
+        assert_eq!(evaluate_push_actions(&highlight, RoomNotificationMode::MentionsAndKeywordsOnly), Some(true));
+
+        assert_eq!(evaluate_push_actions(&notify, RoomNotificationMode::Mute), None);
+        assert_eq!(evaluate_push_actions(&highlight, RoomNotificationMode::Mute), None);
+    }
+}
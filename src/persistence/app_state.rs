@@ -1,13 +1,21 @@
+use std::collections::{BTreeSet, HashMap};
+
 use makepad_widgets::{makepad_micro_serde::{DeRon, SerRon}, *};
 use serde::{self, Deserialize, Serialize};
-use matrix_sdk::ruma::{OwnedUserId, UserId};
-use crate::{app::{AppState, SelectedRoom}, app_data_dir, persistence::persistent_state_dir};
+use matrix_sdk::ruma::{presence::PresenceState, OwnedRoomId, OwnedUserId, UserId};
+use crate::{app::{AppState, SelectedRoom}, app_data_dir, persistence::persistent_state_dir, settings::notification_settings::DefaultNotificationCategory, utils::OwnedRoomIdRon};
 
 
 const LATEST_APP_STATE_FILE_NAME: &str = "latest_app_state.ron";
 
 const WINDOW_GEOM_STATE_FILE_NAME: &str = "window_geom_state.json";
 
+const ROOM_DRAFTS_FILE_NAME: &str = "room_drafts.ron";
+
+const PRESENCE_SETTINGS_FILE_NAME: &str = "presence_settings.json";
+
+const DEFAULT_MUTE_SETTINGS_FILE_NAME: &str = "default_mute_settings.json";
+
 
 /// Persistable state of the window's size, position, and fullscreen status.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -20,6 +28,115 @@ pub struct WindowGeomState {
     pub is_fullscreen: bool,
 }
 
+/// An unsent message that the user was composing in a room.
+///
+/// Replies and edits are stored by event ID, since the timeline items they refer to
+/// can only be re-obtained once the room's timeline has been loaded again.
+#[derive(Clone, Debug, Default, PartialEq, SerRon, DeRon)]
+pub struct RoomDraft {
+    /// The text in the room's message input bar.
+    pub text: String,
+    /// The ID of the event that the user was replying to, if any.
+    pub replying_to: Option<String>,
+    /// The message that the user was editing, if any.
+    pub editing: Option<EditDraft>,
+}
+
+impl RoomDraft {
+    /// Returns `true` if this draft contains nothing worth saving.
+    pub fn is_empty(&self) -> bool {
+        self.text.trim().is_empty()
+            && self.replying_to.is_none()
+            && self.editing.is_none()
+    }
+}
+
+/// An in-progress edit of a previously-sent message.
+#[derive(Clone, Debug, Default, PartialEq, SerRon, DeRon)]
+pub struct EditDraft {
+    /// The ID of the event being edited.
+    pub event_id: String,
+    /// The edited text that has not yet been sent.
+    pub text: String,
+}
+
+/// The unsent drafts for all rooms, keyed by room ID.
+#[derive(Clone, Debug, Default, SerRon, DeRon)]
+pub struct RoomDrafts {
+    pub drafts: HashMap<OwnedRoomIdRon, RoomDraft>,
+}
+
+
+/// An account's presence settings: the presence and status message it shares with other users.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PresenceSettings {
+    /// Whether to send this account's presence to the homeserver.
+    ///
+    /// If `false`, this account always appears offline to other users.
+    pub share_presence: bool,
+    /// The presence that the user chose to show while Robrix is running.
+    pub presence: PresenceState,
+    /// The user's custom status message, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_msg: Option<String>,
+}
+
+impl Default for PresenceSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PresenceSettings {
+    /// Returns the default settings, in which the user shares that they're online.
+    pub const fn new() -> Self {
+        Self {
+            share_presence: true,
+            presence: PresenceState::Online,
+            status_msg: None,
+        }
+    }
+
+    /// Returns the presence that should be sent to the homeserver,
+    /// which is always offline if the user doesn't want to share their presence.
+    pub fn presence_to_send(&self) -> PresenceState {
+        if self.share_presence {
+            self.presence.clone()
+        } else {
+            PresenceState::Offline
+        }
+    }
+}
+
+/// The kinds of rooms that an account mutes by default.
+///
+/// Push rules cannot express muting as a default notification mode,
+/// so Robrix mutes each room of a muted kind individually and remembers which rooms
+/// it muted, such that it can un-mute them again if the user changes the default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DefaultMuteSettings {
+    /// The kinds of rooms whose default notification mode is `Mute`.
+    pub muted_categories: Vec<DefaultNotificationCategory>,
+    /// The rooms that Robrix muted because of their kind's default mode,
+    /// as opposed to rooms that the user muted explicitly.
+    pub rooms_muted_by_default: BTreeSet<OwnedRoomId>,
+}
+
+impl DefaultMuteSettings {
+    /// Returns empty settings, in which no kind of room is muted by default.
+    pub const fn new() -> Self {
+        Self {
+            muted_categories: Vec::new(),
+            rooms_muted_by_default: BTreeSet::new(),
+        }
+    }
+
+    /// Returns whether rooms of the given kind are muted by default.
+    pub fn is_muted(&self, category: DefaultNotificationCategory) -> bool {
+        self.muted_categories.contains(&category)
+    }
+}
+
 
 /// Save the current app state to persistent storage.
 pub fn save_app_state(
@@ -44,6 +161,53 @@ pub fn save_app_state(
     Ok(())
 }
 
+/// Save the given room drafts to persistent storage.
+pub fn save_room_drafts(
+    room_drafts: &RoomDrafts,
+    user_id: &UserId,
+) -> anyhow::Result<()> {
+    std::fs::write(
+        persistent_state_dir(user_id).join(ROOM_DRAFTS_FILE_NAME),
+        room_drafts.serialize_ron(),
+    )?;
+    log!("Successfully saved {} room drafts to persistent storage.", room_drafts.drafts.len());
+    Ok(())
+}
+
+/// Save the given account's presence settings.
+pub fn save_presence_settings(user_id: &UserId, presence_settings: &PresenceSettings) -> anyhow::Result<()> {
+    std::fs::write(
+        persistent_state_dir(user_id).join(PRESENCE_SETTINGS_FILE_NAME),
+        serde_json::to_string(presence_settings)?,
+    )?;
+    Ok(())
+}
+
+/// Loads the given account's presence settings, or the default settings if none were saved.
+pub fn load_presence_settings(user_id: &UserId) -> PresenceSettings {
+    std::fs::read_to_string(persistent_state_dir(user_id).join(PRESENCE_SETTINGS_FILE_NAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Save the given account's default mute settings.
+pub fn save_default_mute_settings(user_id: &UserId, default_mute_settings: &DefaultMuteSettings) -> anyhow::Result<()> {
+    std::fs::write(
+        persistent_state_dir(user_id).join(DEFAULT_MUTE_SETTINGS_FILE_NAME),
+        serde_json::to_string(default_mute_settings)?,
+    )?;
+    Ok(())
+}
+
+/// Loads the given account's default mute settings, or empty settings if none were saved.
+pub fn load_default_mute_settings(user_id: &UserId) -> DefaultMuteSettings {
+    std::fs::read_to_string(persistent_state_dir(user_id).join(DEFAULT_MUTE_SETTINGS_FILE_NAME))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
 /// Save the current state of the given window's geometry to persistent storage.
 pub fn save_window_state(window_ref: WindowRef, cx: &Cx) -> anyhow::Result<()> {
     let inner_size = window_ref.get_inner_size(cx);
@@ -72,6 +236,17 @@ pub async fn load_app_state(user_id: &UserId) -> anyhow::Result<AppState> {
         .map_err(|er| anyhow::Error::msg(er.msg))
 }
 
+/// Loads the room drafts from persistent storage.
+pub async fn load_room_drafts(user_id: &UserId) -> anyhow::Result<RoomDrafts> {
+    let content = match tokio::fs::read_to_string(persistent_state_dir(user_id).join(ROOM_DRAFTS_FILE_NAME)).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(RoomDrafts::default()),
+        Err(e) => return Err(e.into())
+    };
+    RoomDrafts::deserialize_ron(&content)
+        .map_err(|er| anyhow::Error::msg(er.msg))
+}
+
 /// Loads the window geometry's state from persistent storage.
 pub fn load_window_state(window_ref: WindowRef, cx: &mut Cx) -> anyhow::Result<()> {
     let file = match std::fs::File::open(app_data_dir().join(WINDOW_GEOM_STATE_FILE_NAME)) {
@@ -95,3 +270,34 @@ pub fn load_window_state(window_ref: WindowRef, cx: &mut Cx) -> anyhow::Result<(
     );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests_room_drafts {
+    use super::*;
+
+    #[test]
+    fn room_drafts_round_trip_through_ron() {
+        let room_id = matrix_sdk::ruma::owned_room_id!("!abc:example.org");
+        let draft = RoomDraft {
+            text: String::from("half-written \"message\"\nwith a newline"),
+            replying_to: Some(String::from("$reply:example.org")),
+            editing: Some(EditDraft {
+                event_id: String::from("$edit:example.org"),
+                text: String::from("edited text"),
+            }),
+        };
+        let mut room_drafts = RoomDrafts::default();
+        room_drafts.drafts.insert(room_id.clone().into(), draft.clone());
+
+        let restored = RoomDrafts::deserialize_ron(&room_drafts.serialize_ron()).unwrap();
+        assert_eq!(restored.drafts.get(&OwnedRoomIdRon(room_id)), Some(&draft));
+    }
+
+    #[test]
+    fn whitespace_only_draft_is_empty() {
+        let draft = RoomDraft { text: String::from("  \n "), ..Default::default() };
+        assert!(draft.is_empty());
+        let reply_only = RoomDraft { replying_to: Some(String::from("$e:example.org")), ..draft };
+        assert!(!reply_only.is_empty());
+    }
+}
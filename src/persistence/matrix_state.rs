@@ -1,19 +1,29 @@
 //! Handles app persistence by saving and restoring client session data to/from the filesystem.
 
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf, sync::{Arc, LazyLock, Mutex}};
 use anyhow::{anyhow, bail};
 use makepad_widgets::{log, Cx};
 use matrix_sdk::{
-    authentication::matrix::MatrixSession,
+    authentication::{
+        matrix::MatrixSession,
+        oauth::{ClientId, OAuthSession, UserSession},
+        AuthSession,
+    },
     ruma::{OwnedUserId, UserId},
     sliding_sync,
     Client,
+    SessionMeta,
+    SessionTokens,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_data_dir,
     login::login_screen::LoginAction,
+    persistence::{
+        proxy_settings::{set_active_proxy_settings, ProxySettings},
+        secret_store::{self, SecretStore, SecretStoreKind},
+    },
 };
 
 /// The data needed to re-build a client.
@@ -27,10 +37,14 @@ pub struct ClientSessionPersisted {
 
     /// The passphrase of the database.
     pub passphrase: String,
+
+    /// The proxy settings used for all of this account's network requests.
+    #[serde(default)]
+    pub proxy: ProxySettings,
 }
 
 /// The full session to persist.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FullSessionPersisted {
     /// The data to re-build the client.
     pub client_session: ClientSessionPersisted,
@@ -56,10 +70,18 @@ pub struct FullSessionPersisted {
     /// when rebuilding the session from persistent storage.
     #[serde(default)]
     pub sliding_sync_version: SlidingSyncVersion,
+
+    /// The ID that the OAuth 2.0 authorization server assigned to this client,
+    /// if this session was logged in via OAuth (next-generation auth) rather than
+    /// the legacy Matrix auth API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth_client_id: Option<String>,
 }
 
 /// A serializable duplicate of [`sliding_sync::Version`].
-#[derive(Debug, Default, Serialize, Deserialize)]
+///
+/// Robrix refers to this as the "sync mode": `None` means classic sync.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SlidingSyncVersion {
     #[default]
     Native,
@@ -82,6 +104,160 @@ impl From<sliding_sync::Version> for SlidingSyncVersion {
     }
 }
 
+/// The secret parts of a persisted session, which are kept in a [`SecretStore`].
+#[derive(Serialize, Deserialize)]
+struct SessionSecrets {
+    /// The passphrase of the database.
+    passphrase: String,
+    /// The access token and refresh token of the Matrix user session.
+    tokens: SessionTokens,
+    /// The password used to authenticate with the account's proxies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy_password: Option<String>,
+}
+
+/// The contents of a session file, which contains no secrets.
+///
+/// The session's secrets are kept in the `secret_store` that they were saved to.
+#[derive(Serialize, Deserialize)]
+struct SessionFile {
+    homeserver: String,
+    db_path: PathBuf,
+    session_meta: SessionMeta,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sync_token: Option<String>,
+    #[serde(default)]
+    sliding_sync_version: SlidingSyncVersion,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    oauth_client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "ProxySettings::is_empty")]
+    proxy: ProxySettings,
+    secret_store: SecretStoreKind,
+}
+
+/// The formats of session files that can be read.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSession {
+    Current(SessionFile),
+    /// Older session files stored the session's secrets in plaintext.
+    Legacy(FullSessionPersisted),
+}
+
+/// Returns the key under which the given user's session secrets are kept in a [`SecretStore`].
+fn session_secrets_key(user_id: &UserId) -> String {
+    format!("{user_id}/session")
+}
+
+/// Saves the secrets of the given `session` into the `secret_store`,
+/// and returns the contents of the session file for it.
+fn serialize_session(
+    session: FullSessionPersisted,
+    secret_store: &dyn SecretStore,
+) -> anyhow::Result<String> {
+    let FullSessionPersisted { client_session, user_session, sync_token, sliding_sync_version, oauth_client_id } = session;
+    let ClientSessionPersisted { homeserver, db_path, passphrase, mut proxy } = client_session;
+    let secrets = SessionSecrets {
+        passphrase,
+        tokens: user_session.tokens,
+        proxy_password: proxy.password.take(),
+    };
+    secret_store.set(
+        &session_secrets_key(&user_session.meta.user_id),
+        &serde_json::to_string(&secrets)?,
+    )?;
+    Ok(serde_json::to_string(&SessionFile {
+        homeserver,
+        db_path,
+        session_meta: user_session.meta,
+        sync_token,
+        sliding_sync_version,
+        oauth_client_id,
+        proxy,
+        secret_store: secret_store.kind(),
+    })?)
+}
+
+/// Deserializes the contents of a session file, retrieving its secrets from
+/// the secret store returned by `open_store`.
+///
+/// If the session file is a legacy one with plaintext secrets, its secrets are moved
+/// into the secret store returned by `migration_store`, and the new contents
+/// that should replace the plaintext session file are also returned.
+fn deserialize_session(
+    serialized: &str,
+    open_store: impl FnOnce(SecretStoreKind) -> anyhow::Result<Box<dyn SecretStore>>,
+    migration_store: impl FnOnce() -> anyhow::Result<Box<dyn SecretStore>>,
+) -> anyhow::Result<(FullSessionPersisted, Option<String>)> {
+    deserialize_session_with_store(serialized, open_store, migration_store)
+        .map(|(session, migrated, _)| (session, migrated))
+}
+
+/// Like [`deserialize_session()`], but also returns the secret store that holds the session's secrets,
+/// such that updated secrets can be saved back into that same store.
+fn deserialize_session_with_store(
+    serialized: &str,
+    open_store: impl FnOnce(SecretStoreKind) -> anyhow::Result<Box<dyn SecretStore>>,
+    migration_store: impl FnOnce() -> anyhow::Result<Box<dyn SecretStore>>,
+) -> anyhow::Result<(FullSessionPersisted, Option<String>, Box<dyn SecretStore>)> {
+    match serde_json::from_str(serialized)? {
+        StoredSession::Current(session_file) => {
+            let SessionFile { homeserver, db_path, session_meta, sync_token, sliding_sync_version, oauth_client_id, mut proxy, secret_store } = session_file;
+            let store = open_store(secret_store)?;
+            let secrets = store
+                .get(&session_secrets_key(&session_meta.user_id))?
+                .ok_or_else(|| anyhow!("Could not find the secrets for this session in the {secret_store:?} secret store"))?;
+            let SessionSecrets { passphrase, tokens, proxy_password } = serde_json::from_str(&secrets)?;
+            proxy.password = proxy_password;
+            let session = FullSessionPersisted {
+                client_session: ClientSessionPersisted { homeserver, db_path, passphrase, proxy },
+                user_session: MatrixSession { meta: session_meta, tokens },
+                sync_token,
+                sliding_sync_version,
+                oauth_client_id,
+            };
+            Ok((session, None, store))
+        }
+        StoredSession::Legacy(session) => {
+            let store = migration_store()?;
+            let migrated = serialize_session(session.clone(), store.as_ref())?;
+            Ok((session, Some(migrated), store))
+        }
+    }
+}
+
+/// Applies the given `update` to the session whose file has the given contents,
+/// and returns the new contents of that session file.
+///
+/// The session's secrets are saved back into the secret store they were read from,
+/// such that they aren't orphaned in a different store.
+fn update_serialized_session(
+    serialized: &str,
+    open_store: impl FnOnce(SecretStoreKind) -> anyhow::Result<Box<dyn SecretStore>>,
+    migration_store: impl FnOnce() -> anyhow::Result<Box<dyn SecretStore>>,
+    update: impl FnOnce(&mut FullSessionPersisted),
+) -> anyhow::Result<String> {
+    let (mut full_session, _, store) = deserialize_session_with_store(serialized, open_store, migration_store)?;
+    update(&mut full_session);
+    serialize_session(full_session, store.as_ref())
+}
+
+/// Deletes the secrets of the session whose file has the given contents
+/// from the secret store returned by `open_store`.
+///
+/// Legacy session files keep their secrets in the session file itself,
+/// so there is nothing to delete from a secret store for them.
+fn delete_session_secrets_from_store(
+    serialized: &str,
+    open_store: impl FnOnce(SecretStoreKind) -> anyhow::Result<Box<dyn SecretStore>>,
+) -> anyhow::Result<()> {
+    match serde_json::from_str(serialized)? {
+        StoredSession::Current(session_file) => open_store(session_file.secret_store)?
+            .delete(&session_secrets_key(&session_file.session_meta.user_id)),
+        StoredSession::Legacy(_) => Ok(()),
+    }
+}
+
 fn user_id_to_file_name(user_id: &UserId) -> String {
     user_id.as_str()
         .replace(":", "_")
@@ -114,7 +290,9 @@ pub fn most_recent_user_id() -> Option<OwnedUserId> {
 }
 
 /// Save which user was the most recently logged in.
-async fn save_latest_user_id(user_id: &UserId) -> anyhow::Result<()> {
+///
+/// This user's session will be restored the next time the Matrix client starts.
+pub async fn save_latest_user_id(user_id: &UserId) -> anyhow::Result<()> {
     tokio::fs::write(
         app_data_dir().join(LATEST_USER_ID_FILE_NAME),
         user_id.as_str(),
@@ -122,6 +300,44 @@ async fn save_latest_user_id(user_id: &UserId) -> anyhow::Result<()> {
     Ok(())
 }
 
+const LOGGED_IN_ACCOUNTS_FILE_NAME: &str = "logged_in_accounts.json";
+
+/// Returns the IDs of all accounts that are currently logged in, in the order they were added.
+pub fn logged_in_accounts() -> Vec<OwnedUserId> {
+    std::fs::read_to_string(app_data_dir().join(LOGGED_IN_ACCOUNTS_FILE_NAME))
+        .ok()
+        .and_then(|accounts| serde_json::from_str(&accounts).ok())
+        .unwrap_or_default()
+}
+
+async fn save_logged_in_accounts(accounts: &[OwnedUserId]) -> anyhow::Result<()> {
+    tokio::fs::write(
+        app_data_dir().join(LOGGED_IN_ACCOUNTS_FILE_NAME),
+        serde_json::to_string(accounts)?,
+    ).await?;
+    Ok(())
+}
+
+/// Adds the given account to the list of logged-in accounts, if it isn't already in it.
+async fn add_logged_in_account(user_id: &UserId) -> anyhow::Result<()> {
+    let mut accounts = logged_in_accounts();
+    if !accounts.iter().any(|id| id == user_id) {
+        accounts.push(user_id.to_owned());
+        save_logged_in_accounts(&accounts).await?;
+    }
+    Ok(())
+}
+
+/// Removes the given account from the list of logged-in accounts, e.g., after logging out of it.
+///
+/// Returns the accounts that remain logged in.
+pub async fn remove_logged_in_account(user_id: &UserId) -> anyhow::Result<Vec<OwnedUserId>> {
+    let mut accounts = logged_in_accounts();
+    accounts.retain(|id| id != user_id);
+    save_logged_in_accounts(&accounts).await?;
+    Ok(accounts)
+}
+
 
 /// Restores the given user's previous session from the filesystem.
 ///
@@ -134,80 +350,124 @@ pub async fn restore_session(
         log!("Could not find previous latest User ID");
         bail!("Could not find previous latest User ID");
     };
-    let session_file = session_file_path(&user_id);
+    let (client, sync_token) = restore_client(&user_id, true).await?;
+    save_latest_user_id(&user_id).await?;
+    add_logged_in_account(&user_id).await?;
+    Ok((client, sync_token))
+}
+
+/// Restores the given user's previous session from the filesystem
+/// without making it the active or most recent account,
+/// e.g., such that an inactive account can keep syncing in the background.
+pub async fn restore_background_session(user_id: &UserId) -> anyhow::Result<Client> {
+    restore_client(user_id, false).await.map(|(client, _sync_token)| client)
+}
+
+/// Restores a client for the given user's previous session from the filesystem.
+///
+/// If `is_active` is `true`, the restoration status is shown on the login screen,
+/// and the session's proxy settings become the active proxy settings.
+async fn restore_client(
+    user_id: &UserId,
+    is_active: bool,
+) -> anyhow::Result<(Client, Option<String>)> {
+    let show_status = |title: &str, status: String| if is_active {
+        post_restore_status(title, status);
+    };
+    let session_file = session_file_path(user_id);
     if !session_file.exists() {
         log!("Could not find previous session file for user {user_id}");
         bail!("Could not find previous session file");
     }
     let status_str = format!("Loading previous session file for {user_id}...");
     log!("{status_str}: '{}'", session_file.display());
-    Cx::post_action(LoginAction::Status {
-        title: "Restoring session".into(),
-        status: status_str,
-    });
+    show_status("Restoring session", status_str);
 
-    // The session was serialized as JSON in a file.
-    let serialized_session = tokio::fs::read_to_string(session_file).await?;
-    let FullSessionPersisted { client_session, user_session, sync_token, sliding_sync_version } =
-        serde_json::from_str(&serialized_session)?;
+    // The session was serialized as JSON in a file, with its secrets kept in a secret store.
+    // Accessing a secret store may block, e.g., while waiting for the user to unlock it.
+    let serialized_session = tokio::fs::read_to_string(&session_file).await?;
+    let (full_session, migrated_session) = tokio::task::spawn_blocking(move ||
+        deserialize_session(&serialized_session, secret_store::open_store, secret_store::default_store)
+    ).await??;
+    if let Some(migrated_session) = migrated_session {
+        tokio::fs::write(&session_file, migrated_session).await?;
+        log!("Moved the plaintext secrets of {user_id}'s session file into secure storage.");
+    }
+    let FullSessionPersisted { client_session, user_session, sync_token, sliding_sync_version, oauth_client_id } = full_session;
 
     let status_str = format!(
         "Loaded session file for {user_id}. Trying to connect to homeserver ({})...",
         client_session.homeserver,
     );
     log!("{status_str}");
-    Cx::post_action(LoginAction::Status {
-        title: "Connecting to homeserver".into(),
-        status: status_str,
-    });
+    show_status("Connecting to homeserver", status_str);
     // Build the client with the previous settings from the session.
-    let client = Client::builder()
+    let builder = Client::builder()
         .homeserver_url(client_session.homeserver)
         .sqlite_store(client_session.db_path, Some(&client_session.passphrase))
-        .handle_refresh_tokens()
+        .handle_refresh_tokens();
+    let client = client_session.proxy.apply_to_matrix_client(builder)?
         .build()
         .await?;
+    if is_active {
+        set_active_proxy_settings(client_session.proxy);
+    }
     let sliding_sync_version = sliding_sync_version.into();
     client.set_sliding_sync_version(sliding_sync_version);
     let status_str = format!("Authenticating previous login session for {}...", user_session.meta.user_id);
     log!("{status_str}");
-    Cx::post_action(LoginAction::Status {
-        title: "Authenticating session".into(),
-        status: status_str,
-    });
-
-    // Restore the Matrix user session.
-    client.restore_session(user_session).await?;
-    save_latest_user_id(&user_id).await?;
+    show_status("Authenticating session", status_str);
 
+    // Restore the user session, using the same auth API that it was logged in with.
+    if let Some(client_id) = oauth_client_id {
+        client.restore_session(OAuthSession {
+            client_id: ClientId::new(client_id),
+            user: UserSession { meta: user_session.meta, tokens: user_session.tokens },
+        }).await?;
+    } else {
+        client.restore_session(user_session).await?;
+    }
     Ok((client, sync_token))
 }
 
+/// Shows the given session restoration status on the login screen,
+/// unless Robrix is running in headless mode, in which there is no UI.
+fn post_restore_status(title: &str, status: String) {
+    if !crate::headless::is_headless() {
+        Cx::post_action(LoginAction::Status { title: title.into(), status });
+    }
+}
+
 /// Persist a logged-in client session to the filesystem for later use.
 ///
-/// TODO: This is not very secure, for simplicity. We should use robius-keychain
-///       or `keyring-rs` to storing secrets securely.
+/// The session's secrets (its tokens and database passphrase) are stored
+/// in the default [`SecretStore`], not in the session file itself.
 ///
 /// Note that we could also build the user session from the login response.
 pub async fn save_session(
     client: &Client,
     client_session: ClientSessionPersisted,
 ) -> anyhow::Result<()> {
-    let user_session = client
-        .matrix_auth()
-        .session()
-        .ok_or_else(|| anyhow!("A logged-in client should have a session"))?;
+    let (user_session, oauth_client_id) = logged_in_session(client)?;
+    set_active_proxy_settings(client_session.proxy.clone());
+    let session_lock = session_update_lock(&user_session.meta.user_id);
+    let _session_guard = session_lock.lock().await;
 
     save_latest_user_id(&user_session.meta.user_id).await?;
+    add_logged_in_account(&user_session.meta.user_id).await?;
     let sliding_sync_version = client.sliding_sync_version().into();
     // Save that user's session.
     let session_file = session_file_path(&user_session.meta.user_id);
-    let serialized_session = serde_json::to_string(&FullSessionPersisted {
+    let full_session = FullSessionPersisted {
         client_session,
         user_session,
         sync_token: None,
-        sliding_sync_version
-    })?;
+        sliding_sync_version,
+        oauth_client_id,
+    };
+    let serialized_session = tokio::task::spawn_blocking(move ||
+        serialize_session(full_session, secret_store::default_store()?.as_ref())
+    ).await??;
     if let Some(parent) = session_file.parent() {
         tokio::fs::create_dir_all(parent).await?;
     }
@@ -217,6 +477,159 @@ pub async fn save_session(
     Ok(())
 }
 
+/// Returns the given logged-in client's user session, along with its OAuth client ID
+/// if it was logged in via OAuth.
+fn logged_in_session(client: &Client) -> anyhow::Result<(MatrixSession, Option<String>)> {
+    match client.session().ok_or_else(|| anyhow!("A logged-in client should have a session"))? {
+        AuthSession::Matrix(session) => Ok((session, None)),
+        AuthSession::OAuth(session) => Ok((
+            MatrixSession { meta: session.user.meta, tokens: session.user.tokens },
+            Some(session.client_id.as_str().to_owned()),
+        )),
+    }
+}
+
+/// Reads the given user's persisted session and returns the result of `read` applied to it.
+async fn read_persisted_session<T: Send + 'static>(
+    user_id: &UserId,
+    read: impl FnOnce(FullSessionPersisted) -> T + Send + 'static,
+) -> anyhow::Result<T> {
+    let serialized_session = tokio::fs::read_to_string(session_file_path(user_id)).await?;
+    tokio::task::spawn_blocking(move || {
+        let (full_session, _) = deserialize_session(
+            &serialized_session,
+            secret_store::open_store,
+            secret_store::default_store,
+        )?;
+        Ok(read(full_session))
+    }).await?
+}
+
+/// The locks that serialize all writes to each user's persisted session,
+/// such that concurrent updates (e.g., saving refreshed tokens while saving proxy settings)
+/// can't overwrite each other's changes.
+static SESSION_UPDATE_LOCKS: LazyLock<Mutex<BTreeMap<OwnedUserId, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Returns the lock that must be held while writing the given user's persisted session.
+fn session_update_lock(user_id: &UserId) -> Arc<tokio::sync::Mutex<()>> {
+    SESSION_UPDATE_LOCKS.lock().unwrap()
+        .entry(user_id.to_owned())
+        .or_default()
+        .clone()
+}
+
+/// Applies the given `update` to the given user's persisted session and saves it again.
+///
+/// Updates to the same user's session are serialized, and the session's secrets are saved
+/// back into the secret store that the session file records.
+async fn update_persisted_session(
+    user_id: &UserId,
+    update: impl FnOnce(&mut FullSessionPersisted) + Send + 'static,
+) -> anyhow::Result<()> {
+    let session_lock = session_update_lock(user_id);
+    let _session_guard = session_lock.lock().await;
+    let session_file = session_file_path(user_id);
+    let serialized_session = tokio::fs::read_to_string(&session_file).await?;
+    let serialized_session = tokio::task::spawn_blocking(move ||
+        update_serialized_session(
+            &serialized_session,
+            secret_store::open_store,
+            secret_store::default_store,
+            update,
+        )
+    ).await??;
+    tokio::fs::write(&session_file, serialized_session).await?;
+    Ok(())
+}
+
+/// Saves the given client's current access and refresh tokens into its persisted session,
+/// which must be done whenever the Matrix SDK refreshes them.
+pub async fn save_session_tokens(client: &Client) -> anyhow::Result<()> {
+    let (user_session, _) = logged_in_session(client)?;
+    update_persisted_session(&user_session.meta.user_id, |full_session| {
+        full_session.user_session.tokens = user_session.tokens;
+    }).await
+}
+
+/// Returns the proxy settings saved in the given user's persisted session.
+pub async fn load_proxy_settings(user_id: &UserId) -> anyhow::Result<ProxySettings> {
+    read_persisted_session(user_id, |full_session| full_session.client_session.proxy).await
+}
+
+/// Saves the given proxy settings into the given user's persisted session.
+///
+/// The Matrix client can't change its proxy once it has been built,
+/// so the new settings take effect the next time the session is restored.
+pub async fn save_proxy_settings(user_id: &UserId, proxy: ProxySettings) -> anyhow::Result<()> {
+    update_persisted_session(user_id, |full_session| full_session.client_session.proxy = proxy).await
+}
+
+/// Saves the given sliding sync version (sync mode) into the given user's persisted session.
+pub async fn save_sliding_sync_version(user_id: &UserId, sliding_sync_version: SlidingSyncVersion) -> anyhow::Result<()> {
+    update_persisted_session(user_id, move |full_session| full_session.sliding_sync_version = sliding_sync_version).await
+}
+
+/// Deletes the secrets (tokens, database passphrase, and proxy password) of the given user's
+/// persisted session from the secret store they were saved to.
+///
+/// This must be done once the session has been logged out or its account deactivated,
+/// as its secrets can no longer be used and must not be left behind.
+pub async fn delete_session_secrets(user_id: &UserId) -> anyhow::Result<()> {
+    let serialized_session = match tokio::fs::read_to_string(session_file_path(user_id)).await {
+        Ok(serialized_session) => serialized_session,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    tokio::task::spawn_blocking(move ||
+        delete_session_secrets_from_store(&serialized_session, secret_store::open_store)
+    ).await?
+}
+
+/// Deletes the given user's persisted session: both its secrets and its session file.
+///
+/// This must be done once the session's account has been deactivated,
+/// as the session can never be restored again.
+pub async fn delete_persisted_session(user_id: &UserId) -> anyhow::Result<()> {
+    delete_session_secrets(user_id).await?;
+    match tokio::fs::remove_file(session_file_path(user_id)).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The files of a session's database that only contain data cached from the homeserver.
+///
+/// The crypto store is not included, as it contains this session's encryption keys,
+/// which can't be re-fetched from the homeserver.
+const SESSION_CACHE_FILE_NAMES: [&str; 3] = [
+    "matrix-sdk-state.sqlite3",
+    "matrix-sdk-event-cache.sqlite3",
+    "matrix-sdk-media.sqlite3",
+];
+
+/// Deletes the locally-cached data of the given user's persisted session,
+/// such that it is re-fetched from the homeserver the next time the session is restored.
+///
+/// This must only be called while the session's client isn't running.
+pub async fn clear_session_caches(user_id: &UserId) -> anyhow::Result<()> {
+    let db_path = read_persisted_session(user_id, |full_session| full_session.client_session.db_path).await?;
+
+    for file_name in SESSION_CACHE_FILE_NAMES {
+        // Also delete SQLite's write-ahead log and shared-memory files.
+        for suffix in ["", "-wal", "-shm"] {
+            let path = db_path.join(format!("{file_name}{suffix}"));
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => log!("Deleted cached session data: {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => { }
+                Err(e) => bail!("Failed to delete {}: {e}", path.display()),
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Remove the LATEST_USER_ID_FILE_NAME file if it exists
 /// 
 /// Returns:
@@ -234,3 +647,159 @@ pub async fn delete_latest_user_id() -> anyhow::Result<bool> {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests_session_secrets {
+    use matrix_sdk::ruma::{owned_device_id, owned_user_id};
+
+    use super::*;
+    use crate::persistence::secret_store::encrypted_file::{temp_store, EncryptedFileStore};
+
+    fn test_session() -> FullSessionPersisted {
+        FullSessionPersisted {
+            client_session: ClientSessionPersisted {
+                homeserver: String::from("https://matrix.example.org"),
+                db_path: PathBuf::from("/tmp/robrix_db"),
+                passphrase: String::from("database-passphrase-secret"),
+                proxy: ProxySettings::default(),
+            },
+            user_session: MatrixSession {
+                meta: SessionMeta {
+                    user_id: owned_user_id!("@alice:example.org"),
+                    device_id: owned_device_id!("ALICEDEVICE"),
+                },
+                tokens: SessionTokens {
+                    access_token: String::from("access-token-secret"),
+                    refresh_token: Some(String::from("refresh-token-secret")),
+                },
+            },
+            sync_token: Some(String::from("sync-token")),
+            sliding_sync_version: SlidingSyncVersion::Native,
+            oauth_client_id: None,
+        }
+    }
+
+    fn assert_same_session(a: &FullSessionPersisted, b: &FullSessionPersisted) {
+        assert_eq!(a.client_session.homeserver, b.client_session.homeserver);
+        assert_eq!(a.client_session.db_path, b.client_session.db_path);
+        assert_eq!(a.client_session.passphrase, b.client_session.passphrase);
+        assert_eq!(a.user_session.meta.user_id, b.user_session.meta.user_id);
+        assert_eq!(a.user_session.meta.device_id, b.user_session.meta.device_id);
+        assert_eq!(a.user_session.tokens.access_token, b.user_session.tokens.access_token);
+        assert_eq!(a.user_session.tokens.refresh_token, b.user_session.tokens.refresh_token);
+        assert_eq!(a.sync_token, b.sync_token);
+        assert_eq!(a.sliding_sync_version, b.sliding_sync_version);
+        assert_eq!(a.oauth_client_id, b.oauth_client_id);
+        assert_eq!(a.client_session.proxy, b.client_session.proxy);
+    }
+
+    fn boxed(store: &EncryptedFileStore) -> anyhow::Result<Box<dyn SecretStore>> {
+        Ok(Box::new(store.clone()))
+    }
+
+    fn assert_no_secrets(contents: &str) {
+        for secret in ["database-passphrase-secret", "access-token-secret", "refresh-token-secret"] {
+            assert!(!contents.contains(secret), "session file contains secret {secret:?}");
+        }
+    }
+
+    #[test]
+    fn session_file_round_trip_keeps_secrets_out_of_file() {
+        let store = temp_store("session_round_trip", "passphrase");
+        let serialized = serialize_session(test_session(), &store).unwrap();
+        assert_no_secrets(&serialized);
+
+        let (restored, migrated) = deserialize_session(
+            &serialized,
+            |kind| { assert_eq!(kind, SecretStoreKind::EncryptedFile); boxed(&store) },
+            || panic!("a current session file must not be migrated"),
+        ).unwrap();
+        assert!(migrated.is_none());
+        assert_same_session(&restored, &test_session());
+    }
+
+    #[test]
+    fn legacy_plaintext_session_is_migrated() {
+        let store = temp_store("session_migration", "passphrase");
+        let legacy = serde_json::to_string(&test_session()).unwrap();
+
+        let (restored, migrated) = deserialize_session(
+            &legacy,
+            |_| panic!("a legacy session file has no secret store"),
+            || boxed(&store),
+        ).unwrap();
+        assert_same_session(&restored, &test_session());
+
+        // The migrated session file must not contain any secrets,
+        // and must be readable using the secrets moved into the store.
+        let migrated = migrated.expect("a legacy session file must be migrated");
+        assert_no_secrets(&migrated);
+        let (restored_again, migrated_again) = deserialize_session(&migrated, |_| boxed(&store), || boxed(&store)).unwrap();
+        assert!(migrated_again.is_none());
+        assert_same_session(&restored_again, &test_session());
+    }
+
+    #[test]
+    fn oauth_client_id_round_trips() {
+        let store = temp_store("session_oauth_client_id", "passphrase");
+        let session = FullSessionPersisted {
+            oauth_client_id: Some(String::from("01JOAUTHCLIENTID")),
+            ..test_session()
+        };
+        let serialized = serialize_session(session.clone(), &store).unwrap();
+        let (restored, _) = deserialize_session(&serialized, |_| boxed(&store), || boxed(&store)).unwrap();
+        assert_same_session(&restored, &session);
+    }
+
+    #[test]
+    fn proxy_password_is_kept_in_secret_store() {
+        let store = temp_store("session_proxy_password", "passphrase");
+        let mut session = test_session();
+        session.client_session.proxy = ProxySettings {
+            socks5: Some(String::from("socks5://127.0.0.1:1080")),
+            username: Some(String::from("alice")),
+            password: Some(String::from("proxy-password-secret")),
+            ..Default::default()
+        };
+        let serialized = serialize_session(session.clone(), &store).unwrap();
+        assert!(serialized.contains("socks5://127.0.0.1:1080"));
+        assert!(!serialized.contains("proxy-password-secret"));
+        let (restored, _) = deserialize_session(&serialized, |_| boxed(&store), || boxed(&store)).unwrap();
+        assert_same_session(&restored, &session);
+    }
+
+    #[test]
+    fn updated_secrets_are_saved_into_the_recorded_store() {
+        let recorded_store = temp_store("session_update_recorded", "passphrase");
+        let other_store = temp_store("session_update_other", "passphrase");
+        let serialized = serialize_session(test_session(), &recorded_store).unwrap();
+
+        let updated = update_serialized_session(
+            &serialized,
+            |_| boxed(&recorded_store),
+            || panic!("a current session file must not be migrated"),
+            |session| session.user_session.tokens.refresh_token = Some(String::from("rotated-refresh-token")),
+        ).unwrap();
+        let key = session_secrets_key(&test_session().user_session.meta.user_id);
+        assert!(other_store.get(&key).unwrap().is_none());
+        let (restored, _) = deserialize_session(&updated, |_| boxed(&recorded_store), || boxed(&other_store)).unwrap();
+        assert_eq!(restored.user_session.tokens.refresh_token.as_deref(), Some("rotated-refresh-token"));
+    }
+
+    #[test]
+    fn deleting_session_secrets_removes_them_from_store() {
+        let store = temp_store("session_delete_secrets", "passphrase");
+        let serialized = serialize_session(test_session(), &store).unwrap();
+        delete_session_secrets_from_store(&serialized, |_| boxed(&store)).unwrap();
+        let key = session_secrets_key(&test_session().user_session.meta.user_id);
+        assert!(store.get(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_secrets_are_an_error() {
+        let store = temp_store("session_missing_secrets", "passphrase");
+        let serialized = serialize_session(test_session(), &store).unwrap();
+        store.delete(&session_secrets_key(&test_session().user_session.meta.user_id)).unwrap();
+        assert!(deserialize_session(&serialized, |_| boxed(&store), || boxed(&store)).is_err());
+    }
+}
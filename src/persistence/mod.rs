@@ -4,6 +4,13 @@
 pub mod matrix_state;
 pub use matrix_state::*;
 
+/// For the per-account proxy settings used for all network requests.
+pub mod proxy_settings;
+pub use proxy_settings::ProxySettings;
+
+/// For storing secrets, e.g., access tokens and passphrases, in secure storage.
+pub mod secret_store;
+
 /// For persisting application state not related to Matrix.
 pub mod app_state;
 pub use app_state::*;
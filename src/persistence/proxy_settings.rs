@@ -0,0 +1,206 @@
+//! Per-account proxy settings, which apply to all network requests made on behalf of an account:
+//! those of the Matrix client, link previews (which use the Matrix client's HTTP client),
+//! and TSP requests.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// The proxy settings of an account.
+///
+/// These are persisted as part of the account's [`ClientSessionPersisted`](super::ClientSessionPersisted),
+/// with the proxy password kept in the session's secrets rather than in the session file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxySettings {
+    /// The proxy used for `http://` requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    /// The proxy used for `https://` requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https: Option<String>,
+    /// The SOCKS5 proxy used for all requests not handled by the above HTTP(S) proxies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socks5: Option<String>,
+    /// The username used to authenticate with the proxies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// The password used to authenticate with the proxies.
+    #[serde(skip)]
+    pub password: Option<String>,
+    /// A comma-separated list of hosts, domains, and IP ranges that bypass the proxies,
+    /// e.g., `localhost, .example.org, 192.168.0.0/16`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub no_proxy: String,
+}
+
+impl ProxySettings {
+    /// Creates proxy settings from the single proxy URL given on the command line,
+    /// which is used for all requests.
+    pub fn from_cli_proxy(proxy: &str) -> Self {
+        let is_socks = proxy.starts_with("socks5://") || proxy.starts_with("socks5h://");
+        Self {
+            http: (!is_socks).then(|| proxy.to_owned()),
+            https: (!is_socks).then(|| proxy.to_owned()),
+            socks5: is_socks.then(|| proxy.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns `true` if no proxy is configured.
+    pub fn is_empty(&self) -> bool {
+        self.http.is_none() && self.https.is_none() && self.socks5.is_none()
+    }
+
+    /// Checks that all of the proxy URLs are valid, returning a user-facing error if not.
+    pub fn validate(&self) -> Result<(), String> {
+        let check = |kind: &str, proxy: &Option<String>, schemes: &[&str]| {
+            let Some(proxy) = proxy else { return Ok(()) };
+            let url = Url::parse(proxy).map_err(|e| format!("The {kind} proxy URL \"{proxy}\" is invalid: {e}."))?;
+            if !schemes.contains(&url.scheme()) || url.host_str().is_none() {
+                return Err(format!("The {kind} proxy URL must look like \"{}://host:port\".", schemes[0]));
+            }
+            Ok(())
+        };
+        check("HTTP", &self.http, &["http", "https"])?;
+        check("HTTPS", &self.https, &["http", "https"])?;
+        check("SOCKS5", &self.socks5, &["socks5", "socks5h"])?;
+        if self.password.is_some() && self.username.is_none() {
+            return Err(String::from("A proxy password requires a proxy username."));
+        }
+        Ok(())
+    }
+
+    /// Returns the proxies to be added to a `reqwest` client, in order of precedence.
+    fn proxies(&self) -> reqwest::Result<Vec<reqwest::Proxy>> {
+        let no_proxy = reqwest::NoProxy::from_string(&self.no_proxy);
+        let configure = |proxy: reqwest::Proxy| {
+            let proxy = proxy.no_proxy(no_proxy.clone());
+            match &self.username {
+                Some(username) => proxy.basic_auth(username, self.password.as_deref().unwrap_or_default()),
+                None => proxy,
+            }
+        };
+        let mut proxies = Vec::new();
+        if let Some(http) = &self.http {
+            proxies.push(configure(reqwest::Proxy::http(http)?));
+        }
+        if let Some(https) = &self.https {
+            proxies.push(configure(reqwest::Proxy::https(https)?));
+        }
+        // `reqwest` uses the first matching proxy, so the SOCKS5 proxy is the fallback.
+        if let Some(socks5) = &self.socks5 {
+            proxies.push(configure(reqwest::Proxy::all(socks5)?));
+        }
+        Ok(proxies)
+    }
+
+    /// Adds these proxy settings to the given `reqwest` client builder.
+    pub fn apply_to_reqwest(&self, mut builder: reqwest::ClientBuilder) -> reqwest::Result<reqwest::ClientBuilder> {
+        for proxy in self.proxies()? {
+            builder = builder.proxy(proxy);
+        }
+        Ok(builder)
+    }
+
+    /// Adds these proxy settings to the given Matrix client builder.
+    ///
+    /// If no proxy is configured, the Matrix SDK's default HTTP client is used.
+    pub fn apply_to_matrix_client(&self, builder: matrix_sdk::ClientBuilder) -> reqwest::Result<matrix_sdk::ClientBuilder> {
+        if self.is_empty() {
+            return Ok(builder);
+        }
+        let http_client = self.apply_to_reqwest(
+            reqwest::ClientBuilder::new()
+                .user_agent(format!("Robrix v{}", env!("CARGO_PKG_VERSION")))
+        )?.build()?;
+        Ok(builder.http_client(http_client))
+    }
+}
+
+/// The proxy settings of the currently logged-in account's Matrix client.
+///
+/// These are set when the client is built, such that other network requests
+/// (e.g., TSP requests) use the same proxy settings as the Matrix client.
+static ACTIVE_PROXY_SETTINGS: Mutex<Option<ProxySettings>> = Mutex::new(None);
+
+/// Returns the proxy settings of the currently logged-in account's Matrix client.
+pub fn active_proxy_settings() -> ProxySettings {
+    ACTIVE_PROXY_SETTINGS.lock().unwrap().clone().unwrap_or_default()
+}
+
+/// Sets the proxy settings of the currently logged-in account's Matrix client.
+pub fn set_active_proxy_settings(proxy_settings: ProxySettings) {
+    *ACTIVE_PROXY_SETTINGS.lock().unwrap() = Some(proxy_settings);
+}
+
+/// The proxy settings entered on the login screen, if any.
+///
+/// These apply to all network requests made before an account is logged in
+/// (e.g., checking a homeserver and logging in or registering),
+/// and are then saved as the newly-logged-in account's proxy settings.
+static LOGIN_PROXY_SETTINGS: Mutex<Option<ProxySettings>> = Mutex::new(None);
+
+/// Returns the proxy settings entered on the login screen, if any.
+pub fn login_proxy_settings() -> Option<ProxySettings> {
+    LOGIN_PROXY_SETTINGS.lock().unwrap().clone()
+}
+
+/// Sets the proxy settings entered on the login screen, or `None` to not use a proxy.
+pub fn set_login_proxy_settings(proxy_settings: Option<ProxySettings>) {
+    *LOGIN_PROXY_SETTINGS.lock().unwrap() = proxy_settings;
+}
+
+#[cfg(test)]
+mod tests_proxy_settings {
+    use super::*;
+
+    #[test]
+    fn cli_proxy_applies_to_all_requests() {
+        let settings = ProxySettings::from_cli_proxy("http://proxy.example.org:8080");
+        assert_eq!(settings.http.as_deref(), Some("http://proxy.example.org:8080"));
+        assert_eq!(settings.https.as_deref(), Some("http://proxy.example.org:8080"));
+        assert_eq!(settings.socks5, None);
+
+        let settings = ProxySettings::from_cli_proxy("socks5://127.0.0.1:1080");
+        assert!(settings.http.is_none() && settings.https.is_none());
+        assert_eq!(settings.socks5.as_deref(), Some("socks5://127.0.0.1:1080"));
+    }
+
+    #[test]
+    fn validates_proxy_urls() {
+        assert!(ProxySettings::default().validate().is_ok());
+        let valid = ProxySettings {
+            https: Some("http://proxy.example.org:3128".into()),
+            socks5: Some("socks5h://127.0.0.1:1080".into()),
+            username: Some("alice".into()),
+            password: Some("secret".into()),
+            no_proxy: "localhost, .example.org".into(),
+            ..Default::default()
+        };
+        assert!(valid.validate().is_ok());
+        assert!(valid.proxies().is_ok_and(|proxies| proxies.len() == 2));
+
+        for invalid in [
+            ProxySettings { http: Some("proxy.example.org".into()), ..Default::default() },
+            ProxySettings { socks5: Some("http://127.0.0.1:1080".into()), ..Default::default() },
+            ProxySettings { https: Some("http://proxy".into()), password: Some("secret".into()), ..Default::default() },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn password_is_not_serialized() {
+        let settings = ProxySettings {
+            http: Some("http://proxy.example.org:8080".into()),
+            username: Some("alice".into()),
+            password: Some("secret".into()),
+            ..Default::default()
+        };
+        let serialized = serde_json::to_string(&settings).unwrap();
+        assert!(!serialized.contains("secret"));
+        let deserialized: ProxySettings = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, ProxySettings { password: None, ..settings });
+    }
+}
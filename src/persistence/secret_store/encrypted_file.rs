@@ -0,0 +1,197 @@
+//! A secret store that keeps each secret in a passphrase-encrypted file.
+//!
+//! This is the fallback used when the platform's native credential storage is unavailable.
+//! The encryption is only as strong as the secrecy of the passphrase, so if the passphrase
+//! is stored alongside these files (see `encrypted_file_passphrase()`), it is merely obfuscation.
+//! Each secret is encrypted with XChaCha20-Poly1305 using a key derived from
+//! the passphrase and a random per-file salt via Argon2id.
+//!
+//! The file format is: `MAGIC || salt (16 bytes) || nonce (24 bytes) || ciphertext`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use argon2::Argon2;
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+
+use super::{SecretStore, SecretStoreKind};
+
+/// Identifies an encrypted secret file and the version of its format.
+const MAGIC: &[u8; 5] = b"RBXS\x01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const KEY_LEN: usize = 32;
+
+/// Stores secrets as passphrase-encrypted files within a directory.
+#[derive(Clone)]
+pub struct EncryptedFileStore {
+    dir: PathBuf,
+    passphrase: String,
+}
+
+impl EncryptedFileStore {
+    /// Returns a store that keeps its files in `dir`, encrypted with the given `passphrase`.
+    pub fn new(dir: PathBuf, passphrase: String) -> Self {
+        Self { dir, passphrase }
+    }
+
+    /// Returns the path of the file that holds the secret for the given `key`.
+    ///
+    /// Keys are hex-encoded so that any key maps to a distinct, valid file name.
+    fn path_for(&self, key: &str) -> PathBuf {
+        let file_name: String = key.bytes().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(file_name).with_extension("secret")
+    }
+
+    fn cipher(&self, salt: &[u8]) -> anyhow::Result<XChaCha20Poly1305> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| anyhow!("Failed to derive encryption key: {e}"))?;
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.cipher(&salt)?
+            .encrypt(XNonce::from_slice(&nonce), plaintext)
+            .map_err(|_| anyhow!("Failed to encrypt secret"))?;
+
+        let mut contents = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        contents.extend_from_slice(MAGIC);
+        contents.extend_from_slice(&salt);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+        Ok(contents)
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let Some(rest) = contents.strip_prefix(MAGIC.as_slice()) else {
+            bail!("Unrecognized secret file format");
+        };
+        if rest.len() < SALT_LEN + NONCE_LEN {
+            bail!("Secret file is truncated");
+        }
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        self.cipher(salt)?
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("Failed to decrypt secret; the passphrase may be incorrect"))
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn kind(&self) -> SecretStoreKind {
+        SecretStoreKind::EncryptedFile
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let contents = match std::fs::read(self.path_for(key)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let plaintext = self.decrypt(&contents)?;
+        Ok(Some(String::from_utf8(plaintext)?))
+    }
+
+    fn set(&self, key: &str, secret: &str) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let contents = self.encrypt(secret.as_bytes())?;
+        write_private_file(&self.path_for(key), &contents)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match std::fs::remove_file(self.path_for(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes the given `contents` to a file that only the current user can read (on Unix).
+pub(super) fn write_private_file(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    use std::io::Write;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)] {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests_encrypted_file_store {
+    use super::*;
+
+    /// Returns a store in a fresh temporary directory that is unique to the given test.
+    pub(crate) fn temp_store(test_name: &str, passphrase: &str) -> EncryptedFileStore {
+        let dir = std::env::temp_dir()
+            .join(format!("robrix_test_secrets_{}_{test_name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        EncryptedFileStore::new(dir, passphrase.to_owned())
+    }
+
+    #[test]
+    fn set_get_and_delete() {
+        let store = temp_store("set_get_and_delete", "correct horse");
+        assert_eq!(store.get("@alice:example.org/session").unwrap(), None);
+
+        store.set("@alice:example.org/session", "s3cret").unwrap();
+        assert_eq!(store.get("@alice:example.org/session").unwrap().as_deref(), Some("s3cret"));
+
+        store.set("@alice:example.org/session", "replaced").unwrap();
+        assert_eq!(store.get("@alice:example.org/session").unwrap().as_deref(), Some("replaced"));
+
+        store.delete("@alice:example.org/session").unwrap();
+        assert_eq!(store.get("@alice:example.org/session").unwrap(), None);
+        // Deleting a missing secret is not an error.
+        store.delete("@alice:example.org/session").unwrap();
+    }
+
+    #[test]
+    fn secret_is_not_stored_in_plaintext() {
+        let store = temp_store("not_plaintext", "correct horse");
+        store.set("key", "very-recognizable-secret").unwrap();
+        let contents = std::fs::read(store.path_for("key")).unwrap();
+        assert!(contents.starts_with(MAGIC));
+        assert!(!contents.windows(b"very-recognizable-secret".len()).any(|w| w == b"very-recognizable-secret"));
+    }
+
+    #[test]
+    fn wrong_passphrase_fails_to_decrypt() {
+        let store = temp_store("wrong_passphrase", "correct horse");
+        store.set("key", "s3cret").unwrap();
+        let wrong = EncryptedFileStore::new(store.dir.clone(), String::from("battery staple"));
+        assert!(wrong.get("key").is_err());
+    }
+
+    #[test]
+    fn tampered_file_fails_to_decrypt() {
+        let store = temp_store("tampered", "correct horse");
+        store.set("key", "s3cret").unwrap();
+        let path = store.path_for("key");
+        let mut contents = std::fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() ^= 0xFF;
+        std::fs::write(&path, contents).unwrap();
+        assert!(store.get("key").is_err());
+    }
+
+    #[test]
+    fn distinct_keys_use_distinct_files() {
+        let store = temp_store("distinct_keys", "correct horse");
+        store.set("a/b", "first").unwrap();
+        store.set("a_b", "second").unwrap();
+        assert_eq!(store.get("a/b").unwrap().as_deref(), Some("first"));
+        assert_eq!(store.get("a_b").unwrap().as_deref(), Some("second"));
+    }
+}
+
+#[cfg(test)]
+pub(crate) use tests_encrypted_file_store::temp_store;
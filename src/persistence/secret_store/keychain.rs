@@ -0,0 +1,50 @@
+//! A secret store backed by the platform's native credential storage.
+
+use anyhow::anyhow;
+
+use super::{SecretStore, SecretStoreKind};
+
+/// The service name under which all of Robrix's secrets are stored.
+const SERVICE_NAME: &str = "org.robius.robrix";
+
+/// Stores secrets in the Secret Service (Linux), Keychain (macOS/iOS),
+/// or Credential Manager (Windows).
+pub struct KeychainStore;
+
+impl KeychainStore {
+    /// Returns a new keychain store, if the platform's credential storage is reachable.
+    pub fn new() -> anyhow::Result<Self> {
+        // Probe the credential storage, since it may be missing or locked,
+        // e.g., on a Linux system without a Secret Service provider running.
+        match keyring::Entry::new(SERVICE_NAME, "availability_probe")?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(Self),
+            Err(e) => Err(anyhow!(e)),
+        }
+    }
+}
+
+impl SecretStore for KeychainStore {
+    fn kind(&self) -> SecretStoreKind {
+        SecretStoreKind::Keychain
+    }
+
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        match keyring::Entry::new(SERVICE_NAME, key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> anyhow::Result<()> {
+        keyring::Entry::new(SERVICE_NAME, key)?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        match keyring::Entry::new(SERVICE_NAME, key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Secure storage for secrets, such as access tokens and database passphrases.
+//!
+//! Secrets are stored in the platform's secure credential storage when it's available:
+//! * the freedesktop Secret Service on Linux,
+//! * the Keychain on macOS and iOS,
+//! * the Credential Manager on Windows.
+//!
+//! Otherwise, we fall back to storing each secret in a file that is encrypted
+//! with a key derived from a passphrase using Argon2.
+//! That fallback only truly protects secrets if the user provides the passphrase
+//! via the `ROBRIX_SECRETS_PASSPHRASE` environment variable;
+//! see [`encrypted_file_passphrase()`] for details.
+
+use std::{path::PathBuf, sync::Once};
+
+use makepad_widgets::{log, warning};
+use serde::{Deserialize, Serialize};
+
+use crate::app_data_dir;
+
+pub mod encrypted_file;
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "windows"))]
+pub mod keychain;
+
+use encrypted_file::EncryptedFileStore;
+
+/// The name of the directory (within the app data directory) that holds encrypted secret files.
+const SECRETS_DIR_NAME: &str = "secrets";
+
+/// The environment variable that can be used to specify the passphrase
+/// for the encrypted-file secret store.
+const PASSPHRASE_ENV_VAR: &str = "ROBRIX_SECRETS_PASSPHRASE";
+
+/// A storage backend for secrets, each of which is identified by a string key.
+pub trait SecretStore: Send + Sync {
+    /// Returns which kind of backend this is.
+    fn kind(&self) -> SecretStoreKind;
+
+    /// Returns the secret stored under the given `key`, or `None` if there isn't one.
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+
+    /// Stores the given `secret` under the given `key`, replacing any existing secret.
+    fn set(&self, key: &str, secret: &str) -> anyhow::Result<()>;
+
+    /// Deletes the secret stored under the given `key`, if any.
+    fn delete(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// The kinds of [`SecretStore`] backends.
+///
+/// This is persisted alongside data whose secrets are kept in a secret store,
+/// such that those secrets can be retrieved from the same backend later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SecretStoreKind {
+    /// The platform's native credential storage.
+    Keychain,
+    /// Passphrase-encrypted files in the app data directory.
+    EncryptedFile,
+}
+
+/// Opens the secret store of the given kind.
+pub fn open_store(kind: SecretStoreKind) -> anyhow::Result<Box<dyn SecretStore>> {
+    match kind {
+        #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "windows"))]
+        SecretStoreKind::Keychain => Ok(Box::new(keychain::KeychainStore::new()?)),
+        #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "windows")))]
+        SecretStoreKind::Keychain => anyhow::bail!("Secure credential storage is not supported on this platform"),
+        SecretStoreKind::EncryptedFile => Ok(Box::new(EncryptedFileStore::new(
+            secrets_dir(),
+            encrypted_file_passphrase()?,
+        ))),
+    }
+}
+
+/// Returns the preferred secret store for saving new secrets.
+///
+/// This is the platform's native credential storage if it's available,
+/// otherwise the encrypted-file fallback.
+pub fn default_store() -> anyhow::Result<Box<dyn SecretStore>> {
+    match open_store(SecretStoreKind::Keychain) {
+        Ok(store) => Ok(store),
+        Err(e) => {
+            warning!("Secure credential storage is unavailable ({e}); falling back to encrypted files.");
+            open_store(SecretStoreKind::EncryptedFile)
+        }
+    }
+}
+
+/// Returns the directory in which encrypted secret files are stored.
+fn secrets_dir() -> PathBuf {
+    app_data_dir().join(SECRETS_DIR_NAME)
+}
+
+/// Returns the passphrase used to encrypt secret files.
+///
+/// If the `ROBRIX_SECRETS_PASSPHRASE` environment variable is set, its value is used,
+/// in which case the secret files cannot be decrypted without knowing that passphrase.
+///
+/// Otherwise, a random passphrase is generated once and stored in plaintext
+/// in a file next to the encrypted secret files, readable only by the current user.
+/// **This is merely obfuscation, not encryption:** anyone who can read the secrets directory,
+/// e.g., any process running as the same user or a backup of the app data directory,
+/// can also read that passphrase and thus decrypt every secret.
+/// It only prevents secrets from appearing in plaintext if individual files are shared.
+fn encrypted_file_passphrase() -> anyhow::Result<String> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV_VAR) {
+        if !passphrase.is_empty() {
+            return Ok(passphrase);
+        }
+    }
+    let dir = secrets_dir();
+    let key_file = dir.join("local_passphrase");
+    static WARN_ONCE: Once = Once::new();
+    WARN_ONCE.call_once(|| warning!(
+        "Secure credential storage is unavailable and {PASSPHRASE_ENV_VAR} is not set, \
+        so secrets are only obfuscated with a passphrase stored in {}.",
+        key_file.display(),
+    ));
+    match std::fs::read_to_string(&key_file) {
+        Ok(passphrase) => return Ok(passphrase),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { }
+        Err(e) => return Err(e.into()),
+    }
+
+    let passphrase = crate::utils::random_alphanumeric_string(32);
+    std::fs::create_dir_all(&dir)?;
+    encrypted_file::write_private_file(&key_file, passphrase.as_bytes())?;
+    log!("Generated a new local passphrase for encrypted secret files.");
+    Ok(passphrase)
+}
@@ -0,0 +1,104 @@
+//! Preparing an image file to be uploaded as the current user's avatar.
+//!
+//! Avatars are only ever shown at small sizes, so large images are scaled down
+//! (preserving their aspect ratio) and re-encoded before being uploaded.
+
+use std::{io::Cursor, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageFormat};
+use mime::Mime;
+
+/// The maximum width and height of an uploaded avatar image, in pixels.
+pub const MAX_AVATAR_DIMENSION: u32 = 512;
+
+/// The maximum size of an image file that we'll attempt to load as an avatar.
+const MAX_AVATAR_FILE_SIZE: u64 = 20 * 1024 * 1024;
+
+/// The quality used when re-encoding an avatar image as a JPEG.
+const AVATAR_JPEG_QUALITY: u8 = 90;
+
+/// Loads the image file at the given `path` and prepares it to be uploaded as an avatar.
+///
+/// This blocks on file I/O and image processing, so it should be run on a blocking thread.
+pub fn load_avatar_image(path: &Path) -> Result<(Vec<u8>, Mime)> {
+    let metadata = std::fs::metadata(path)
+        .map_err(|e| anyhow!("Couldn't open \"{}\": {e}", path.display()))?;
+    if !metadata.is_file() {
+        bail!("\"{}\" is not a file.", path.display());
+    }
+    if metadata.len() > MAX_AVATAR_FILE_SIZE {
+        bail!(
+            "\"{}\" is too large to use as an avatar ({}).",
+            path.display(),
+            bytesize::ByteSize::b(metadata.len()),
+        );
+    }
+    let bytes = std::fs::read(path)
+        .map_err(|e| anyhow!("Couldn't read \"{}\": {e}", path.display()))?;
+    prepare_avatar_image(&bytes)
+}
+
+/// Decodes the given image data, scales it down to fit within [`MAX_AVATAR_DIMENSION`],
+/// and re-encodes it.
+///
+/// Images with transparency are encoded as PNG; all others are encoded as JPEG.
+/// Returns the encoded image data and its content type.
+pub fn prepare_avatar_image(bytes: &[u8]) -> Result<(Vec<u8>, Mime)> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| anyhow!("The selected file is not a supported image: {e}"))?;
+    let image = if image.width() > MAX_AVATAR_DIMENSION || image.height() > MAX_AVATAR_DIMENSION {
+        image.resize(MAX_AVATAR_DIMENSION, MAX_AVATAR_DIMENSION, FilterType::Lanczos3)
+    } else {
+        image
+    };
+    encode_avatar_image(&image)
+}
+
+fn encode_avatar_image(image: &DynamicImage) -> Result<(Vec<u8>, Mime)> {
+    let mut encoded = Cursor::new(Vec::new());
+    let content_type = if image.color().has_alpha() {
+        image.write_to(&mut encoded, ImageFormat::Png)?;
+        mime::IMAGE_PNG
+    } else {
+        let encoder = JpegEncoder::new_with_quality(&mut encoded, AVATAR_JPEG_QUALITY);
+        image.to_rgb8().write_with_encoder(encoder)?;
+        mime::IMAGE_JPEG
+    };
+    Ok((encoded.into_inner(), content_type))
+}
+
+#[cfg(test)]
+mod tests_avatar_upload {
+    use image::{GenericImageView, RgbImage, RgbaImage};
+
+    use super::*;
+
+    fn encode_png(image: DynamicImage) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn large_images_are_scaled_down() {
+        let png = encode_png(DynamicImage::ImageRgb8(RgbImage::new(1024, 768)));
+        let (data, content_type) = prepare_avatar_image(&png).unwrap();
+        assert_eq!(content_type, mime::IMAGE_JPEG);
+        let resized = image::load_from_memory(&data).unwrap();
+        assert_eq!(resized.dimensions(), (512, 384));
+    }
+
+    #[test]
+    fn small_transparent_images_keep_their_size() {
+        let png = encode_png(DynamicImage::ImageRgba8(RgbaImage::new(64, 48)));
+        let (data, content_type) = prepare_avatar_image(&png).unwrap();
+        assert_eq!(content_type, mime::IMAGE_PNG);
+        assert_eq!(image::load_from_memory(&data).unwrap().dimensions(), (64, 48));
+    }
+
+    #[test]
+    fn non_images_are_rejected() {
+        assert!(prepare_avatar_image(b"definitely not an image").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use makepad_widgets::Cx;
 
+pub mod avatar_upload;
+pub mod presence_cache;
 pub mod user_profile;
 pub mod user_profile_cache;
 
@@ -0,0 +1,228 @@
+//! A cache of other users' presence (online, unavailable, or offline) and status messages.
+//!
+//! Presence is received via `m.presence` events when using classic sync.
+//! Native sliding sync doesn't deliver presence, so in that case presence is fetched on demand
+//! and re-fetched once it becomes stale, backing off for users whose presence can't be fetched.
+
+use std::{cell::RefCell, collections::BTreeMap, sync::atomic::{AtomicBool, Ordering}, time::{Duration, Instant, SystemTime}};
+use crossbeam_queue::SegQueue;
+use makepad_widgets::{Cx, SignalToUI};
+use matrix_sdk::ruma::{presence::PresenceState, OwnedUserId, UserId};
+
+use crate::sliding_sync::{submit_async_request, MatrixRequest};
+
+/// How long a user's cached presence is considered up-to-date before it is re-fetched.
+const PRESENCE_REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+/// The longest time to wait before re-fetching a user's presence after repeated failures.
+const MAX_PRESENCE_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Whether presence is delivered via sync (i.e., classic sync),
+/// in which case it doesn't need to be fetched on demand.
+static IS_PRESENCE_SYNCED: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether presence is delivered via sync, which depends on the current sync mode.
+pub fn set_presence_synced(is_synced: bool) {
+    IS_PRESENCE_SYNCED.store(is_synced, Ordering::Relaxed);
+}
+
+thread_local! {
+    /// A cache of each user's presence, indexed by user ID.
+    ///
+    /// To be of any use, this cache must only be accessed by the main UI thread.
+    static PRESENCE_CACHE: RefCell<BTreeMap<OwnedUserId, PresenceCacheEntry>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// An entry in the presence cache.
+struct PresenceCacheEntry {
+    /// The user's presence, if it has been received.
+    presence: Option<UserPresence>,
+    /// When this user's presence was last requested or received.
+    updated_at: Instant,
+    /// How many times in a row fetching this user's presence has failed.
+    failed_fetches: u32,
+}
+impl PresenceCacheEntry {
+    /// Returns how long to wait after `updated_at` before re-fetching this user's presence,
+    /// which doubles with each failed fetch.
+    fn refresh_interval(&self) -> Duration {
+        PRESENCE_REFRESH_INTERVAL
+            .saturating_mul(2u32.saturating_pow(self.failed_fetches))
+            .min(MAX_PRESENCE_RETRY_INTERVAL)
+    }
+}
+
+/// A user's presence and custom status message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserPresence {
+    pub presence: PresenceState,
+    /// The user's custom status message, if any.
+    pub status_msg: Option<String>,
+    /// Whether the user is currently active, i.e., actively using one of their devices.
+    pub currently_active: bool,
+    /// When the user was last active, if known.
+    pub last_active: Option<SystemTime>,
+}
+impl UserPresence {
+    /// Creates a user's presence from the "last active ago" duration given by the homeserver.
+    pub fn new(
+        presence: PresenceState,
+        status_msg: Option<String>,
+        currently_active: Option<bool>,
+        last_active_ago: Option<Duration>,
+    ) -> Self {
+        Self {
+            presence,
+            status_msg: status_msg.filter(|s| !s.trim().is_empty()),
+            currently_active: currently_active.unwrap_or(false),
+            last_active: last_active_ago.and_then(|ago| SystemTime::now().checked_sub(ago)),
+        }
+    }
+
+    /// Returns a short displayable description of this presence,
+    /// e.g., "Online" or "Offline, last active 3 hours ago".
+    pub fn description(&self) -> String {
+        let state = match self.presence {
+            PresenceState::Online => "Online",
+            PresenceState::Unavailable => "Away",
+            _ => "Offline",
+        };
+        let last_active_ago = self.last_active
+            .and_then(|t| SystemTime::now().duration_since(t).ok());
+        match last_active_ago {
+            Some(ago) if !self.currently_active => format!("{state}, last active {}", last_active_text(ago)),
+            _ => state.to_string(),
+        }
+    }
+}
+
+/// Returns a human-readable description of how long ago something happened,
+/// e.g., "just now", "5 minutes ago", or "2 days ago".
+pub fn last_active_text(ago: Duration) -> String {
+    let plural = |n: u64, unit: &str| if n == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{n} {unit}s ago")
+    };
+    let minutes = ago.as_secs() / 60;
+    match minutes {
+        0 => String::from("just now"),
+        m if m < 60 => plural(m, "minute"),
+        m if m < 60 * 24 => plural(m / 60, "hour"),
+        m => plural(m / (60 * 24), "day"),
+    }
+}
+
+pub struct PresenceUpdate {
+    pub user_id: OwnedUserId,
+    pub presence: UserPresence,
+}
+
+/// The queue of presence updates waiting to be processed by the UI thread's event handler.
+static PENDING_PRESENCE_UPDATES: SegQueue<PresenceUpdate> = SegQueue::new();
+
+/// Enqueues a new presence update and signals the UI that an update is available.
+pub fn enqueue_presence_update(update: PresenceUpdate) {
+    PENDING_PRESENCE_UPDATES.push(update);
+    SignalToUI::set_ui_signal();
+}
+
+/// The queue of users whose presence failed to be fetched, waiting to be processed by the UI thread.
+static FAILED_PRESENCE_FETCHES: SegQueue<OwnedUserId> = SegQueue::new();
+
+/// Enqueues the given user whose presence failed to be fetched,
+/// such that fetching it again is backed off.
+pub fn enqueue_presence_fetch_failure(user_id: OwnedUserId) {
+    FAILED_PRESENCE_FETCHES.push(user_id);
+}
+
+/// Processes all pending presence updates in the queue.
+///
+/// This function requires passing in a reference to `Cx`,
+/// which isn't used, but acts as a guarantee that this function
+/// must only be called by the main UI thread.
+pub fn process_presence_updates(_cx: &mut Cx) {
+    PRESENCE_CACHE.with_borrow_mut(|cache| {
+        while let Some(update) = PENDING_PRESENCE_UPDATES.pop() {
+            cache.insert(update.user_id, PresenceCacheEntry {
+                presence: Some(update.presence),
+                updated_at: Instant::now(),
+                failed_fetches: 0,
+            });
+        }
+        while let Some(user_id) = FAILED_PRESENCE_FETCHES.pop() {
+            if let Some(entry) = cache.get_mut(&user_id) {
+                entry.failed_fetches = entry.failed_fetches.saturating_add(1);
+                entry.updated_at = Instant::now();
+            }
+        }
+    });
+}
+
+/// Returns the cached presence of the given user, if it exists,
+/// and submits a request to fetch it from the server if it isn't cached or is stale
+/// (unless presence is delivered via sync).
+///
+/// This function requires passing in a reference to `Cx`,
+/// which isn't used, but acts as a guarantee that this function
+/// must only be called by the main UI thread.
+pub fn get_or_fetch_presence(_cx: &mut Cx, user_id: &UserId) -> Option<UserPresence> {
+    PRESENCE_CACHE.with_borrow_mut(|cache| {
+        if IS_PRESENCE_SYNCED.load(Ordering::Relaxed) {
+            return cache.get(user_id).and_then(|entry| entry.presence.clone());
+        }
+        let now = Instant::now();
+        let entry = cache.entry(user_id.to_owned()).or_insert_with(|| {
+            submit_async_request(MatrixRequest::GetPresence { user_id: user_id.to_owned() });
+            PresenceCacheEntry { presence: None, updated_at: now, failed_fetches: 0 }
+        });
+        if now.duration_since(entry.updated_at) > entry.refresh_interval() {
+            submit_async_request(MatrixRequest::GetPresence { user_id: user_id.to_owned() });
+            entry.updated_at = now;
+        }
+        entry.presence.clone()
+    })
+}
+
+/// Clears all cached presence.
+///
+/// This function requires passing in a reference to `Cx`,
+/// which acts as a guarantee that this function must only be called by the main UI thread.
+pub fn clear_presence_cache(_cx: &mut Cx) {
+    PRESENCE_CACHE.with_borrow_mut(|cache| cache.clear());
+}
+
+#[cfg(test)]
+mod tests_presence_cache {
+    use super::*;
+
+    #[test]
+    fn formats_last_active_times() {
+        assert_eq!(last_active_text(Duration::from_secs(30)), "just now");
+        assert_eq!(last_active_text(Duration::from_secs(60)), "1 minute ago");
+        assert_eq!(last_active_text(Duration::from_secs(5 * 60 + 10)), "5 minutes ago");
+        assert_eq!(last_active_text(Duration::from_secs(3 * 60 * 60)), "3 hours ago");
+        assert_eq!(last_active_text(Duration::from_secs(49 * 60 * 60)), "2 days ago");
+    }
+
+    #[test]
+    fn backs_off_after_failed_fetches() {
+        let entry = |failed_fetches| PresenceCacheEntry { presence: None, updated_at: Instant::now(), failed_fetches };
+        assert_eq!(entry(0).refresh_interval(), PRESENCE_REFRESH_INTERVAL);
+        assert_eq!(entry(2).refresh_interval(), PRESENCE_REFRESH_INTERVAL * 4);
+        assert_eq!(entry(40).refresh_interval(), MAX_PRESENCE_RETRY_INTERVAL);
+    }
+
+    #[test]
+    fn describes_presence() {
+        let online = UserPresence::new(PresenceState::Online, None, Some(true), Some(Duration::from_secs(5)));
+        assert_eq!(online.description(), "Online");
+
+        let away = UserPresence::new(PresenceState::Unavailable, Some(String::from(" ")), None, Some(Duration::from_secs(600)));
+        assert_eq!(away.description(), "Away, last active 10 minutes ago");
+        assert_eq!(away.status_msg, None);
+
+        let offline = UserPresence::new(PresenceState::Offline, Some(String::from("On vacation")), None, None);
+        assert_eq!(offline.description(), "Offline");
+        assert_eq!(offline.status_msg.as_deref(), Some("On vacation"));
+    }
+}
@@ -2,10 +2,10 @@ use std::{borrow::Cow, ops::{Deref, DerefMut}, sync::Arc};
 use makepad_widgets::*;
 use matrix_sdk::{room::{RoomMember, RoomMemberRole}, ruma::{events::room::member::MembershipState, OwnedMxcUri, OwnedRoomId, OwnedUserId}};
 use crate::{
-    avatar_cache::{self, AvatarCacheEntry}, shared::{avatar::AvatarWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}}, sliding_sync::{current_user_id, is_user_ignored, submit_async_request, MatrixRequest}, utils
+    avatar_cache::{self, AvatarCacheEntry}, shared::{avatar::AvatarWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, presence_indicator::PresenceIndicatorWidgetExt}, sliding_sync::{current_user_id, is_user_ignored, submit_async_request, MatrixRequest}, utils, verification::VerificationTarget
 };
 
-use super::user_profile_cache::{self, get_user_profile_and_room_member};
+use super::{presence_cache, user_profile_cache::{self, get_user_profile_and_room_member}};
 
 /// The currently-known state of a user's avatar.
 #[derive(Clone)]
@@ -110,6 +110,7 @@ live_design! {
     use crate::shared::styles::*;
     use crate::shared::avatar::*;
     use crate::shared::icon_button::*;
+    use crate::shared::presence_indicator::*;
 
     use link::tsp_link::TspVerifyUser;
 
@@ -136,13 +137,25 @@ live_design! {
             spacing: 3
             flow: Down
 
-            avatar = <Avatar> {
-                width: 150,
-                height: 150,
+            <View> {
+                width: Fit, height: Fit
+                flow: Overlay
+                align: {x: 1.0, y: 1.0}
                 margin: 10.0,
-                text_view = { text = { draw_text: {
-                    text_style: { font_size: 40.0 }
-                }}}
+
+                avatar = <Avatar> {
+                    width: 150,
+                    height: 150,
+                    text_view = { text = { draw_text: {
+                        text_style: { font_size: 40.0 }
+                    }}}
+                }
+
+                presence_indicator = <PresenceIndicator> {
+                    width: 30, height: 30,
+                    margin: {right: 8, bottom: 8}
+                    draw_bg: { border_size: 3.0 }
+                }
             }
 
             user_name = <Label> {
@@ -164,6 +177,27 @@ live_design! {
                 }
                 text: "User ID"
             }
+
+            presence_label = <Label> {
+                width: Fit, height: Fit
+                margin: {top: 5}
+                draw_text: {
+                    wrap: Word,
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <MESSAGE_TEXT_STYLE>{ font_size: 10 },
+                }
+                text: ""
+            }
+
+            status_message_label = <Label> {
+                width: Fit, height: Fit
+                draw_text: {
+                    wrap: Word,
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <THEME_FONT_ITALIC>{ font_size: 10 },
+                }
+                text: ""
+            }
         }
 
         <LineH> { padding: 15 }
@@ -260,6 +294,19 @@ live_design! {
                 text: "Copy Link to User"
             }
 
+            verify_user_button = <RobrixIconButton> {
+                padding: {top: 10, bottom: 10, left: 12, right: 15}
+                margin: 0,
+                draw_bg: {
+                    color: (COLOR_SECONDARY)
+                }
+                draw_icon: {
+                    svg_file: (ICON_CHECKMARK)
+                }
+                icon_walk: {width: 16, height: 16, margin: {left: -1, right: -1} }
+                text: "Verify User"
+            }
+
             jump_to_read_receipt_button = <RobrixIconButton> {
                 enabled: false, // TODO: support this button
                 padding: {top: 10, bottom: 10, left: 12, right: 15}
@@ -514,9 +561,11 @@ impl Widget for UserProfileSlidingPane {
         if let Event::Signal = event {
             user_profile_cache::process_user_profile_updates(cx);
             avatar_cache::process_avatar_updates(cx);
+            presence_cache::process_presence_updates(cx);
 
             // Re-fetch the currently-displayed user profile info from the cache in case it was updated.
-            let mut redraw_this_pane = false;
+            // The user's presence is re-fetched from the presence cache when this pane is redrawn.
+            let mut redraw_this_pane = self.info.is_some();
             if let Some(our_info) = self.info.as_mut() {
                 if let (Some(new_profile), room_member) = get_user_profile_and_room_member(
                     cx,
@@ -573,6 +622,14 @@ impl Widget for UserProfileSlidingPane {
                 });
             }
 
+            // Verification requests to other users are sent in the DM room shared with them.
+            if self.button(id!(verify_user_button)).clicked(actions) {
+                submit_async_request(MatrixRequest::RequestVerification {
+                    target: VerificationTarget::User(info.user_id.clone()),
+                });
+                log!("Submitting request to verify user {}.", info.user_id);
+            }
+
             // TODO: implement the third button: `jump_to_read_receipt_button`,
             //       which involves calling `Timeline::latest_user_read_receipt()`
             //       or `Room::load_user_receipt()`, which are async functions.
@@ -605,6 +662,18 @@ impl Widget for UserProfileSlidingPane {
         self.label(id!(user_name)).set_text(cx, info.displayable_name());
         self.label(id!(user_id)).set_text(cx, info.user_id.as_str());
 
+        // Show the user's presence and status message, if known.
+        self.view.presence_indicator(id!(presence_indicator)).set_user(cx, Some(&info.user_id));
+        let presence = presence_cache::get_or_fetch_presence(cx, &info.user_id);
+        self.label(id!(presence_label)).set_text(
+            cx,
+            &presence.as_ref().map(|p| p.description()).unwrap_or_default(),
+        );
+        self.label(id!(status_message_label)).set_text(
+            cx,
+            presence.as_ref().and_then(|p| p.status_msg.as_deref()).unwrap_or_default(),
+        );
+
         // Set the avatar image, using the user name as a fallback.
         let avatar_ref = self.avatar(id!(avatar));
         info.avatar_state
@@ -622,6 +691,8 @@ impl Widget for UserProfileSlidingPane {
         //    since you cannot direct message yourself.
         // * `copy_link_to_user_button` is always enabled with the same text.
         // * `jump_to_read_receipt_button` is always enabled with the same text.
+        // * `verify_user_button` is disabled if the user is the same as the account user,
+        //    since the current session is verified from the Sessions settings instead.
         // * `ignore_user_button` is disabled if the user is not a member of the room,
         //    or if the user is the same as the account user, since you cannot ignore yourself.
         //    * The button text changes to "Unignore" if the user is already ignored.
@@ -632,6 +703,8 @@ impl Widget for UserProfileSlidingPane {
         // TODO: uncomment the line below once the `direct_message_button` logic is implemented.
         // self.button(id!(direct_message_button)).set_enabled(!is_pane_showing_current_account);
 
+        self.button(id!(verify_user_button)).set_enabled(cx, !is_pane_showing_current_account);
+
         let ignore_user_button = self.button(id!(ignore_user_button));
         ignore_user_button.set_enabled(cx, !is_pane_showing_current_account && info.room_member.is_some());
         // Unfortunately the Matrix SDK's RoomMember type does not properly track
@@ -715,6 +788,7 @@ impl UserProfileSlidingPane {
         self.view.button(id!(close_button)).reset_hover(cx);
         self.view.button(id!(direct_message_button)).reset_hover(cx);
         self.view.button(id!(copy_link_to_user_button)).reset_hover(cx);
+        self.view.button(id!(verify_user_button)).reset_hover(cx);
         self.view.button(id!(jump_to_read_receipt_button)).reset_hover(cx);
         self.view.button(id!(ignore_user_button)).reset_hover(cx);
         self.redraw(cx);
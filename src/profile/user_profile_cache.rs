@@ -266,6 +266,18 @@ pub fn get_user_profile_and_room_member(
     )
 }
 
+/// Removes the given user's profile and room membership info from the cache,
+/// such that it will be re-fetched from the server the next time it's requested.
+///
+/// This function requires passing in a reference to `Cx`,
+/// which isn't used, but acts as a guarantee that this function
+/// must only be called by the main UI thread.
+pub fn invalidate_user_profile(_cx: &mut Cx, user_id: &UserId) {
+    USER_PROFILE_CACHE.with_borrow_mut(|cache| {
+        cache.remove(user_id);
+    });
+}
+
 /// Clears cached user profile.
 /// This function requires passing in a reference to `Cx`,
 /// which acts as a guarantee that these thread-local caches are cleared on the main UI thread, 
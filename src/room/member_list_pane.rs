@@ -0,0 +1,766 @@
+//! A pane that slides in from the right side of a room screen
+//! and lists all members of that room, grouped by their role.
+//!
+//! Joined members are grouped into admins, moderators, and regular members,
+//! followed by invited and banned users. Each member is shown with their avatar,
+//! display name, and presence; clicking on a member opens their user profile.
+//!
+//! Rooms can have many thousands of members, so only the members that are visible
+//! in the list are drawn, and their avatars and presence are only fetched once visible.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use makepad_widgets::*;
+use matrix_sdk::{
+    room::{RoomMember, RoomMemberRole},
+    ruma::{events::room::member::MembershipState, OwnedRoomId, RoomId, UserId},
+    RoomMemberships,
+};
+
+use crate::{
+    avatar_cache::{self, AvatarCacheEntry},
+    profile::user_profile::{AvatarState, ShowUserProfileAction, UserProfile, UserProfileAndRoomId},
+    shared::{avatar::AvatarWidgetExt, presence_indicator::PresenceIndicatorWidgetExt},
+    utils,
+};
+
+/// The memberships of the users that are shown in the member list.
+pub const DISPLAYED_MEMBERSHIPS: RoomMemberships = RoomMemberships::JOIN
+    .union(RoomMemberships::INVITE)
+    .union(RoomMemberships::BAN);
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::avatar::*;
+    use crate::shared::icon_button::*;
+    use crate::shared::presence_indicator::*;
+
+    ICON_SEARCH = dep("crate://self/resources/icons/search.svg")
+
+    MEMBER_LIST_PANE_WIDTH = 320
+
+    MemberListSectionHeader = <View> {
+        width: Fill, height: Fit
+        padding: {left: 15, right: 15, top: 12, bottom: 4}
+
+        label = <Label> {
+            width: Fill, height: Fit
+            flow: Right, // do not wrap
+            draw_text: {
+                wrap: Ellipsis,
+                color: (TIMESTAMP_TEXT_COLOR),
+                text_style: <USERNAME_TEXT_STYLE>{ font_size: 9 },
+            }
+            text: ""
+        }
+    }
+
+    pub RoomMemberListItem = {{RoomMemberListItem}} {
+        width: Fill, height: Fit
+        flow: Right,
+        padding: {left: 15, right: 15, top: 6, bottom: 6}
+        spacing: 10,
+        align: {y: 0.5}
+        cursor: Hand,
+
+        <View> {
+            width: Fit, height: Fit
+            flow: Overlay
+            align: {x: 1.0, y: 1.0}
+
+            avatar = <Avatar> {
+                width: 36, height: 36
+                text_view = { text = { draw_text: {
+                    text_style: { font_size: 14.0 }
+                }}}
+            }
+
+            presence_indicator = <PresenceIndicator> {
+                width: 11, height: 11
+            }
+        }
+
+        <View> {
+            width: Fill, height: Fit
+            flow: Down,
+            spacing: 2,
+
+            display_name = <Label> {
+                width: Fill, height: Fit
+                flow: Right, // do not wrap
+                draw_text: {
+                    wrap: Ellipsis,
+                    color: (MESSAGE_TEXT_COLOR),
+                    text_style: <USERNAME_TEXT_STYLE>{ font_size: 10 },
+                }
+                text: ""
+            }
+
+            user_id = <Label> {
+                width: Fill, height: Fit
+                flow: Right, // do not wrap
+                draw_text: {
+                    wrap: Ellipsis,
+                    color: (TIMESTAMP_TEXT_COLOR),
+                    text_style: <REGULAR_TEXT>{ font_size: 8.5 },
+                }
+                text: ""
+            }
+        }
+    }
+
+    MemberSearchBar = <RoundedView> {
+        width: Fill,
+        height: 35,
+        margin: {left: 10, right: 10, bottom: 5}
+        padding: {top: 3, bottom: 3, left: 10, right: 10}
+        spacing: 4,
+        align: {x: 0.0, y: 0.5},
+
+        show_bg: true,
+        draw_bg: {
+            color: (COLOR_PRIMARY),
+            border_radius: 4.0,
+            border_color: (COLOR_SECONDARY),
+            border_size: 1.0,
+        }
+
+        <Icon> {
+            draw_icon: {
+                svg_file: (ICON_SEARCH),
+                fn get_color(self) -> vec4 {
+                    return (COLOR_TEXT_INPUT_IDLE);
+                }
+            }
+            icon_walk: {width: 14, height: Fit}
+        }
+
+        search_input = <RobrixTextInput> {
+            width: Fill,
+            height: Fit,
+            flow: Right, // do not wrap
+
+            empty_text: "Search members..."
+
+            draw_text: {
+                text_style: { font_size: 10 },
+            }
+        }
+
+        clear_button = <RobrixIconButton> {
+            visible: false,
+            padding: {top: 6, bottom: 6, left: 9, right: 9},
+            spacing: 0,
+            align: {x: 0.5, y: 0.5}
+            draw_bg: {
+                color: (COLOR_SECONDARY)
+            }
+            draw_icon: {
+                svg_file: (ICON_CLOSE),
+                color: (COLOR_TEXT_INPUT_IDLE)
+            }
+            icon_walk: {width: Fit, height: 10, margin: 0}
+        }
+    }
+
+    pub MemberListPane = {{MemberListPane}} {
+        visible: false,
+        flow: Overlay,
+        width: Fill,
+        height: Fill,
+        align: {x: 1.0, y: 0}
+
+        bg_view = <View> {
+            width: Fill
+            height: Fill
+            visible: false,
+            show_bg: true
+            draw_bg: {
+                uniform bg_color: #00000055
+                fn pixel(self) -> vec4 {
+                    return self.bg_color;
+                }
+            }
+        }
+
+        main_content = <View> {
+            width: (MEMBER_LIST_PANE_WIDTH),
+            height: Fill
+            flow: Down,
+            cursor: Default,
+
+            show_bg: true,
+            draw_bg: {
+                color: (COLOR_PRIMARY)
+            }
+
+            header = <View> {
+                width: Fill, height: Fit
+                flow: Right,
+                padding: {left: 15, right: 7, top: 7, bottom: 3}
+                align: {y: 0.5}
+
+                title = <Label> {
+                    width: Fill, height: Fit
+                    flow: Right, // do not wrap
+                    draw_text: {
+                        wrap: Ellipsis,
+                        color: #000,
+                        text_style: <USERNAME_TEXT_STYLE>{ font_size: 12 },
+                    }
+                    text: "Members"
+                }
+
+                close_button = <RobrixIconButton> {
+                    width: Fit,
+                    height: Fit,
+                    spacing: 0,
+                    padding: 12,
+
+                    draw_bg: {
+                        color: (COLOR_SECONDARY)
+                    }
+                    draw_icon: {
+                        svg_file: (ICON_CLOSE),
+                        fn get_color(self) -> vec4 {
+                            return #x0;
+                        }
+                    }
+                    icon_walk: {width: 12, height: 12}
+                }
+            }
+
+            search_bar = <MemberSearchBar> { }
+
+            member_list = <PortalList> {
+                keep_invisible: false,
+                auto_tail: false,
+                width: Fill, height: Fill
+                flow: Down, spacing: 0.0
+
+                section_header = <MemberListSectionHeader> {}
+                member_item = <RoomMemberListItem> {}
+                status_label = <View> {
+                    width: Fill, height: Fit,
+                    align: { x: 0.5, y: 0.5 }
+                    padding: 15.0,
+
+                    label = <Label> {
+                        width: Fill,
+                        align: { x: 0.5, y: 0.5 }
+                        draw_text: {
+                            wrap: Word,
+                            color: (MESSAGE_TEXT_COLOR),
+                            text_style: <REGULAR_TEXT>{}
+                        }
+                        text: ""
+                    }
+                }
+            }
+        }
+
+        animator: {
+            panel = {
+                default: hide,
+                show = {
+                    redraw: true,
+                    from: {all: Forward {duration: 0.4}}
+                    ease: ExpDecay {d1: 0.80, d2: 0.97}
+                    apply: {
+                        main_content = { margin: {right: 0} },
+                        bg_view = {
+                            draw_bg: { bg_color: #00000055 }
+                        }
+                    }
+                }
+                hide = {
+                    redraw: true,
+                    from: {all: Forward {duration: 0.5}}
+                    ease: ExpDecay {d1: 0.80, d2: 0.97}
+                    apply: {
+                        main_content = { margin: {right: -320} },
+                        bg_view = {
+                            draw_bg: { bg_color: #x00000000 }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/// The sections of the member list, in the order they are displayed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum MemberCategory {
+    Admins,
+    Moderators,
+    Members,
+    Invited,
+    Banned,
+}
+impl MemberCategory {
+    /// Returns the section that a member with the given membership and role belongs in,
+    /// or `None` if they shouldn't be shown in the member list.
+    fn of(membership: &MembershipState, role: RoomMemberRole) -> Option<Self> {
+        match membership {
+            MembershipState::Join => Some(match role {
+                RoomMemberRole::Creator | RoomMemberRole::Administrator => Self::Admins,
+                RoomMemberRole::Moderator => Self::Moderators,
+                RoomMemberRole::User => Self::Members,
+            }),
+            MembershipState::Invite => Some(Self::Invited),
+            MembershipState::Ban => Some(Self::Banned),
+            _ => None,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            Self::Admins => "Admins",
+            Self::Moderators => "Moderators",
+            Self::Members => "Members",
+            Self::Invited => "Invited",
+            Self::Banned => "Banned",
+        }
+    }
+}
+
+/// A member in one of the member list's sections,
+/// along with the lowercased strings used to sort and search the members.
+struct SectionMember {
+    /// The member's index in the list of all room members.
+    index: usize,
+    /// The member's display name, or their user ID if they don't have one.
+    name_lowercase: String,
+    user_id_lowercase: String,
+}
+impl SectionMember {
+    fn new(index: usize, display_name: Option<&str>, user_id: &UserId) -> Self {
+        Self {
+            index,
+            name_lowercase: display_name.unwrap_or(user_id.as_str()).to_lowercase(),
+            user_id_lowercase: user_id.as_str().to_lowercase(),
+        }
+    }
+
+    /// Returns whether this member matches the given (already lowercased) search query,
+    /// based on their display name or user ID.
+    fn matches_search(&self, query_lowercase: &str) -> bool {
+        query_lowercase.is_empty()
+            || self.name_lowercase.contains(query_lowercase)
+            || self.user_id_lowercase.contains(query_lowercase)
+    }
+}
+
+/// An entry drawn in the member list.
+enum MemberListEntry {
+    /// The header at the top of a section, along with the number of members in that section.
+    Header { category: MemberCategory, count: usize },
+    /// A member, given by their index in the list of all room members.
+    Member(usize),
+}
+
+
+/// A single member shown in the member list.
+#[derive(Live, LiveHook, Widget)]
+pub struct RoomMemberListItem {
+    #[deref] view: View,
+    #[rust] info: Option<UserProfileAndRoomId>,
+}
+
+impl Widget for RoomMemberListItem {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        // Handle hits on this view as a whole before passing the event to the inner view.
+        if let Hit::FingerUp(fue) = event.hits(cx, self.view.area()) {
+            if fue.is_over && fue.is_primary_hit() && fue.was_tap() {
+                if let Some(info) = self.info.clone() {
+                    cx.widget_action(
+                        self.widget_uid(),
+                        &scope.path,
+                        ShowUserProfileAction::ShowUserProfile(info),
+                    );
+                }
+            }
+        }
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+impl RoomMemberListItem {
+    /// Populates this item with the given room member's details.
+    fn set_member(&mut self, cx: &mut Cx, room_id: &RoomId, member: &RoomMember) {
+        let user_id = member.user_id();
+        let display_name = member.display_name();
+        let displayable_name = display_name.unwrap_or(user_id.as_str());
+        self.label(id!(display_name)).set_text(cx, displayable_name);
+        self.label(id!(user_id)).set_text(cx, user_id.as_str());
+
+        // Presence is only relevant for users who have actually joined the room.
+        self.view.presence_indicator(id!(presence_indicator)).set_user(
+            cx,
+            (member.membership() == &MembershipState::Join).then_some(user_id),
+        );
+
+        // Set the avatar image, using the user name as a fallback.
+        let avatar_ref = self.avatar(id!(avatar));
+        member.avatar_url()
+            .and_then(|uri| match avatar_cache::get_or_fetch_avatar(cx, uri.to_owned()) {
+                AvatarCacheEntry::Loaded(data) => Some(data),
+                _ => None,
+            })
+            .and_then(|data| avatar_ref.show_image(cx, None, |cx, img| utils::load_png_or_jpg(&img, cx, &data)).ok())
+            .unwrap_or_else(|| avatar_ref.show_text(cx, None, None, displayable_name));
+
+        self.info = Some(UserProfileAndRoomId {
+            user_profile: UserProfile {
+                user_id: user_id.to_owned(),
+                username: display_name.map(ToOwned::to_owned),
+                avatar_state: AvatarState::Known(member.avatar_url().map(ToOwned::to_owned)),
+            },
+            room_id: room_id.to_owned(),
+        });
+    }
+}
+
+
+/// A sliding pane that shows all members of a room.
+#[derive(Live, LiveHook, Widget)]
+pub struct MemberListPane {
+    #[deref] view: View,
+    #[animator] animator: Animator,
+
+    /// The room whose members are shown in this pane.
+    #[rust] room_id: Option<OwnedRoomId>,
+    /// All members of the room, or `None` if they haven't been fetched yet.
+    #[rust] members: Option<Arc<Vec<RoomMember>>>,
+    /// The current search query entered by the user.
+    #[rust] search_query: String,
+    /// The members grouped into sections, each sorted by name, which are derived from `members`.
+    #[rust] sections: Vec<(MemberCategory, Vec<SectionMember>)>,
+    /// Whether `sections` must be rebuilt because `members` has changed.
+    #[rust] sections_outdated: bool,
+    /// The entries displayed in the list, which are derived from `sections` and `search_query`.
+    #[rust] entries: Vec<MemberListEntry>,
+    /// Whether `entries` must be recalculated before the next draw.
+    #[rust] entries_outdated: bool,
+    #[rust] is_animating_out: bool,
+}
+
+impl Widget for MemberListPane {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        if !self.visible { return; }
+
+        let animator_action = self.animator_handle_event(cx, event);
+        if animator_action.must_redraw() {
+            self.redraw(cx);
+        }
+        // If the animator is in the `hide` state and has finished animating out,
+        // that means it has fully animated off-screen and can be set to invisible.
+        if self.animator_in_state(cx, id!(panel.hide)) {
+            match (self.is_animating_out, animator_action.is_animating()) {
+                (true, false) => {
+                    self.visible = false;
+                    self.is_animating_out = false;
+                    cx.revert_key_focus();
+                    self.view(id!(bg_view)).set_visible(cx, false);
+                    self.redraw(cx);
+                    return;
+                }
+                (false, true) => {
+                    self.is_animating_out = true;
+                }
+                _ => { }
+            }
+        }
+
+        let area = self.view.area();
+
+        // Close the pane if:
+        // 1. The close button is clicked,
+        // 2. The back navigational gesture/action occurs (e.g., Back on Android),
+        // 3. The escape key is pressed if this pane has key focus,
+        // 4. The back mouse button is clicked within this view,
+        // 5. The user clicks/touches outside the main_content view area.
+        let close_pane = {
+            matches!(
+                event,
+                Event::Actions(actions) if self.button(id!(close_button)).clicked(actions)
+            )
+            || event.back_pressed()
+            || match event.hits_with_capture_overload(cx, area, true) {
+                Hit::KeyUp(key) => key.key_code == KeyCode::Escape,
+                Hit::FingerDown(fde) => {
+                    // Don't steal key focus away from the search input.
+                    if !self.view(id!(search_bar)).area().rect(cx).contains(fde.abs) {
+                        cx.set_key_focus(area);
+                    }
+                    false
+                }
+                Hit::FingerUp(fue) if fue.is_over => {
+                    fue.mouse_button().is_some_and(|b| b.is_back())
+                    || !self.view(id!(main_content)).area().rect(cx).contains(fue.abs)
+                }
+                _ => false,
+            }
+        };
+        if close_pane {
+            self.animator_play(cx, id!(panel.hide));
+            self.redraw(cx);
+            return;
+        }
+
+        // A UI Signal may indicate that a member's avatar has been fetched.
+        // Presence indicators handle updates to members' presence themselves.
+        if let Event::Signal = event {
+            self.redraw(cx);
+        }
+
+        if let Event::Actions(actions) = event {
+            let search_input = self.view.text_input(id!(search_input));
+            let clear_button = self.view.button(id!(clear_button));
+            if let Some(query) = search_input.changed(actions) {
+                clear_button.set_visible(cx, !query.is_empty());
+                self.set_search_query(cx, query);
+            }
+            if clear_button.clicked(actions) {
+                search_input.set_text(cx, "");
+                clear_button.set_visible(cx, false);
+                cx.set_key_focus(search_input.area());
+                self.set_search_query(cx, String::new());
+            }
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        if self.entries_outdated {
+            self.update_entries();
+        }
+        let status_text = if self.members.is_none() {
+            "Loading members..."
+        } else if self.entries.is_empty() && !self.search_query.is_empty() {
+            "No members match your search."
+        } else if self.entries.is_empty() {
+            "This room has no members."
+        } else {
+            ""
+        };
+        // Add one for the status label.
+        let total_count = self.entries.len() + 1;
+
+        while let Some(widget_to_draw) = self.view.draw_walk(cx, scope, walk).step() {
+            // We only care about drawing the portal list.
+            let portal_list_ref = widget_to_draw.as_portal_list();
+            let Some(mut list) = portal_list_ref.borrow_mut() else { continue };
+
+            list.set_item_range(cx, 0, total_count);
+
+            while let Some(portal_list_index) = list.next_visible_item(cx) {
+                let mut scope = Scope::empty();
+                match self.entries.get(portal_list_index) {
+                    Some(MemberListEntry::Header { category, count }) => {
+                        let item = list.item(cx, portal_list_index, live_id!(section_header));
+                        item.label(id!(label)).set_text(cx, &format!("{} ({count})", category.title()));
+                        item.draw_all(cx, &mut scope);
+                    }
+                    Some(MemberListEntry::Member(member_index)) => {
+                        let item = list.item(cx, portal_list_index, live_id!(member_item));
+                        if let (Some(room_id), Some(member)) = (
+                            self.room_id.as_deref(),
+                            self.members.as_ref().and_then(|m| m.get(*member_index)),
+                        ) {
+                            if let Some(mut inner) = item.as_room_member_list_item().borrow_mut() {
+                                inner.set_member(cx, room_id, member);
+                            }
+                        }
+                        item.draw_all(cx, &mut scope);
+                    }
+                    None => {
+                        let item = list.item(cx, portal_list_index, live_id!(status_label));
+                        item.label(id!(label)).set_text(cx, status_text);
+                        item.draw_all(cx, &mut scope);
+                    }
+                }
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl MemberListPane {
+    /// Returns `true` if this pane is currently being shown.
+    pub fn is_currently_shown(&self, _cx: &mut Cx) -> bool {
+        self.visible
+    }
+
+    /// Shows this pane with the members of the given room.
+    ///
+    /// If `members` is `None`, a loading message is shown until
+    /// the members are set via [`MemberListPane::set_members()`].
+    pub fn show(&mut self, cx: &mut Cx, room_id: OwnedRoomId, members: Option<Arc<Vec<RoomMember>>>) {
+        if self.room_id.as_ref() != Some(&room_id) {
+            self.clear_search(cx);
+        }
+        self.room_id = Some(room_id);
+        self.members = members;
+        self.sections_outdated = true;
+        self.entries_outdated = true;
+
+        self.visible = true;
+        self.is_animating_out = false;
+        cx.set_key_focus(self.view.area());
+        self.animator_play(cx, id!(panel.show));
+        self.view(id!(bg_view)).set_visible(cx, true);
+        self.view.button(id!(close_button)).reset_hover(cx);
+        self.view.portal_list(id!(member_list)).set_first_id_and_scroll(0, 0.0);
+        self.redraw(cx);
+    }
+
+    /// Sets the list of room members shown in this pane.
+    pub fn set_members(&mut self, cx: &mut Cx, members: Arc<Vec<RoomMember>>) {
+        self.members = Some(members);
+        self.sections_outdated = true;
+        self.entries_outdated = true;
+        if self.visible {
+            self.redraw(cx);
+        }
+    }
+
+    /// Immediately hides this pane and clears all of its state.
+    pub fn reset(&mut self, cx: &mut Cx) {
+        self.visible = false;
+        self.is_animating_out = false;
+        self.room_id = None;
+        self.members = None;
+        self.sections.clear();
+        self.entries.clear();
+        self.clear_search(cx);
+        self.animator_cut(cx, id!(panel.hide));
+        self.view(id!(bg_view)).set_visible(cx, false);
+        self.redraw(cx);
+    }
+
+    fn clear_search(&mut self, cx: &mut Cx) {
+        self.search_query.clear();
+        self.view.text_input(id!(search_input)).set_text(cx, "");
+        self.view.button(id!(clear_button)).set_visible(cx, false);
+    }
+
+    fn set_search_query(&mut self, cx: &mut Cx, query: String) {
+        self.search_query = query;
+        self.entries_outdated = true;
+        self.view.portal_list(id!(member_list)).set_first_id_and_scroll(0, 0.0);
+        self.redraw(cx);
+    }
+
+    /// Rebuilds the sections of room members, each sorted by name.
+    ///
+    /// This is only done when the room members change, not whenever the search query changes.
+    fn update_sections(&mut self) {
+        self.sections_outdated = false;
+        self.sections.clear();
+        let Some(members) = self.members.as_ref() else { return };
+
+        let mut sections: BTreeMap<MemberCategory, Vec<SectionMember>> = BTreeMap::new();
+        for (index, member) in members.iter().enumerate() {
+            let Some(category) = MemberCategory::of(member.membership(), member.suggested_role_for_power_level()) else {
+                continue;
+            };
+            sections.entry(category).or_default()
+                .push(SectionMember::new(index, member.display_name(), member.user_id()));
+        }
+        for section_members in sections.values_mut() {
+            section_members.sort_by(|a, b| a.name_lowercase.cmp(&b.name_lowercase));
+        }
+        self.sections = sections.into_iter().collect();
+    }
+
+    /// Recalculates the displayed list entries by filtering each section of room members
+    /// by the current search query.
+    fn update_entries(&mut self) {
+        if self.sections_outdated {
+            self.update_sections();
+        }
+        self.entries_outdated = false;
+        self.entries.clear();
+
+        let query = self.search_query.trim().to_lowercase();
+        for (category, section_members) in &self.sections {
+            let header_index = self.entries.len();
+            self.entries.extend(section_members.iter()
+                .filter(|member| member.matches_search(&query))
+                .map(|member| MemberListEntry::Member(member.index))
+            );
+            let count = self.entries.len() - header_index;
+            if count > 0 {
+                self.entries.insert(header_index, MemberListEntry::Header { category: *category, count });
+            }
+        }
+    }
+}
+
+impl MemberListPaneRef {
+    /// See [`MemberListPane::is_currently_shown()`].
+    pub fn is_currently_shown(&self, cx: &mut Cx) -> bool {
+        let Some(inner) = self.borrow() else { return false };
+        inner.is_currently_shown(cx)
+    }
+
+    /// See [`MemberListPane::show()`].
+    pub fn show(&self, cx: &mut Cx, room_id: OwnedRoomId, members: Option<Arc<Vec<RoomMember>>>) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.show(cx, room_id, members);
+    }
+
+    /// See [`MemberListPane::set_members()`].
+    pub fn set_members(&self, cx: &mut Cx, members: Arc<Vec<RoomMember>>) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.set_members(cx, members);
+    }
+
+    /// See [`MemberListPane::reset()`].
+    pub fn reset(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.reset(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests_member_list_pane {
+    use matrix_sdk::ruma::user_id;
+
+    use super::*;
+
+    #[test]
+    fn members_are_categorized_by_membership_and_role() {
+        assert_eq!(MemberCategory::of(&MembershipState::Join, RoomMemberRole::Creator), Some(MemberCategory::Admins));
+        assert_eq!(MemberCategory::of(&MembershipState::Join, RoomMemberRole::Administrator), Some(MemberCategory::Admins));
+        assert_eq!(MemberCategory::of(&MembershipState::Join, RoomMemberRole::Moderator), Some(MemberCategory::Moderators));
+        assert_eq!(MemberCategory::of(&MembershipState::Join, RoomMemberRole::User), Some(MemberCategory::Members));
+        assert_eq!(MemberCategory::of(&MembershipState::Invite, RoomMemberRole::Moderator), Some(MemberCategory::Invited));
+        assert_eq!(MemberCategory::of(&MembershipState::Ban, RoomMemberRole::User), Some(MemberCategory::Banned));
+        assert_eq!(MemberCategory::of(&MembershipState::Leave, RoomMemberRole::User), None);
+    }
+
+    #[test]
+    fn search_matches_display_name_or_user_id() {
+        let user_id = user_id!("@alice:example.org");
+        let member = SectionMember::new(0, Some("Alice Liddell"), user_id);
+        assert!(member.matches_search(""));
+        assert!(member.matches_search("liddell"));
+        assert!(member.matches_search("alice:example"));
+        assert!(!member.matches_search("bob"));
+        assert!(SectionMember::new(0, None, user_id).matches_search("alice:example"));
+    }
+}
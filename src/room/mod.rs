@@ -2,12 +2,15 @@ use std::sync::Arc;
 use makepad_widgets::Cx;
 use matrix_sdk::ruma::OwnedRoomId;
 
+pub mod member_list_pane;
 pub mod reply_preview;
 pub mod room_input_bar;
 pub mod room_display_filter;
+pub mod room_drafts;
 pub mod typing_notice;
 
 pub fn live_design(cx: &mut Cx) {
+    member_list_pane::live_design(cx);
     reply_preview::live_design(cx);
     room_input_bar::live_design(cx);
     typing_notice::live_design(cx);
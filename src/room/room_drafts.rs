@@ -0,0 +1,110 @@
+//! A cache of the unsent message drafts in each room.
+//!
+//! This cache is only accessed from the main UI thread.
+//! It is loaded from persistent storage upon startup and saved back shortly after
+//! the user edits a draft, upon leaving a room, and upon shutdown,
+//! such that half-written messages, replies, and edits survive an app restart or crash.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use makepad_widgets::error;
+use matrix_sdk::ruma::{OwnedRoomId, RoomId, UserId};
+
+use crate::{persistence::{self, RoomDraft, RoomDrafts}, sliding_sync::current_user_id};
+
+thread_local! {
+    /// The unsent draft for each room that has one.
+    static ROOM_DRAFTS: RefCell<HashMap<OwnedRoomId, RoomDraft>> = RefCell::new(HashMap::new());
+}
+
+/// Returns `true` if the given room has an unsent draft.
+pub fn has_draft(room_id: &RoomId) -> bool {
+    ROOM_DRAFTS.with_borrow(|drafts| drafts.contains_key(room_id))
+}
+
+/// Returns a copy of the given room's unsent draft, if any.
+pub fn get_draft(room_id: &RoomId) -> Option<RoomDraft> {
+    ROOM_DRAFTS.with_borrow(|drafts| drafts.get(room_id).cloned())
+}
+
+/// Sets the unsent draft for the given room, removing it if the draft is empty.
+pub fn set_draft(room_id: OwnedRoomId, draft: RoomDraft) {
+    ROOM_DRAFTS.with_borrow_mut(|drafts| {
+        if draft.is_empty() {
+            drafts.remove(&room_id);
+        } else {
+            drafts.insert(room_id, draft);
+        }
+    });
+}
+
+/// Replaces all cached drafts with the given drafts loaded from persistent storage.
+///
+/// Drafts for rooms that were already modified in this session are kept as-is.
+pub fn restore_drafts(room_drafts: RoomDrafts) {
+    ROOM_DRAFTS.with_borrow_mut(|drafts| {
+        for (room_id, draft) in room_drafts.drafts {
+            drafts.entry(room_id.into()).or_insert(draft);
+        }
+    });
+}
+
+/// Returns a snapshot of all cached drafts, for saving to persistent storage.
+pub fn snapshot_drafts() -> RoomDrafts {
+    ROOM_DRAFTS.with_borrow(|drafts| RoomDrafts {
+        drafts: drafts.iter()
+            .map(|(room_id, draft)| (room_id.clone().into(), draft.clone()))
+            .collect(),
+    })
+}
+
+/// Saves all cached drafts to the currently-active account's persistent storage.
+pub fn save_drafts() {
+    let Some(user_id) = current_user_id() else { return };
+    save_room_drafts(&snapshot_drafts(), &user_id);
+}
+
+/// Saves all cached drafts to the given account's persistent storage, and then clears them,
+/// e.g., upon logout or switching to a different account.
+///
+/// The open rooms must have already recorded their drafts before this is called.
+pub fn save_and_clear_drafts(user_id: &UserId) {
+    take_drafts(|room_drafts| save_room_drafts(room_drafts, user_id));
+}
+
+/// Clears all cached drafts after passing a snapshot of them to the given `save` function.
+fn take_drafts(save: impl FnOnce(&RoomDrafts)) {
+    save(&snapshot_drafts());
+    clear_drafts();
+}
+
+fn save_room_drafts(room_drafts: &RoomDrafts, user_id: &UserId) {
+    if let Err(e) = persistence::save_room_drafts(room_drafts, user_id) {
+        error!("Failed to save room drafts. Error: {e}");
+    }
+}
+
+/// Clears all cached drafts, e.g., upon logout.
+pub fn clear_drafts() {
+    ROOM_DRAFTS.with_borrow_mut(|drafts| drafts.clear());
+}
+
+#[cfg(test)]
+mod tests_room_drafts {
+    use matrix_sdk::ruma::owned_room_id;
+
+    use super::*;
+    use crate::utils::OwnedRoomIdRon;
+
+    #[test]
+    fn drafts_are_saved_before_being_cleared() {
+        let room_id = owned_room_id!("!room:example.org");
+        set_draft(room_id.clone(), RoomDraft { text: String::from("unsent text"), ..Default::default() });
+
+        let mut saved = None;
+        take_drafts(|room_drafts| saved = Some(room_drafts.drafts.clone()));
+        assert!(!has_draft(&room_id));
+        let saved = saved.expect("drafts must be saved before being cleared");
+        assert_eq!(saved.get(&OwnedRoomIdRon::from(room_id.clone())).map(|draft| draft.text.as_str()), Some("unsent text"));
+    }
+}
@@ -15,11 +15,14 @@
 //! * A "cannot-send-message" notice, which is shown if the user cannot send messages to the room.
 //!
 
+use std::sync::Arc;
+
+use imbl::Vector;
 use makepad_widgets::*;
 use matrix_sdk::{room::reply::{EnforceThread, Reply}, SuccessorRoom};
-use matrix_sdk_ui::timeline::{EmbeddedEvent, EventTimelineItem, TimelineEventItemId};
+use matrix_sdk_ui::timeline::{EmbeddedEvent, EventTimelineItem, TimelineEventItemId, TimelineItem};
 use ruma::{events::room::message::{LocationMessageEventContent, MessageType, RoomMessageEventContent}, OwnedRoomId};
-use crate::{home::{editing_pane::{EditingPaneState, EditingPaneWidgetExt}, location_preview::LocationPreviewWidgetExt, room_screen::{populate_preview_of_timeline_item, MessageAction, RoomScreenProps}, tombstone_footer::TombstoneFooterWidgetExt}, location::init_location_subscriber, shared::{avatar::AvatarWidgetRefExt, html_or_plaintext::HtmlOrPlaintextWidgetRefExt, mentionable_text_input::MentionableTextInputWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, styles::*}, sliding_sync::{submit_async_request, MatrixRequest, UserPowerLevels}, utils};
+use crate::{home::{editing_pane::{EditingPaneState, EditingPaneWidgetExt}, location_preview::LocationPreviewWidgetExt, room_screen::{populate_preview_of_timeline_item, MessageAction, RoomScreenProps}, tombstone_footer::TombstoneFooterWidgetExt}, location::init_location_subscriber, persistence::RoomDraft, shared::{avatar::AvatarWidgetRefExt, html_or_plaintext::HtmlOrPlaintextWidgetRefExt, mentionable_text_input::MentionableTextInputWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, styles::*}, sliding_sync::{submit_async_request, MatrixRequest, UserPowerLevels}, utils};
 
 live_design! {
     use link::theme::*;
@@ -607,6 +610,67 @@ impl RoomInputBarRef {
         //    This depends on the `EditingPane` state, so it must be done after Step 3.
         inner.update_tombstone_footer(cx, room_id, tombstone_info);
     }
+
+    /// Returns the unsent message draft in this `RoomInputBar`, such that it can be persisted.
+    pub fn draft(&self) -> RoomDraft {
+        let Some(inner) = self.borrow() else { return Default::default() };
+        RoomDraft {
+            text: inner.mentionable_text_input(id!(input_bar.mentionable_text_input)).text(),
+            replying_to: inner.replying_to.as_ref()
+                .and_then(|(event_tl_item, _)| event_tl_item.event_id())
+                .map(ToString::to_string),
+            editing: inner.editing_pane(id!(editing_pane)).edit_draft(),
+        }
+    }
+
+    /// Restores the text of the given persisted `draft` into the message input.
+    ///
+    /// The draft's reply and edit are restored separately by [`Self::restore_draft_target()`],
+    /// because they can only be restored once their target events are in the timeline.
+    pub fn restore_draft_text(&self, cx: &mut Cx, draft: &RoomDraft) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.mentionable_text_input(id!(input_bar.mentionable_text_input))
+            .set_text(cx, &draft.text);
+        inner.enable_send_message_button(cx, !draft.text.trim().is_empty());
+    }
+
+    /// Restores the reply and/or edit of the given persisted `draft`
+    /// if their target events are present in the given timeline `items`.
+    ///
+    /// Returns the parts of the draft whose target events were not yet found, if any,
+    /// such that this can be attempted again after more timeline items have been loaded.
+    pub fn restore_draft_target(
+        &self,
+        cx: &mut Cx,
+        room_id: &OwnedRoomId,
+        mut draft: RoomDraft,
+        items: &Vector<Arc<TimelineItem>>,
+    ) -> Option<RoomDraft> {
+        let Some(mut inner) = self.borrow_mut() else { return Some(draft) };
+        let find_event = |event_id: &str| items.iter()
+            .rev()
+            .filter_map(|item| item.as_event())
+            .find(|ev| ev.event_id().is_some_and(|id| id.as_str() == event_id))
+            .cloned();
+
+        if let Some(event_tl_item) = draft.replying_to.as_deref().and_then(find_event) {
+            let replied_to_info = EmbeddedEvent::from_timeline_item(&event_tl_item);
+            inner.show_replying_to(cx, (event_tl_item, replied_to_info), room_id, false);
+            draft.replying_to = None;
+        }
+        if let Some(event_tl_item) = draft.editing.as_ref().and_then(|edit| find_event(&edit.event_id)) {
+            let edit_text = draft.editing.take().map(|edit| edit.text).unwrap_or_default();
+            inner.show_editing_pane(
+                cx,
+                ShowEditingPaneBehavior::ShowNew { event_tl_item },
+                room_id.clone(),
+            );
+            inner.mentionable_text_input(id!(editing_pane.editing_content.edit_text_input))
+                .set_text(cx, &edit_text);
+        }
+
+        (draft.replying_to.is_some() || draft.editing.is_some()).then_some(draft)
+    }
 }
 
 /// The saved UI state of a `RoomInputBar` widget.
@@ -1,6 +1,9 @@
+use std::path::PathBuf;
+
 use makepad_widgets::{text::selection::Cursor, *};
+use matrix_sdk::ruma::OwnedMxcUri;
 
-use crate::{logout::logout_confirm_modal::{LogoutAction, LogoutConfirmModalAction}, profile::user_profile::UserProfile, shared::{avatar::AvatarWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, styles::*}, utils};
+use crate::{settings::room_keys_settings::RoomKeysSettingsWidgetExt, home::spaces_dock::get_own_profile, logout::{logout_confirm_modal::{LogoutAction, LogoutConfirmModalAction}, logout_state_machine::LogoutMode}, profile::user_profile::{AvatarState, UserProfile}, shared::{avatar::AvatarWidgetExt, popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, styles::*}, sliding_sync::{submit_async_request, MatrixRequest}, utils};
 
 live_design! {
     use link::theme::*;
@@ -11,6 +14,7 @@ live_design! {
     use crate::shared::styles::*;
     use crate::shared::avatar::*;
     use crate::shared::icon_button::*;
+    use crate::settings::room_keys_settings::RoomKeysSettings;
 
     // The view containing all user account-related settings.
     pub AccountSettings = {{AccountSettings}} {
@@ -182,6 +186,9 @@ live_design! {
             }
         }
 
+        // Exporting and importing end-to-end encryption room keys.
+        room_keys_settings = <RoomKeysSettings> {}
+
         <SubsectionLabel> {
             text: "Other actions:"
         }
@@ -230,9 +237,133 @@ live_design! {
                 text: "Log out"
             }
         }
+
+        <SubsectionLabel> {
+            text: "Account lifecycle:"
+        }
+
+        <View> {
+            width: Fill, height: Fit
+            flow: RightWrap,
+            align: {y: 0.5},
+            spacing: 10
+
+            clear_local_data_button = <RobrixIconButton> {
+                padding: {top: 10, bottom: 10, left: 12, right: 15}
+                margin: {left: 5}
+                draw_bg: {
+                    color: (COLOR_SECONDARY)
+                }
+                draw_icon: {
+                    svg_file: (ICON_TRASH)
+                    color: (COLOR_TEXT)
+                }
+                draw_text: {
+                    color: (COLOR_TEXT)
+                }
+                icon_walk: {width: 16, height: 16}
+                text: "Clear Local Data"
+            }
+
+            sign_out_other_sessions_button = <RobrixIconButton> {
+                padding: {top: 10, bottom: 10, left: 12, right: 15}
+                margin: {left: 5}
+                draw_bg: {
+                    color: (COLOR_BG_DANGER_RED)
+                    border_color: (COLOR_FG_DANGER_RED)
+                }
+                draw_icon: {
+                    svg_file: (ICON_LOGOUT),
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                draw_text: {
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                icon_walk: { width: 16, height: 16, margin: {right: -2} }
+                text: "Sign Out Other Sessions"
+            }
+
+            deactivate_account_button = <RobrixIconButton> {
+                padding: {top: 10, bottom: 10, left: 12, right: 15}
+                margin: {left: 5}
+                draw_bg: {
+                    color: (COLOR_BG_DANGER_RED)
+                    border_color: (COLOR_FG_DANGER_RED)
+                }
+                draw_icon: {
+                    svg_file: (ICON_FORBIDDEN),
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                draw_text: {
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                icon_walk: { width: 16, height: 16, margin: {right: -2} }
+                text: "Deactivate Account"
+            }
+        }
     }
 }
 
+/// Actions that report changes to the current user's own profile.
+///
+/// These are posted by the background async worker once a profile change
+/// has been applied on the homeserver, or has failed.
+#[derive(Clone, Debug)]
+pub enum OwnProfileAction {
+    /// The user's display name was changed; `None` means it was removed.
+    DisplayNameChanged(Option<String>),
+    /// The user's avatar was changed or removed.
+    AvatarChanged {
+        /// The previous avatar, which should be evicted from the avatar cache.
+        old_avatar_uri: Option<OwnedMxcUri>,
+        /// The new avatar, or `None` if it was removed.
+        new_avatar_uri: Option<OwnedMxcUri>,
+    },
+    /// Changing the user's display name failed, with the given error message.
+    DisplayNameUpdateFailed(String),
+    /// Changing or removing the user's avatar failed, with the given error message.
+    AvatarUpdateFailed(String),
+}
+
+/// An action posted once the user has chosen an image file to use as their new avatar.
+#[derive(Clone, Debug)]
+pub enum AvatarImageAction {
+    /// The user chose the image file at the given path.
+    Chosen(PathBuf),
+    /// The user canceled choosing an image file.
+    Canceled,
+}
+
+/// The file extensions of the image formats that can be used as an avatar.
+const AVATAR_IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Shows a native file dialog for choosing an image file to use as the user's new avatar,
+/// starting in the user's pictures directory.
+///
+/// This must be run on the background async runtime, such that the UI thread isn't blocked
+/// while the dialog is open. Returns `None` if the user canceled the dialog.
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+pub async fn choose_avatar_image() -> Option<PathBuf> {
+    let mut dialog = rfd::AsyncFileDialog::new()
+        .set_title("Choose your new avatar")
+        .add_filter("Images", &AVATAR_IMAGE_EXTENSIONS);
+    if let Some(pictures_dir) = robius_directories::UserDirs::new().and_then(|dirs| dirs.picture_dir().map(|d| d.to_path_buf())) {
+        dialog = dialog.set_directory(pictures_dir);
+    }
+    dialog.pick_file().await.map(|file| file.path().to_path_buf())
+}
+
+/// Native file dialogs aren't yet supported on mobile platforms.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub async fn choose_avatar_image() -> Option<PathBuf> {
+    enqueue_popup_notification(PopupItem {
+        message: String::from("Choosing a new avatar is not yet supported on this platform."),
+        auto_dismissal_duration: Some(4.0),
+        kind: PopupKind::Info,
+    });
+    None
+}
+
 /// The view containing all user account-related settings.
 #[derive(Live, LiveHook, Widget)]
 pub struct AccountSettings {
@@ -243,6 +374,16 @@ pub struct AccountSettings {
 
 impl Widget for AccountSettings {
     fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        // A UI Signal indicates that our new avatar image may have been fetched in the background.
+        if let Event::Signal = event {
+            if self.own_profile.as_ref().is_some_and(|p| p.avatar_state.uri().is_some()) {
+                if let Some(own_profile) = get_own_profile(cx).filter(|p| p.avatar_state.data().is_some()) {
+                    self.own_profile = Some(own_profile);
+                    self.populate_from_profile(cx);
+                    self.view.redraw(cx);
+                }
+            }
+        }
         self.match_event(cx, event);
         self.view.handle_event(cx, event, scope);
     }
@@ -267,73 +408,98 @@ impl MatchEvent for AccountSettings {
                     logout_button.set_enabled(cx, true);
                 }
             }
+
+            match action.downcast_ref() {
+                Some(OwnProfileAction::DisplayNameChanged(new_display_name)) => {
+                    let Some(own_profile) = self.own_profile.as_mut() else { continue };
+                    own_profile.username = new_display_name.clone();
+                    self.populate_from_profile(cx);
+                    self.enable_display_name_buttons(cx, false);
+                    enqueue_popup_notification(PopupItem {
+                        message: String::from("Your display name was changed."),
+                        auto_dismissal_duration: Some(3.0),
+                        kind: PopupKind::Success,
+                    });
+                }
+                Some(OwnProfileAction::AvatarChanged { new_avatar_uri, .. }) => {
+                    self.reset_upload_avatar_button(cx);
+                    let Some(own_profile) = self.own_profile.as_mut() else { continue };
+                    own_profile.avatar_state = AvatarState::Known(new_avatar_uri.clone());
+                    self.populate_from_profile(cx);
+                    enqueue_popup_notification(PopupItem {
+                        message: String::from(if new_avatar_uri.is_some() {
+                            "Your avatar was updated."
+                        } else {
+                            "Your avatar was removed."
+                        }),
+                        auto_dismissal_duration: Some(3.0),
+                        kind: PopupKind::Success,
+                    });
+                }
+                Some(OwnProfileAction::DisplayNameUpdateFailed(error)) => {
+                    // Allow the user to retry changing their display name.
+                    let display_name_input = self.view.text_input(id!(display_name_input));
+                    let is_name_changed = self.own_profile.as_ref().is_some_and(|p|
+                        display_name_input.text() != p.username.as_deref().unwrap_or("")
+                    );
+                    self.enable_display_name_buttons(cx, is_name_changed);
+                    enqueue_popup_notification(PopupItem {
+                        message: error.clone(),
+                        auto_dismissal_duration: None,
+                        kind: PopupKind::Error,
+                    });
+                }
+                Some(OwnProfileAction::AvatarUpdateFailed(error)) => {
+                    self.reset_upload_avatar_button(cx);
+                    enqueue_popup_notification(PopupItem {
+                        message: error.clone(),
+                        auto_dismissal_duration: None,
+                        kind: PopupKind::Error,
+                    });
+                }
+                None => { }
+            }
+
+            match action.downcast_ref() {
+                Some(AvatarImageAction::Chosen(path)) => {
+                    // The chosen image is scaled down before being uploaded.
+                    self.view.button(id!(upload_avatar_button)).set_text(cx, "Uploading...");
+                    submit_async_request(MatrixRequest::UploadAvatar { path: path.clone() });
+                }
+                Some(AvatarImageAction::Canceled) => self.reset_upload_avatar_button(cx),
+                None => { }
+            }
         }
         
         let Some(own_profile) = &self.own_profile else { return };
 
-        if self.view.button(id!(upload_avatar_button)).clicked(actions) {
-            // TODO: support uploading a new avatar picture.
-            enqueue_popup_notification(PopupItem {
-                message: String::from("Avatar uploading is not yet implemented."),
-                auto_dismissal_duration: Some(4.0),
-                kind: PopupKind::Warning
-            });
+        let upload_avatar_button = self.view.button(id!(upload_avatar_button));
+        if upload_avatar_button.clicked(actions) {
+            upload_avatar_button.set_text(cx, "Choosing...");
+            upload_avatar_button.set_enabled(cx, false);
+            upload_avatar_button.reset_hover(cx);
+            submit_async_request(MatrixRequest::ChooseAvatarImage);
         }
 
         if self.view.button(id!(delete_avatar_button)).clicked(actions) {
-            // TODO: support removing the avatar picture.
-            enqueue_popup_notification(PopupItem {
-                message: String::from("Avatar deletion is not yet implemented."),
-                auto_dismissal_duration: Some(4.0),
-                kind: PopupKind::Warning,
-            });
+            if matches!(own_profile.avatar_state, AvatarState::Known(None)) {
+                enqueue_popup_notification(PopupItem {
+                    message: String::from("You don't have an avatar to delete."),
+                    auto_dismissal_duration: Some(3.0),
+                    kind: PopupKind::Info,
+                });
+            } else {
+                submit_async_request(MatrixRequest::DeleteAvatar);
+            }
         }
 
         let accept_display_name_button = self.view.button(id!(accept_display_name_button));
         let cancel_display_name_button = self.view.button(id!(cancel_display_name_button));
         let display_name_input = self.view.text_input(id!(display_name_input));
-        let enable_buttons = |cx: &mut Cx, enable: bool| {
-            accept_display_name_button.set_enabled(cx, enable);
-            cancel_display_name_button.set_enabled(cx, enable);
-            let (accept_button_fg_color, accept_button_bg_color) = if enable {
-                (COLOR_FG_ACCEPT_GREEN, COLOR_BG_ACCEPT_GREEN)
-            } else {
-                (COLOR_FG_DISABLED, COLOR_BG_DISABLED)
-            };
-            let (cancel_button_fg_color, cancel_button_bg_color) = if enable {
-                (COLOR_FG_DANGER_RED, COLOR_BG_DANGER_RED)
-            } else {
-                (COLOR_FG_DISABLED, COLOR_BG_DISABLED)
-            };
-            accept_display_name_button.apply_over(cx, live!(
-                draw_bg: {
-                    color: (accept_button_bg_color),
-                    border_color: (accept_button_fg_color),
-                },
-                draw_text: {
-                    color: (accept_button_fg_color),
-                },
-                draw_icon: {
-                    color: (accept_button_fg_color),
-                }
-            ));
-            cancel_display_name_button.apply_over(cx, live!(
-                draw_bg: {
-                    color: (cancel_button_bg_color),
-                    border_color: (cancel_button_fg_color),
-                },
-                draw_text: {
-                    color: (cancel_button_fg_color),
-                },
-                draw_icon: {
-                    color: (cancel_button_fg_color),
-                }
-            ));
-        };
 
         if let Some(new_name) = display_name_input.changed(actions) {
             let should_enable = new_name.as_str() != own_profile.username.as_deref().unwrap_or("");
-            enable_buttons(cx, should_enable);
+            self.enable_display_name_buttons(cx, should_enable);
         }
 
         if cancel_display_name_button.clicked(actions) {
@@ -341,16 +507,17 @@ impl MatchEvent for AccountSettings {
             let new_text = own_profile.username.as_deref().unwrap_or("");
             display_name_input.set_text(cx, new_text);
             display_name_input.set_cursor(cx, Cursor { index: new_text.len(), prefer_next_row: false }, false);
-            enable_buttons(cx, false);
+            self.enable_display_name_buttons(cx, false);
         }
 
         if accept_display_name_button.clicked(actions) {
-            // TODO: support changing the display name.
-            enqueue_popup_notification(PopupItem {
-                message: String::from("Display name change is not yet implemented."),
-                auto_dismissal_duration: Some(4.0),
-                kind: PopupKind::Warning
+            // An empty name removes the display name.
+            let new_display_name = display_name_input.text().trim().to_owned();
+            submit_async_request(MatrixRequest::SetDisplayName {
+                new_display_name: (!new_display_name.is_empty()).then_some(new_display_name),
             });
+            // Prevent duplicate requests; the buttons are re-enabled if the request fails.
+            self.enable_display_name_buttons(cx, false);
         }
 
         if self.view.button(id!(copy_user_id_button)).clicked(actions) {
@@ -363,22 +530,80 @@ impl MatchEvent for AccountSettings {
         }
 
         if self.view.button(id!(manage_account_button)).clicked(actions) {
-            // TODO: support opening the user's account management page in a browser,
-            //       or perhaps in an in-app pane if that's what is needed for regular UN+PW login.
-            enqueue_popup_notification(PopupItem {
-                message: String::from("Account management is not yet implemented."),
-                auto_dismissal_duration: Some(4.0),
-                kind: PopupKind::Warning
-            });
+            // This opens the account management page of the homeserver's OAuth provider.
+            // For accounts that logged in via a password or SSO, a popup explains that it isn't available.
+            submit_async_request(MatrixRequest::OpenAccountManagement);
         }
 
         if self.view.button(id!(logout_button)).clicked(actions) {
-            cx.action(LogoutConfirmModalAction::Open);
+            cx.action(LogoutConfirmModalAction::Open(LogoutMode::ThisSession));
+        }
+
+        if self.view.button(id!(clear_local_data_button)).clicked(actions) {
+            cx.action(LogoutConfirmModalAction::Open(LogoutMode::ClearLocalDataOnly));
+        }
+
+        if self.view.button(id!(sign_out_other_sessions_button)).clicked(actions) {
+            cx.action(LogoutConfirmModalAction::Open(LogoutMode::OtherSessions));
+        }
+
+        if self.view.button(id!(deactivate_account_button)).clicked(actions) {
+            // Whether to erase the account's data is chosen in the confirmation modal.
+            cx.action(LogoutConfirmModalAction::Open(LogoutMode::DeactivateAccount { erase: false }));
         }
     }
 }
 
 impl AccountSettings {
+    /// Enables or disables the buttons for saving or canceling a display name change.
+    fn enable_display_name_buttons(&self, cx: &mut Cx, enable: bool) {
+        let accept_display_name_button = self.view.button(id!(accept_display_name_button));
+        let cancel_display_name_button = self.view.button(id!(cancel_display_name_button));
+        accept_display_name_button.set_enabled(cx, enable);
+        cancel_display_name_button.set_enabled(cx, enable);
+        let (accept_button_fg_color, accept_button_bg_color) = if enable {
+            (COLOR_FG_ACCEPT_GREEN, COLOR_BG_ACCEPT_GREEN)
+        } else {
+            (COLOR_FG_DISABLED, COLOR_BG_DISABLED)
+        };
+        let (cancel_button_fg_color, cancel_button_bg_color) = if enable {
+            (COLOR_FG_DANGER_RED, COLOR_BG_DANGER_RED)
+        } else {
+            (COLOR_FG_DISABLED, COLOR_BG_DISABLED)
+        };
+        accept_display_name_button.apply_over(cx, live!(
+            draw_bg: {
+                color: (accept_button_bg_color),
+                border_color: (accept_button_fg_color),
+            },
+            draw_text: {
+                color: (accept_button_fg_color),
+            },
+            draw_icon: {
+                color: (accept_button_fg_color),
+            }
+        ));
+        cancel_display_name_button.apply_over(cx, live!(
+            draw_bg: {
+                color: (cancel_button_bg_color),
+                border_color: (cancel_button_fg_color),
+            },
+            draw_text: {
+                color: (cancel_button_fg_color),
+            },
+            draw_icon: {
+                color: (cancel_button_fg_color),
+            }
+        ));
+    }
+
+    /// Restores the upload avatar button after an upload has finished.
+    fn reset_upload_avatar_button(&self, cx: &mut Cx) {
+        let upload_avatar_button = self.view.button(id!(upload_avatar_button));
+        upload_avatar_button.set_text(cx, "Upload Avatar");
+        upload_avatar_button.set_enabled(cx, true);
+    }
+
     /// Populate the account settings view with the user's profile data.
     ///
     /// This does nothing if `self.own_profile` is `None`.
@@ -426,6 +651,7 @@ impl AccountSettings {
         self.view.button(id!(copy_user_id_button)).reset_hover(cx);
         self.view.button(id!(manage_account_button)).reset_hover(cx);
         self.view.button(id!(logout_button)).reset_hover(cx);
+        self.view.room_keys_settings(id!(room_keys_settings)).populate(cx);
         self.view.redraw(cx);
     }
 }
@@ -0,0 +1,681 @@
+//! The credentials settings section, which lets the user change their password
+//! and manage the email addresses and phone numbers (3PIDs) bound to their account.
+//!
+//! Both changing the password and adding a new 3PID require user-interactive auth,
+//! for which the user is asked to enter their current password.
+//! Adding a new 3PID is a two-step process: the homeserver first sends a validation
+//! email or SMS, and the 3PID is only added once the user has confirmed it.
+//!
+//! Accounts that log in via OAuth manage their password and 3PIDs via their OAuth provider,
+//! so for them this section only links to the provider's account management page.
+
+use makepad_widgets::*;
+use matrix_sdk::{authentication::AuthSession, ruma::{thirdparty::Medium, OwnedClientSecret, OwnedSessionId}};
+
+use crate::sliding_sync::{get_client, submit_async_request, MatrixRequest};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    CredentialsNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 3, bottom: 3}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    CredentialsTextInput = <SimpleTextInput> {
+        margin: {top: 3, left: 5, right: 5, bottom: 3},
+        width: 216, height: Fit
+    }
+
+    CredentialsButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: 10,
+        margin: {left: 5, top: 5},
+        draw_bg: {
+            color: (COLOR_ACTIVE_PRIMARY),
+            border_radius: 5
+        }
+        draw_icon: {
+            color: (COLOR_PRIMARY),
+        }
+        icon_walk: {width: 16, height: 16, margin: 0}
+        draw_text: {
+            color: (COLOR_PRIMARY),
+        }
+    }
+
+    // A single email address or phone number in the list of the user's 3PIDs.
+    ThreepidEntry = {{ThreepidEntry}} {
+        width: Fill, height: Fit
+        flow: Right,
+        align: {y: 0.5},
+        padding: {left: 5, top: 5, bottom: 5}
+        spacing: 8
+
+        address = <Label> {
+            width: Fill, height: Fit
+            draw_text: {
+                wrap: Ellipsis,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <REGULAR_TEXT>{ font_size: 10.5 },
+            }
+            text: ""
+        }
+
+        remove_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 8,
+            margin: 0,
+            draw_bg: {
+                color: (COLOR_BG_DANGER_RED)
+                border_color: (COLOR_FG_DANGER_RED)
+            }
+            draw_icon: {
+                svg_file: (ICON_TRASH),
+                color: (COLOR_FG_DANGER_RED),
+            }
+            icon_walk: {width: 14, height: 14, margin: 0}
+            draw_text: {
+                color: (COLOR_FG_DANGER_RED),
+                text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+            }
+            text: "Remove"
+        }
+    }
+
+    // The view containing the password and 3PID settings.
+    pub CredentialsSettings = {{CredentialsSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Password & Contact Info"
+        }
+
+        // Accounts that log in via OAuth change their password and contact info
+        // on their OAuth provider's account management page instead.
+        oauth_notice_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            <CredentialsNoticeLabel> {
+                text: "Your account is managed by your homeserver's authentication service. Change your password and contact info on its account management page."
+            }
+
+            manage_account_button = <CredentialsButton> {
+                draw_icon: { svg_file: (ICON_EXTERNAL_LINK) }
+                text: "Manage Account"
+            }
+        }
+
+        change_password_view = <View> {
+            width: Fill, height: Fit
+            flow: Down
+
+            <SubsectionLabel> {
+                text: "Change Password:"
+            }
+
+            current_password_input = <CredentialsTextInput> {
+                is_password: true,
+                empty_text: "Current password"
+            }
+            new_password_input = <CredentialsTextInput> {
+                is_password: true,
+                empty_text: "New password"
+            }
+            confirm_password_input = <CredentialsTextInput> {
+                is_password: true,
+                empty_text: "Confirm new password"
+            }
+
+            sign_out_other_devices_checkbox = <CheckBoxFlat> {
+                margin: {left: 5, top: 5}
+                draw_text: {
+                    color: (COLOR_TEXT),
+                    text_style: <REGULAR_TEXT>{ font_size: 10 },
+                }
+                text: "Sign out of all other sessions"
+            }
+
+            change_password_button = <CredentialsButton> {
+                draw_icon: { svg_file: (ICON_CHECKMARK) }
+                text: "Change Password"
+            }
+
+            password_status_label = <CredentialsNoticeLabel> {
+                visible: false
+            }
+        }
+
+        <SubsectionLabel> {
+            margin: {top: 15}
+            text: "Email Addresses & Phone Numbers:"
+        }
+
+        <CredentialsNoticeLabel> {
+            text: "These can be used to sign in or to recover your account, and let others find you."
+        }
+
+        threepid_status_label = <CredentialsNoticeLabel> {
+            text: "Loading your email addresses and phone numbers..."
+        }
+
+        threepid_list = <FlatList> {
+            width: Fill,
+            height: Fit,
+            spacing: 0.0
+            flow: Down,
+
+            grab_key_focus: true,
+            drag_scrolling: true,
+            scroll_bars: { show_scroll_x: false, show_scroll_y: false },
+
+            threepid_entry = <ThreepidEntry> { }
+        }
+
+        add_threepid_view = <View> {
+            width: Fill, height: Fit
+            flow: Down
+
+            <View> {
+                width: Fill, height: Fit
+                flow: RightWrap,
+                align: {y: 0.5},
+
+                new_email_input = <CredentialsTextInput> {
+                    empty_text: "Email address"
+                }
+                add_email_button = <CredentialsButton> {
+                    margin: {left: 5}
+                    draw_icon: { svg_file: (ICON_ADD) }
+                    text: "Add Email"
+                }
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: RightWrap,
+                align: {y: 0.5},
+
+                phone_country_input = <CredentialsTextInput> {
+                    width: 60,
+                    empty_text: "US"
+                }
+                phone_number_input = <CredentialsTextInput> {
+                    width: 148,
+                    empty_text: "Phone number"
+                }
+                add_phone_button = <CredentialsButton> {
+                    margin: {left: 5}
+                    draw_icon: { svg_file: (ICON_ADD) }
+                    text: "Add Phone"
+                }
+            }
+
+            // Shown once the homeserver has sent a validation email or SMS for a new 3PID.
+            verify_threepid_view = <View> {
+                visible: false
+                width: Fill, height: Fit
+                flow: Down
+
+                verify_threepid_label = <CredentialsNoticeLabel> { }
+
+                verification_code_input = <CredentialsTextInput> {
+                    empty_text: "Verification code"
+                }
+                threepid_password_input = <CredentialsTextInput> {
+                    is_password: true,
+                    empty_text: "Your password"
+                }
+
+                <View> {
+                    width: Fill, height: Fit
+                    flow: RightWrap,
+                    align: {y: 0.5},
+
+                    finish_add_threepid_button = <CredentialsButton> {
+                        draw_icon: { svg_file: (ICON_CHECKMARK) }
+                        text: "Verify"
+                    }
+                    cancel_add_threepid_button = <CredentialsButton> {
+                        draw_bg: {
+                            color: (COLOR_SECONDARY)
+                        }
+                        draw_icon: {
+                            svg_file: (ICON_FORBIDDEN),
+                            color: (COLOR_TEXT),
+                        }
+                        draw_text: {
+                            color: (COLOR_TEXT),
+                        }
+                        text: "Cancel"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An email address or phone number bound to the user's account.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreepidInfo {
+    pub medium: Medium,
+    pub address: String,
+}
+
+/// A new email address or phone number to be added to the user's account.
+#[derive(Clone, Debug)]
+pub enum NewThreepid {
+    Email(String),
+    PhoneNumber {
+        /// The two-letter ISO 3166-1 country code that the phone number belongs to.
+        country: String,
+        number: String,
+    },
+}
+
+/// An in-progress validation of a new email address or phone number.
+#[derive(Clone, Debug)]
+pub struct ThreepidValidation {
+    pub medium: Medium,
+    /// The email address or phone number being validated.
+    pub address: String,
+    pub client_secret: OwnedClientSecret,
+    pub session_id: OwnedSessionId,
+    /// The URL to which the phone number's verification code must be submitted,
+    /// if the homeserver validates phone numbers itself.
+    pub submit_url: Option<String>,
+}
+
+/// Actions related to the user's password and 3PIDs.
+#[derive(Debug)]
+pub enum CredentialsAction {
+    /// The user's password was successfully changed.
+    PasswordChanged,
+    /// Changing the user's password failed.
+    PasswordChangeFailed(String),
+    /// The list of the user's 3PIDs was fetched from the homeserver.
+    ThreepidsFetched(Vec<ThreepidInfo>),
+    /// Fetching the list of 3PIDs failed.
+    ThreepidsFetchFailed(String),
+    /// The homeserver sent a validation email or SMS for a new 3PID.
+    ValidationStarted(ThreepidValidation),
+    /// The given 3PID was successfully added to the user's account.
+    ThreepidAdded(String),
+    /// Adding or removing a 3PID failed.
+    ThreepidOperationFailed(String),
+}
+
+/// Actions emitted by a [`ThreepidEntry`] to its parent [`CredentialsSettings`] view.
+#[derive(Clone, Debug, DefaultNone)]
+pub enum ThreepidEntryAction {
+    /// The user wants to remove the given 3PID from their account.
+    Remove(ThreepidInfo),
+    None,
+}
+
+/// The view containing the password and 3PID settings.
+#[derive(Live, LiveHook, Widget)]
+pub struct CredentialsSettings {
+    #[deref] view: View,
+
+    /// The most recently fetched list of 3PIDs.
+    #[rust] threepids: Vec<ThreepidInfo>,
+    /// The new 3PID that is currently being validated, if any.
+    #[rust] pending_validation: Option<ThreepidValidation>,
+}
+
+impl Widget for CredentialsSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+        self.widget_match_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        while let Some(subview) = self.view.draw_walk(cx, scope, walk).step() {
+            // Here, we only need to handle drawing the 3PID list.
+            let flat_list_ref = subview.as_flat_list();
+            let Some(mut list) = flat_list_ref.borrow_mut() else {
+                error!("!!! CredentialsSettings::draw_walk(): BUG: expected a FlatList widget, but got something else");
+                continue;
+            };
+            for threepid in &self.threepids {
+                let item_id = LiveId::from_str(&format!("{}:{}", threepid.medium.as_str(), threepid.address));
+                let item = list.item(cx, item_id, live_id!(threepid_entry)).unwrap();
+                // Pass the 3PID info in through Scope via props.
+                let mut scope = Scope::with_props(threepid);
+                item.draw_all(cx, &mut scope);
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl WidgetMatchEvent for CredentialsSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        for action in actions {
+            match action.downcast_ref() {
+                Some(CredentialsAction::PasswordChanged) => {
+                    self.reset_password_inputs(cx);
+                    self.set_password_status(cx, "Your password was changed.");
+                }
+                Some(CredentialsAction::PasswordChangeFailed(error)) => {
+                    self.view.button(id!(change_password_button)).set_enabled(cx, true);
+                    self.set_password_status(cx, &format!("Failed to change your password: {error}"));
+                }
+                Some(CredentialsAction::ThreepidsFetched(threepids)) => {
+                    self.threepids = threepids.clone();
+                    let status = if self.threepids.is_empty() {
+                        "You haven't added any email addresses or phone numbers."
+                    } else {
+                        ""
+                    };
+                    self.set_threepid_status(cx, status);
+                }
+                Some(CredentialsAction::ThreepidsFetchFailed(error)) => {
+                    self.set_threepid_status(cx, &format!("Failed to load your email addresses and phone numbers: {error}"));
+                }
+                Some(CredentialsAction::ValidationStarted(validation)) => {
+                    self.show_validation(cx, validation.clone());
+                }
+                Some(CredentialsAction::ThreepidAdded(address)) => {
+                    self.hide_validation(cx);
+                    self.set_threepid_status(cx, &format!("Added {address} to your account."));
+                }
+                Some(CredentialsAction::ThreepidOperationFailed(error)) => {
+                    self.view.button(id!(finish_add_threepid_button)).set_enabled(cx, true);
+                    self.set_threepid_status(cx, error);
+                }
+                None => { }
+            }
+
+            if let ThreepidEntryAction::Remove(threepid) = action.as_widget_action().cast() {
+                submit_async_request(MatrixRequest::DeleteThreepid {
+                    medium: threepid.medium,
+                    address: threepid.address,
+                });
+            }
+        }
+
+        if self.view.button(id!(manage_account_button)).clicked(actions) {
+            submit_async_request(MatrixRequest::OpenAccountManagement);
+        }
+
+        let confirm_password_input = self.view.text_input(id!(confirm_password_input));
+        if self.view.button(id!(change_password_button)).clicked(actions)
+            || confirm_password_input.returned(actions).is_some()
+        {
+            let current_password = self.view.text_input(id!(current_password_input)).text();
+            let new_password = self.view.text_input(id!(new_password_input)).text();
+            match validate_new_password(&current_password, &new_password, &confirm_password_input.text()) {
+                Ok(()) => {
+                    self.view.button(id!(change_password_button)).set_enabled(cx, false);
+                    self.set_password_status(cx, "Changing your password...");
+                    submit_async_request(MatrixRequest::ChangePassword {
+                        current_password,
+                        new_password,
+                        sign_out_other_devices: self.view.check_box(id!(sign_out_other_devices_checkbox)).active(cx),
+                    });
+                }
+                Err(error) => self.set_password_status(cx, error),
+            }
+        }
+
+        let new_email_input = self.view.text_input(id!(new_email_input));
+        if self.view.button(id!(add_email_button)).clicked(actions) || new_email_input.returned(actions).is_some() {
+            let email = new_email_input.text().trim().to_owned();
+            if is_plausible_email(&email) {
+                self.set_threepid_status(cx, &format!("Sending a verification email to {email}..."));
+                submit_async_request(MatrixRequest::RequestThreepidToken { threepid: NewThreepid::Email(email) });
+            } else {
+                self.set_threepid_status(cx, "Please enter a valid email address.");
+            }
+        }
+
+        let phone_number_input = self.view.text_input(id!(phone_number_input));
+        if self.view.button(id!(add_phone_button)).clicked(actions) || phone_number_input.returned(actions).is_some() {
+            let country = self.view.text_input(id!(phone_country_input)).text();
+            match parse_phone_number(&country, &phone_number_input.text()) {
+                Ok((country, number)) => {
+                    self.set_threepid_status(cx, &format!("Sending a verification code to {number}..."));
+                    submit_async_request(MatrixRequest::RequestThreepidToken {
+                        threepid: NewThreepid::PhoneNumber { country, number },
+                    });
+                }
+                Err(error) => self.set_threepid_status(cx, error),
+            }
+        }
+
+        if self.view.button(id!(cancel_add_threepid_button)).clicked(actions) {
+            self.hide_validation(cx);
+            self.set_threepid_status(cx, "");
+        }
+
+        let threepid_password_input = self.view.text_input(id!(threepid_password_input));
+        if self.view.button(id!(finish_add_threepid_button)).clicked(actions) || threepid_password_input.returned(actions).is_some() {
+            let Some(validation) = self.pending_validation.clone() else { return };
+            let verification_code = self.view.text_input(id!(verification_code_input)).text().trim().to_owned();
+            if validation.medium == Medium::Msisdn && verification_code.is_empty() {
+                self.set_threepid_status(cx, "Please enter the verification code that was sent to your phone.");
+                return;
+            }
+            let password = threepid_password_input.text();
+            threepid_password_input.set_text(cx, "");
+            self.view.button(id!(finish_add_threepid_button)).set_enabled(cx, false);
+            submit_async_request(MatrixRequest::AddThreepid {
+                verification_code: (validation.medium == Medium::Msisdn).then_some(verification_code),
+                validation,
+                password: (!password.is_empty()).then_some(password),
+            });
+        }
+    }
+}
+
+impl CredentialsSettings {
+    fn set_password_status(&mut self, cx: &mut Cx, status: &str) {
+        let status_label = self.view.label(id!(password_status_label));
+        status_label.set_text(cx, status);
+        status_label.set_visible(cx, !status.is_empty());
+        self.view.redraw(cx);
+    }
+
+    fn set_threepid_status(&mut self, cx: &mut Cx, status: &str) {
+        let status_label = self.view.label(id!(threepid_status_label));
+        status_label.set_text(cx, status);
+        status_label.set_visible(cx, !status.is_empty());
+        self.view.redraw(cx);
+    }
+
+    fn reset_password_inputs(&mut self, cx: &mut Cx) {
+        for input_id in [id!(current_password_input), id!(new_password_input), id!(confirm_password_input)] {
+            self.view.text_input(input_id).set_text(cx, "");
+        }
+        self.view.button(id!(change_password_button)).set_enabled(cx, true);
+    }
+
+    /// Asks the user to confirm the new 3PID that is being validated.
+    fn show_validation(&mut self, cx: &mut Cx, validation: ThreepidValidation) {
+        let is_phone_number = validation.medium == Medium::Msisdn;
+        let prompt = if is_phone_number {
+            format!("Enter the verification code that was sent to {}, then confirm your password.", validation.address)
+        } else {
+            format!("Click the link in the email that was sent to {}, then confirm your password.", validation.address)
+        };
+        self.view.label(id!(verify_threepid_label)).set_text(cx, &prompt);
+        let verification_code_input = self.view.text_input(id!(verification_code_input));
+        verification_code_input.set_text(cx, "");
+        verification_code_input.set_visible(cx, is_phone_number);
+        self.view.button(id!(finish_add_threepid_button)).set_enabled(cx, true);
+        self.view.view(id!(verify_threepid_view)).set_visible(cx, true);
+        self.pending_validation = Some(validation);
+        self.set_threepid_status(cx, "");
+    }
+
+    fn hide_validation(&mut self, cx: &mut Cx) {
+        self.pending_validation = None;
+        self.view.text_input(id!(threepid_password_input)).set_text(cx, "");
+        self.view.view(id!(verify_threepid_view)).set_visible(cx, false);
+        self.view.redraw(cx);
+    }
+
+    /// Resets the password inputs and fetches the user's 3PIDs in order to populate this view.
+    ///
+    /// For accounts that log in via OAuth, changing the password and adding 3PIDs is hidden,
+    /// as those must be done on the OAuth provider's account management page.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        let is_oauth = get_client().is_some_and(|client| matches!(client.session(), Some(AuthSession::OAuth(_))));
+        self.view.view(id!(oauth_notice_view)).set_visible(cx, is_oauth);
+        self.view.view(id!(change_password_view)).set_visible(cx, !is_oauth);
+        self.view.view(id!(add_threepid_view)).set_visible(cx, !is_oauth);
+        self.reset_password_inputs(cx);
+        self.view.check_box(id!(sign_out_other_devices_checkbox)).set_active(cx, true);
+        self.set_password_status(cx, "");
+        submit_async_request(MatrixRequest::GetThreepids);
+        if self.threepids.is_empty() {
+            self.set_threepid_status(cx, "Loading your email addresses and phone numbers...");
+        }
+    }
+}
+
+impl CredentialsSettingsRef {
+    /// See [`CredentialsSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
+
+/// A single email address or phone number in the list of the user's 3PIDs.
+#[derive(Live, LiveHook, Widget)]
+pub struct ThreepidEntry {
+    #[deref] view: View,
+
+    #[rust] threepid: Option<ThreepidInfo>,
+}
+
+impl Widget for ThreepidEntry {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        let Event::Actions(actions) = event else { return };
+        let Some(threepid) = self.threepid.as_ref() else { return };
+        let remove_button = self.view.button(id!(remove_button));
+        for action in actions {
+            if let Some(CredentialsAction::ThreepidOperationFailed(_)) = action.downcast_ref() {
+                remove_button.set_enabled(cx, true);
+            }
+        }
+        if remove_button.clicked(actions) {
+            // The entry will be removed once the updated list of 3PIDs has been fetched.
+            remove_button.set_enabled(cx, false);
+            cx.widget_action(self.widget_uid(), &scope.path, ThreepidEntryAction::Remove(threepid.clone()));
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        // The 3PID info was passed in through Scope via props.
+        let threepid = scope.props.get::<ThreepidInfo>().unwrap();
+        if self.threepid.as_ref() != Some(threepid) {
+            self.threepid = Some(threepid.clone());
+            let address = match threepid.medium {
+                Medium::Msisdn => format!("Phone: +{}", threepid.address),
+                _ => format!("Email: {}", threepid.address),
+            };
+            self.label(id!(address)).set_text(cx, &address);
+            self.view.button(id!(remove_button)).set_enabled(cx, true);
+        }
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+/// Checks that the given passwords can be used to change the user's password.
+fn validate_new_password(current_password: &str, new_password: &str, confirm_password: &str) -> Result<(), &'static str> {
+    if current_password.is_empty() {
+        Err("Please enter your current password.")
+    } else if new_password.is_empty() {
+        Err("Please enter a new password.")
+    } else if new_password != confirm_password {
+        Err("The new passwords don't match.")
+    } else if new_password == current_password {
+        Err("The new password must be different from your current password.")
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns whether the given string looks like an email address.
+///
+/// The homeserver performs the actual validation by sending an email to it.
+fn is_plausible_email(email: &str) -> bool {
+    email.split_once('@').is_some_and(|(local, domain)|
+        !local.is_empty()
+            && !domain.is_empty()
+            && !domain.contains('@')
+            && !email.contains(char::is_whitespace)
+    )
+}
+
+/// Normalizes the given country code and phone number into the form expected by the homeserver:
+/// an uppercase two-letter country code and a phone number consisting of only digits.
+fn parse_phone_number(country: &str, number: &str) -> Result<(String, String), &'static str> {
+    let country = country.trim().to_ascii_uppercase();
+    if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err("Please enter a two-letter country code, e.g., \"US\" or \"GB\".");
+    }
+    let number: String = number.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    if !(4..=15).contains(&number.len()) || !number.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Please enter a valid phone number, using only digits.");
+    }
+    Ok((country, number))
+}
+
+#[cfg(test)]
+mod tests_credentials_settings {
+    use super::*;
+
+    #[test]
+    fn new_password_must_be_confirmed_and_different() {
+        assert!(validate_new_password("", "new", "new").is_err());
+        assert!(validate_new_password("old", "", "").is_err());
+        assert!(validate_new_password("old", "new", "newer").is_err());
+        assert!(validate_new_password("same", "same", "same").is_err());
+        assert!(validate_new_password("old", "new", "new").is_ok());
+    }
+
+    #[test]
+    fn email_addresses() {
+        assert!(is_plausible_email("alice@example.org"));
+        assert!(!is_plausible_email("alice"));
+        assert!(!is_plausible_email("@example.org"));
+        assert!(!is_plausible_email("alice@"));
+        assert!(!is_plausible_email("alice@foo@example.org"));
+        assert!(!is_plausible_email("alice smith@example.org"));
+    }
+
+    #[test]
+    fn phone_numbers_are_normalized() {
+        assert_eq!(
+            parse_phone_number(" us", "(555) 123-4567"),
+            Ok((String::from("US"), String::from("5551234567"))),
+        );
+        assert!(parse_phone_number("USA", "5551234567").is_err());
+        assert!(parse_phone_number("US", "+1 555 123 4567").is_err());
+        assert!(parse_phone_number("US", "12").is_err());
+    }
+}
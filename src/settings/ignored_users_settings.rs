@@ -0,0 +1,213 @@
+//! The ignored users settings section, which lists the users that the current user
+//! has ignored (blocked) and lets them unignore those users.
+
+use std::collections::BTreeSet;
+
+use makepad_widgets::*;
+use matrix_sdk::ruma::OwnedUserId;
+
+use crate::sliding_sync::{get_ignored_users, submit_async_request, MatrixRequest};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    IgnoredUsersNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 3, bottom: 3}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    // A single ignored user in the list of ignored users.
+    IgnoredUserEntry = {{IgnoredUserEntry}} {
+        width: Fill, height: Fit
+        flow: Right,
+        align: {y: 0.5},
+        padding: {left: 5, top: 5, bottom: 5}
+        spacing: 8
+
+        user_id = <Label> {
+            width: Fill, height: Fit
+            draw_text: {
+                wrap: Ellipsis,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <REGULAR_TEXT>{ font_size: 10.5 },
+            }
+            text: ""
+        }
+
+        unignore_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 8,
+            margin: 0,
+            draw_bg: {
+                color: (COLOR_SECONDARY)
+            }
+            draw_icon: {
+                svg_file: (ICON_FORBIDDEN),
+                color: (COLOR_TEXT),
+            }
+            icon_walk: {width: 14, height: 14, margin: 0}
+            draw_text: {
+                color: (COLOR_TEXT),
+                text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+            }
+            text: "Unignore"
+        }
+    }
+
+    // The view containing the list of ignored users.
+    pub IgnoredUsersSettings = {{IgnoredUsersSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Ignored Users"
+        }
+
+        <IgnoredUsersNoticeLabel> {
+            text: "Messages and invites from ignored users are hidden from you."
+        }
+
+        status_label = <IgnoredUsersNoticeLabel> {
+            text: "You haven't ignored any users."
+        }
+
+        ignored_user_list = <FlatList> {
+            width: Fill,
+            height: Fit,
+            spacing: 0.0
+            flow: Down,
+
+            grab_key_focus: true,
+            drag_scrolling: true,
+            scroll_bars: { show_scroll_x: false, show_scroll_y: false },
+
+            ignored_user_entry = <IgnoredUserEntry> { }
+        }
+    }
+}
+
+/// Actions related to the list of ignored users.
+#[derive(Debug)]
+pub enum IgnoredUsersAction {
+    /// The list of ignored users has changed, and now contains the given users.
+    Updated(BTreeSet<OwnedUserId>),
+    /// The request to unignore the given user failed.
+    UnignoreFailed(OwnedUserId),
+}
+
+/// The view containing the list of ignored users.
+#[derive(Live, LiveHook, Widget)]
+pub struct IgnoredUsersSettings {
+    #[deref] view: View,
+
+    #[rust] ignored_users: BTreeSet<OwnedUserId>,
+}
+
+impl Widget for IgnoredUsersSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+        self.widget_match_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        while let Some(subview) = self.view.draw_walk(cx, scope, walk).step() {
+            // Here, we only need to handle drawing the ignored user list.
+            let flat_list_ref = subview.as_flat_list();
+            let Some(mut list) = flat_list_ref.borrow_mut() else {
+                error!("!!! IgnoredUsersSettings::draw_walk(): BUG: expected a FlatList widget, but got something else");
+                continue;
+            };
+            for user_id in &self.ignored_users {
+                let item = list.item(cx, LiveId::from_str(user_id.as_str()), live_id!(ignored_user_entry)).unwrap();
+                // Pass the user ID in through Scope via props.
+                let mut scope = Scope::with_props(user_id);
+                item.draw_all(cx, &mut scope);
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl WidgetMatchEvent for IgnoredUsersSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        for action in actions {
+            if let Some(IgnoredUsersAction::Updated(ignored_users)) = action.downcast_ref() {
+                self.set_ignored_users(cx, ignored_users.clone());
+            }
+        }
+    }
+}
+
+impl IgnoredUsersSettings {
+    fn set_ignored_users(&mut self, cx: &mut Cx, ignored_users: BTreeSet<OwnedUserId>) {
+        self.ignored_users = ignored_users;
+        self.view.label(id!(status_label)).set_visible(cx, self.ignored_users.is_empty());
+        self.view.redraw(cx);
+    }
+
+    /// Populates this view with the current list of ignored users.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        self.set_ignored_users(cx, get_ignored_users());
+    }
+}
+
+impl IgnoredUsersSettingsRef {
+    /// See [`IgnoredUsersSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
+
+/// A single ignored user in the list of ignored users.
+#[derive(Live, LiveHook, Widget)]
+pub struct IgnoredUserEntry {
+    #[deref] view: View,
+
+    #[rust] user_id: Option<OwnedUserId>,
+}
+
+impl Widget for IgnoredUserEntry {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        let Event::Actions(actions) = event else { return };
+        let Some(user_id) = self.user_id.as_ref() else { return };
+        let unignore_button = self.view.button(id!(unignore_button));
+        for action in actions {
+            if let Some(IgnoredUsersAction::UnignoreFailed(failed_user_id)) = action.downcast_ref() {
+                if failed_user_id == user_id {
+                    unignore_button.set_enabled(cx, true);
+                }
+            }
+        }
+        if unignore_button.clicked(actions) {
+            // The entry will be removed once the updated ignore list has been synced.
+            unignore_button.set_enabled(cx, false);
+            submit_async_request(MatrixRequest::UnignoreUser { user_id: user_id.clone() });
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        // The user ID was passed in through Scope via props.
+        let user_id = scope.props.get::<OwnedUserId>().unwrap();
+        if self.user_id.as_ref() != Some(user_id) {
+            self.user_id = Some(user_id.clone());
+            self.label(id!(user_id)).set_text(cx, user_id.as_str());
+            self.view.button(id!(unignore_button)).set_enabled(cx, true);
+        }
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
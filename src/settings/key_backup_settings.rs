@@ -0,0 +1,492 @@
+//! The key backup settings section, which lets the user set up server-side key backup
+//! and recovery, restore from an existing backup, or reset their backup.
+//!
+//! Key backup stores the user's room keys on the homeserver, encrypted with a recovery key,
+//! such that encrypted history can be decrypted on a new device without needing
+//! another one of the user's sessions to share its keys.
+
+use makepad_widgets::*;
+use matrix_sdk::encryption::recovery::RecoveryState;
+
+use crate::sliding_sync::{submit_async_request, MatrixRequest};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    KeyBackupNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 5, bottom: 5}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    KeyBackupButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: 10,
+        margin: {left: 5},
+        draw_bg: {
+            color: (COLOR_ACTIVE_PRIMARY),
+            border_radius: 5
+        }
+        draw_icon: {
+            svg_file: (ICON_CHECKMARK)
+            color: (COLOR_PRIMARY),
+        }
+        icon_walk: {width: 16, height: 16, margin: 0}
+        draw_text: {
+            color: (COLOR_PRIMARY),
+        }
+    }
+
+    KeyBackupDangerButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: 10,
+        margin: {left: 5},
+        draw_bg: {
+            color: (COLOR_BG_DANGER_RED)
+            border_color: (COLOR_FG_DANGER_RED)
+        }
+        draw_icon: {
+            svg_file: (ICON_TRASH),
+            color: (COLOR_FG_DANGER_RED),
+        }
+        icon_walk: {width: 16, height: 16, margin: 0}
+        draw_text: {
+            color: (COLOR_FG_DANGER_RED),
+        }
+    }
+
+    // The view containing the user's key backup and recovery settings.
+    pub KeyBackupSettings = {{KeyBackupSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Key Backup & Recovery"
+        }
+
+        status_label = <Label> {
+            width: Fill, height: Fit
+            margin: {left: 5, top: 5}
+            draw_text: {
+                wrap: Word,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <REGULAR_TEXT>{ font_size: 11 },
+            }
+            text: "Checking the status of your key backup..."
+        }
+
+        progress_label = <KeyBackupNoticeLabel> {
+            visible: false
+        }
+
+        // Shown once after a new recovery key has been generated.
+        recovery_key_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            <SubsectionLabel> {
+                text: "Your Recovery Key:"
+            }
+
+            <KeyBackupNoticeLabel> {
+                text: "Save this recovery key somewhere safe, such as a password manager. You'll need it to access your encrypted messages on a new device. It won't be shown again."
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: Right,
+                align: {y: 0.5}
+                spacing: 10
+
+                copy_recovery_key_button = <RobrixIconButton> {
+                    margin: {left: 5}
+                    padding: 12,
+                    spacing: 0,
+                    draw_bg: {
+                        color: (COLOR_SECONDARY)
+                    }
+                    draw_icon: {
+                        svg_file: (ICON_COPY)
+                    }
+                    icon_walk: {width: 16, height: 16, margin: {right: -2} }
+                }
+
+                recovery_key_label = <Label> {
+                    width: Fill, height: Fit
+                    flow: RightWrap,
+                    draw_text: {
+                        wrap: Word,
+                        color: (MESSAGE_TEXT_COLOR),
+                        text_style: <MESSAGE_TEXT_STYLE>{ font_size: 11 },
+                    }
+                    text: ""
+                }
+            }
+
+            saved_recovery_key_button = <KeyBackupButton> {
+                margin: {left: 5, top: 10}
+                text: "I've Saved My Recovery Key"
+            }
+        }
+
+        // Shown when key backup and recovery have not been set up yet.
+        setup_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            <SubsectionLabel> {
+                text: "Set up key backup:"
+            }
+
+            <KeyBackupNoticeLabel> {
+                text: "A recovery key will be generated for you. You can optionally also set a passphrase that can be used instead of the recovery key."
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: RightWrap,
+                align: {y: 0.5},
+                spacing: 10
+
+                backup_passphrase_input = <SimpleTextInput> {
+                    margin: {top: 3, left: 5, right: 5, bottom: 3},
+                    width: 216, height: Fit
+                    is_password: true,
+                    empty_text: "Passphrase (optional)"
+                }
+
+                enable_backup_button = <KeyBackupButton> {
+                    text: "Set Up Key Backup"
+                }
+            }
+        }
+
+        // Shown when a backup exists but this session can't yet access it.
+        recover_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            <SubsectionLabel> {
+                text: "Restore from your key backup:"
+            }
+
+            <KeyBackupNoticeLabel> {
+                text: "Enter your recovery key or passphrase to decrypt your message history on this device."
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: RightWrap,
+                align: {y: 0.5},
+                spacing: 10
+
+                recovery_key_input = <SimpleTextInput> {
+                    margin: {top: 3, left: 5, right: 5, bottom: 3},
+                    width: 216, height: Fit
+                    is_password: true,
+                    empty_text: "Recovery key or passphrase"
+                }
+
+                recover_button = <KeyBackupButton> {
+                    draw_icon: {
+                        svg_file: (ICON_IMPORT)
+                    }
+                    text: "Restore"
+                }
+            }
+        }
+
+        // Shown when a backup exists, whether or not this session can access it.
+        reset_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            <SubsectionLabel> {
+                text: "Lost your recovery key?"
+            }
+
+            reset_backup_button = <KeyBackupDangerButton> {
+                text: "Reset Key Backup"
+            }
+
+            reset_confirm_view = <View> {
+                visible: false
+                width: Fill, height: Fit
+                flow: Down
+
+                <KeyBackupNoticeLabel> {
+                    text: "Resetting deletes your existing key backup and recovery key. Messages that can only be decrypted using the old backup will become permanently unreadable."
+                }
+
+                <View> {
+                    width: Fill, height: Fit
+                    flow: RightWrap,
+                    align: {y: 0.5},
+                    spacing: 10
+
+                    confirm_reset_button = <KeyBackupDangerButton> {
+                        text: "Delete Backup and Reset"
+                    }
+
+                    cancel_reset_button = <RobrixIconButton> {
+                        width: Fit, height: Fit,
+                        padding: 10,
+                        margin: {left: 5},
+                        draw_bg: {
+                            color: (COLOR_SECONDARY)
+                        }
+                        draw_icon: {
+                            svg_file: (ICON_FORBIDDEN),
+                            color: (COLOR_TEXT)
+                        }
+                        icon_walk: {width: 16, height: 16, margin: 0}
+                        draw_text: {
+                            color: (COLOR_TEXT),
+                        }
+                        text: "Cancel"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The state of the user's key backup and recovery, as fetched from the homeserver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyBackupStatus {
+    /// Whether recovery (secret storage plus key backup) is set up and usable by this session.
+    pub recovery_state: RecoveryState,
+    /// Whether this session is currently uploading its room keys to the backup.
+    pub backup_enabled: bool,
+    /// Whether a key backup exists on the homeserver.
+    pub backup_exists_on_server: bool,
+}
+
+/// The progress of setting up or resetting key backup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyBackupProgress {
+    /// A new backup is being created on the homeserver.
+    CreatingBackup,
+    /// A new recovery key is being created and stored in secret storage.
+    CreatingRecoveryKey,
+    /// Room keys are being uploaded to the backup.
+    BackingUp { backed_up: usize, total: usize },
+    /// Uploading room keys failed; the upload will be retried.
+    UploadError,
+}
+
+/// Actions related to the user's key backup and recovery.
+#[derive(Debug)]
+pub enum KeyBackupAction {
+    /// The key backup status was fetched from the homeserver, or it has changed.
+    StatusFetched(KeyBackupStatus),
+    /// Setting up or resetting key backup has progressed.
+    Progress(KeyBackupProgress),
+    /// A new recovery key was generated after setting up or resetting key backup.
+    RecoveryKeyGenerated(String),
+    /// This session's secrets and room keys were successfully restored from the backup.
+    Recovered,
+    /// Setting up, restoring, or resetting key backup failed.
+    Failed(String),
+}
+
+/// The view containing the user's key backup and recovery settings.
+#[derive(Live, LiveHook, Widget)]
+pub struct KeyBackupSettings {
+    #[deref] view: View,
+
+    /// The most recently fetched status, if any.
+    #[rust] status: Option<KeyBackupStatus>,
+    /// The newly-generated recovery key, which is shown until the user confirms they saved it.
+    #[rust] recovery_key: Option<String>,
+    /// Whether a key backup request is in progress.
+    #[rust] is_busy: bool,
+}
+
+impl Widget for KeyBackupSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.match_event(cx, event);
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+impl MatchEvent for KeyBackupSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
+        for action in actions {
+            match action.downcast_ref() {
+                Some(KeyBackupAction::StatusFetched(status)) => {
+                    self.status = Some(*status);
+                    self.update_views(cx);
+                }
+                Some(KeyBackupAction::Progress(progress)) => {
+                    let text = match progress {
+                        KeyBackupProgress::CreatingBackup => String::from("Creating a new key backup..."),
+                        KeyBackupProgress::CreatingRecoveryKey => String::from("Creating your recovery key..."),
+                        KeyBackupProgress::BackingUp { backed_up, total } => format!("Backing up your keys: {backed_up} of {total}..."),
+                        KeyBackupProgress::UploadError => String::from("Failed to upload some keys. Retrying..."),
+                    };
+                    let progress_label = self.view.label(id!(progress_label));
+                    progress_label.set_text(cx, &text);
+                    progress_label.set_visible(cx, true);
+                    self.view.redraw(cx);
+                }
+                Some(KeyBackupAction::RecoveryKeyGenerated(recovery_key)) => {
+                    self.is_busy = false;
+                    self.recovery_key = Some(recovery_key.clone());
+                    self.view.label(id!(recovery_key_label)).set_text(cx, recovery_key);
+                    self.update_views(cx);
+                }
+                Some(KeyBackupAction::Recovered) => {
+                    self.is_busy = false;
+                    self.view.text_input(id!(recovery_key_input)).set_text(cx, "");
+                    self.update_views(cx);
+                }
+                Some(KeyBackupAction::Failed(_error)) => {
+                    // The error itself is shown in a popup notification.
+                    self.is_busy = false;
+                    self.update_views(cx);
+                }
+                None => { }
+            }
+        }
+
+        if self.view.button(id!(copy_recovery_key_button)).clicked(actions) {
+            if let Some(recovery_key) = self.recovery_key.as_deref() {
+                cx.copy_to_clipboard(recovery_key);
+            }
+        }
+        if self.view.button(id!(saved_recovery_key_button)).clicked(actions) {
+            self.recovery_key = None;
+            self.view.label(id!(recovery_key_label)).set_text(cx, "");
+            self.update_views(cx);
+        }
+
+        if self.view.button(id!(reset_backup_button)).clicked(actions) {
+            self.view.view(id!(reset_confirm_view)).set_visible(cx, true);
+            self.view.redraw(cx);
+        }
+        if self.view.button(id!(cancel_reset_button)).clicked(actions) {
+            self.view.view(id!(reset_confirm_view)).set_visible(cx, false);
+            self.view.redraw(cx);
+        }
+
+        if self.is_busy {
+            return;
+        }
+
+        let passphrase_input = self.view.text_input(id!(backup_passphrase_input));
+        if self.view.button(id!(enable_backup_button)).clicked(actions)
+            || passphrase_input.returned(actions).is_some()
+        {
+            let passphrase = passphrase_input.text();
+            passphrase_input.set_text(cx, "");
+            self.start_request(cx, MatrixRequest::EnableKeyBackup {
+                passphrase: (!passphrase.is_empty()).then_some(passphrase),
+                reset: false,
+            });
+        }
+
+        let recovery_key_input = self.view.text_input(id!(recovery_key_input));
+        if self.view.button(id!(recover_button)).clicked(actions)
+            || recovery_key_input.returned(actions).is_some()
+        {
+            let recovery_key = recovery_key_input.text().trim().to_string();
+            if !recovery_key.is_empty() {
+                self.start_request(cx, MatrixRequest::RecoverKeyBackup { recovery_key });
+            }
+        }
+
+        if self.view.button(id!(confirm_reset_button)).clicked(actions) {
+            self.view.view(id!(reset_confirm_view)).set_visible(cx, false);
+            self.start_request(cx, MatrixRequest::EnableKeyBackup { passphrase: None, reset: true });
+        }
+    }
+}
+
+impl KeyBackupSettings {
+    /// Submits the given key backup request and marks this view as busy until it completes.
+    fn start_request(&mut self, cx: &mut Cx, request: MatrixRequest) {
+        self.is_busy = true;
+        let progress_label = self.view.label(id!(progress_label));
+        progress_label.set_text(cx, "Working...");
+        progress_label.set_visible(cx, true);
+        submit_async_request(request);
+        self.update_views(cx);
+    }
+
+    /// Shows the subviews that are relevant to the last-known key backup status.
+    fn update_views(&mut self, cx: &mut Cx) {
+        let status_text = match self.status {
+            None => "Checking the status of your key backup...",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Enabled, backup_enabled: true, .. }) =>
+                "Key backup is set up. Your keys are being backed up securely.",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Enabled, .. }) =>
+                "Recovery is set up, but this session is not currently backing up its keys.",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Incomplete, .. }) =>
+                "A key backup exists, but this session can't access it yet. Restore it using your recovery key or passphrase.",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Disabled, backup_exists_on_server: true, .. }) =>
+                "A key backup exists, but recovery is not set up. Reset your key backup to set up recovery.",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Disabled, .. }) =>
+                "Key backup is not set up. Without it, you won't be able to read your encrypted message history on new devices.",
+            Some(KeyBackupStatus { recovery_state: RecoveryState::Unknown, .. }) =>
+                "Checking the status of your key backup...",
+        };
+        self.view.label(id!(status_label)).set_text(cx, status_text);
+
+        if !self.is_busy {
+            self.view.label(id!(progress_label)).set_visible(cx, false);
+        }
+
+        let showing_recovery_key = self.recovery_key.is_some();
+        let (can_set_up, can_recover, can_reset) = match self.status {
+            Some(status) if !showing_recovery_key => (
+                status.recovery_state == RecoveryState::Disabled && !status.backup_exists_on_server,
+                status.recovery_state == RecoveryState::Incomplete,
+                status.backup_exists_on_server || status.recovery_state != RecoveryState::Disabled,
+            ),
+            _ => (false, false, false),
+        };
+        self.view.view(id!(recovery_key_view)).set_visible(cx, showing_recovery_key);
+        self.view.view(id!(setup_view)).set_visible(cx, can_set_up);
+        self.view.view(id!(recover_view)).set_visible(cx, can_recover);
+        self.view.view(id!(reset_view)).set_visible(cx, can_reset);
+        for button_id in [id!(enable_backup_button), id!(recover_button), id!(confirm_reset_button)] {
+            self.view.button(button_id).set_enabled(cx, !self.is_busy);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Fetches the latest key backup status from the homeserver
+    /// in order to populate this view.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        submit_async_request(MatrixRequest::GetKeyBackupStatus);
+        self.update_views(cx);
+    }
+}
+
+impl KeyBackupSettingsRef {
+    /// See [`KeyBackupSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
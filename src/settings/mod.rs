@@ -2,9 +2,23 @@ use makepad_widgets::Cx;
 
 pub mod settings_screen;
 pub mod account_settings;
+pub mod credentials_settings;
+pub mod key_backup_settings;
+pub mod room_keys_settings;
+pub mod sessions_settings;
+pub mod ignored_users_settings;
+pub mod network_settings;
+pub mod notification_settings;
 
 pub fn live_design(cx: &mut Cx) {
+    room_keys_settings::live_design(cx);
     account_settings::live_design(cx);
+    credentials_settings::live_design(cx);
+    notification_settings::live_design(cx);
+    key_backup_settings::live_design(cx);
+    sessions_settings::live_design(cx);
+    ignored_users_settings::live_design(cx);
+    network_settings::live_design(cx);
     settings_screen::live_design(cx);
 }
 
@@ -13,6 +27,8 @@ pub fn live_design(cx: &mut Cx) {
 pub enum SettingsAction {
     /// Action to open the settings screen.
     OpenSettings,
+    /// Action to open the settings screen and scroll to the given section.
+    OpenSettingsSection(SettingsSection),
     /// Action to close the settings screen.
     CloseSettings,
     // TODO: add specific actions for settings changes,
@@ -20,3 +36,10 @@ pub enum SettingsAction {
     //       that they need to respond to.
     //       Examples: changed avatar, changed display name, etc.
 }
+
+/// A section of the settings screen that other parts of the app can link to directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsSection {
+    /// The key backup and recovery settings.
+    KeyBackup,
+}
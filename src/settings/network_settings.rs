@@ -0,0 +1,296 @@
+//! The network settings section, which lets the user configure the proxies
+//! used for all of the current account's network requests, and its sync mode.
+
+use makepad_widgets::*;
+
+use crate::{
+    persistence::{ProxySettings, SlidingSyncVersion},
+    shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind},
+    sliding_sync::{submit_async_request, MatrixRequest},
+};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    NetworkNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 5, bottom: 5}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    ProxyTextInput = <SimpleTextInput> {
+        margin: {top: 3, left: 5, right: 5, bottom: 3},
+        width: 300, height: Fit
+    }
+
+    // The view containing the user's network (proxy) settings.
+    pub NetworkSettings = {{NetworkSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Network Proxy"
+        }
+
+        <NetworkNoticeLabel> {
+            text: "These proxies are used for this account's connection to its homeserver, link previews, and TSP requests. Changes take effect the next time Robrix starts."
+        }
+
+        <SubsectionLabel> {
+            text: "Proxy URLs:"
+        }
+
+        http_proxy_input = <ProxyTextInput> {
+            empty_text: "HTTP proxy, e.g., http://proxy.example.org:8080"
+        }
+        https_proxy_input = <ProxyTextInput> {
+            empty_text: "HTTPS proxy, e.g., http://proxy.example.org:8080"
+        }
+        socks5_proxy_input = <ProxyTextInput> {
+            empty_text: "SOCKS5 proxy, e.g., socks5://127.0.0.1:1080"
+        }
+
+        <SubsectionLabel> {
+            text: "Authentication (optional):"
+        }
+
+        proxy_username_input = <ProxyTextInput> {
+            empty_text: "Username"
+        }
+        proxy_password_input = <ProxyTextInput> {
+            is_password: true,
+            empty_text: "Password"
+        }
+
+        <SubsectionLabel> {
+            text: "Bypass the proxy for:"
+        }
+
+        no_proxy_input = <ProxyTextInput> {
+            empty_text: "e.g., localhost, .example.org, 192.168.0.0/16"
+        }
+
+        save_proxy_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 10,
+            margin: {left: 5, top: 10},
+            draw_bg: {
+                color: (COLOR_ACTIVE_PRIMARY),
+                border_radius: 5
+            }
+            draw_icon: {
+                svg_file: (ICON_CHECKMARK)
+                color: (COLOR_PRIMARY),
+            }
+            icon_walk: {width: 16, height: 16, margin: 0}
+            draw_text: {
+                color: (COLOR_PRIMARY),
+            }
+            text: "Save Proxy Settings"
+        }
+
+        <TitleLabel> {
+            margin: {top: 20}
+            text: "Sync Mode"
+        }
+
+        sync_mode_label = <NetworkNoticeLabel> {
+            text: "Loading the current sync mode..."
+        }
+
+        switch_sync_mode_button = <RobrixIconButton> {
+            visible: false
+            width: Fit, height: Fit,
+            padding: 10,
+            margin: {left: 5, top: 5},
+            draw_bg: {
+                color: (COLOR_SECONDARY),
+                border_radius: 5
+            }
+            draw_icon: {
+                svg_file: (ICON_CLOUD_CHECKMARK)
+                color: (COLOR_TEXT),
+            }
+            icon_walk: {width: 16, height: 16, margin: 0}
+            draw_text: {
+                color: (COLOR_TEXT),
+            }
+            text: ""
+        }
+    }
+}
+
+/// Actions related to the current account's network (proxy) settings.
+#[derive(Debug)]
+pub enum NetworkSettingsAction {
+    /// The current account's saved proxy settings were loaded.
+    Loaded(ProxySettings),
+    /// The new proxy settings were saved.
+    Saved,
+    /// Saving the new proxy settings failed.
+    SaveFailed,
+    /// The current account's sync mode was loaded or changed.
+    SyncModeLoaded {
+        sync_mode: SlidingSyncVersion,
+        /// Whether the homeserver supports native sliding sync, if known.
+        supports_native_sliding_sync: Option<bool>,
+    },
+}
+
+/// The view containing the user's network (proxy) settings.
+#[derive(Live, LiveHook, Widget)]
+pub struct NetworkSettings {
+    #[deref] view: View,
+
+    /// Whether a request to save the proxy settings is in progress.
+    #[rust] is_saving: bool,
+    /// The current account's sync mode, if known.
+    #[rust] sync_mode: Option<SlidingSyncVersion>,
+}
+
+impl Widget for NetworkSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.match_event(cx, event);
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+impl MatchEvent for NetworkSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
+        for action in actions {
+            match action.downcast_ref() {
+                Some(NetworkSettingsAction::Loaded(proxy)) => self.set_proxy_settings(cx, proxy),
+                Some(NetworkSettingsAction::Saved | NetworkSettingsAction::SaveFailed) => {
+                    // The result itself is shown in a popup notification.
+                    self.is_saving = false;
+                    self.view.button(id!(save_proxy_button)).set_enabled(cx, true);
+                }
+                Some(NetworkSettingsAction::SyncModeLoaded { sync_mode, supports_native_sliding_sync }) => {
+                    self.set_sync_mode(cx, *sync_mode, *supports_native_sliding_sync);
+                }
+                None => { }
+            }
+        }
+
+        let switch_sync_mode_button = self.view.button(id!(switch_sync_mode_button));
+        if let (true, Some(sync_mode)) = (switch_sync_mode_button.clicked(actions), self.sync_mode) {
+            switch_sync_mode_button.set_enabled(cx, false);
+            submit_async_request(MatrixRequest::SetSyncMode {
+                sync_mode: match sync_mode {
+                    SlidingSyncVersion::Native => SlidingSyncVersion::None,
+                    SlidingSyncVersion::None => SlidingSyncVersion::Native,
+                },
+            });
+        }
+
+        if self.is_saving || !self.view.button(id!(save_proxy_button)).clicked(actions) {
+            return;
+        }
+        let proxy = self.proxy_settings_from_inputs();
+        if let Err(error) = proxy.validate() {
+            enqueue_popup_notification(PopupItem {
+                message: error,
+                kind: PopupKind::Error,
+                auto_dismissal_duration: None,
+            });
+            return;
+        }
+        self.is_saving = true;
+        self.view.button(id!(save_proxy_button)).set_enabled(cx, false);
+        submit_async_request(MatrixRequest::SaveProxySettings { proxy });
+    }
+}
+
+impl NetworkSettings {
+    /// Returns the proxy settings currently entered into this view's text inputs.
+    fn proxy_settings_from_inputs(&self) -> ProxySettings {
+        let input = |id: &[LiveId]| {
+            let text = self.view.text_input(id).text();
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_owned())
+        };
+        ProxySettings {
+            http: input(id!(http_proxy_input)),
+            https: input(id!(https_proxy_input)),
+            socks5: input(id!(socks5_proxy_input)),
+            username: input(id!(proxy_username_input)),
+            password: input(id!(proxy_password_input)),
+            no_proxy: input(id!(no_proxy_input)).unwrap_or_default(),
+        }
+    }
+
+    /// Sets this view's text inputs to the given proxy settings.
+    fn set_proxy_settings(&mut self, cx: &mut Cx, proxy: &ProxySettings) {
+        let inputs: [(&[LiveId], &str); 6] = [
+            (id!(http_proxy_input), proxy.http.as_deref().unwrap_or_default()),
+            (id!(https_proxy_input), proxy.https.as_deref().unwrap_or_default()),
+            (id!(socks5_proxy_input), proxy.socks5.as_deref().unwrap_or_default()),
+            (id!(proxy_username_input), proxy.username.as_deref().unwrap_or_default()),
+            (id!(proxy_password_input), proxy.password.as_deref().unwrap_or_default()),
+            (id!(no_proxy_input), &proxy.no_proxy),
+        ];
+        for (id, text) in inputs {
+            self.view.text_input(id).set_text(cx, text);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Shows the given sync mode, and offers to switch to the other mode if possible.
+    fn set_sync_mode(&mut self, cx: &mut Cx, sync_mode: SlidingSyncVersion, supports_native_sliding_sync: Option<bool>) {
+        self.sync_mode = Some(sync_mode);
+        let (description, switch_text) = match (sync_mode, supports_native_sliding_sync) {
+            (SlidingSyncVersion::Native, _) => (
+                "Robrix is using native sliding sync, which quickly loads your rooms one page at a time.",
+                Some("Switch to Classic Sync"),
+            ),
+            (SlidingSyncVersion::None, Some(false)) => (
+                "Robrix is using classic sync, because your homeserver does not support native sliding sync. \
+                All of your rooms are loaded at once, which may be slow for accounts with many rooms.",
+                None,
+            ),
+            (SlidingSyncVersion::None, _) => (
+                "Robrix is using classic sync, which loads all of your rooms at once.",
+                Some("Switch to Native Sliding Sync"),
+            ),
+        };
+        self.view.label(id!(sync_mode_label)).set_text(cx, description);
+        let switch_sync_mode_button = self.view.button(id!(switch_sync_mode_button));
+        switch_sync_mode_button.set_visible(cx, switch_text.is_some());
+        switch_sync_mode_button.set_enabled(cx, true);
+        if let Some(switch_text) = switch_text {
+            switch_sync_mode_button.set_text(cx, switch_text);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Populates this view with the current account's saved proxy settings and sync mode.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        self.is_saving = false;
+        self.view.button(id!(save_proxy_button)).set_enabled(cx, true);
+        submit_async_request(MatrixRequest::GetProxySettings);
+        submit_async_request(MatrixRequest::GetSyncMode);
+    }
+}
+
+impl NetworkSettingsRef {
+    /// See [`NetworkSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
@@ -0,0 +1,495 @@
+//! The notification settings section, which lets the user choose the default
+//! notification mode for each kind of room.
+//!
+//! These defaults are backed by the user's push rules on the homeserver,
+//! so they apply to all of the user's clients, not just Robrix.
+//! The exception is muting a kind of room by default, which push rules cannot express:
+//! instead, Robrix mutes each room of that kind that doesn't have its own notification mode.
+//! Per-room notification modes are set from the room context menu.
+//!
+//! This section also lets the user manage their notification keywords
+//! and choose whether mentions of their display name or user ID should notify them.
+
+use makepad_widgets::*;
+use matrix_sdk::notification_settings::RoomNotificationMode;
+use serde::{Deserialize, Serialize};
+
+use crate::sliding_sync::{submit_async_request, MatrixRequest};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    NotificationModeCheckBox = <CheckBoxFlat> {
+        margin: {left: 5, bottom: 5}
+        draw_text: {
+            color: (MESSAGE_TEXT_COLOR),
+            text_style: <REGULAR_TEXT>{ font_size: 11 },
+        }
+        active: true,
+    }
+
+    NotificationCategoryLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 3}
+        draw_text: {
+            color: (MESSAGE_TEXT_COLOR),
+            text_style: <REGULAR_TEXT>{ font_size: 11 },
+        }
+    }
+
+    NotificationModeRadioButton = <RadioButtonFlat> {
+        draw_text: {
+            color: (MESSAGE_TEXT_COLOR),
+            text_style: <REGULAR_TEXT>{ font_size: 10.5 },
+        }
+    }
+
+    // The choice of default notification mode for one kind of room.
+    // The order of these buttons must match `DEFAULT_MODE_OPTIONS`.
+    NotificationModeRadioButtons = <View> {
+        width: Fill, height: Fit
+        flow: RightWrap,
+        spacing: 15,
+        margin: {left: 5, top: 5, bottom: 8}
+
+        all_messages = <NotificationModeRadioButton> {
+            text: "All messages"
+            animator: { active = { default: on } }
+        }
+        mentions_and_keywords = <NotificationModeRadioButton> {
+            text: "Mentions & keywords"
+        }
+        mute = <NotificationModeRadioButton> {
+            text: "Mute"
+        }
+    }
+
+    // An entry in the list of notification keywords.
+    KeywordEntry = {{KeywordEntry}} {
+        width: Fill, height: Fit
+        flow: Right,
+        align: {y: 0.5},
+        padding: {left: 5, top: 2, bottom: 2}
+        spacing: 10
+
+        keyword_label = <Label> {
+            width: Fill, height: Fit
+            flow: Right,
+            draw_text: {
+                wrap: Ellipsis,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <REGULAR_TEXT>{ font_size: 11 },
+            }
+            text: ""
+        }
+
+        remove_keyword_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 8,
+            spacing: 0,
+            draw_bg: {
+                color: (COLOR_BG_DANGER_RED)
+                border_color: (COLOR_FG_DANGER_RED)
+            }
+            draw_icon: {
+                svg_file: (ICON_CLOSE),
+                color: (COLOR_FG_DANGER_RED),
+            }
+            icon_walk: {width: 12, height: 12, margin: 0}
+        }
+    }
+
+    // The view containing the user's default notification settings.
+    pub NotificationSettings = {{NotificationSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Notification Settings"
+        }
+
+        <SubsectionLabel> {
+            text: "By default, notify me about:"
+        }
+
+        <NotificationCategoryLabel> { text: "Direct messages" }
+        direct_rooms_modes = <NotificationModeRadioButtons> { }
+        <NotificationCategoryLabel> { text: "Group rooms" }
+        group_rooms_modes = <NotificationModeRadioButtons> { }
+        <NotificationCategoryLabel> { text: "Encrypted group rooms" }
+        encrypted_group_rooms_modes = <NotificationModeRadioButtons> { }
+
+        <Label> {
+            width: Fill, height: Fit
+            margin: {left: 5, top: 5}
+            draw_text: {
+                wrap: Word,
+                color: (COLOR_MESSAGE_NOTICE_TEXT),
+                text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+            }
+            text: "These defaults can be overridden for each room from its context menu. Muting a kind of room by default only applies to rooms that don't have their own notification setting."
+        }
+
+        <SubsectionLabel> {
+            text: "Notify me about mentions of:"
+        }
+
+        display_name_mentions_checkbox = <NotificationModeCheckBox> {
+            text: "My display name"
+        }
+        user_id_mentions_checkbox = <NotificationModeCheckBox> {
+            text: "My user ID"
+        }
+
+        <SubsectionLabel> {
+            text: "Notify me about messages containing these keywords:"
+        }
+
+        no_keywords_label = <Label> {
+            width: Fill, height: Fit
+            margin: {left: 5, bottom: 5}
+            draw_text: {
+                wrap: Word,
+                color: (COLOR_MESSAGE_NOTICE_TEXT),
+                text_style: <REGULAR_TEXT>{ font_size: 10.5 },
+            }
+            text: "You haven't added any keywords yet."
+        }
+
+        keyword_list = <FlatList> {
+            width: Fill,
+            height: Fit,
+            spacing: 0.0
+            flow: Down,
+
+            grab_key_focus: true,
+            drag_scrolling: true,
+            scroll_bars: { show_scroll_x: false, show_scroll_y: false },
+
+            keyword_entry = <KeywordEntry> { }
+        }
+
+        <View> {
+            width: Fill, height: Fit
+            flow: RightWrap,
+            align: {y: 0.5},
+            spacing: 10
+
+            keyword_input = <SimpleTextInput> {
+                margin: {top: 3, left: 5, right: 5, bottom: 3},
+                width: 216, height: Fit
+                empty_text: "Add a keyword..."
+            }
+
+            add_keyword_button = <RobrixIconButton> {
+                width: Fit, height: Fit,
+                padding: 10,
+                margin: {left: 5},
+
+                draw_bg: {
+                    color: (COLOR_ACTIVE_PRIMARY),
+                    border_radius: 5
+                }
+                draw_icon: {
+                    svg_file: (ICON_ADD)
+                    color: (COLOR_PRIMARY),
+                }
+                icon_walk: {width: 16, height: 16, margin: 0}
+                draw_text: {
+                    color: (COLOR_PRIMARY),
+                }
+                text: "Add Keyword"
+            }
+        }
+    }
+
+}
+
+/// The default notification modes that the user can choose from,
+/// in the same order as the radio buttons in each `NotificationModeRadioButtons` view.
+const DEFAULT_MODE_OPTIONS: [RoomNotificationMode; 3] = [
+    RoomNotificationMode::AllMessages,
+    RoomNotificationMode::MentionsAndKeywordsOnly,
+    RoomNotificationMode::Mute,
+];
+
+/// The kinds of rooms that have their own default notification mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DefaultNotificationCategory {
+    /// One-to-one rooms, both encrypted and unencrypted.
+    DirectRooms,
+    /// Unencrypted rooms with more than two members.
+    GroupRooms,
+    /// Encrypted rooms with more than two members.
+    EncryptedGroupRooms,
+}
+
+impl DefaultNotificationCategory {
+    pub const ALL: [Self; 3] = [Self::DirectRooms, Self::GroupRooms, Self::EncryptedGroupRooms];
+
+    /// Returns the category of a room with the given number of joined members and encryption state,
+    /// using the same criteria as the homeserver's default push rules.
+    pub fn of_room(joined_members_count: u64, is_encrypted: bool) -> Self {
+        match (joined_members_count == 2, is_encrypted) {
+            (true, _) => Self::DirectRooms,
+            (false, false) => Self::GroupRooms,
+            (false, true) => Self::EncryptedGroupRooms,
+        }
+    }
+}
+
+/// The user's default notification mode for each [`DefaultNotificationCategory`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DefaultNotificationModes {
+    pub direct_rooms: RoomNotificationMode,
+    pub group_rooms: RoomNotificationMode,
+    pub encrypted_group_rooms: RoomNotificationMode,
+}
+
+impl DefaultNotificationModes {
+    /// Returns the default mode for the given category.
+    pub fn get(&self, category: DefaultNotificationCategory) -> RoomNotificationMode {
+        match category {
+            DefaultNotificationCategory::DirectRooms => self.direct_rooms,
+            DefaultNotificationCategory::GroupRooms => self.group_rooms,
+            DefaultNotificationCategory::EncryptedGroupRooms => self.encrypted_group_rooms,
+        }
+    }
+
+    /// Returns a mutable reference to the default mode for the given category.
+    pub fn get_mut(&mut self, category: DefaultNotificationCategory) -> &mut RoomNotificationMode {
+        match category {
+            DefaultNotificationCategory::DirectRooms => &mut self.direct_rooms,
+            DefaultNotificationCategory::GroupRooms => &mut self.group_rooms,
+            DefaultNotificationCategory::EncryptedGroupRooms => &mut self.encrypted_group_rooms,
+        }
+    }
+}
+
+/// The kinds of mentions that can be enabled or disabled via predefined push rules.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MentionRule {
+    /// Messages that contain the user's display name.
+    DisplayName,
+    /// Messages that mention the user's ID, either explicitly or in their plaintext body.
+    UserId,
+}
+
+/// The user's notification keywords and mention settings.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeywordSettings {
+    /// The keywords that the user wants to be notified about, sorted alphabetically.
+    pub keywords: Vec<String>,
+    /// Whether messages containing the user's display name trigger a notification.
+    pub display_name_mentions: bool,
+    /// Whether messages mentioning the user's ID trigger a notification.
+    pub user_id_mentions: bool,
+}
+
+/// Actions related to the user's notification settings.
+#[derive(Debug)]
+pub enum NotificationSettingsAction {
+    /// The default notification modes were fetched from (or changed on) the homeserver.
+    DefaultModesFetched(DefaultNotificationModes),
+    /// The keyword and mention settings were fetched from (or changed on) the homeserver.
+    KeywordSettingsFetched(KeywordSettings),
+    /// Setting the default notification mode for the given category failed.
+    SetDefaultModeFailed(DefaultNotificationCategory),
+}
+
+/// The view containing the user's default notification settings.
+#[derive(Live, LiveHook, Widget)]
+pub struct NotificationSettings {
+    #[deref] view: View,
+
+    /// The most recently fetched default modes, if any.
+    #[rust] default_modes: Option<DefaultNotificationModes>,
+    /// The most recently fetched keyword and mention settings, if any.
+    #[rust] keyword_settings: Option<KeywordSettings>,
+}
+
+impl Widget for NotificationSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.match_event(cx, event);
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        let keywords = self.keyword_settings.as_ref().map(|ks| ks.keywords.as_slice()).unwrap_or_default();
+        self.view.label(id!(no_keywords_label)).set_visible(cx, keywords.is_empty());
+
+        while let Some(subview) = self.view.draw_walk(cx, scope, walk).step() {
+            // Here, we only need to handle drawing the keyword list.
+            let flat_list_ref = subview.as_flat_list();
+            let Some(mut list) = flat_list_ref.borrow_mut() else {
+                error!("!!! NotificationSettings::draw_walk(): BUG: expected a FlatList widget, but got something else");
+                continue;
+            };
+            for keyword in keywords {
+                let item = list.item(cx, LiveId::from_str(keyword), live_id!(keyword_entry)).unwrap();
+                // Pass the keyword in through Scope via props.
+                let mut scope = Scope::with_props(keyword);
+                item.draw_all(cx, &mut scope);
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl MatchEvent for NotificationSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
+        for action in actions {
+            match action.downcast_ref() {
+                Some(NotificationSettingsAction::DefaultModesFetched(modes)) => {
+                    self.default_modes = Some(*modes);
+                    self.update_checkboxes(cx);
+                }
+                Some(NotificationSettingsAction::KeywordSettingsFetched(keyword_settings)) => {
+                    self.keyword_settings = Some(keyword_settings.clone());
+                    self.update_checkboxes(cx);
+                }
+                Some(NotificationSettingsAction::SetDefaultModeFailed(_category)) => {
+                    // The selected mode was already applied locally, so we re-fetch
+                    // the actual modes from the homeserver to revert the radio buttons.
+                    submit_async_request(MatrixRequest::GetNotificationSettings);
+                }
+                None => { }
+            }
+        }
+
+        for (checkbox_id, rule) in [
+            (id!(display_name_mentions_checkbox), MentionRule::DisplayName),
+            (id!(user_id_mentions_checkbox), MentionRule::UserId),
+        ] {
+            if let Some(enabled) = self.view.check_box(checkbox_id).changed(actions) {
+                submit_async_request(MatrixRequest::SetMentionRuleEnabled { rule, enabled });
+            }
+        }
+
+        let keyword_input = self.view.text_input(id!(keyword_input));
+        if self.view.button(id!(add_keyword_button)).clicked(actions)
+            || keyword_input.returned(actions).is_some()
+        {
+            let keyword = keyword_input.text().trim().to_string();
+            let is_duplicate = self.keyword_settings.as_ref()
+                .is_some_and(|ks| ks.keywords.iter().any(|k| k.eq_ignore_ascii_case(&keyword)));
+            if !keyword.is_empty() && !is_duplicate {
+                submit_async_request(MatrixRequest::AddNotificationKeyword { keyword });
+            }
+            keyword_input.set_text(cx, "");
+        }
+
+        for category in DefaultNotificationCategory::ALL {
+            let radio_buttons = self.view.radio_button_set(Self::mode_radio_button_ids(category));
+            let Some(selected) = radio_buttons.selected(cx, actions) else { continue };
+            let mode = DEFAULT_MODE_OPTIONS[selected];
+            // Selecting a radio button programmatically also emits an action,
+            // so we only submit a request if the mode actually changed.
+            let Some(modes) = self.default_modes.as_mut() else { continue };
+            let current_mode = modes.get_mut(category);
+            if *current_mode != mode {
+                *current_mode = mode;
+                submit_async_request(MatrixRequest::SetDefaultNotificationMode { category, mode });
+            }
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Returns the paths of the radio buttons used to choose the default mode for the given category,
+    /// in the same order as `DEFAULT_MODE_OPTIONS`.
+    fn mode_radio_button_ids(category: DefaultNotificationCategory) -> &'static [&'static [LiveId]] {
+        match category {
+            DefaultNotificationCategory::DirectRooms => ids!(
+                direct_rooms_modes.all_messages,
+                direct_rooms_modes.mentions_and_keywords,
+                direct_rooms_modes.mute,
+            ),
+            DefaultNotificationCategory::GroupRooms => ids!(
+                group_rooms_modes.all_messages,
+                group_rooms_modes.mentions_and_keywords,
+                group_rooms_modes.mute,
+            ),
+            DefaultNotificationCategory::EncryptedGroupRooms => ids!(
+                encrypted_group_rooms_modes.all_messages,
+                encrypted_group_rooms_modes.mentions_and_keywords,
+                encrypted_group_rooms_modes.mute,
+            ),
+        }
+    }
+
+    /// Sets the state of each checkbox and radio button
+    /// based on the last-known default modes and mention settings.
+    fn update_checkboxes(&mut self, cx: &mut Cx) {
+        if let Some(modes) = self.default_modes {
+            for category in DefaultNotificationCategory::ALL {
+                for (radio_button_id, option) in Self::mode_radio_button_ids(category).iter().zip(DEFAULT_MODE_OPTIONS) {
+                    let radio_button = self.view.radio_button(radio_button_id);
+                    if option == modes.get(category) {
+                        radio_button.select(cx, &mut Scope::empty());
+                    } else {
+                        radio_button.unselect(cx);
+                    }
+                }
+            }
+        }
+        if let Some(keyword_settings) = self.keyword_settings.as_ref() {
+            self.view.check_box(id!(display_name_mentions_checkbox)).set_active(cx, keyword_settings.display_name_mentions);
+            self.view.check_box(id!(user_id_mentions_checkbox)).set_active(cx, keyword_settings.user_id_mentions);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Fetches the latest notification settings from the homeserver
+    /// in order to populate this view.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        submit_async_request(MatrixRequest::GetNotificationSettings);
+        self.update_checkboxes(cx);
+    }
+}
+
+impl NotificationSettingsRef {
+    /// See [`NotificationSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
+
+/// A single entry in the list of notification keywords.
+#[derive(Live, LiveHook, Widget)]
+pub struct KeywordEntry {
+    #[deref] view: View,
+
+    #[rust] keyword: String,
+}
+
+impl Widget for KeywordEntry {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        if let Event::Actions(actions) = event {
+            if self.view.button(id!(remove_keyword_button)).clicked(actions) && !self.keyword.is_empty() {
+                submit_async_request(MatrixRequest::RemoveNotificationKeyword {
+                    keyword: self.keyword.clone(),
+                });
+            }
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        // The keyword was passed in through Scope via props.
+        let keyword = scope.props.get::<String>().unwrap();
+        if &self.keyword != keyword {
+            self.keyword = keyword.clone();
+        }
+        self.label(id!(keyword_label)).set_text(cx, &self.keyword);
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
@@ -0,0 +1,409 @@
+//! A subsection of the account settings for exporting and importing
+//! end-to-end encryption room keys (Megolm sessions).
+//!
+//! Room keys are exported to (and imported from) the standard passphrase-encrypted file format
+//! that begins with `-----BEGIN MEGOLM SESSION DATA-----`, which is supported by other
+//! Matrix clients as well. This allows moving keys between clients without a server-side backup.
+
+use std::path::PathBuf;
+
+use makepad_widgets::*;
+
+use crate::sliding_sync::{submit_async_request, MatrixRequest};
+
+/// The default file name for exported room keys.
+const DEFAULT_EXPORT_FILE_NAME: &str = "robrix-room-keys.txt";
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    RoomKeysNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 3, bottom: 3}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    RoomKeysTextInput = <SimpleTextInput> {
+        margin: {top: 3, left: 5, right: 5, bottom: 3},
+        width: 300, height: Fit
+    }
+
+    RoomKeysButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: 10,
+        margin: {left: 5, top: 5},
+        draw_bg: {
+            color: (COLOR_ACTIVE_PRIMARY),
+            border_radius: 5
+        }
+        icon_walk: {width: 16, height: 16, margin: 0}
+        draw_text: {
+            color: (COLOR_PRIMARY),
+        }
+    }
+
+    // The subsection of the account settings for exporting and importing room keys.
+    pub RoomKeysSettings = {{RoomKeysSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <SubsectionLabel> {
+            text: "Export room keys:"
+        }
+
+        <RoomKeysNoticeLabel> {
+            text: "Save the keys for your encrypted messages to a file, protected by a passphrase. Anyone with this file and its passphrase can read your encrypted messages, so keep it safe."
+        }
+
+        export_path_input = <RoomKeysTextInput> {
+            empty_text: "File to export to"
+        }
+        export_passphrase_input = <RoomKeysTextInput> {
+            is_password: true,
+            empty_text: "Passphrase"
+        }
+        export_passphrase_confirm_input = <RoomKeysTextInput> {
+            is_password: true,
+            empty_text: "Confirm passphrase"
+        }
+        passphrase_strength_label = <RoomKeysNoticeLabel> {
+            visible: false
+        }
+
+        export_button = <RoomKeysButton> {
+            draw_icon: {
+                svg_file: (ICON_UPLOAD)
+                color: (COLOR_PRIMARY),
+            }
+            text: "Export Keys"
+        }
+
+        <SubsectionLabel> {
+            margin: {top: 15}
+            text: "Import room keys:"
+        }
+
+        <RoomKeysNoticeLabel> {
+            text: "Load keys that were exported from another Matrix client, such that you can read your older encrypted messages."
+        }
+
+        import_path_input = <RoomKeysTextInput> {
+            empty_text: "File to import from"
+        }
+        import_passphrase_input = <RoomKeysTextInput> {
+            is_password: true,
+            empty_text: "Passphrase"
+        }
+
+        import_button = <RoomKeysButton> {
+            draw_icon: {
+                svg_file: (ICON_IMPORT)
+                color: (COLOR_PRIMARY),
+            }
+            text: "Import Keys"
+        }
+
+        // Shows how many of the keys in the import file have been imported so far.
+        import_progress_bar = <View> {
+            visible: false
+            width: 300, height: 6
+            margin: {left: 5, top: 8}
+            show_bg: true
+            draw_bg: {
+                instance progress: 0.0
+
+                fn pixel(self) -> vec4 {
+                    let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                    sdf.box(0.0, 0.0, self.rect_size.x, self.rect_size.y, 3.0);
+                    sdf.fill((COLOR_SECONDARY));
+                    sdf.box(0.0, 0.0, self.rect_size.x * self.progress, self.rect_size.y, 3.0);
+                    sdf.fill((COLOR_ACTIVE_PRIMARY));
+                    return sdf.result;
+                }
+            }
+        }
+
+        room_keys_status_label = <Label> {
+            visible: false
+            width: Fill, height: Fit
+            margin: {left: 5, top: 8}
+            draw_text: {
+                wrap: Word,
+                color: (MESSAGE_TEXT_COLOR),
+                text_style: <REGULAR_TEXT>{ font_size: 10.5 },
+            }
+            text: ""
+        }
+    }
+}
+
+/// An estimate of how hard a passphrase would be to guess.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PassphraseStrength {
+    Empty,
+    /// Shorter than [`PassphraseStrength::MIN_LEN`] characters.
+    TooShort,
+    Weak,
+    Fair,
+    Strong,
+}
+
+impl PassphraseStrength {
+    /// The minimum number of characters in an acceptable passphrase.
+    pub const MIN_LEN: usize = 8;
+
+    /// Estimates the strength of the given passphrase based on its length and character variety.
+    ///
+    /// This is a simple heuristic that catches obviously weak passphrases;
+    /// it is not a substitute for a proper entropy estimate.
+    pub fn of(passphrase: &str) -> Self {
+        let len = passphrase.chars().count();
+        if len == 0 {
+            return Self::Empty;
+        }
+        if len < Self::MIN_LEN {
+            return Self::TooShort;
+        }
+        let num_unique_chars = passphrase.chars().collect::<std::collections::BTreeSet<_>>().len();
+        if num_unique_chars < 4 {
+            return Self::Weak;
+        }
+        let num_char_classes = [
+            passphrase.chars().any(|c| c.is_lowercase()),
+            passphrase.chars().any(|c| c.is_uppercase()),
+            passphrase.chars().any(|c| c.is_numeric()),
+            passphrase.chars().any(|c| !c.is_alphanumeric()),
+        ].into_iter().filter(|&has_class| has_class).count();
+
+        let score = [len >= 12, len >= 16, num_char_classes >= 2, num_char_classes >= 3]
+            .into_iter()
+            .filter(|&met| met)
+            .count();
+        match score {
+            0 | 1 => Self::Weak,
+            2 => Self::Fair,
+            _ => Self::Strong,
+        }
+    }
+
+    /// Returns whether this strength is acceptable for encrypting exported keys.
+    pub fn is_acceptable(self) -> bool {
+        self >= Self::Fair
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Empty => "",
+            Self::TooShort => "Passphrase is too short: use at least 8 characters.",
+            Self::Weak => "Passphrase is weak: use a longer passphrase, or mix in uppercase letters, numbers, or symbols.",
+            Self::Fair => "Passphrase strength: fair.",
+            Self::Strong => "Passphrase strength: strong.",
+        }
+    }
+}
+
+/// Actions related to exporting and importing room keys.
+#[derive(Debug)]
+pub enum RoomKeysAction {
+    /// Room keys were successfully exported to the given file.
+    Exported(PathBuf),
+    /// The room keys in the import file were decrypted, and importing them has progressed.
+    ImportProgress { processed: usize, total: usize },
+    /// Importing room keys finished.
+    Imported {
+        /// The number of sessions that were new or better than the ones we already had.
+        imported_count: usize,
+        /// The total number of sessions in the file.
+        total_count: usize,
+        /// The number of rooms that the imported sessions belong to.
+        num_rooms: usize,
+    },
+    /// Exporting or importing room keys failed.
+    Failed(String),
+}
+
+/// The subsection of the account settings for exporting and importing room keys.
+#[derive(Live, LiveHook, Widget)]
+pub struct RoomKeysSettings {
+    #[deref] view: View,
+
+    /// Whether an export or import is in progress.
+    #[rust] is_busy: bool,
+}
+
+impl Widget for RoomKeysSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.match_event(cx, event);
+        self.view.handle_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+impl MatchEvent for RoomKeysSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions) {
+        for action in actions {
+            let status = match action.downcast_ref() {
+                Some(RoomKeysAction::Exported(path)) => {
+                    self.is_busy = false;
+                    format!("Exported your room keys to {}.", path.display())
+                }
+                Some(RoomKeysAction::ImportProgress { processed, total }) => {
+                    self.set_import_progress(cx, Some((*processed, *total)));
+                    format!("Importing room keys: {processed} of {total}...")
+                }
+                Some(RoomKeysAction::Imported { imported_count, total_count, num_rooms }) => {
+                    self.is_busy = false;
+                    self.set_import_progress(cx, None);
+                    self.view.text_input(id!(import_passphrase_input)).set_text(cx, "");
+                    format!("Imported {imported_count} of {total_count} sessions across {num_rooms} rooms. \
+                        The remaining sessions were already known.")
+                }
+                Some(RoomKeysAction::Failed(error)) => {
+                    self.is_busy = false;
+                    self.set_import_progress(cx, None);
+                    error.clone()
+                }
+                None => continue,
+            };
+            self.set_status(cx, &status);
+        }
+
+        let export_passphrase_input = self.view.text_input(id!(export_passphrase_input));
+        if let Some(passphrase) = export_passphrase_input.changed(actions) {
+            let strength = PassphraseStrength::of(&passphrase);
+            let strength_label = self.view.label(id!(passphrase_strength_label));
+            strength_label.set_text(cx, strength.description());
+            strength_label.set_visible(cx, strength != PassphraseStrength::Empty);
+            self.view.redraw(cx);
+        }
+
+        if self.is_busy {
+            return;
+        }
+
+        if self.view.button(id!(export_button)).clicked(actions) {
+            let passphrase = export_passphrase_input.text();
+            let path = self.view.text_input(id!(export_path_input)).text();
+            if path.trim().is_empty() {
+                self.set_status(cx, "Please enter a file to export your room keys to.");
+            } else if !PassphraseStrength::of(&passphrase).is_acceptable() {
+                self.set_status(cx, "Please choose a stronger passphrase.");
+            } else if passphrase != self.view.text_input(id!(export_passphrase_confirm_input)).text() {
+                self.set_status(cx, "The passphrases don't match.");
+            } else {
+                self.is_busy = true;
+                self.set_status(cx, "Exporting room keys...");
+                submit_async_request(MatrixRequest::ExportRoomKeys {
+                    path: PathBuf::from(path.trim()),
+                    passphrase,
+                });
+            }
+        }
+
+        if self.view.button(id!(import_button)).clicked(actions) {
+            let path = self.view.text_input(id!(import_path_input)).text();
+            let passphrase = self.view.text_input(id!(import_passphrase_input)).text();
+            if path.trim().is_empty() || passphrase.is_empty() {
+                self.set_status(cx, "Please enter the file to import from and its passphrase.");
+            } else {
+                self.is_busy = true;
+                self.set_status(cx, "Decrypting the room key file...");
+                submit_async_request(MatrixRequest::ImportRoomKeys {
+                    path: PathBuf::from(path.trim()),
+                    passphrase,
+                });
+            }
+        }
+    }
+}
+
+impl RoomKeysSettings {
+    fn set_status(&mut self, cx: &mut Cx, status: &str) {
+        let status_label = self.view.label(id!(room_keys_status_label));
+        status_label.set_text(cx, status);
+        status_label.set_visible(cx, !status.is_empty());
+        for button_id in [id!(export_button), id!(import_button)] {
+            self.view.button(button_id).set_enabled(cx, !self.is_busy);
+        }
+        self.view.redraw(cx);
+    }
+
+    /// Shows the given import progress (processed and total number of keys) in the progress bar,
+    /// or hides the progress bar if `None`.
+    fn set_import_progress(&mut self, cx: &mut Cx, progress: Option<(usize, usize)>) {
+        let progress_bar = self.view.view(id!(import_progress_bar));
+        progress_bar.set_visible(cx, progress.is_some());
+        if let Some((processed, total)) = progress {
+            let fraction = if total == 0 { 1.0 } else { processed as f64 / total as f64 };
+            progress_bar.apply_over(cx, live!{
+                draw_bg: { progress: (fraction) }
+            });
+        }
+    }
+
+    /// Resets the inputs of this subsection, filling in a default export path.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        let export_path_input = self.view.text_input(id!(export_path_input));
+        if export_path_input.text().is_empty() {
+            let default_dir = robius_directories::UserDirs::new()
+                .and_then(|dirs| dirs.download_dir().map(|d| d.to_path_buf()))
+                .unwrap_or_default();
+            export_path_input.set_text(cx, &default_dir.join(DEFAULT_EXPORT_FILE_NAME).to_string_lossy());
+        }
+        for input_id in [id!(export_passphrase_input), id!(export_passphrase_confirm_input), id!(import_passphrase_input)] {
+            self.view.text_input(input_id).set_text(cx, "");
+        }
+        self.view.label(id!(passphrase_strength_label)).set_visible(cx, false);
+        if !self.is_busy {
+            self.set_status(cx, "");
+        }
+    }
+}
+
+impl RoomKeysSettingsRef {
+    /// See [`RoomKeysSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
+
+#[cfg(test)]
+mod tests_passphrase_strength {
+    use super::PassphraseStrength;
+
+    #[test]
+    fn empty_and_short_passphrases() {
+        assert_eq!(PassphraseStrength::of(""), PassphraseStrength::Empty);
+        assert_eq!(PassphraseStrength::of("abc123!"), PassphraseStrength::TooShort);
+        assert!(!PassphraseStrength::of("abc123!").is_acceptable());
+    }
+
+    #[test]
+    fn repetitive_and_simple_passphrases_are_weak() {
+        assert_eq!(PassphraseStrength::of("aaaaaaaaaaaaaaaaaaaa"), PassphraseStrength::Weak);
+        assert_eq!(PassphraseStrength::of("password"), PassphraseStrength::Weak);
+        assert!(!PassphraseStrength::of("password").is_acceptable());
+    }
+
+    #[test]
+    fn varied_or_long_passphrases_are_acceptable() {
+        assert_eq!(PassphraseStrength::of("Tr0ub4dor&3"), PassphraseStrength::Fair);
+        assert_eq!(PassphraseStrength::of("correct horse battery staple"), PassphraseStrength::Strong);
+        assert!(PassphraseStrength::of("Tr0ub4dor&3").is_acceptable());
+    }
+}
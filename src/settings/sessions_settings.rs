@@ -0,0 +1,539 @@
+//! The sessions settings section, which lists all of the user's devices (sessions)
+//! and lets the user rename them, sign them out, or verify them.
+
+use std::collections::BTreeSet;
+
+use makepad_widgets::*;
+use matrix_sdk::ruma::{MilliSecondsSinceUnixEpoch, OwnedDeviceId};
+
+use crate::{
+    sliding_sync::{submit_async_request, MatrixRequest},
+    utils::relative_format,
+    verification::VerificationTarget,
+};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    use crate::shared::helpers::*;
+    use crate::shared::styles::*;
+    use crate::shared::icon_button::*;
+
+    SessionsNoticeLabel = <Label> {
+        width: Fill, height: Fit
+        margin: {left: 5, top: 3, bottom: 3}
+        draw_text: {
+            wrap: Word,
+            color: (COLOR_MESSAGE_NOTICE_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+        text: ""
+    }
+
+    SessionButton = <RobrixIconButton> {
+        width: Fit, height: Fit,
+        padding: 8,
+        margin: 0,
+        draw_bg: {
+            color: (COLOR_SECONDARY)
+        }
+        draw_icon: {
+            color: (COLOR_TEXT),
+        }
+        icon_walk: {width: 14, height: 14, margin: 0}
+        draw_text: {
+            color: (COLOR_TEXT),
+            text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+        }
+    }
+
+    // A single device (session) in the list of the user's sessions.
+    DeviceEntry = {{DeviceEntry}} {
+        width: Fill, height: Fit
+        flow: Down,
+        padding: {left: 5, top: 8, bottom: 8}
+        spacing: 5
+
+        <View> {
+            width: Fill, height: Fit
+            flow: Right,
+            align: {y: 0.5},
+            spacing: 8
+
+            select_checkbox = <CheckBoxFlat> {
+                text: ""
+                margin: 0
+            }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: Down,
+                spacing: 3
+
+                device_name = <Label> {
+                    width: Fill, height: Fit
+                    draw_text: {
+                        wrap: Ellipsis,
+                        color: (MESSAGE_TEXT_COLOR),
+                        text_style: <USERNAME_TEXT_STYLE>{ font_size: 11 },
+                    }
+                    text: ""
+                }
+
+                device_details = <Label> {
+                    width: Fill, height: Fit
+                    draw_text: {
+                        wrap: Word,
+                        color: (COLOR_MESSAGE_NOTICE_TEXT),
+                        text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+                    }
+                    text: ""
+                }
+            }
+
+            verification_status = <Label> {
+                width: Fit, height: Fit
+                draw_text: {
+                    color: (COLOR_MESSAGE_NOTICE_TEXT),
+                    text_style: <REGULAR_TEXT>{ font_size: 9.5 },
+                }
+                text: ""
+            }
+        }
+
+        <View> {
+            width: Fill, height: Fit
+            flow: RightWrap,
+            align: {y: 0.5},
+            margin: {left: 30}
+            spacing: 8
+
+            rename_button = <SessionButton> {
+                draw_icon: { svg_file: (ICON_EDIT) }
+                text: "Rename"
+            }
+            verify_button = <SessionButton> {
+                draw_icon: { svg_file: (ICON_CHECKMARK) }
+                text: "Verify"
+            }
+            sign_out_button = <SessionButton> {
+                draw_bg: {
+                    color: (COLOR_BG_DANGER_RED)
+                    border_color: (COLOR_FG_DANGER_RED)
+                }
+                draw_icon: {
+                    svg_file: (ICON_LOGOUT),
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                draw_text: {
+                    color: (COLOR_FG_DANGER_RED),
+                }
+                text: "Sign Out"
+            }
+        }
+
+        rename_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: RightWrap,
+            align: {y: 0.5},
+            margin: {left: 25}
+            spacing: 8
+
+            rename_input = <SimpleTextInput> {
+                margin: {top: 3, left: 5, right: 5, bottom: 3},
+                width: 216, height: Fit
+                empty_text: "Session name"
+            }
+            save_name_button = <SessionButton> {
+                draw_icon: { svg_file: (ICON_CHECKMARK) }
+                text: "Save"
+            }
+        }
+
+        <LineH> { margin: {top: 5} }
+    }
+
+    // The view containing the list of the user's sessions.
+    pub SessionsSettings = {{SessionsSettings}} {
+        width: Fill, height: Fit
+        flow: Down
+
+        <TitleLabel> {
+            text: "Sessions"
+        }
+
+        <SessionsNoticeLabel> {
+            text: "These are the devices where you're logged in. Sign out of any sessions that you don't recognize."
+        }
+
+        status_label = <SessionsNoticeLabel> {
+            text: "Loading your sessions..."
+        }
+
+        device_list = <FlatList> {
+            width: Fill,
+            height: Fit,
+            spacing: 0.0
+            flow: Down,
+
+            grab_key_focus: true,
+            drag_scrolling: true,
+            scroll_bars: { show_scroll_x: false, show_scroll_y: false },
+
+            device_entry = <DeviceEntry> { }
+        }
+
+        sign_out_selected_button = <RobrixIconButton> {
+            width: Fit, height: Fit,
+            padding: 10,
+            margin: {left: 5, top: 5},
+            enabled: false,
+            draw_bg: {
+                color: (COLOR_BG_DANGER_RED)
+                border_color: (COLOR_FG_DANGER_RED)
+            }
+            draw_icon: {
+                svg_file: (ICON_LOGOUT),
+                color: (COLOR_FG_DANGER_RED),
+            }
+            icon_walk: {width: 16, height: 16, margin: 0}
+            draw_text: {
+                color: (COLOR_FG_DANGER_RED),
+            }
+            text: "Sign Out Selected Sessions"
+        }
+
+        // Shown when the user has chosen to sign out one or more sessions,
+        // since the homeserver requires the user's password to do so.
+        confirm_sign_out_view = <View> {
+            visible: false
+            width: Fill, height: Fit
+            flow: Down
+
+            confirm_sign_out_label = <SessionsNoticeLabel> { }
+
+            <View> {
+                width: Fill, height: Fit
+                flow: RightWrap,
+                align: {y: 0.5},
+                spacing: 10
+
+                sign_out_password_input = <SimpleTextInput> {
+                    margin: {top: 3, left: 5, right: 5, bottom: 3},
+                    width: 216, height: Fit
+                    is_password: true,
+                    empty_text: "Your password"
+                }
+                confirm_sign_out_button = <SessionButton> {
+                    padding: 10,
+                    draw_bg: {
+                        color: (COLOR_BG_DANGER_RED)
+                        border_color: (COLOR_FG_DANGER_RED)
+                    }
+                    draw_icon: {
+                        svg_file: (ICON_LOGOUT),
+                        color: (COLOR_FG_DANGER_RED),
+                    }
+                    draw_text: {
+                        color: (COLOR_FG_DANGER_RED),
+                    }
+                    text: "Sign Out"
+                }
+                cancel_sign_out_button = <SessionButton> {
+                    padding: 10,
+                    draw_icon: { svg_file: (ICON_FORBIDDEN) }
+                    text: "Cancel"
+                }
+            }
+        }
+    }
+}
+
+/// Info about one of the user's devices (sessions).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceInfo {
+    pub device_id: OwnedDeviceId,
+    pub display_name: Option<String>,
+    pub last_seen_ip: Option<String>,
+    pub last_seen_ts: Option<MilliSecondsSinceUnixEpoch>,
+    /// Whether this device has been verified, i.e., cross-signed by the user.
+    pub is_verified: bool,
+    /// Whether this is the device that Robrix is currently running on.
+    pub is_current: bool,
+}
+
+/// Actions related to the user's sessions.
+#[derive(Debug)]
+pub enum SessionsAction {
+    /// The list of the user's devices was fetched from the homeserver.
+    DevicesFetched(Vec<DeviceInfo>),
+    /// Fetching the list of devices failed.
+    FetchFailed(String),
+    /// Signing out the given devices finished, either successfully or not.
+    SignOutFinished { success: bool },
+}
+
+/// Actions emitted by a [`DeviceEntry`] to its parent [`SessionsSettings`] view.
+#[derive(Clone, Debug, DefaultNone)]
+pub enum DeviceEntryAction {
+    /// The given device was selected or deselected for signing out.
+    SelectionChanged { device_id: OwnedDeviceId, selected: bool },
+    /// The user wants to sign out of only the given device.
+    SignOut(OwnedDeviceId),
+    None,
+}
+
+/// The view containing the list of the user's sessions.
+#[derive(Live, LiveHook, Widget)]
+pub struct SessionsSettings {
+    #[deref] view: View,
+
+    /// The most recently fetched list of devices, sorted with the current device first
+    /// and then by how recently each device was last seen.
+    #[rust] devices: Vec<DeviceInfo>,
+    /// The devices that are selected for signing out.
+    #[rust] selected: BTreeSet<OwnedDeviceId>,
+    /// The devices that the user has requested to sign out, pending password confirmation.
+    #[rust] pending_sign_out: Vec<OwnedDeviceId>,
+}
+
+impl Widget for SessionsSettings {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+        self.widget_match_event(cx, event, scope);
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        while let Some(subview) = self.view.draw_walk(cx, scope, walk).step() {
+            // Here, we only need to handle drawing the device list.
+            let flat_list_ref = subview.as_flat_list();
+            let Some(mut list) = flat_list_ref.borrow_mut() else {
+                error!("!!! SessionsSettings::draw_walk(): BUG: expected a FlatList widget, but got something else");
+                continue;
+            };
+            for device in &self.devices {
+                let item = list.item(cx, LiveId::from_str(device.device_id.as_str()), live_id!(device_entry)).unwrap();
+                // Pass the device info in through Scope via props.
+                let mut scope = Scope::with_props(device);
+                item.draw_all(cx, &mut scope);
+            }
+        }
+        DrawStep::done()
+    }
+}
+
+impl WidgetMatchEvent for SessionsSettings {
+    fn handle_actions(&mut self, cx: &mut Cx, actions: &Actions, _scope: &mut Scope) {
+        for action in actions {
+            match action.downcast_ref() {
+                Some(SessionsAction::DevicesFetched(devices)) => {
+                    self.devices = devices.clone();
+                    self.selected.retain(|id| self.devices.iter().any(|d| &d.device_id == id));
+                    let status = if self.devices.is_empty() { "No sessions found." } else { "" };
+                    self.set_status(cx, status);
+                }
+                Some(SessionsAction::FetchFailed(error)) => {
+                    self.set_status(cx, &format!("Failed to load your sessions: {error}"));
+                }
+                Some(SessionsAction::SignOutFinished { success }) => {
+                    self.view.button(id!(confirm_sign_out_button)).set_enabled(cx, true);
+                    if *success {
+                        self.pending_sign_out.clear();
+                        self.selected.clear();
+                        self.view.view(id!(confirm_sign_out_view)).set_visible(cx, false);
+                    }
+                    self.update_sign_out_button(cx);
+                }
+                None => { }
+            }
+
+            match action.as_widget_action().cast() {
+                DeviceEntryAction::SelectionChanged { device_id, selected } => {
+                    if selected {
+                        self.selected.insert(device_id);
+                    } else {
+                        self.selected.remove(&device_id);
+                    }
+                    self.update_sign_out_button(cx);
+                }
+                DeviceEntryAction::SignOut(device_id) => {
+                    self.confirm_sign_out(cx, vec![device_id]);
+                }
+                DeviceEntryAction::None => { }
+            }
+        }
+
+        if self.view.button(id!(sign_out_selected_button)).clicked(actions) {
+            let device_ids = self.selected.iter().cloned().collect();
+            self.confirm_sign_out(cx, device_ids);
+        }
+
+        if self.view.button(id!(cancel_sign_out_button)).clicked(actions) {
+            self.pending_sign_out.clear();
+            self.view.text_input(id!(sign_out_password_input)).set_text(cx, "");
+            self.view.view(id!(confirm_sign_out_view)).set_visible(cx, false);
+            self.view.redraw(cx);
+        }
+
+        let password_input = self.view.text_input(id!(sign_out_password_input));
+        if (self.view.button(id!(confirm_sign_out_button)).clicked(actions) || password_input.returned(actions).is_some())
+            && !self.pending_sign_out.is_empty()
+        {
+            let password = password_input.text();
+            password_input.set_text(cx, "");
+            self.view.button(id!(confirm_sign_out_button)).set_enabled(cx, false);
+            submit_async_request(MatrixRequest::DeleteDevices {
+                device_ids: self.pending_sign_out.clone(),
+                password: (!password.is_empty()).then_some(password),
+            });
+        }
+    }
+}
+
+impl SessionsSettings {
+    fn set_status(&mut self, cx: &mut Cx, status: &str) {
+        let status_label = self.view.label(id!(status_label));
+        status_label.set_text(cx, status);
+        status_label.set_visible(cx, !status.is_empty());
+        self.update_sign_out_button(cx);
+    }
+
+    fn update_sign_out_button(&mut self, cx: &mut Cx) {
+        let num_selected = self.selected.len();
+        let button = self.view.button(id!(sign_out_selected_button));
+        button.set_enabled(cx, num_selected > 0);
+        button.set_text(cx, &match num_selected {
+            0 | 1 => String::from("Sign Out Selected Session"),
+            n => format!("Sign Out {n} Selected Sessions"),
+        });
+        self.view.redraw(cx);
+    }
+
+    /// Asks the user to confirm signing out of the given devices by entering their password.
+    fn confirm_sign_out(&mut self, cx: &mut Cx, device_ids: Vec<OwnedDeviceId>) {
+        if device_ids.is_empty() {
+            return;
+        }
+        let prompt = match device_ids.as_slice() {
+            [device_id] => format!("To sign out of session {device_id}, please confirm your password."),
+            _ => format!("To sign out of {} sessions, please confirm your password.", device_ids.len()),
+        };
+        self.pending_sign_out = device_ids;
+        self.view.label(id!(confirm_sign_out_label)).set_text(cx, &prompt);
+        self.view.view(id!(confirm_sign_out_view)).set_visible(cx, true);
+        self.view.redraw(cx);
+    }
+
+    /// Fetches the latest list of devices from the homeserver in order to populate this view.
+    pub fn populate(&mut self, cx: &mut Cx) {
+        submit_async_request(MatrixRequest::GetDevices);
+        if self.devices.is_empty() {
+            self.set_status(cx, "Loading your sessions...");
+        }
+    }
+}
+
+impl SessionsSettingsRef {
+    /// See [`SessionsSettings::populate()`].
+    pub fn populate(&self, cx: &mut Cx) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.populate(cx);
+    }
+}
+
+/// A single device (session) in the list of the user's sessions.
+#[derive(Live, LiveHook, Widget)]
+pub struct DeviceEntry {
+    #[deref] view: View,
+
+    #[rust] device: Option<DeviceInfo>,
+}
+
+impl Widget for DeviceEntry {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        self.view.handle_event(cx, event, scope);
+
+        let Event::Actions(actions) = event else { return };
+        let Some(device) = self.device.as_ref() else { return };
+        let device_id = device.device_id.clone();
+
+        if let Some(selected) = self.view.check_box(id!(select_checkbox)).changed(actions) {
+            cx.widget_action(
+                self.widget_uid(),
+                &scope.path,
+                DeviceEntryAction::SelectionChanged { device_id: device_id.clone(), selected },
+            );
+        }
+        if self.view.button(id!(sign_out_button)).clicked(actions) {
+            cx.widget_action(self.widget_uid(), &scope.path, DeviceEntryAction::SignOut(device_id.clone()));
+        }
+        if self.view.button(id!(verify_button)).clicked(actions) {
+            let target = if device.is_current {
+                VerificationTarget::OwnSession
+            } else {
+                VerificationTarget::Device(device_id.clone())
+            };
+            submit_async_request(MatrixRequest::RequestVerification { target });
+        }
+        if self.view.button(id!(rename_button)).clicked(actions) {
+            let rename_view = self.view.view(id!(rename_view));
+            let show = !rename_view.visible();
+            rename_view.set_visible(cx, show);
+            if show {
+                self.view.text_input(id!(rename_input))
+                    .set_text(cx, device.display_name.as_deref().unwrap_or_default());
+            }
+            self.view.redraw(cx);
+        }
+        let rename_input = self.view.text_input(id!(rename_input));
+        if self.view.button(id!(save_name_button)).clicked(actions) || rename_input.returned(actions).is_some() {
+            let display_name = rename_input.text().trim().to_string();
+            if !display_name.is_empty() && Some(&display_name) != device.display_name.as_ref() {
+                submit_async_request(MatrixRequest::RenameDevice { device_id, display_name });
+            }
+            self.view.view(id!(rename_view)).set_visible(cx, false);
+            self.view.redraw(cx);
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        // The device info was passed in through Scope via props.
+        let device = scope.props.get::<DeviceInfo>().unwrap();
+        if self.device.as_ref() != Some(device) {
+            self.device = Some(device.clone());
+        }
+
+        let name = device.display_name.as_deref().unwrap_or("Unnamed session");
+        self.label(id!(device_name)).set_text(cx, &if device.is_current {
+            format!("{name} (this session)")
+        } else {
+            name.to_string()
+        });
+
+        let mut details = vec![device.device_id.to_string()];
+        if let Some(ip) = device.last_seen_ip.as_deref() {
+            details.push(ip.to_string());
+        }
+        if let Some(last_seen) = device.last_seen_ts.and_then(relative_format) {
+            details.push(format!("last seen {last_seen}"));
+        }
+        self.label(id!(device_details)).set_text(cx, &details.join(" · "));
+
+        self.label(id!(verification_status)).set_text(
+            cx,
+            if device.is_verified { "✅ Verified" } else { "❌ Unverified" },
+        );
+        // The current session is logged out via the regular "Log out" button.
+        self.view.check_box(id!(select_checkbox)).set_visible(cx, !device.is_current);
+        self.view.button(id!(sign_out_button)).set_visible(cx, !device.is_current);
+        // The current session is verified from one of the user's other sessions.
+        let verify_button = self.view.button(id!(verify_button));
+        verify_button.set_visible(cx, !device.is_verified);
+        verify_button.set_text(cx, if device.is_current { "Verify This Session" } else { "Verify" });
+
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
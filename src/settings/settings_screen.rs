@@ -1,7 +1,7 @@
 
 use makepad_widgets::*;
 
-use crate::{home::spaces_dock::get_own_profile, profile::user_profile::UserProfile, settings::{account_settings::AccountSettingsWidgetExt, SettingsAction}};
+use crate::{home::spaces_dock::get_own_profile, profile::user_profile::UserProfile, settings::{account_settings::AccountSettingsWidgetExt, credentials_settings::CredentialsSettingsWidgetExt, ignored_users_settings::IgnoredUsersSettingsWidgetExt, key_backup_settings::KeyBackupSettingsWidgetExt, network_settings::NetworkSettingsWidgetExt, notification_settings::NotificationSettingsWidgetExt, sessions_settings::SessionsSettingsWidgetExt, SettingsAction, SettingsSection}};
 
 live_design! {
     use link::theme::*;
@@ -13,6 +13,12 @@ live_design! {
     use crate::shared::icon_button::*;
     use crate::shared::confirmation_modal::*;
     use crate::settings::account_settings::AccountSettings;
+    use crate::settings::credentials_settings::CredentialsSettings;
+    use crate::settings::notification_settings::NotificationSettings;
+    use crate::settings::key_backup_settings::KeyBackupSettings;
+    use crate::settings::sessions_settings::SessionsSettings;
+    use crate::settings::ignored_users_settings::IgnoredUsersSettings;
+    use crate::settings::network_settings::NetworkSettings;
     use link::tsp_link::TspSettingsScreen;
     use link::tsp_link::CreateWalletModal;
     use link::tsp_link::CreateDidModal;
@@ -72,7 +78,7 @@ live_design! {
 
             <LineH> { padding: 10 }
 
-            <ScrollXYView> {
+            settings_scroll_view = <ScrollXYView> {
                 width: Fill, height: Fill
                 flow: Down
 
@@ -81,6 +87,36 @@ live_design! {
 
                 <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
 
+                // The password and contact info (3PID) settings section.
+                credentials_settings = <CredentialsSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
+                // The notification settings section.
+                notification_settings = <NotificationSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
+                // The key backup and recovery settings section.
+                key_backup_settings = <KeyBackupSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
+                // The sessions (devices) settings section.
+                sessions_settings = <SessionsSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
+                // The ignored users settings section.
+                ignored_users_settings = <IgnoredUsersSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
+                // The network (proxy) settings section.
+                network_settings = <NetworkSettings> {}
+
+                <LineH> { width: 400, padding: 10, margin: {top: 20, bottom: 5} }
+
                 // The TSP wallet settings section.
                 tsp_settings_screen = <TspSettingsScreen> {}
 
@@ -118,6 +154,9 @@ live_design! {
 #[derive(Live, LiveHook, Widget)]
 pub struct SettingsScreen {
     #[deref] view: View,
+
+    /// The section to scroll to once the settings screen has been laid out.
+    #[rust] pending_section: Option<SettingsSection>,
 }
 
 impl Widget for SettingsScreen {
@@ -201,7 +240,29 @@ impl Widget for SettingsScreen {
     }
 
     fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
-        self.view.draw_walk(cx, scope, walk)
+        let step = self.view.draw_walk(cx, scope, walk);
+        // A section's position is only known after it has been drawn,
+        // so we scroll to it afterwards and then draw again.
+        if let Some(section) = self.pending_section.take() {
+            let section_top = self.view.widget(section.widget_path()).area().rect(cx).pos.y;
+            let scroll_view = self.view.view(id!(settings_scroll_view));
+            let view_top = scroll_view.area().rect(cx).pos.y;
+            if let Some(mut scroll_view) = scroll_view.borrow_mut() {
+                let scroll_pos = scroll_view.get_scroll_pos();
+                scroll_view.set_scroll_pos(cx, dvec2(scroll_pos.x, scroll_pos.y + section_top - view_top));
+            }
+            self.view.redraw(cx);
+        }
+        step
+    }
+}
+
+impl SettingsSection {
+    /// Returns the path of this section's widget within the settings screen.
+    fn widget_path(self) -> &'static [LiveId] {
+        match self {
+            SettingsSection::KeyBackup => id!(key_backup_settings),
+        }
     }
 }
 
@@ -213,10 +274,22 @@ impl SettingsScreen {
             return;
         };
         self.view.account_settings(id!(account_settings)).populate(cx, profile);
+        self.view.credentials_settings(id!(credentials_settings)).populate(cx);
+        self.view.notification_settings(id!(notification_settings)).populate(cx);
+        self.view.key_backup_settings(id!(key_backup_settings)).populate(cx);
+        self.view.sessions_settings(id!(sessions_settings)).populate(cx);
+        self.view.ignored_users_settings(id!(ignored_users_settings)).populate(cx);
+        self.view.network_settings(id!(network_settings)).populate(cx);
         self.view.button(id!(close_button)).reset_hover(cx);
         cx.set_key_focus(self.view.area());
         self.redraw(cx);
     }
+
+    /// Scrolls the settings screen to the given section the next time it is drawn.
+    pub fn show_section(&mut self, cx: &mut Cx, section: SettingsSection) {
+        self.pending_section = Some(section);
+        self.redraw(cx);
+    }
 }
 
 impl SettingsScreenRef {
@@ -225,4 +298,10 @@ impl SettingsScreenRef {
         let Some(mut inner) = self.borrow_mut() else { return; };
         inner.populate(cx, own_profile);
     }
+
+    /// See [`SettingsScreen::show_section()`].
+    pub fn show_section(&self, cx: &mut Cx, section: SettingsSection) {
+        let Some(mut inner) = self.borrow_mut() else { return; };
+        inner.show_section(cx, section);
+    }
 }
@@ -9,7 +9,7 @@
 
 use makepad_widgets::*;
 
-use crate::home::rooms_list::RoomsListScopeProps;
+use crate::home::{rooms_list::RoomsListScopeProps, unread_totals::UnreadCounts};
 
 use super::unread_badge::UnreadBadgeWidgetExt;
 
@@ -162,19 +162,20 @@ impl CollapsibleHeader {
 }
 
 impl CollapsibleHeaderRef {
-    /// Sets the category and expanded state of the header.
+    /// Sets the category, expanded state, and total unread counts of the header.
     pub fn set_details(
         &self,
         cx: &mut Cx,
         is_expanded: bool,
         category: HeaderCategory,
-        num_unread_mentions: u64,
+        unread_counts: UnreadCounts,
     ) {
         if let Some(mut inner) = self.borrow_mut() {
             inner.is_expanded = is_expanded;
             inner.category = category;
             inner.label(id!(label)).set_text(cx, category.as_str());
-            inner.unread_badge(id!(unread_badge)).update_counts(num_unread_mentions, 0);
+            inner.unread_badge(id!(unread_badge))
+                .update_counts(unread_counts.mentions, unread_counts.messages);
         }
     }
 }
@@ -1,9 +1,11 @@
 //! A `HtmlOrPlaintext` view can display either plaintext or rich HTML content.
 
+use std::borrow::Cow;
+
 use makepad_widgets::{makepad_html::HtmlDoc, *};
 use matrix_sdk::{ruma::{matrix_uri::MatrixId, OwnedMxcUri}, OwnedServerName};
 
-use crate::{avatar_cache::{self, AvatarCacheEntry}, profile::user_profile_cache, sliding_sync::{current_user_id, submit_async_request, MatrixRequest}, utils};
+use crate::{avatar_cache::{self, AvatarCacheEntry}, profile::user_profile_cache, sliding_sync::{current_user_id, get_notification_keywords, submit_async_request, MatrixRequest}, utils};
 
 use super::avatar::AvatarWidgetExt;
 
@@ -646,6 +648,8 @@ impl Widget for MatrixHtmlSpan {
 #[derive(LiveHook, Live, Widget)]
 pub struct HtmlOrPlaintext {
     #[deref] view: View,
+    /// Whether to highlight the user's notification keywords in the displayed content.
+    #[live] highlight_keywords: bool,
 }
 
 impl Widget for HtmlOrPlaintext {
@@ -660,7 +664,17 @@ impl Widget for HtmlOrPlaintext {
 
 impl HtmlOrPlaintext {
     /// Sets the plaintext content and makes it visible, hiding the rich HTML content.
+    ///
+    /// If `highlight_keywords` is enabled and the text contains any of the user's
+    /// notification keywords, the text is escaped and shown as HTML instead.
     pub fn show_plaintext<T: AsRef<str>>(&mut self, cx: &mut Cx, text: T) {
+        if self.highlight_keywords {
+            let escaped_text = htmlize::escape_text(text.as_ref());
+            if let Cow::Owned(highlighted) = utils::highlight_keywords(&escaped_text, &get_notification_keywords()) {
+                self.set_html_visible(cx, &highlighted);
+                return;
+            }
+        }
         self.view(id!(html_view)).set_visible(cx, false);
         self.view(id!(plaintext_view)).set_visible(cx, true);
         self.label(id!(plaintext_view.pt_label)).set_text(cx, text.as_ref());
@@ -668,7 +682,16 @@ impl HtmlOrPlaintext {
 
     /// Sets the HTML content, making the HTML visible and the plaintext invisible.
     pub fn show_html<T: AsRef<str>>(&mut self, cx: &mut Cx, html_body: T) {
-        self.html(id!(html_view.html)).set_text(cx, html_body.as_ref());
+        if self.highlight_keywords {
+            let highlighted = utils::highlight_keywords(html_body.as_ref(), &get_notification_keywords());
+            self.set_html_visible(cx, &highlighted);
+        } else {
+            self.set_html_visible(cx, html_body.as_ref());
+        }
+    }
+
+    fn set_html_visible(&mut self, cx: &mut Cx, html_body: &str) {
+        self.html(id!(html_view.html)).set_text(cx, html_body);
         self.view(id!(html_view)).set_visible(cx, true);
         self.view(id!(plaintext_view)).set_visible(cx, false);
     }
@@ -9,7 +9,7 @@ use crate::utils;
 
 
 use makepad_widgets::{text::selection::Cursor, *};
-use matrix_sdk::ruma::{events::{room::message::RoomMessageEventContent, Mentions}, OwnedRoomId, OwnedUserId};
+use matrix_sdk::ruma::{events::{room::{member::MembershipState, message::RoomMessageEventContent}, Mentions}, OwnedRoomId, OwnedUserId};
 use matrix_sdk::room::RoomMember;
 use std::collections::{BTreeMap, BTreeSet};
 use unicode_segmentation::UnicodeSegmentation;
@@ -563,6 +563,11 @@ impl MentionableTextInput {
                 }
             }
 
+            // Skip invited and banned users, which are also included in the room's member list.
+            if member.membership() != &MembershipState::Join {
+                continue;
+            }
+
             // Check if this member matches the search text (including Matrix ID)
             if self.user_matches_search(member, search_text) {
                 let display_name = member
@@ -10,6 +10,7 @@ pub mod icon_button;
 pub mod jump_to_bottom_button;
 pub mod mentionable_text_input;
 pub mod popup_list;
+pub mod presence_indicator;
 pub mod room_filter_input_bar;
 pub mod styles;
 pub mod text_or_image;
@@ -36,6 +37,7 @@ pub fn live_design(cx: &mut Cx) {
     jump_to_bottom_button::live_design(cx);
     popup_list::live_design(cx);
     verification_badge::live_design(cx);
+    presence_indicator::live_design(cx);
     callout_tooltip::live_design(cx);
     mentionable_text_input::live_design(cx);
     restore_status_view::live_design(cx);
@@ -0,0 +1,126 @@
+//! A small colored dot that shows a user's presence (online, away, or offline),
+//! typically overlaid on the bottom-right corner of their avatar.
+//!
+//! Hovering over the dot shows a tooltip with the user's last active time and status message.
+
+use makepad_widgets::*;
+use matrix_sdk::ruma::{presence::PresenceState, OwnedUserId, UserId};
+
+use crate::{
+    profile::presence_cache::{self, UserPresence},
+    shared::{callout_tooltip::TooltipAction, styles::{COLOR_FG_ACCEPT_GREEN, COLOR_FG_DISABLED, COLOR_PRESENCE_AWAY}},
+};
+
+live_design! {
+    use link::theme::*;
+    use link::shaders::*;
+    use link::widgets::*;
+
+    pub PresenceIndicator = {{PresenceIndicator}} {
+        visible: false,
+        width: 12, height: 12,
+        show_bg: true,
+        draw_bg: {
+            instance border_color: #fff
+            instance border_size: 1.5
+            fn pixel(self) -> vec4 {
+                let sdf = Sdf2d::viewport(self.pos * self.rect_size);
+                let radius = min(self.rect_size.x, self.rect_size.y) * 0.5;
+                sdf.circle(radius, radius, radius - self.border_size * 0.5);
+                sdf.fill_keep(self.color);
+                sdf.stroke(self.border_color, self.border_size);
+                return sdf.result;
+            }
+        }
+    }
+}
+
+/// Returns the color used to show the given presence state.
+pub fn presence_color(presence: &PresenceState) -> Vec4 {
+    match presence {
+        PresenceState::Online => COLOR_FG_ACCEPT_GREEN,
+        PresenceState::Unavailable => COLOR_PRESENCE_AWAY,
+        _ => COLOR_FG_DISABLED,
+    }
+}
+
+#[derive(Live, LiveHook, Widget)]
+pub struct PresenceIndicator {
+    #[deref] view: View,
+    /// The user whose presence is shown by this indicator.
+    #[rust] user_id: Option<OwnedUserId>,
+    /// The user's most recently drawn presence.
+    #[rust] presence: Option<UserPresence>,
+}
+
+impl Widget for PresenceIndicator {
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, scope: &mut Scope) {
+        let Some(user_id) = self.user_id.as_deref() else { return };
+
+        // A UI Signal indicates that this user's presence may have been updated in the background.
+        if let Event::Signal = event {
+            presence_cache::process_presence_updates(cx);
+            if presence_cache::get_or_fetch_presence(cx, user_id) != self.presence {
+                self.redraw(cx);
+            }
+        }
+
+        let area = self.view.area();
+        match event.hits(cx, area) {
+            Hit::FingerHoverIn(_) | Hit::FingerLongPress(_) => {
+                let Some(presence) = self.presence.as_ref() else { return };
+                let text = match presence.status_msg.as_deref() {
+                    Some(status_msg) => format!("{}\n\n\"{status_msg}\"", presence.description()),
+                    None => presence.description(),
+                };
+                cx.widget_action(
+                    self.widget_uid(),
+                    &scope.path,
+                    TooltipAction::HoverIn {
+                        widget_rect: area.rect(cx),
+                        text,
+                        text_color: None,
+                        bg_color: None,
+                    },
+                );
+            }
+            Hit::FingerHoverOut(_) => {
+                cx.widget_action(self.widget_uid(), &scope.path, TooltipAction::HoverOut);
+            }
+            _ => { }
+        }
+    }
+
+    fn draw_walk(&mut self, cx: &mut Cx2d, scope: &mut Scope, walk: Walk) -> DrawStep {
+        self.presence = self.user_id.as_deref()
+            .and_then(|user_id| presence_cache::get_or_fetch_presence(cx, user_id));
+        self.visible = self.presence.is_some();
+        if let Some(presence) = self.presence.as_ref() {
+            let color = presence_color(&presence.presence);
+            self.view.apply_over(cx, live! {
+                draw_bg: { color: (color) }
+            });
+        }
+        self.view.draw_walk(cx, scope, walk)
+    }
+}
+
+impl PresenceIndicator {
+    /// Sets the user whose presence is shown by this indicator.
+    ///
+    /// If `user_id` is `None`, this indicator is hidden.
+    pub fn set_user(&mut self, cx: &mut Cx, user_id: Option<&UserId>) {
+        if self.user_id.as_deref() != user_id {
+            self.user_id = user_id.map(ToOwned::to_owned);
+            self.redraw(cx);
+        }
+    }
+}
+
+impl PresenceIndicatorRef {
+    /// See [`PresenceIndicator::set_user()`].
+    pub fn set_user(&self, cx: &mut Cx, user_id: Option<&UserId>) {
+        let Some(mut inner) = self.borrow_mut() else { return };
+        inner.set_user(cx, user_id);
+    }
+}
@@ -9,6 +9,8 @@ live_design! {
     pub ICON_ADD_REACTION    = dep("crate://self/resources/icons/add_reaction.svg")
     pub ICON_ADD_USER        = dep("crate://self/resources/icons/add_user.svg") // TODO: FIX
     pub ICON_ADD_WALLET      = dep("crate://self/resources/icons/add_wallet.svg")
+    pub ICON_BELL            = dep("crate://self/resources/icons/bell.svg")
+    pub ICON_BELL_OFF        = dep("crate://self/resources/icons/bell_off.svg")
     pub ICON_FORBIDDEN       = dep("crate://self/resources/icons/forbidden.svg")
     pub ICON_CHECKMARK       = dep("crate://self/resources/icons/checkmark.svg")
     pub ICON_CLOSE           = dep("crate://self/resources/icons/close.svg")
@@ -24,6 +26,7 @@ live_design! {
     pub ICON_JUMP            = dep("crate://self/resources/icons/go_back.svg")
     pub ICON_LOGOUT          = dep("crate://self/resources/icons/logout.svg")
     pub ICON_LINK            = dep("crate://self/resources/icons/link.svg")
+    pub ICON_MEMBERS         = dep("crate://self/resources/icons/members.svg")
     pub ICON_PIN             = dep("crate://self/resources/icons/pin.svg")
     pub ICON_REPLY           = dep("crate://self/resources/icons/reply.svg")
     pub ICON_SEND            = dep("crate://self/resources/icon_send.svg")
@@ -96,6 +99,7 @@ live_design! {
     pub COLOR_FG_DISABLED = #B3B3B3
     pub COLOR_BG_DISABLED = #E0E0E0
     pub COLOR_WARNING_NOT_FOUND = #953800
+    pub COLOR_PRESENCE_AWAY = #FF6E00
 
     pub COLOR_SELECT_TEXT = #A6CDFE
 
@@ -299,3 +303,5 @@ pub const COLOR_WHITE:                 Vec4 = vec4(1.0, 1.0, 1.0, 1.0);
 pub const COLOR_MESSAGE_NOTICE_TEXT:   Vec4 = vec4(0.5, 0.5, 0.5, 1.0);
 /// #953800
 pub const COLOR_WARNING_NOT_FOUND:    Vec4 = vec4(0.584, 0.219, 0.0, 1.0);
+/// #FF6E00
+pub const COLOR_PRESENCE_AWAY:         Vec4 = vec4(1.0, 0.431, 0.0, 1.0);
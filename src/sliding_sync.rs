@@ -7,25 +7,25 @@ use futures_util::{pin_mut, StreamExt};
 use imbl::Vector;
 use makepad_widgets::{error, log, warning, Cx, SignalToUI};
 use matrix_sdk::{
-    config::RequestConfig, crypto::{DecryptionSettings, TrustRequirement}, encryption::EncryptionSettings, event_handler::EventHandlerDropGuard, media::MediaRequestParameters, room::{edit::EditedContent, reply::Reply, RoomMember}, ruma::{
-        api::client::{profile::{AvatarUrl, DisplayName}, receipt::create_receipt::v3::ReceiptType}, events::{
+    authentication::oauth::UrlOrQuery, config::{RequestConfig, SyncSettings}, crypto::{decrypt_room_key_export, encrypt_room_key_export, olm::ExportedRoomKey, DecryptionSettings, TrustRequirement}, encryption::{recovery::EnableProgress, EncryptionSettings}, event_handler::{EventHandlerDropGuard, EventHandlerHandle}, media::MediaRequestParameters, notification_settings::{IsEncrypted, IsOneToOne, NotificationSettings, RoomNotificationMode}, room::{edit::EditedContent, reply::Reply, RoomMember}, ruma::{
+        api::client::{account::{change_password, get_username_availability, register}, discovery::get_supported_versions, error::ErrorKind, session::get_login_types::v3::LoginType, presence::{get_presence, set_presence}, profile::{AvatarUrl, DisplayName}, push::set_pushrule, receipt::create_receipt::v3::ReceiptType, uiaa::{self, UserIdentifier}}, events::{
             room::{
                 message::RoomMessageEventContent, power_levels::RoomPowerLevels, MediaSource
-            }, MessageLikeEventType, StateEventType
-        }, matrix_uri::MatrixId, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomOrAliasId, UserId
-    }, sliding_sync::VersionBuilder, Client, ClientBuildError, Error, OwnedServerName, Room, RoomDisplayName, RoomMemberships, RoomState, SuccessorRoom
+            }, presence::PresenceEvent, MessageLikeEventType, StateEventType
+        }, matrix_uri::MatrixId, presence::PresenceState, serde::Raw, thirdparty::Medium, push::{Action, NewPatternedPushRule, NewPushRule, PredefinedContentRuleId, PredefinedOverrideRuleId, RuleKind, Tweak}, MilliSecondsSinceUnixEpoch, OwnedClientSecret, OwnedDeviceId, OwnedEventId, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedUserId, RoomOrAliasId, UInt, UserId
+    }, sliding_sync::VersionBuilder, Client, ClientBuildError, Error, HttpError, OwnedServerName, Room, RoomDisplayName, RoomMemberships, RoomState, SessionChange, SuccessorRoom
 };
 use matrix_sdk_ui::{
-    room_list_service::{RoomListItem, RoomListLoadingState, SyncIndicator}, sync_service::{self, SyncService}, timeline::{EventTimelineItem, LatestEventValue, RoomExt, TimelineDetails, TimelineEventItemId, TimelineItem}, RoomListService, Timeline
+    room_list_service::{RoomListItem, RoomListLoadingState, SyncIndicator}, sync_service::{self, SyncService}, timeline::{EventTimelineItem, LatestEventValue, MsgLikeKind, RoomExt, TimelineDetails, TimelineEventItemId, TimelineItem, TimelineItemContent}, Timeline
 };
 use robius_open::Uri;
 use ruma::events::tag::Tags;
 use tokio::{
     runtime::Handle,
-    sync::{mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender}, watch, Notify}, task::JoinHandle, time::error::Elapsed,
+    sync::{broadcast, mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender}, watch, Notify, Semaphore}, task::{AbortHandle, JoinHandle}, time::error::Elapsed,
 };
 use url::Url;
-use std::{cmp::{max, min}, collections::{BTreeMap, BTreeSet}, future::Future, iter::Peekable, ops::{Deref, Not}, path:: Path, sync::{Arc, LazyLock, Mutex}, time::Duration};
+use std::{cmp::{max, min}, collections::{BTreeMap, BTreeSet}, future::Future, iter::Peekable, ops::{Deref, Not}, path::{Path, PathBuf}, sync::{Arc, LazyLock, Mutex}, time::Duration};
 use std::io;
 use crate::{
     app::AppStateAction,
@@ -33,23 +33,36 @@ use crate::{
     avatar_cache::AvatarUpdate,
     event_preview::text_preview_of_timeline_item,
     home::{
-        invite_screen::{JoinRoomResultAction, LeaveRoomResultAction}, link_preview::{LinkPreviewData, LinkPreviewRateLimitResponse, LinkPreviewDataNonNumeric}, room_screen::TimelineUpdate, rooms_list::{self, enqueue_rooms_list_update, InvitedRoomInfo, InviterInfo, JoinedRoomInfo, RoomsListUpdate}, rooms_list_header::RoomsListHeaderAction
+        account_switcher::BackgroundAccountAction, invite_screen::{JoinRoomResultAction, LeaveRoomResultAction}, main_desktop_ui::MainDesktopUiAction, link_preview::{LinkPreviewData, LinkPreviewRateLimitResponse, LinkPreviewDataNonNumeric}, room_screen::TimelineUpdate, rooms_list::{self, enqueue_rooms_list_update, InvitedRoomInfo, InviterInfo, JoinedRoomInfo, RoomsListUpdate}, rooms_list_header::RoomsListHeaderAction, unread_totals::UnreadCounts
     },
-    login::login_screen::LoginAction,
-    logout::{logout_confirm_modal::LogoutAction, logout_state_machine::{is_logout_in_progress, logout_with_state_machine, LogoutConfig}}, media_cache::{MediaCacheEntry, MediaCacheEntryRef},
-    persistence::{self, load_app_state, ClientSessionPersisted},
+    login::{homeserver_discovery::{self, HomeserverSummary}, login_screen::LoginAction, oauth::{self, LoopbackRedirectServer}, registration},
+    notifications,
+    logout::{logout_confirm_modal::LogoutAction, logout_state_machine::{is_logout_in_progress, logout_with_state_machine, LogoutConfig, LogoutMode}}, media_cache::{MediaCacheEntry, MediaCacheEntryRef},
+    persistence::{self, load_app_state, load_room_drafts, proxy_settings::{active_proxy_settings, login_proxy_settings, set_active_proxy_settings}, ClientSessionPersisted, DefaultMuteSettings, PresenceSettings, ProxySettings, SlidingSyncVersion},
     profile::{
+        avatar_upload,
+        presence_cache::{enqueue_presence_fetch_failure, enqueue_presence_update, set_presence_synced, PresenceUpdate, UserPresence},
         user_profile::{AvatarState, UserProfile},
         user_profile_cache::{enqueue_user_profile_update, UserProfileUpdate},
     },
     room::RoomPreviewAvatar,
+    settings::{
+        account_settings::{self, AvatarImageAction, OwnProfileAction},
+        credentials_settings::{CredentialsAction, NewThreepid, ThreepidInfo, ThreepidValidation},
+        key_backup_settings::{KeyBackupAction, KeyBackupProgress, KeyBackupStatus},
+        ignored_users_settings::IgnoredUsersAction,
+        network_settings::NetworkSettingsAction,
+        room_keys_settings::RoomKeysAction,
+        sessions_settings::{DeviceInfo, SessionsAction},
+        notification_settings::{DefaultNotificationCategory, DefaultNotificationModes, KeywordSettings, MentionRule, NotificationSettingsAction},
+    },
     shared::{
         html_or_plaintext::MatrixLinkPillState,
         jump_to_bottom_button::UnreadMessageCount,
         popup_list::{enqueue_popup_notification, PopupItem, PopupKind}
     },
     utils::{self, avatar_from_room_name, AVATAR_THUMBNAIL_FORMAT},
-    verification::add_verification_event_handlers_and_sync_client
+    verification::{add_verification_event_handlers_and_sync_client, request_verification, VerificationTarget}
 };
 
 #[derive(Parser, Debug, Default)]
@@ -104,25 +117,23 @@ async fn build_client(
     let db_path = data_dir.join(db_subfolder_name);
 
     // Generate a random passphrase.
-    let passphrase: String = {
-        use rand::{Rng, thread_rng};
-        thread_rng()
-            .sample_iter(rand::distributions::Alphanumeric)
-            .take(32)
-            .map(char::from)
-            .collect()
-    };
+    let passphrase = utils::random_alphanumeric_string(32);
 
-    let homeserver_url = cli.homeserver.as_deref()
-        .unwrap_or("https://matrix-client.matrix.org/");
-        // .unwrap_or("https://matrix.org/");
+    // If no homeserver was given, discover it from the server name of the user ID (if it's a full one).
+    let discovery_target = homeserver_discovery::discovery_target(
+        cli.homeserver.as_deref().unwrap_or_default(),
+        &cli.user_id,
+    );
+    let homeserver_url = discovery_target.as_deref()
+        .unwrap_or(DEFAULT_HOMESERVER_URL);
 
     let mut builder = Client::builder()
         .server_name_or_homeserver_url(homeserver_url)
         // Use a sqlite database to persist the client's encryption setup.
         .sqlite_store(&db_path, Some(&passphrase))
-        // The sliding sync proxy has now been deprecated in favor of native sliding sync.
-        .sliding_sync_version_builder(VersionBuilder::DiscoverNative)
+        // Native sliding sync support is detected below, such that homeservers without it
+        // can fall back to classic sync rather than failing to build the client.
+        .sliding_sync_version_builder(VersionBuilder::None)
         .with_decryption_settings(DecryptionSettings {
             sender_device_trust_requirement: TrustRequirement::Untrusted,
         })
@@ -134,9 +145,13 @@ async fn build_client(
         .with_enable_share_history_on_invite(true)
         .handle_refresh_tokens();
 
-    if let Some(proxy) = cli.proxy.as_ref() {
-        builder = builder.proxy(proxy.clone());
-    }
+    // The proxy given on the command line takes precedence over the one entered on the login screen.
+    let proxy = cli.proxy.as_deref()
+        .map(ProxySettings::from_cli_proxy)
+        .or_else(login_proxy_settings)
+        .unwrap_or_default();
+    builder = proxy.apply_to_matrix_client(builder)
+        .map_err(|e| ClientBuildError::Http(HttpError::Reqwest(e)))?;
 
     // Use a 60 second timeout for all requests to the homeserver.
     // Yes, this is a long timeout, but the standard matrix homeserver is often very slow.
@@ -146,17 +161,119 @@ async fn build_client(
     );
 
     let client = builder.build().await?;
-    let homeserver_url =  client.homeserver().to_string();
+    if homeserver_supports_native_sliding_sync(&client).await.map_err(ClientBuildError::Http)? {
+        client.set_sliding_sync_version(SlidingSyncVersion::Native.into());
+    } else {
+        warning!("Homeserver {} does not support native sliding sync; falling back to classic sync.", client.homeserver());
+    }
+    let homeserver_url = client.homeserver().to_string();
     Ok((
         client,
         ClientSessionPersisted {
             homeserver: homeserver_url,
             db_path,
             passphrase,
+            proxy,
         },
     ))
 }
 
+/// Returns whether the given client's homeserver supports native sliding sync.
+async fn homeserver_supports_native_sliding_sync(client: &Client) -> Result<bool, HttpError> {
+    let versions = client.send(get_supported_versions::Request::new()).await?;
+    Ok(homeserver_discovery::supports_native_sliding_sync(&versions.unstable_features))
+}
+
+/// Returns a user-facing explanation of why a client for the given CLI arguments couldn't be built.
+fn describe_client_build_error(cli: &Cli, error: &ClientBuildError) -> String {
+    let target = homeserver_discovery::discovery_target(cli.homeserver.as_deref().unwrap_or_default(), &cli.user_id)
+        .unwrap_or_else(|| DEFAULT_HOMESERVER_URL.to_owned());
+    describe_homeserver_build_error(&target, error)
+}
+
+/// Returns a user-facing explanation of why a client for the given homeserver couldn't be built.
+fn describe_homeserver_build_error(target: &str, error: &ClientBuildError) -> String {
+    match error {
+        ClientBuildError::AutoDiscovery(_) => format!(
+            "Could not find a Matrix homeserver for \"{target}\": its .well-known/matrix/client file \
+            is missing or invalid, and it does not appear to be a homeserver itself.\n\n\
+            Please check the spelling, or enter your homeserver's full URL (e.g., https://matrix.example.org)."
+        ),
+        ClientBuildError::Url(_) => format!("\"{target}\" is not a valid server name or homeserver URL."),
+        ClientBuildError::Http(e) => format!("Could not connect to the homeserver for \"{target}\": {e}"),
+        ClientBuildError::SlidingSyncVersion(_) => homeserver_discovery::no_sliding_sync_error(target),
+        other => format!("Could not set up a client for the homeserver \"{target}\": {other}"),
+    }
+}
+
+/// Discovers the homeserver for the given server name or URL, validates that Robrix supports it,
+/// and returns a summary of it.
+async fn discover_homeserver(target: &str) -> Result<HomeserverSummary, String> {
+    let builder = login_proxy_settings().unwrap_or_default()
+        .apply_to_matrix_client(Client::builder().server_name_or_homeserver_url(target))
+        .map_err(|e| describe_homeserver_build_error(target, &ClientBuildError::Http(HttpError::Reqwest(e))))?;
+    let client = builder
+        .build()
+        .await
+        .map_err(|e| describe_homeserver_build_error(target, &e))?;
+    let homeserver_url = client.homeserver().to_string();
+
+    let versions = client.send(get_supported_versions::Request::new()).await
+        .map_err(|e| format!("Could not get the supported Matrix versions of the homeserver at {homeserver_url}: {e}"))?;
+    homeserver_discovery::validate_spec_versions(&versions.versions)?;
+    let supports_native_sliding_sync = homeserver_discovery::supports_native_sliding_sync(&versions.unstable_features);
+
+    let mut login_methods = Vec::new();
+    match client.matrix_auth().get_login_types().await {
+        Ok(login_types) => for login_type in login_types.flows {
+            match login_type {
+                LoginType::Password(_) => login_methods.push(String::from("password")),
+                LoginType::Sso(sso) if sso.identity_providers.is_empty() => login_methods.push(String::from("SSO")),
+                LoginType::Sso(sso) => login_methods.push(format!(
+                    "SSO ({})",
+                    sso.identity_providers.iter().map(|idp| idp.name.as_str()).collect::<Vec<_>>().join(", "),
+                )),
+                _ => { }
+            }
+        },
+        Err(e) => warning!("Failed to get the login types of the homeserver at {homeserver_url}: {e:?}"),
+    }
+    if client.oauth().server_metadata().await.is_ok() {
+        login_methods.push(String::from("OAuth 2.0 (via browser)"));
+    }
+
+    let mut spec_versions = versions.versions;
+    spec_versions.retain(|version| version.starts_with("v1."));
+    spec_versions.sort_by_key(|version| version[3..].parse::<u32>().unwrap_or_default());
+
+    Ok(HomeserverSummary {
+        server_name: target.to_owned(),
+        software: server_software(&client).await,
+        homeserver_url,
+        spec_versions,
+        supports_native_sliding_sync,
+        login_methods,
+    })
+}
+
+/// Returns the name and version of the given client's homeserver software, if it provides them.
+///
+/// There is no client API for this, but most homeservers also serve the federation API's version endpoint.
+async fn server_software(client: &Client) -> Option<String> {
+    let url = client.homeserver().join("/_matrix/federation/v1/version").ok()?;
+    let response: serde_json::Value = client.http_client()
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send().await.ok()?
+        .json().await.ok()?;
+    let server = response.get("server")?;
+    let name = server.get("name")?.as_str()?;
+    Some(match server.get("version").and_then(|version| version.as_str()) {
+        Some(version) => format!("{name} {version}"),
+        None => name.to_owned(),
+    })
+}
+
 /// Logs in to the given Matrix homeserver using the given username and password.
 ///
 /// This function is used by the login screen to log in to the Matrix server.
@@ -173,7 +290,8 @@ async fn login(
             } else {
                 cli
             };
-            let (client, client_session) = build_client(cli, app_data_dir()).await?;
+            let (client, client_session) = build_client(cli, app_data_dir()).await
+                .map_err(|e| anyhow!(describe_client_build_error(cli, &e)))?;
             // Attempt to login using the CLI-provided username & password.
             let login_result = client
                 .matrix_auth()
@@ -207,9 +325,124 @@ async fn login(
             Ok((client, None))
         }
         LoginRequest::HomeserverLoginTypesQuery(_) => {
-            bail!("LoginRequest::HomeserverLoginTypesQuery not handled earlier");
+            bail!("BUG: LoginRequest::HomeserverLoginTypesQuery should be handled by the async worker");
+        }
+        LoginRequest::Register(register_account) => {
+            let (client, client_session) = register_new_account(register_account).await?;
+            if let Err(e) = persistence::save_session(&client, client_session).await {
+                let err_msg = format!("Failed to save session state to storage: {e}");
+                error!("{err_msg}");
+                enqueue_popup_notification(PopupItem { message: err_msg, kind: PopupKind::Error, auto_dismissal_duration: None });
+            }
+            Ok((client, None))
+        }
+    }
+}
+
+/// The maximum number of user-interactive auth stages that we attempt to complete
+/// during registration, which guards against a homeserver that never accepts a stage.
+const MAX_REGISTRATION_STAGES: usize = 8;
+
+/// Registers a new account and logs into it.
+///
+/// This first checks whether the requested username is available,
+/// and then completes one of the homeserver's registration flows,
+/// as long as all of its stages are supported (see [`registration::SUPPORTED_STAGES`]).
+async fn register_new_account(register_account: RegisterAccount) -> Result<(Client, ClientSessionPersisted)> {
+    let RegisterAccount { username, password, homeserver, registration_token, accepted_terms } = register_account;
+    let cli = Cli { homeserver, ..Default::default() };
+    let (client, client_session) = build_client(&cli, app_data_dir()).await
+        .map_err(|e| anyhow!(describe_client_build_error(&cli, &e)))?;
+
+    Cx::post_action(LoginAction::Status {
+        title: "Creating account...".into(),
+        status: format!("Checking whether the username \"{username}\" is available..."),
+    });
+    match client.send(get_username_availability::v3::Request::new(username.clone())).await {
+        Ok(response) if response.available => { }
+        Ok(_) => bail!("The username \"{username}\" is already taken. Please choose another username."),
+        Err(e) => match e.client_api_error_kind() {
+            Some(ErrorKind::UserInUse) => bail!("The username \"{username}\" is already taken. Please choose another username."),
+            Some(ErrorKind::InvalidUsername) => bail!("The username \"{username}\" is invalid. Usernames may only contain lowercase letters, digits, and the characters \"-._=/+\"."),
+            Some(ErrorKind::Forbidden { .. }) => bail!("This homeserver does not allow registration."),
+            _ => bail!("Failed to check whether the username \"{username}\" is available: {e}"),
+        },
+    }
+
+    Cx::post_action(LoginAction::Status {
+        title: "Creating account...".into(),
+        status: format!("Registering the new account \"{username}\"..."),
+    });
+    let mut request = register::v3::Request::new();
+    request.username = Some(username.clone());
+    request.password = Some(password.clone());
+    request.initial_device_display_name = Some("robrix-un-pw".into());
+
+    for _ in 0..MAX_REGISTRATION_STAGES {
+        let e = match client.matrix_auth().register(request.clone()).await {
+            Ok(_response) => {
+                // The homeserver may not have logged us in during registration.
+                if !client.matrix_auth().logged_in() {
+                    client.matrix_auth()
+                        .login_username(&username, &password)
+                        .initial_device_display_name("robrix-un-pw")
+                        .send()
+                        .await?;
+                }
+                log!("Registered a new account {:?}", client.user_id());
+                return Ok((client, client_session));
+            }
+            Err(e) => e,
+        };
+        let Some(uiaa_info) = e.as_uiaa_response() else {
+            if matches!(e.client_api_error_kind(), Some(ErrorKind::Forbidden { .. })) {
+                bail!("This homeserver does not allow registration.");
+            }
+            return Err(e.into());
+        };
+        if let Some(auth_error) = &uiaa_info.auth_error {
+            bail!("The homeserver rejected the registration: {}", auth_error.message);
         }
+
+        let flow = registration::choose_flow(&uiaa_info.flows, registration_token.is_some())
+            .map_err(|unsupported| anyhow!(registration::unsupported_stages_message(&unsupported)))?;
+        let Some(stage) = flow.stages.iter().find(|stage| !uiaa_info.completed.contains(stage)) else {
+            bail!("The homeserver did not accept the completed registration steps.");
+        };
+        let session = uiaa_info.session.clone();
+        request.auth = Some(match stage {
+            uiaa::AuthType::Dummy => {
+                let mut dummy = uiaa::Dummy::new();
+                dummy.session = session;
+                uiaa::AuthData::Dummy(dummy)
+            }
+            uiaa::AuthType::Terms => {
+                if !accepted_terms {
+                    let policies = uiaa_info.params.as_ref()
+                        .map(|params| registration::terms_policies(params.get()))
+                        .unwrap_or_default();
+                    Cx::post_action(LoginAction::RegistrationTermsRequired(policies));
+                    bail!("This homeserver requires you to accept its terms and policies, \
+                        which are now shown on the registration form.\n\n\
+                        Please review and accept them, then try again.");
+                }
+                let mut terms = uiaa::Terms::new();
+                terms.session = session;
+                uiaa::AuthData::Terms(terms)
+            }
+            uiaa::AuthType::RegistrationToken => {
+                let Some(token) = registration_token.clone() else {
+                    bail!("This homeserver requires a registration token to create an account. \
+                        Please enter the token you received from the homeserver's administrators.");
+                };
+                let mut registration_token = uiaa::RegistrationToken::new(token);
+                registration_token.session = session;
+                uiaa::AuthData::RegistrationToken(registration_token)
+            }
+            other => bail!("BUG: unsupported registration stage {other:?} was chosen."),
+        });
     }
+    bail!("The homeserver required too many registration steps.")
 }
 
 
@@ -286,9 +519,21 @@ pub type OnLinkPreviewFetchedFn = fn(
 pub enum MatrixRequest {
     /// Request from the login screen to log in with the given credentials.
     Login(LoginRequest),
-    /// Request to logout.
+    /// Request to logout, or to perform another account lifecycle operation given by `mode`.
     Logout{
         is_desktop: bool,
+        mode: LogoutMode,
+        /// The account's password, if the homeserver requires it to complete the operation.
+        password: Option<String>,
+        /// Whether the user explicitly confirmed a destructive `mode`.
+        confirmed: bool,
+    },
+    /// Request to switch to another logged-in account without logging out of the current one.
+    ///
+    /// If `user_id` is `None`, the login screen is shown such that a new account can be added.
+    SwitchAccount {
+        user_id: Option<OwnedUserId>,
+        is_desktop: bool,
     },
     /// Request to paginate the older (or newer) events of a room's timeline.
     PaginateRoomTimeline {
@@ -303,6 +548,14 @@ pub enum MatrixRequest {
         timeline_event_item_id: TimelineEventItemId,
         edited_content: EditedContent,
     },
+    /// Request to retry decrypting the events in the given room that were encrypted
+    /// with the given megolm session.
+    ///
+    /// This also attempts to download the session's key from the server-side key backup.
+    RetryDecryption {
+        room_id: OwnedRoomId,
+        session_id: String,
+    },
     /// Request to fetch the full details of the given event in the given room's timeline.
     FetchDetailsForEvent {
         room_id: OwnedRoomId,
@@ -346,6 +599,159 @@ pub enum MatrixRequest {
     GetNumberUnreadMessages {
         room_id: OwnedRoomId,
     },
+    /// Request to load the next page of rooms from the room list service.
+    ///
+    /// This is issued by the RoomsList when the user scrolls to the bottom
+    /// of the currently-loaded rooms.
+    LoadMoreRooms,
+    /// Request to fetch the full details of the given joined room,
+    /// i.e., subscribe to its state in the room list service and fetch its avatar.
+    ///
+    /// These details are deferred until the room is first visible in the RoomsList,
+    /// which avoids fetching details for thousands of rooms upon startup.
+    FetchRoomDetails {
+        room_id: OwnedRoomId,
+    },
+    /// Request to set the user's notification mode for the given room.
+    SetRoomNotificationMode {
+        room_id: OwnedRoomId,
+        mode: RoomNotificationMode,
+    },
+    /// Request to fetch the user's notification settings: the default notification modes
+    /// for each kind of room, their notification keywords, and their mention rules.
+    ///
+    /// The results are posted as `NotificationSettingsAction::DefaultModesFetched`
+    /// and `NotificationSettingsAction::KeywordSettingsFetched` actions.
+    GetNotificationSettings,
+    /// Request to set the user's default notification mode for the given kind of room.
+    SetDefaultNotificationMode {
+        category: DefaultNotificationCategory,
+        mode: RoomNotificationMode,
+    },
+    /// Request to add a keyword that the user wants to be notified about.
+    ///
+    /// Messages containing this keyword are highlighted and count as mentions.
+    AddNotificationKeyword {
+        keyword: String,
+    },
+    /// Request to remove one of the user's notification keywords.
+    RemoveNotificationKeyword {
+        keyword: String,
+    },
+    /// Request to enable or disable the given predefined mention push rule.
+    SetMentionRuleEnabled {
+        rule: MentionRule,
+        enabled: bool,
+    },
+    /// Request to fetch the current status of the user's key backup and recovery.
+    ///
+    /// The result is posted as a `KeyBackupAction::StatusFetched` action.
+    GetKeyBackupStatus,
+    /// Request to set up key backup and recovery, which generates a new recovery key.
+    ///
+    /// If `reset` is `true`, the existing key backup and recovery key are deleted first.
+    /// Progress is posted as `KeyBackupAction::Progress` actions,
+    /// and the new recovery key as a `KeyBackupAction::RecoveryKeyGenerated` action.
+    EnableKeyBackup {
+        /// An optional passphrase that can be used instead of the recovery key.
+        passphrase: Option<String>,
+        reset: bool,
+    },
+    /// Request to restore this session's secrets and room keys from the key backup
+    /// using the given recovery key or passphrase.
+    RecoverKeyBackup {
+        recovery_key: String,
+    },
+    /// Request to export all of this session's room keys to the given file,
+    /// encrypted with the given passphrase.
+    ///
+    /// The result is posted as a `RoomKeysAction`.
+    ExportRoomKeys {
+        path: PathBuf,
+        passphrase: String,
+    },
+    /// Request to import room keys from the given passphrase-encrypted file.
+    ///
+    /// Progress and the result are posted as `RoomKeysAction`s.
+    ImportRoomKeys {
+        path: PathBuf,
+        passphrase: String,
+    },
+    /// Request to fetch the list of the user's devices (sessions).
+    ///
+    /// The result is posted as a `SessionsAction`.
+    GetDevices,
+    /// Request to rename one of the user's devices.
+    RenameDevice {
+        device_id: OwnedDeviceId,
+        display_name: String,
+    },
+    /// Request to sign out (delete) the given devices.
+    ///
+    /// The homeserver typically requires user-interactive authentication to do this,
+    /// for which the given `password` is used.
+    DeleteDevices {
+        device_ids: Vec<OwnedDeviceId>,
+        password: Option<String>,
+    },
+    /// Request to change the current user's password.
+    ///
+    /// The homeserver requires user-interactive authentication to do this,
+    /// for which the given `current_password` is used.
+    ChangePassword {
+        current_password: String,
+        new_password: String,
+        /// Whether the homeserver should also sign out all of the user's other devices.
+        sign_out_other_devices: bool,
+    },
+    /// Request to fetch the email addresses and phone numbers (3PIDs) bound to the user's account.
+    GetThreepids,
+    /// Request that the homeserver sends a validation email or SMS for the given new 3PID.
+    RequestThreepidToken {
+        threepid: NewThreepid,
+    },
+    /// Request to add a new 3PID to the user's account, once the user has validated it.
+    ///
+    /// For phone numbers, the given `verification_code` that was sent via SMS is submitted first.
+    /// The homeserver typically requires user-interactive authentication to add a 3PID,
+    /// for which the given `password` is used.
+    AddThreepid {
+        validation: ThreepidValidation,
+        verification_code: Option<String>,
+        password: Option<String>,
+    },
+    /// Request to remove the given 3PID from the user's account.
+    DeleteThreepid {
+        medium: Medium,
+        address: String,
+    },
+    /// Request to start verifying the given device or user, with this session as the initiator.
+    RequestVerification {
+        target: VerificationTarget,
+    },
+    /// Request to unignore/unblock the given user, who may not share any rooms with us.
+    UnignoreUser {
+        user_id: OwnedUserId,
+    },
+    /// Request to load the current account's saved proxy settings.
+    ///
+    /// The result is posted as a `NetworkSettingsAction::Loaded` action.
+    GetProxySettings,
+    /// Request to save new proxy settings for the current account,
+    /// which take effect the next time its session is restored.
+    SaveProxySettings {
+        proxy: ProxySettings,
+    },
+    /// Request to get the sync mode that the current account uses,
+    /// and whether its homeserver supports native sliding sync.
+    ///
+    /// The result is posted as a `NetworkSettingsAction::SyncModeLoaded` action.
+    GetSyncMode,
+    /// Request to switch the current account to the given sync mode,
+    /// restarting the sync loop without logging out.
+    SetSyncMode {
+        sync_mode: SlidingSyncVersion,
+    },
     /// Request to ignore/block or unignore/unblock a user.
     IgnoreUser {
         /// Whether to ignore (`true`) or unignore (`false`) the user.
@@ -401,6 +807,54 @@ pub enum MatrixRequest {
         homeserver_url: String,
         identity_provider_id: String,
     },
+    /// Request to log in via OAuth 2.0 with a homeserver that uses next-generation auth (MSC3861),
+    /// such as one that delegates authentication to the Matrix Authentication Service.
+    ///
+    /// Like [`MatrixRequest::SpawnSSOServer`], this opens the user's browser
+    /// and waits for it to redirect back to a local loopback server.
+    SpawnOAuthLogin {
+        homeserver_url: String,
+    },
+    /// Request to open the current user's account management page in their browser.
+    ///
+    /// This is only available for accounts that were logged in via OAuth.
+    OpenAccountManagement,
+    /// Request to change the current user's display name.
+    ///
+    /// If `new_display_name` is `None`, the display name will be removed.
+    SetDisplayName {
+        new_display_name: Option<String>,
+    },
+    /// Request to show a file dialog for choosing the current user's new avatar image.
+    ///
+    /// The result is posted as an `AvatarImageAction`.
+    ChooseAvatarImage,
+    /// Request to upload the image file at the given path as the current user's new avatar.
+    ///
+    /// The image is scaled down and re-encoded before being uploaded.
+    UploadAvatar {
+        path: PathBuf,
+    },
+    /// Request to remove the current user's avatar.
+    DeleteAvatar,
+    /// Request to fetch the given user's presence and status message.
+    ///
+    /// The result is enqueued as a `PresenceUpdate` for the presence cache.
+    GetPresence {
+        user_id: OwnedUserId,
+    },
+    /// Request to set the current user's presence and status message.
+    ///
+    /// If the user has chosen not to share their presence, this is saved
+    /// and only sent to the homeserver once presence sharing is re-enabled.
+    SetPresence {
+        presence: PresenceState,
+        status_msg: Option<String>,
+    },
+    /// Request to enable or disable sending the current user's presence to the homeserver.
+    SetPresenceSharing {
+        share_presence: bool,
+    },
     /// Subscribe to typing notices for the given room.
     ///
     /// This request does not return a response or notify the UI thread.
@@ -488,8 +942,13 @@ pub enum LoginRequest{
     LoginByPassword(LoginByPassword),
     LoginBySSOSuccess(Client, ClientSessionPersisted),
     LoginByCli,
+    /// Discovers the homeserver for the given server name, URL, or user ID's server name,
+    /// and validates that Robrix supports it.
+    ///
+    /// The result is posted as a `LoginAction::HomeserverDiscovered` action,
+    /// or a `LoginAction::HomeserverDiscoveryFailed` action.
     HomeserverLoginTypesQuery(String),
-
+    Register(RegisterAccount),
 }
 /// Information needed to log in to a Matrix homeserver.
 pub struct LoginByPassword {
@@ -498,6 +957,17 @@ pub struct LoginByPassword {
     pub homeserver: Option<String>,
 }
 
+/// Information needed to register a new account on a Matrix homeserver.
+pub struct RegisterAccount {
+    pub username: String,
+    pub password: String,
+    pub homeserver: Option<String>,
+    /// The registration token, for homeservers that only allow registration by invitation.
+    pub registration_token: Option<String>,
+    /// Whether the user has accepted the homeserver's terms and policies.
+    pub accepted_terms: bool,
+}
+
 
 /// The entry point for an async worker thread that can run async tasks.
 ///
@@ -506,6 +976,7 @@ pub struct LoginByPassword {
 async fn async_worker(
     mut request_receiver: UnboundedReceiver<MatrixRequest>,
     login_sender: Sender<LoginRequest>,
+    account_switch_sender: UnboundedSender<AccountSwitchRequest>,
 ) -> Result<()> {
     log!("Started async_worker task.");
     let mut subscribers_own_user_read_receipts: BTreeMap<OwnedRoomId, JoinHandle<()>> = BTreeMap::new();
@@ -513,6 +984,15 @@ async fn async_worker(
 
     while let Some(request) = request_receiver.recv().await {
         match request {
+            MatrixRequest::Login(LoginRequest::HomeserverLoginTypesQuery(target)) => {
+                // This doesn't log in, so it's handled here rather than by the login task.
+                let _discover_homeserver_task = Handle::current().spawn(async move {
+                    Cx::post_action(match discover_homeserver(&target).await {
+                        Ok(summary) => LoginAction::HomeserverDiscovered(summary),
+                        Err(error) => LoginAction::HomeserverDiscoveryFailed(error),
+                    });
+                });
+            }
             MatrixRequest::Login(login_request) => {
                 if let Err(e) = login_sender.send(login_request).await {
                     error!("Error sending login request to login_sender: {e:?}");
@@ -522,12 +1002,13 @@ async fn async_worker(
                 }
             }
 
-            MatrixRequest::Logout { is_desktop } => {
-                log!("Received MatrixRequest::Logout, is_desktop={}", is_desktop);
+            MatrixRequest::Logout { is_desktop, mode, password, confirmed } => {
+                log!("Received MatrixRequest::Logout, is_desktop={}, mode={:?}", is_desktop, mode);
+                let config = LogoutConfig { is_desktop, mode, password, confirmed, ..Default::default() };
                 let _logout_task = Handle::current().spawn(async move {
                     log!("Starting logout task");
                     // Use the state machine implementation
-                    match logout_with_state_machine(is_desktop).await {
+                    match logout_with_state_machine(config).await {
                         Ok(()) => {
                             log!("Logout completed successfully via state machine");
                         },
@@ -538,6 +1019,13 @@ async fn async_worker(
                 });
             }
 
+            MatrixRequest::SwitchAccount { user_id, is_desktop } => {
+                // The main loop owns all accounts' clients, so it performs the actual switch.
+                if account_switch_sender.send(AccountSwitchRequest { user_id, is_desktop }).is_err() {
+                    error!("BUG: failed to send account switch request to the main loop.");
+                }
+            }
+
             MatrixRequest::PaginateRoomTimeline { room_id, num_events, direction } => {
                 let (timeline, sender) = {
                     let mut all_joined_rooms = ALL_JOINED_ROOMS.lock().unwrap();
@@ -644,6 +1132,31 @@ async fn async_worker(
                 });
             }
 
+            MatrixRequest::RetryDecryption { room_id, session_id } => {
+                let Some(client) = get_client() else { continue };
+                let timeline = {
+                    let all_joined_rooms = ALL_JOINED_ROOMS.lock().unwrap();
+                    let Some(room_info) = all_joined_rooms.get(&room_id) else {
+                        error!("BUG: room info not found for retry decryption request {room_id}");
+                        continue;
+                    };
+                    room_info.timeline.clone()
+                };
+
+                let _retry_decryption_task = Handle::current().spawn(async move {
+                    let backups = client.encryption().backups();
+                    if backups.are_enabled().await {
+                        if let Err(e) = backups.download_room_key(&room_id, &session_id).await {
+                            warning!("Failed to download room key {session_id} from backup for room {room_id}: {e:?}");
+                        }
+                    }
+                    // Because the `automatic-room-key-forwarding` feature is enabled,
+                    // retrying decryption also re-requests the missing key from our other devices.
+                    timeline.retry_decryption([session_id]).await;
+                    log!("Retried decryption of events in room {room_id}.");
+                });
+            }
+
             MatrixRequest::SyncRoomMemberList { room_id } => {
                 let (timeline, sender) = {
                     let all_joined_rooms = ALL_JOINED_ROOMS.lock().unwrap();
@@ -668,60 +1181,14 @@ async fn async_worker(
             MatrixRequest::JoinRoom { room_id } => {
                 let Some(client) = get_client() else { continue };
                 let _join_room_task = Handle::current().spawn(async move {
-                    log!("Sending request to join room {room_id}...");
-                    let result_action = if let Some(room) = client.get_room(&room_id) {
-                        match room.join().await {
-                            Ok(()) => {
-                                log!("Successfully joined known room {room_id}.");
-                                JoinRoomResultAction::Joined { room_id }
-                            }
-                            Err(e) => {
-                                error!("Error joining known room {room_id}: {e:?}");
-                                JoinRoomResultAction::Failed { room_id, error: e }
-                            }
-                        }
-                    }
-                    else {
-                        match client.join_room_by_id(&room_id).await {
-                            Ok(_room) => {
-                                log!("Successfully joined new unknown room {room_id}.");
-                                JoinRoomResultAction::Joined { room_id }
-                            }
-                            Err(e) => {
-                                error!("Error joining new unknown room {room_id}: {e:?}");
-                                JoinRoomResultAction::Failed { room_id, error: e }
-                            }
-                        }
-                    };
-                    Cx::post_action(result_action);
+                    Cx::post_action(join_room(&client, room_id).await);
                 });
             }
 
             MatrixRequest::LeaveRoom { room_id } => {
                 let Some(client) = get_client() else { continue };
                 let _leave_room_task = Handle::current().spawn(async move {
-                    log!("Sending request to leave room {room_id}...");
-                    let result_action = if let Some(room) = client.get_room(&room_id) {
-                        match room.leave().await {
-                            Ok(()) => {
-                                log!("Successfully left room {room_id}.");
-                                LeaveRoomResultAction::Left { room_id }
-                            }
-                            Err(e) => {
-                                error!("Error leaving room {room_id}: {e:?}");
-                                LeaveRoomResultAction::Failed { room_id, error: e }
-                            }
-                        }
-                    } else {
-                        error!("BUG: client could not get room with ID {room_id}");
-                        LeaveRoomResultAction::Failed {
-                            room_id,
-                            error: matrix_sdk::Error::UnknownError(
-                                String::from("Client couldn't locate room to leave it.").into()
-                            ),
-                        }
-                    };
-                    Cx::post_action(result_action);
+                    Cx::post_action(leave_room(&client, room_id).await);
                 });
             }
 
@@ -851,84 +1318,573 @@ async fn async_worker(
                     });
                 });
             }
-            MatrixRequest::IgnoreUser { ignore, room_member, room_id } => {
+            MatrixRequest::LoadMoreRooms => {
+                LOAD_MORE_ROOMS_NOTIFIER.notify_one();
+            }
+            MatrixRequest::FetchRoomDetails { room_id } => {
+                // With classic sync (i.e., without a sync service), the full state of every room
+                // is already synced, so there is no room list service to subscribe to.
+                let room_list_service = get_sync_service().map(|ss| ss.room_list_service());
+                // The RoomsList may request a room's details several times before it learns
+                // that they've been requested, so we only handle the first such request here.
+                // A request for a not-yet-known room isn't marked as handled, such that it is retried.
+                if ALL_JOINED_ROOMS.lock().unwrap().get(&room_id).is_some_and(|jrd| jrd.has_requested_details) {
+                    continue;
+                }
+                let Some(room) = get_client().and_then(|c| c.get_room(&room_id)) else {
+                    log!("Skipping fetch room details request for not-yet-known room {room_id}");
+                    continue;
+                };
+                if let Some(jrd) = ALL_JOINED_ROOMS.lock().unwrap().get_mut(&room_id) {
+                    jrd.has_requested_details = true;
+                }
+                enqueue_rooms_list_update(RoomsListUpdate::RoomDetailsRequested { room_id: room_id.clone() });
+                let _fetch_details_task = Handle::current().spawn(async move {
+                    // Subscribe to all updates for this room in order to properly receive all of its states,
+                    // as well as its latest event (via `Room::new_latest_event_*()` and the `LatestEvents` API).
+                    if let Some(room_list_service) = room_list_service {
+                        room_list_service.subscribe_to_rooms(&[&room_id]).await;
+                    }
+                    let room_name = room.display_name().await.ok().map(|n| n.to_string());
+                    let avatar = room_avatar(&room, room_name.as_deref()).await;
+                    enqueue_rooms_list_update(RoomsListUpdate::UpdateRoomAvatar {
+                        room_id,
+                        avatar,
+                    });
+                });
+            }
+            MatrixRequest::SetRoomNotificationMode { room_id, mode } => {
                 let Some(client) = get_client() else { continue };
-                let _ignore_task = Handle::current().spawn(async move {
-                    let user_id = room_member.user_id();
-                    log!("Sending request to {}ignore user: {user_id}...", if ignore { "" } else { "un" });
-                    let ignore_result = if ignore {
-                        room_member.ignore().await
-                    } else {
-                        room_member.unignore().await
-                    };
-
-                    log!("{} user {user_id} {}",
-                        if ignore { "Ignoring" } else { "Unignoring" },
-                        if ignore_result.is_ok() { "succeeded." } else { "failed." },
-                    );
-
-                    if ignore_result.is_err() {
-                        return;
+                let _set_notification_mode_task = Handle::current().spawn(async move {
+                    let notification_settings = client.notification_settings().await;
+                    match notification_settings.set_room_notification_mode(&room_id, mode).await {
+                        Ok(()) => {
+                            log!("Set notification mode for room {room_id} to {mode:?}");
+                            // The user chose this room's mode explicitly, so a later change
+                            // to the default mode should no longer un-mute it.
+                            if DEFAULT_MUTE_SETTINGS.lock().unwrap().rooms_muted_by_default.remove(&room_id) {
+                                save_default_mute_settings(&client);
+                            }
+                            enqueue_rooms_list_update(RoomsListUpdate::UpdateNotificationMode { room_id, mode });
+                        }
+                        Err(e) => {
+                            error!("Failed to set notification mode for room {room_id} to {mode:?}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to change the room's notification settings: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                        }
                     }
-
-                    // We need to re-acquire the `RoomMember` object now that its state
-                    // has changed, i.e., the user has been (un)ignored.
-                    // We then need to send an update to replace the cached `RoomMember`
-                    // with the now-stale ignored state.
-                    if let Some(room) = client.get_room(&room_id) {
-                        if let Ok(Some(new_room_member)) = room.get_member(user_id).await {
-                            log!("Enqueueing user profile update for user {user_id}, who went from {}ignored to {}ignored.",
-                                if room_member.is_ignored() { "" } else { "un" },
-                                if new_room_member.is_ignored() { "" } else { "un" },
-                            );
-                            enqueue_user_profile_update(UserProfileUpdate::RoomMemberOnly {
-                                room_id: room_id.clone(),
-                                room_member: new_room_member,
+                });
+            }
+            MatrixRequest::GetKeyBackupStatus => {
+                let Some(client) = get_client() else { continue };
+                let _get_key_backup_status_task = Handle::current().spawn(async move {
+                    Cx::post_action(KeyBackupAction::StatusFetched(key_backup_status(&client).await));
+                });
+            }
+            MatrixRequest::EnableKeyBackup { passphrase, reset } => {
+                let Some(client) = get_client() else { continue };
+                let _enable_key_backup_task = Handle::current().spawn(async move {
+                    match enable_key_backup(&client, passphrase.as_deref(), reset).await {
+                        Ok(recovery_key) => Cx::post_action(KeyBackupAction::RecoveryKeyGenerated(recovery_key)),
+                        Err(e) => {
+                            error!("Failed to set up key backup (reset: {reset}): {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to set up key backup: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
                             });
+                            Cx::post_action(KeyBackupAction::Failed(e.to_string()));
                         }
                     }
-
-                    // After successfully (un)ignoring a user, all timelines are fully cleared by the Matrix SDK.
-                    // Therefore, we need to re-fetch all timelines for all rooms,
-                    // and currently the only way to actually accomplish this is via pagination.
-                    // See: <https://github.com/matrix-org/matrix-rust-sdk/issues/1703#issuecomment-2250297923>
-                    //
-                    // Note that here we only proactively re-paginate the *current* room
-                    // (the one being viewed by the user when this ignore request was issued),
-                    // and all other rooms will be re-paginated in `handle_ignore_user_list_subscriber()`.`
-                    submit_async_request(MatrixRequest::PaginateRoomTimeline {
-                        room_id,
-                        num_events: 50,
-                        direction: PaginationDirection::Backwards,
-                    });
+                    Cx::post_action(KeyBackupAction::StatusFetched(key_backup_status(&client).await));
                 });
             }
-
-            MatrixRequest::SendTypingNotice { room_id, typing } => {
-                let Some(room) = get_client().and_then(|c| c.get_room(&room_id)) else {
-                    error!("BUG: client/room not found for typing notice request {room_id}");
-                    continue;
-                };
-                let _typing_task = Handle::current().spawn(async move {
-                    if let Err(e) = room.typing_notice(typing).await {
-                        error!("Failed to send typing notice to room {room_id}: {e:?}");
+            MatrixRequest::RecoverKeyBackup { recovery_key } => {
+                let Some(client) = get_client() else { continue };
+                let _recover_key_backup_task = Handle::current().spawn(async move {
+                    match client.encryption().recovery().recover(&recovery_key).await {
+                        Ok(()) => {
+                            log!("Successfully restored secrets and room keys from the key backup.");
+                            enqueue_popup_notification(PopupItem {
+                                message: String::from("Restored your key backup. Older encrypted messages will be decrypted as their keys are downloaded."),
+                                kind: PopupKind::Success,
+                                auto_dismissal_duration: Some(5.0),
+                            });
+                            Cx::post_action(KeyBackupAction::Recovered);
+                        }
+                        Err(e) => {
+                            error!("Failed to restore from the key backup: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to restore from your key backup. Please check your recovery key or passphrase.\n\nError: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                            Cx::post_action(KeyBackupAction::Failed(e.to_string()));
+                        }
                     }
+                    Cx::post_action(KeyBackupAction::StatusFetched(key_backup_status(&client).await));
                 });
             }
-
-            MatrixRequest::SubscribeToTypingNotices { room_id, subscribe } => {
-                let (room, timeline_update_sender, mut typing_notice_receiver) = {
-                    let mut all_joined_rooms = ALL_JOINED_ROOMS.lock().unwrap();
-                    let Some(room_info) = all_joined_rooms.get_mut(&room_id) else {
-                        log!("BUG: room info not found for subscribe to typing notices request, room {room_id}");
-                        continue;
-                    };
-                    let (room, recv) = if subscribe {
-                        if room_info.typing_notice_subscriber.is_some() {
-                            warning!("Note: room {room_id} is already subscribed to typing notices.");
-                            continue;
-                        } else {
-                            let Some(room) = get_client().and_then(|c| c.get_room(&room_id)) else {
+            MatrixRequest::ExportRoomKeys { path, passphrase } => {
+                let Some(client) = get_client() else { continue };
+                let _export_room_keys_task = Handle::current().spawn(async move {
+                    match client.encryption().export_room_keys(path.clone(), &passphrase, |_| true).await {
+                        Ok(()) => {
+                            log!("Exported room keys to {}", path.display());
+                            Cx::post_action(RoomKeysAction::Exported(path));
+                        }
+                        Err(e) => {
+                            error!("Failed to export room keys to {}: {e:?}", path.display());
+                            Cx::post_action(RoomKeysAction::Failed(format!("Failed to export room keys: {e}")));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::ImportRoomKeys { path, passphrase } => {
+                let Some(client) = get_client() else { continue };
+                let _import_room_keys_task = Handle::current().spawn(async move {
+                    match import_room_keys(&client, path, passphrase).await {
+                        Ok((imported_count, total_count, rooms)) => {
+                            log!("Imported {imported_count} of {total_count} room keys for {} rooms.", rooms.len());
+                            Cx::post_action(RoomKeysAction::Imported {
+                                imported_count,
+                                total_count,
+                                num_rooms: rooms.len(),
+                            });
+                        }
+                        Err(e) => {
+                            error!("Failed to import room keys: {e:?}");
+                            Cx::post_action(RoomKeysAction::Failed(format!("Failed to import room keys: {e}")));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::GetDevices => {
+                let Some(client) = get_client() else { continue };
+                let _get_devices_task = Handle::current().spawn(async move {
+                    match fetch_devices(&client).await {
+                        Ok(devices) => Cx::post_action(SessionsAction::DevicesFetched(devices)),
+                        Err(e) => {
+                            error!("Failed to fetch devices: {e:?}");
+                            Cx::post_action(SessionsAction::FetchFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::RenameDevice { device_id, display_name } => {
+                let Some(client) = get_client() else { continue };
+                let _rename_device_task = Handle::current().spawn(async move {
+                    if let Err(e) = client.rename_device(&device_id, &display_name).await {
+                        error!("Failed to rename device {device_id} to {display_name:?}: {e:?}");
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Failed to rename session {device_id}: {e}"),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                    }
+                    submit_async_request(MatrixRequest::GetDevices);
+                });
+            }
+            MatrixRequest::DeleteDevices { device_ids, password } => {
+                let Some(client) = get_client() else { continue };
+                let _delete_devices_task = Handle::current().spawn(async move {
+                    let result = delete_devices(&client, &device_ids, password).await;
+                    if let Err(e) = &result {
+                        error!("Failed to sign out devices {device_ids:?}: {e:?}");
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Failed to sign out of the selected sessions: {e}"),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                    }
+                    Cx::post_action(SessionsAction::SignOutFinished { success: result.is_ok() });
+                    submit_async_request(MatrixRequest::GetDevices);
+                });
+            }
+            MatrixRequest::ChangePassword { current_password, new_password, sign_out_other_devices } => {
+                let Some(client) = get_client() else { continue };
+                let _change_password_task = Handle::current().spawn(async move {
+                    match change_password(&client, current_password, new_password, sign_out_other_devices).await {
+                        Ok(()) => {
+                            log!("Changed password (sign out other devices: {sign_out_other_devices})");
+                            Cx::post_action(CredentialsAction::PasswordChanged);
+                            if sign_out_other_devices {
+                                submit_async_request(MatrixRequest::GetDevices);
+                            }
+                        }
+                        Err(e) => {
+                            error!("Failed to change password: {e:?}");
+                            Cx::post_action(CredentialsAction::PasswordChangeFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::GetThreepids => {
+                let Some(client) = get_client() else { continue };
+                let _get_threepids_task = Handle::current().spawn(async move {
+                    match client.account().get_3pids().await {
+                        Ok(response) => {
+                            let threepids = response.threepids.into_iter()
+                                .map(|threepid| ThreepidInfo { medium: threepid.medium, address: threepid.address })
+                                .collect();
+                            Cx::post_action(CredentialsAction::ThreepidsFetched(threepids));
+                        }
+                        Err(e) => {
+                            error!("Failed to fetch 3PIDs: {e:?}");
+                            Cx::post_action(CredentialsAction::ThreepidsFetchFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::RequestThreepidToken { threepid } => {
+                let Some(client) = get_client() else { continue };
+                let _request_threepid_token_task = Handle::current().spawn(async move {
+                    match request_threepid_token(&client, threepid).await {
+                        Ok(validation) => Cx::post_action(CredentialsAction::ValidationStarted(validation)),
+                        Err(e) => {
+                            error!("Failed to request a 3PID validation token: {e:?}");
+                            Cx::post_action(CredentialsAction::ThreepidOperationFailed(
+                                format!("Failed to send a verification code: {e}")
+                            ));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::AddThreepid { validation, verification_code, password } => {
+                let Some(client) = get_client() else { continue };
+                let _add_threepid_task = Handle::current().spawn(async move {
+                    match add_threepid(&client, &validation, verification_code, password).await {
+                        Ok(()) => {
+                            log!("Added 3PID {} ({})", validation.address, validation.medium.as_str());
+                            Cx::post_action(CredentialsAction::ThreepidAdded(validation.address));
+                        }
+                        Err(e) => {
+                            error!("Failed to add 3PID {}: {e:?}", validation.address);
+                            Cx::post_action(CredentialsAction::ThreepidOperationFailed(
+                                format!("Failed to add {}: {e}", validation.address)
+                            ));
+                        }
+                    }
+                    submit_async_request(MatrixRequest::GetThreepids);
+                });
+            }
+            MatrixRequest::DeleteThreepid { medium, address } => {
+                let Some(client) = get_client() else { continue };
+                let _delete_threepid_task = Handle::current().spawn(async move {
+                    if let Err(e) = client.account().delete_3pid(&address, medium, None).await {
+                        error!("Failed to remove 3PID {address}: {e:?}");
+                        Cx::post_action(CredentialsAction::ThreepidOperationFailed(
+                            format!("Failed to remove {address}: {e}")
+                        ));
+                    }
+                    submit_async_request(MatrixRequest::GetThreepids);
+                });
+            }
+            MatrixRequest::RequestVerification { target } => {
+                let Some(client) = get_client() else { continue };
+                let _request_verification_task = Handle::current().spawn(async move {
+                    if let Err(e) = request_verification(client, target.clone()).await {
+                        error!("Failed to request verification of {target:?}: {e:?}");
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Failed to start verification: {e}"),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                    }
+                });
+            }
+            MatrixRequest::GetNotificationSettings => {
+                let Some(client) = get_client() else { continue };
+                let _get_notification_settings_task = Handle::current().spawn(async move {
+                    let notification_settings = client.notification_settings().await;
+                    let modes = default_notification_modes(&notification_settings).await;
+                    Cx::post_action(NotificationSettingsAction::DefaultModesFetched(modes));
+                    let keyword_settings = keyword_settings(&notification_settings).await;
+                    Cx::post_action(NotificationSettingsAction::KeywordSettingsFetched(keyword_settings));
+                });
+            }
+            MatrixRequest::SetDefaultNotificationMode { category, mode } => {
+                let Some(client) = get_client() else { continue };
+                let _set_default_mode_task = Handle::current().spawn(async move {
+                    let notification_settings = client.notification_settings().await;
+                    // Push rules cannot mute a kind of room by default, so for `Mute` we only notify
+                    // about mentions and keywords, and then mute each room of that kind individually.
+                    let push_rule_mode = match mode {
+                        RoomNotificationMode::Mute => RoomNotificationMode::MentionsAndKeywordsOnly,
+                        other => other,
+                    };
+                    // Direct rooms are covered by two separate push rules, one for encrypted rooms
+                    // and one for unencrypted rooms, so we keep both of them in sync.
+                    let targets: &[(IsEncrypted, IsOneToOne)] = match category {
+                        DefaultNotificationCategory::DirectRooms => &[
+                            (IsEncrypted::Yes, IsOneToOne::Yes),
+                            (IsEncrypted::No, IsOneToOne::Yes),
+                        ],
+                        DefaultNotificationCategory::GroupRooms => &[(IsEncrypted::No, IsOneToOne::No)],
+                        DefaultNotificationCategory::EncryptedGroupRooms => &[(IsEncrypted::Yes, IsOneToOne::No)],
+                    };
+                    for (is_encrypted, is_one_to_one) in targets {
+                        if let Err(e) = notification_settings.set_default_room_notification_mode(*is_encrypted, *is_one_to_one, push_rule_mode).await {
+                            error!("Failed to set default notification mode for {category:?} to {mode:?}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to change the default notification settings: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                            Cx::post_action(NotificationSettingsAction::SetDefaultModeFailed(category));
+                            return;
+                        }
+                    }
+                    apply_default_mute(&client, &notification_settings, category, mode == RoomNotificationMode::Mute).await;
+                    log!("Set default notification mode for {category:?} to {mode:?}");
+                    Cx::post_action(NotificationSettingsAction::DefaultModesFetched(
+                        default_notification_modes(&notification_settings).await
+                    ));
+                });
+            }
+            MatrixRequest::AddNotificationKeyword { keyword } => {
+                let Some(client) = get_client() else { continue };
+                let _add_keyword_task = Handle::current().spawn(async move {
+                    // We don't use `NotificationSettings::add_keyword()` because it creates
+                    // a push rule without the highlight tweak, which means that messages
+                    // containing the keyword would not count as mentions.
+                    let actions = vec![
+                        Action::Notify,
+                        Action::SetTweak(Tweak::Sound("default".into())),
+                        Action::SetTweak(Tweak::Highlight(true)),
+                    ];
+                    let rule = NewPushRule::Content(NewPatternedPushRule::new(keyword.clone(), keyword.clone(), actions));
+                    match client.send(set_pushrule::v3::Request::new(rule)).await {
+                        Ok(_) => log!("Added notification keyword {keyword:?}"),
+                        Err(e) => {
+                            error!("Failed to add notification keyword {keyword:?}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to add the keyword \"{keyword}\": {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                        }
+                    }
+                });
+            }
+            MatrixRequest::RemoveNotificationKeyword { keyword } => {
+                let Some(client) = get_client() else { continue };
+                let _remove_keyword_task = Handle::current().spawn(async move {
+                    let notification_settings = client.notification_settings().await;
+                    match notification_settings.remove_keyword(&keyword).await {
+                        Ok(()) => log!("Removed notification keyword {keyword:?}"),
+                        Err(e) => {
+                            error!("Failed to remove notification keyword {keyword:?}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to remove the keyword \"{keyword}\": {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                            // Re-send the current settings such that the settings screen is accurate.
+                            Cx::post_action(NotificationSettingsAction::KeywordSettingsFetched(
+                                keyword_settings(&notification_settings).await
+                            ));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::SetMentionRuleEnabled { rule, enabled } => {
+                let Some(client) = get_client() else { continue };
+                let _set_mention_rule_task = Handle::current().spawn(async move {
+                    let notification_settings = client.notification_settings().await;
+                    for (kind, rule_id) in mention_push_rules(rule) {
+                        if let Err(e) = notification_settings.set_push_rule_enabled(kind, &rule_id, enabled).await {
+                            error!("Failed to set push rule {rule_id} for {rule:?} mentions to enabled: {enabled}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to change the mention notification settings: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                            Cx::post_action(NotificationSettingsAction::KeywordSettingsFetched(
+                                keyword_settings(&notification_settings).await
+                            ));
+                            return;
+                        }
+                    }
+                    log!("Set {rule:?} mention rule to enabled: {enabled}");
+                });
+            }
+            MatrixRequest::IgnoreUser { ignore, room_member, room_id } => {
+                let Some(client) = get_client() else { continue };
+                let _ignore_task = Handle::current().spawn(async move {
+                    let user_id = room_member.user_id();
+                    log!("Sending request to {}ignore user: {user_id}...", if ignore { "" } else { "un" });
+                    let ignore_result = if ignore {
+                        room_member.ignore().await
+                    } else {
+                        room_member.unignore().await
+                    };
+
+                    log!("{} user {user_id} {}",
+                        if ignore { "Ignoring" } else { "Unignoring" },
+                        if ignore_result.is_ok() { "succeeded." } else { "failed." },
+                    );
+
+                    if ignore_result.is_err() {
+                        return;
+                    }
+
+                    // We need to re-acquire the `RoomMember` object now that its state
+                    // has changed, i.e., the user has been (un)ignored.
+                    // We then need to send an update to replace the cached `RoomMember`
+                    // with the now-stale ignored state.
+                    if let Some(room) = client.get_room(&room_id) {
+                        if let Ok(Some(new_room_member)) = room.get_member(user_id).await {
+                            log!("Enqueueing user profile update for user {user_id}, who went from {}ignored to {}ignored.",
+                                if room_member.is_ignored() { "" } else { "un" },
+                                if new_room_member.is_ignored() { "" } else { "un" },
+                            );
+                            enqueue_user_profile_update(UserProfileUpdate::RoomMemberOnly {
+                                room_id: room_id.clone(),
+                                room_member: new_room_member,
+                            });
+                        }
+                    }
+
+                    // After successfully (un)ignoring a user, all timelines are fully cleared by the Matrix SDK.
+                    // Therefore, we need to re-fetch all timelines for all rooms,
+                    // and currently the only way to actually accomplish this is via pagination.
+                    // See: <https://github.com/matrix-org/matrix-rust-sdk/issues/1703#issuecomment-2250297923>
+                    //
+                    // Note that here we only proactively re-paginate the *current* room
+                    // (the one being viewed by the user when this ignore request was issued),
+                    // and all other rooms will be re-paginated in `handle_ignore_user_list_subscriber()`.`
+                    submit_async_request(MatrixRequest::PaginateRoomTimeline {
+                        room_id,
+                        num_events: 50,
+                        direction: PaginationDirection::Backwards,
+                    });
+                });
+            }
+
+            MatrixRequest::UnignoreUser { user_id } => {
+                let Some(client) = get_client() else { continue };
+                let _unignore_task = Handle::current().spawn(async move {
+                    log!("Sending request to unignore user: {user_id}...");
+                    // Upon success, the ignore list subscriber will update the list of ignored users.
+                    if let Err(e) = client.account().unignore_user(&user_id).await {
+                        error!("Failed to unignore user {user_id}: {e:?}");
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Failed to unignore {user_id}: {e}"),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                        Cx::post_action(IgnoredUsersAction::UnignoreFailed(user_id));
+                    }
+                });
+            }
+
+            MatrixRequest::GetProxySettings => {
+                let Some(user_id) = current_user_id() else { continue };
+                let _get_proxy_settings_task = Handle::current().spawn(async move {
+                    match persistence::load_proxy_settings(&user_id).await {
+                        Ok(proxy) => Cx::post_action(NetworkSettingsAction::Loaded(proxy)),
+                        Err(e) => error!("Failed to load the proxy settings of {user_id}: {e:?}"),
+                    }
+                });
+            }
+
+            MatrixRequest::SaveProxySettings { proxy } => {
+                let Some(user_id) = current_user_id() else { continue };
+                let _save_proxy_settings_task = Handle::current().spawn(async move {
+                    match persistence::save_proxy_settings(&user_id, proxy).await {
+                        Ok(()) => {
+                            enqueue_popup_notification(PopupItem {
+                                message: String::from("Saved your proxy settings. They will take effect the next time Robrix starts."),
+                                kind: PopupKind::Success,
+                                auto_dismissal_duration: Some(5.0),
+                            });
+                            Cx::post_action(NetworkSettingsAction::Saved);
+                        }
+                        Err(e) => {
+                            error!("Failed to save the proxy settings of {user_id}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to save your proxy settings: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                            Cx::post_action(NetworkSettingsAction::SaveFailed);
+                        }
+                    }
+                });
+            }
+
+            MatrixRequest::GetSyncMode => {
+                let Some(client) = get_client() else { continue };
+                let _get_sync_mode_task = Handle::current().spawn(async move {
+                    let supports_native_sliding_sync = homeserver_supports_native_sliding_sync(&client).await
+                        .inspect_err(|e| warning!("Failed to check whether the homeserver supports native sliding sync: {e}"))
+                        .ok();
+                    Cx::post_action(NetworkSettingsAction::SyncModeLoaded {
+                        sync_mode: client.sliding_sync_version().into(),
+                        supports_native_sliding_sync,
+                    });
+                });
+            }
+
+            MatrixRequest::SetSyncMode { sync_mode } => {
+                let Some(client) = get_client() else { continue };
+                let _set_sync_mode_task = Handle::current().spawn(async move {
+                    match set_sync_mode(&client, sync_mode).await {
+                        Ok(()) => {
+                            enqueue_popup_notification(PopupItem {
+                                message: match sync_mode {
+                                    SlidingSyncVersion::Native => String::from("Switched to native sliding sync. Reloading your rooms..."),
+                                    SlidingSyncVersion::None => String::from("Switched to classic sync. Reloading your rooms..."),
+                                },
+                                kind: PopupKind::Success,
+                                auto_dismissal_duration: Some(5.0),
+                            });
+                        }
+                        Err(e) => {
+                            error!("Failed to switch to sync mode {sync_mode:?}: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: format!("Failed to switch the sync mode: {e}"),
+                                kind: PopupKind::Error,
+                                auto_dismissal_duration: None,
+                            });
+                        }
+                    }
+                    Cx::post_action(NetworkSettingsAction::SyncModeLoaded {
+                        sync_mode: client.sliding_sync_version().into(),
+                        supports_native_sliding_sync: None,
+                    });
+                });
+            }
+
+            MatrixRequest::SendTypingNotice { room_id, typing } => {
+                let Some(room) = get_client().and_then(|c| c.get_room(&room_id)) else {
+                    error!("BUG: client/room not found for typing notice request {room_id}");
+                    continue;
+                };
+                let _typing_task = Handle::current().spawn(async move {
+                    if let Err(e) = room.typing_notice(typing).await {
+                        error!("Failed to send typing notice to room {room_id}: {e:?}");
+                    }
+                });
+            }
+
+            MatrixRequest::SubscribeToTypingNotices { room_id, subscribe } => {
+                let (room, timeline_update_sender, mut typing_notice_receiver) = {
+                    let mut all_joined_rooms = ALL_JOINED_ROOMS.lock().unwrap();
+                    let Some(room_info) = all_joined_rooms.get_mut(&room_id) else {
+                        log!("BUG: room info not found for subscribe to typing notices request, room {room_id}");
+                        continue;
+                    };
+                    let (room, recv) = if subscribe {
+                        if room_info.typing_notice_subscriber.is_some() {
+                            warning!("Note: room {room_id} is already subscribed to typing notices.");
+                            continue;
+                        } else {
+                            let Some(room) = get_client().and_then(|c| c.get_room(&room_id)) else {
                                 error!("BUG: client/room not found when subscribing to typing notices request, room: {room_id}");
                                 continue;
                             };
@@ -1055,6 +2011,160 @@ async fn async_worker(
             MatrixRequest::SpawnSSOServer { brand, homeserver_url, identity_provider_id} => {
                 spawn_sso_server(brand, homeserver_url, identity_provider_id, login_sender.clone()).await;
             }
+            MatrixRequest::SpawnOAuthLogin { homeserver_url } => {
+                spawn_oauth_login(homeserver_url, login_sender.clone());
+            }
+            MatrixRequest::OpenAccountManagement => {
+                let Some(client) = get_client() else { continue };
+                let _account_management_task = Handle::current().spawn(async move {
+                    let url = match client.oauth().account_management_url().await {
+                        Ok(Some(url_builder)) => url_builder.build(),
+                        Ok(None) => {
+                            enqueue_popup_notification(PopupItem {
+                                message: String::from("Your homeserver does not provide an account management page."),
+                                kind: PopupKind::Warning,
+                                auto_dismissal_duration: Some(5.0),
+                            });
+                            return;
+                        }
+                        Err(e) => {
+                            error!("Failed to get the account management URL: {e:?}");
+                            enqueue_popup_notification(PopupItem {
+                                message: String::from("Account management is only available for accounts that logged in via OAuth."),
+                                kind: PopupKind::Warning,
+                                auto_dismissal_duration: Some(5.0),
+                            });
+                            return;
+                        }
+                    };
+                    log!("Opening account management URL \"{url}\"");
+                    if let Err(e) = Uri::new(url.as_str()).open() {
+                        error!("Failed to open account management URL {url}: {e:?}");
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Failed to open your account management page: {url}"),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                    }
+                });
+            }
+            MatrixRequest::SetDisplayName { new_display_name } => {
+                let Some(client) = get_client() else { continue };
+                let _set_display_name_task = Handle::current().spawn(async move {
+                    match client.account().set_display_name(new_display_name.as_deref()).await {
+                        Ok(()) => {
+                            log!("Changed display name to {new_display_name:?}");
+                            Cx::post_action(OwnProfileAction::DisplayNameChanged(new_display_name));
+                        }
+                        Err(e) => {
+                            error!("Failed to change display name to {new_display_name:?}: {e:?}");
+                            Cx::post_action(OwnProfileAction::DisplayNameUpdateFailed(
+                                format!("Failed to change your display name: {e}")
+                            ));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::ChooseAvatarImage => {
+                let _choose_avatar_image_task = Handle::current().spawn(async move {
+                    Cx::post_action(match account_settings::choose_avatar_image().await {
+                        Some(path) => AvatarImageAction::Chosen(path),
+                        None => AvatarImageAction::Canceled,
+                    });
+                });
+            }
+            MatrixRequest::UploadAvatar { path } => {
+                let Some(client) = get_client() else { continue };
+                let _upload_avatar_task = Handle::current().spawn(async move {
+                    let account = client.account();
+                    let old_avatar_uri = account.get_avatar_url().await.ok().flatten();
+                    let result = async {
+                        let (data, content_type) = tokio::task::spawn_blocking(
+                            move || avatar_upload::load_avatar_image(&path)
+                        ).await??;
+                        log!("Uploading new avatar ({}, {} bytes)...", content_type, data.len());
+                        account.upload_avatar(&content_type, data).await
+                            .map_err(|e| anyhow!("Failed to upload your new avatar: {e}"))
+                    }.await;
+                    match result {
+                        Ok(new_avatar_uri) => Cx::post_action(OwnProfileAction::AvatarChanged {
+                            old_avatar_uri,
+                            new_avatar_uri: Some(new_avatar_uri),
+                        }),
+                        Err(e) => {
+                            error!("Failed to upload avatar: {e:?}");
+                            Cx::post_action(OwnProfileAction::AvatarUpdateFailed(e.to_string()));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::DeleteAvatar => {
+                let Some(client) = get_client() else { continue };
+                let _delete_avatar_task = Handle::current().spawn(async move {
+                    let account = client.account();
+                    let old_avatar_uri = account.get_avatar_url().await.ok().flatten();
+                    match account.set_avatar_url(None).await {
+                        Ok(()) => Cx::post_action(OwnProfileAction::AvatarChanged {
+                            old_avatar_uri,
+                            new_avatar_uri: None,
+                        }),
+                        Err(e) => {
+                            error!("Failed to remove avatar: {e:?}");
+                            Cx::post_action(OwnProfileAction::AvatarUpdateFailed(
+                                format!("Failed to remove your avatar: {e}")
+                            ));
+                        }
+                    }
+                });
+            }
+            MatrixRequest::GetPresence { user_id } => {
+                let Some(client) = get_client() else { continue };
+                let _get_presence_task = Handle::current().spawn(async move {
+                    // Rate-limit presence requests, as every shown member list item may request one.
+                    let Ok(_permit) = PRESENCE_FETCH_PERMITS.acquire().await else { return };
+                    let result = client.send(get_presence::v3::Request::new(user_id.clone())).await;
+                    tokio::time::sleep(PRESENCE_FETCH_SPACING).await;
+                    match result {
+                        Ok(response) => enqueue_presence_update(PresenceUpdate {
+                            user_id,
+                            presence: UserPresence::new(
+                                response.presence,
+                                response.status_msg,
+                                response.currently_active,
+                                response.last_active_ago,
+                            ),
+                        }),
+                        // Homeservers may have presence disabled, so this isn't worth showing to the user.
+                        Err(e) => {
+                            warning!("Failed to get presence of {user_id}: {e}");
+                            enqueue_presence_fetch_failure(user_id);
+                        }
+                    }
+                });
+            }
+            MatrixRequest::SetPresence { presence, status_msg } => {
+                let Some(client) = get_client() else { continue };
+                let presence_settings = {
+                    let mut presence_settings = PRESENCE_SETTINGS.lock().unwrap();
+                    presence_settings.presence = presence;
+                    presence_settings.status_msg = status_msg.filter(|s| !s.trim().is_empty());
+                    presence_settings.clone()
+                };
+                let _set_presence_task = Handle::current().spawn(async move {
+                    update_presence_settings(&client, presence_settings).await;
+                });
+            }
+            MatrixRequest::SetPresenceSharing { share_presence } => {
+                let Some(client) = get_client() else { continue };
+                let presence_settings = {
+                    let mut presence_settings = PRESENCE_SETTINGS.lock().unwrap();
+                    presence_settings.share_presence = share_presence;
+                    presence_settings.clone()
+                };
+                let _set_presence_sharing_task = Handle::current().spawn(async move {
+                    update_presence_settings(&client, presence_settings).await;
+                });
+            }
             MatrixRequest::ResolveRoomAlias(room_alias) => {
                 let Some(client) = get_client() else { continue };
                 let _resolve_task = Handle::current().spawn(async move {
@@ -1108,65 +2218,17 @@ async fn async_worker(
                 // Spawn a new async task that will send the actual message.
                 let _send_message_task = Handle::current().spawn(async move {
                     log!("Sending message to room {room_id}: {message:?}...");
-                    let message = {
-                        #[cfg(not(feature = "tsp"))] {
-                            message
-                        }
-
-                        #[cfg(feature = "tsp")] {
-                            let mut message = message;
-                            if sign_with_tsp {
-                                log!("Signing message with TSP...");
-                                match serde_json::to_vec(&message) {
-                                    Ok(message_bytes) => {
-                                        log!("Serialized message to bytes, length {}", message_bytes.len());
-                                        match crate::tsp::sign_anycast_with_default_vid(&message_bytes) {
-                                            Ok(signed_msg) => {
-                                                log!("Successfully signed message with TSP, length {}", signed_msg.len());
-                                                use matrix_sdk::ruma::serde::Base64;
-                                                message.tsp_signature = Some(Base64::new(signed_msg));
-                                            }
-                                            Err(e) => {
-                                                error!("Failed to sign message with TSP: {e:?}");
-                                                enqueue_popup_notification(PopupItem {
-                                                    message: format!("Failed to sign message with TSP: {e}"),
-                                                    kind: PopupKind::Error,
-                                                    auto_dismissal_duration: None
-                                                });
-                                                return;
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to serialize message to bytes for TSP signing: {e:?}");
-                                        enqueue_popup_notification(PopupItem {
-                                            message: format!("Failed to serialize message for TSP signing: {e}"),
-                                            kind: PopupKind::Error,
-                                            auto_dismissal_duration: None
-                                        });
-                                        return;
-                                    }
-                                }
-                            }
-                            message
-                        }
-                    };
-
-                    if let Some(replied_to_info) = replied_to {
-                        match timeline.send_reply(message.into(), replied_to_info.event_id).await {
-                            Ok(_send_handle) => log!("Sent reply message to room {room_id}."),
-                            Err(_e) => {
-                                error!("Failed to send reply message to room {room_id}: {_e:?}");
-                                enqueue_popup_notification(PopupItem { message: format!("Failed to send reply: {_e}"), kind: PopupKind::Error, auto_dismissal_duration: None });
-                            }
-                        }
-                    } else {
-                        match timeline.send(message.into()).await {
-                            Ok(_send_handle) => log!("Sent message to room {room_id}."),
-                            Err(_e) => {
-                                error!("Failed to send message to room {room_id}: {_e:?}");
-                                enqueue_popup_notification(PopupItem { message: format!("Failed to send message: {_e}"), kind: PopupKind::Error, auto_dismissal_duration: None });
-                            }
+                    let is_reply = replied_to.is_some();
+                    match send_message(
+                        &timeline,
+                        message,
+                        replied_to,
+                        #[cfg(feature = "tsp")] sign_with_tsp,
+                    ).await {
+                        Ok(()) => log!("Sent {} to room {room_id}.", if is_reply { "reply message" } else { "message" }),
+                        Err(e) => {
+                            error!("Failed to send message to room {room_id}: {e:?}");
+                            enqueue_popup_notification(PopupItem { message: format!("Failed to send message: {e}"), kind: PopupKind::Error, auto_dismissal_duration: None });
                         }
                     }
                     SignalToUI::set_ui_signal();
@@ -1470,6 +2532,43 @@ static DEFAULT_SSO_CLIENT: Mutex<Option<(Client, ClientSessionPersisted)>> = Mut
 static DEFAULT_SSO_CLIENT_NOTIFIER: LazyLock<Arc<Notify>> = LazyLock::new(
     || Arc::new(Notify::new())
 );
+/// Used to notify the main loop that the next page of rooms should be loaded from the room list service.
+static LOAD_MORE_ROOMS_NOTIFIER: LazyLock<Arc<Notify>> = LazyLock::new(
+    || Arc::new(Notify::new())
+);
+
+/// Used to notify the main loop that the client's sync mode has changed,
+/// such that it should restart syncing with the new mode.
+static SYNC_MODE_CHANGED_NOTIFIER: LazyLock<Arc<Notify>> = LazyLock::new(
+    || Arc::new(Notify::new())
+);
+
+/// Whether the active account's classic sync loop is paused, e.g., while logging out.
+///
+/// Classic sync has no sync service that could be stopped, so its sync loop watches this instead.
+static CLASSIC_SYNC_PAUSED: LazyLock<watch::Sender<bool>> = LazyLock::new(|| watch::Sender::new(false));
+
+/// Pauses the active account's classic sync loop (if it uses classic sync),
+/// aborting any in-flight sync request, until [`resume_classic_sync()`] is called.
+pub fn pause_classic_sync() {
+    CLASSIC_SYNC_PAUSED.send_replace(true);
+}
+
+/// Resumes the active account's classic sync loop after [`pause_classic_sync()`].
+pub fn resume_classic_sync() {
+    CLASSIC_SYNC_PAUSED.send_replace(false);
+}
+
+/// The long-polling timeout of each classic sync request.
+const CLASSIC_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before retrying a failed classic sync request.
+const CLASSIC_SYNC_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The number of rooms to load from the room list service in each page.
+///
+/// The first page is loaded upon startup, and subsequent pages are loaded
+/// as the user scrolls down the RoomsList, via [`MatrixRequest::LoadMoreRooms`].
+const ROOM_LIST_PAGE_SIZE: usize = 100;
 
 /// Blocks the current thread until the given future completes.
 ///
@@ -1509,14 +2608,15 @@ pub fn start_matrix_tokio() -> Result<tokio::runtime::Handle> {
     REQUEST_SENDER.lock().unwrap().replace(sender);
 
     let (login_sender, login_receiver) = tokio::sync::mpsc::channel(1);
+    let (account_switch_sender, account_switch_receiver) = tokio::sync::mpsc::unbounded_channel();
     // Start a high-level async task that will start and monitor all other tasks.
     let rt = rt_handle.clone();
     let _monitor = rt_handle.spawn(async move {
         // Spawn the actual async worker thread.
-        let mut worker_join_handle = rt.spawn(async_worker(receiver, login_sender));
+        let mut worker_join_handle = rt.spawn(async_worker(receiver, login_sender, account_switch_sender));
 
         // Start the main loop that drives the Matrix client SDK.
-        let mut main_loop_join_handle = rt.spawn(async_main_loop(login_receiver));
+        let mut main_loop_join_handle = rt.spawn(async_main_loop(login_receiver, account_switch_receiver));
         // Build a Matrix Client in the background so that SSO Server starts earlier.
         rt.spawn(async move {
             match build_client(&Cli::default(), app_data_dir()).await {
@@ -1628,6 +2728,9 @@ struct JoinedRoomDetails {
     typing_notice_subscriber: Option<EventHandlerDropGuard>,
     /// A drop guard for the event handler that represents a subscription to pinned events for this room.
     pinned_events_subscriber: Option<EventHandlerDropGuard>,
+    /// Whether the full details of this room have been requested via [`MatrixRequest::FetchRoomDetails`],
+    /// which only occurs once this room has been visible in the RoomsList.
+    has_requested_details: bool,
 }
 impl Drop for JoinedRoomDetails {
     fn drop(&mut self) {
@@ -1680,6 +2783,34 @@ pub fn is_user_ignored(user_id: &UserId) -> bool {
     IGNORED_USERS.lock().unwrap().contains(user_id)
 }
 
+/// The keywords that the user wants to be notified about,
+/// which are kept in sync with the user's keyword push rules.
+static NOTIFICATION_KEYWORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The maximum number of presence requests that may be in flight at once.
+const MAX_CONCURRENT_PRESENCE_FETCHES: usize = 4;
+/// How long each presence request keeps its permit after it finishes,
+/// which limits the rate of presence requests to the homeserver.
+const PRESENCE_FETCH_SPACING: Duration = Duration::from_millis(250);
+/// Limits how many presence requests are sent to the homeserver at once.
+static PRESENCE_FETCH_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_PRESENCE_FETCHES);
+
+/// The current user's presence settings, which are restored upon login.
+static PRESENCE_SETTINGS: Mutex<PresenceSettings> = Mutex::new(PresenceSettings::new());
+
+/// The kinds of rooms that the current user mutes by default, which are restored upon login.
+static DEFAULT_MUTE_SETTINGS: Mutex<DefaultMuteSettings> = Mutex::new(DefaultMuteSettings::new());
+
+/// Returns a clone of the current user's presence settings.
+pub fn presence_settings() -> PresenceSettings {
+    PRESENCE_SETTINGS.lock().unwrap().clone()
+}
+
+/// Returns a clone of the user's current notification keywords.
+pub fn get_notification_keywords() -> Vec<String> {
+    NOTIFICATION_KEYWORDS.lock().unwrap().clone()
+}
+
 
 /// Returns three channel endpoints related to the timeline for the given joined room.
 ///
@@ -1710,7 +2841,11 @@ pub fn take_timeline_endpoints(
         })
 }
 
+/// The server name of the homeserver used if the user doesn't specify one.
 const DEFAULT_HOMESERVER: &str = "matrix.org";
+/// The URL of the [`DEFAULT_HOMESERVER`]'s client API, which clients connect to
+/// if the user doesn't specify a homeserver.
+const DEFAULT_HOMESERVER_URL: &str = "https://matrix-client.matrix.org/";
 
 fn username_to_full_user_id(
     username: &str,
@@ -1782,6 +2917,7 @@ impl RoomListServiceRoomInfo {
 
 async fn async_main_loop(
     mut login_receiver: Receiver<LoginRequest>,
+    mut account_switch_receiver: UnboundedReceiver<AccountSwitchRequest>,
 ) -> Result<()> {
     // only init subscribe once
     let _ = tracing_subscriber::fmt::try_init();
@@ -1846,35 +2982,70 @@ async fn async_main_loop(
         None
     };
     let cli: Cli = cli_parse_result.unwrap_or(Cli::default());
-    let (client, _sync_token) = match new_login_opt {
-        Some(new_login) => new_login,
-        None => {
-            loop {
-                log!("Waiting for login request...");
-                match login_receiver.recv().await {
-                    Some(login_request) => {
-                        match login(&cli, login_request).await {
-                            Ok((client, sync_token)) => {
-                                break (client, sync_token);
-                            }
-                            Err(e) => {
-                                error!("Login failed: {e:?}");
-                                Cx::post_action(LoginAction::LoginFailure(format!("{e}")));
-                                enqueue_rooms_list_update(RoomsListUpdate::Status {
-                                    status: format!("Login failed: {e}"),
-                                });
-                            }
-                        }
-                    },
-                    None => {
-                        error!("BUG: login_receiver hung up unexpectedly");
-                        return Err(anyhow::anyhow!("BUG: login_receiver hung up unexpectedly"));
+    let mut client = match new_login_opt {
+        Some((client, _sync_token)) => client,
+        None => wait_for_login(&cli, &mut login_receiver).await?,
+    };
+    setup_new_client(&client);
+
+    // Keep all other logged-in accounts syncing in the background,
+    // such that their unread counts are live and switching to them is instant.
+    start_background_accounts(client.user_id().map(ToOwned::to_owned));
+
+    // Bind the UI to the active account until the user switches to a different account.
+    loop {
+        let switch_request = tokio::select! {
+            result = run_active_account(&client) => return result,
+            switch_request = account_switch_receiver.recv() => switch_request
+                .ok_or_else(|| anyhow!("BUG: account_switch_receiver hung up unexpectedly"))?,
+        };
+        client = switch_active_account(client, switch_request, &cli, &mut login_receiver).await?;
+    }
+}
+
+/// Waits for the user to log in via the login screen, returning the newly logged-in client.
+async fn wait_for_login(cli: &Cli, login_receiver: &mut Receiver<LoginRequest>) -> Result<Client> {
+    loop {
+        log!("Waiting for login request...");
+        match login_receiver.recv().await {
+            Some(login_request) => {
+                match login(cli, login_request).await {
+                    Ok((client, _sync_token)) => return Ok(client),
+                    Err(e) => {
+                        error!("Login failed: {e:?}");
+                        Cx::post_action(LoginAction::LoginFailure(format!("{e}")));
+                        enqueue_rooms_list_update(RoomsListUpdate::Status {
+                            status: format!("Login failed: {e}"),
+                        });
                     }
                 }
+            },
+            None => {
+                error!("BUG: login_receiver hung up unexpectedly");
+                return Err(anyhow::anyhow!("BUG: login_receiver hung up unexpectedly"));
             }
         }
-    };
+    }
+}
+
+/// Performs the one-time setup of a newly logged-in or restored client,
+/// regardless of whether it is the active account or a background account.
+fn setup_new_client(client: &Client) {
+    // Persist the session's tokens whenever they are refreshed.
+    handle_session_changes(client.clone());
+
+    // Show local notifications for new events that the user's push rules say should notify.
+    let client = client.clone();
+    Handle::current().spawn(async move {
+        notifications::register_notification_handler(&client).await;
+    });
+}
 
+/// Binds the UI to the given client's account and syncs it.
+///
+/// This only returns if syncing fails; switching accounts simply drops this future
+/// and then unbinds the UI from this account via [`unbind_active_account()`].
+async fn run_active_account(client: &Client) -> Result<()> {
     Cx::post_action(LoginAction::LoginSuccess);
 
     // Deallocate the default SSO client after a successful login.
@@ -1888,25 +3059,102 @@ async fn async_main_loop(
     // enqueue_popup_notification(status.clone());
     enqueue_rooms_list_update(RoomsListUpdate::Status { status });
 
-    client.event_cache().subscribe().expect("BUG: CLIENT's event cache unable to subscribe");
+    client.event_cache().subscribe().expect("BUG: CLIENT's event cache unable to subscribe");
+
+    if let Some(_existing) = CLIENT.lock().unwrap().replace(client.clone()) {
+        error!("BUG: unexpectedly replaced an existing client when initializing the matrix client.");
+    }
+
+    add_verification_event_handlers_and_sync_client(client.clone());
+
+    // Listen for updates to the ignored user list.
+    handle_ignore_user_list_subscriber(client.clone());
+
+    // Listen for changes to the user's notification settings (push rules).
+    handle_notification_settings_subscriber(client.clone());
+
+    // Restore the user's presence settings, and start showing other users' presence.
+    *PRESENCE_SETTINGS.lock().unwrap() = persistence::load_presence_settings(logged_in_user_id);
+    handle_presence(client.clone());
+    *DEFAULT_MUTE_SETTINGS.lock().unwrap() = persistence::load_default_mute_settings(logged_in_user_id);
+
+    // Attempt to load the previously-saved app state.
+    // Include this after re-login.
+    handle_load_app_state(logged_in_user_id.to_owned());
+
+    if SlidingSyncVersion::from(client.sliding_sync_version()) == SlidingSyncVersion::None {
+        enqueue_popup_notification(PopupItem {
+            message: String::from("Your homeserver does not support native sliding sync, so Robrix is using classic sync, \
+                which may be slower to load your rooms. You can change this in the Network settings."),
+            kind: PopupKind::Info,
+            auto_dismissal_duration: Some(8.0),
+        });
+    }
+
+    // Sync using the client's current sync mode, restarting whenever the user switches modes.
+    resume_classic_sync();
+    let mut all_known_rooms: Vector<RoomListServiceRoomInfo> = Vector::new();
+    loop {
+        let sync_mode = SlidingSyncVersion::from(client.sliding_sync_version());
+        log!("Starting to sync with sync mode {sync_mode:?}...");
+        // Only classic sync delivers other users' presence.
+        set_presence_synced(sync_mode == SlidingSyncVersion::None);
+        tokio::select! {
+            result = async {
+                match sync_mode {
+                    SlidingSyncVersion::Native => sync_with_room_list_service(client, &mut all_known_rooms).await,
+                    SlidingSyncVersion::None => sync_with_classic_sync(client, &mut all_known_rooms).await,
+                }
+            } => {
+                result?;
+                bail!("sync loop ended unexpectedly");
+            }
+            _ = SYNC_MODE_CHANGED_NOTIFIER.notified() => { }
+        }
 
-    if let Some(_existing) = CLIENT.lock().unwrap().replace(client.clone()) {
-        error!("BUG: unexpectedly replaced an existing client when initializing the matrix client.");
+        log!("Sync mode changed; stopping the {sync_mode:?} sync loop...");
+        let sync_service = SYNC_SERVICE.lock().unwrap().take();
+        if let Some(sync_service) = sync_service {
+            sync_service.stop().await;
+        }
+        // The new sync loop re-adds all rooms, so remove all of the currently-known rooms.
+        while let Some(room) = all_known_rooms.pop_back() {
+            remove_room(&room);
+        }
+        ALL_JOINED_ROOMS.lock().unwrap().clear();
+        enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
     }
+}
 
-    add_verification_event_handlers_and_sync_client(client.clone());
-
-    // Listen for updates to the ignored user list.
-    handle_ignore_user_list_subscriber(client.clone());
+/// Switches the given client to the given sync mode, saves it into the client's persisted session,
+/// and then notifies the main loop to restart syncing with the new mode.
+async fn set_sync_mode(client: &Client, sync_mode: SlidingSyncVersion) -> Result<()> {
+    if SlidingSyncVersion::from(client.sliding_sync_version()) == sync_mode {
+        return Ok(());
+    }
+    if sync_mode == SlidingSyncVersion::Native && !homeserver_supports_native_sliding_sync(client).await? {
+        bail!("{}", homeserver_discovery::no_sliding_sync_error(client.homeserver().as_str()));
+    }
+    let user_id = client.user_id().ok_or_else(|| anyhow!("The client is not logged in"))?;
+    persistence::save_sliding_sync_version(user_id, sync_mode).await?;
+    client.set_sliding_sync_version(sync_mode.into());
+    SYNC_MODE_CHANGED_NOTIFIER.notify_one();
+    Ok(())
+}
 
+/// Syncs the given client using the room list service (native sliding sync),
+/// adding the rooms that it loads into the given list of `all_known_rooms`.
+///
+/// This only returns if an error occurs.
+async fn sync_with_room_list_service(
+    client: &Client,
+    all_known_rooms: &mut Vector<RoomListServiceRoomInfo>,
+) -> Result<()> {
     let sync_service = SyncService::builder(client.clone())
         .with_offline_mode()
         .build()
         .await?;
 
-    // Attempt to load the previously-saved app state.
-    // Include this after re-login.
-    handle_load_app_state(logged_in_user_id.to_owned());
     handle_sync_indicator_subscriber(&sync_service);
     handle_sync_service_state_subscriber(sync_service.state());
     sync_service.start().await;
@@ -1917,137 +3165,256 @@ async fn async_main_loop(
     }
 
     let all_rooms_list = room_list_service.all_rooms().await?;
-    handle_room_list_service_loading_state(all_rooms_list.loading_state());
+    let mut loading_state = all_rooms_list.loading_state();
+    log!("Initial room list loading state is {:?}", loading_state.get());
+    let mut max_rooms_in_list = None;
+    let mut reported_max_rooms = None;
 
     let (room_diff_stream, room_list_dynamic_entries_controller) =
-        // TODO: paginate room list to avoid loading all rooms at once
-        all_rooms_list.entries_with_dynamic_adapters(usize::MAX);
+        // Only load one page of rooms at a time, to avoid loading all rooms at once
+        // for accounts with thousands of rooms. Subsequent pages are loaded on demand.
+        all_rooms_list.entries_with_dynamic_adapters(ROOM_LIST_PAGE_SIZE);
 
     room_list_dynamic_entries_controller.set_filter(
         Box::new(|_room| true),
     );
 
-    let mut all_known_rooms: Vector<RoomListServiceRoomInfo> = Vector::new();
-
     pin_mut!(room_diff_stream);
-    while let Some(batch) = room_diff_stream.next().await {
-        let mut peekable_diffs = batch.into_iter().peekable();
-        while let Some(diff) = peekable_diffs.next() {
-            match diff {
-                VectorDiff::Append { values: new_rooms } => {
-                    let _num_new_rooms = new_rooms.len();
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Append {_num_new_rooms}"); }
-                    for new_room in new_rooms {
-                        let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
-                        add_new_room(&new_room, &room_list_service).await?;
-                        all_known_rooms.push_back(new_room);
+    loop {
+        tokio::select! {
+            batch_opt = room_diff_stream.next() => {
+                let Some(batch) = batch_opt else { break };
+                handle_room_list_diffs(batch, all_known_rooms).await?;
+                report_max_listed_rooms(max_rooms_in_list, all_known_rooms, &mut reported_max_rooms);
+            }
+            state_opt = loading_state.next() => {
+                let Some(state) = state_opt else { break };
+                log!("Received a room list loading state update: {state:?}");
+                match state {
+                    RoomListLoadingState::NotLoaded => {
+                        reported_max_rooms = None;
+                        enqueue_rooms_list_update(RoomsListUpdate::NotLoaded);
+                    }
+                    RoomListLoadingState::Loaded { maximum_number_of_rooms } => {
+                        max_rooms_in_list = maximum_number_of_rooms;
+                        reported_max_rooms = None;
+                        report_max_listed_rooms(max_rooms_in_list, all_known_rooms, &mut reported_max_rooms);
                     }
                 }
-                VectorDiff::Clear => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Clear"); }
-                    all_known_rooms.clear();
-                    ALL_JOINED_ROOMS.lock().unwrap().clear();
-                    enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
-                }
-                VectorDiff::PushFront { value: new_room } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PushFront"); }
-                    let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
-                    add_new_room(&new_room, &room_list_service).await?;
-                    all_known_rooms.push_front(new_room);
+            }
+            _ = LOAD_MORE_ROOMS_NOTIFIER.notified() => {
+                log!("Loading another page of {ROOM_LIST_PAGE_SIZE} rooms ({} rooms known so far)...", all_known_rooms.len());
+                room_list_dynamic_entries_controller.add_one_page();
+            }
+        }
+    }
+
+    bail!("room list service sync loop ended unexpectedly")
+}
+
+/// Syncs the given client using classic sync, for homeservers that lack native sliding sync,
+/// adding the client's rooms into the given list of `all_known_rooms`.
+///
+/// Classic sync has no server-side room list, so all rooms are loaded at once
+/// rather than page by page, and each sync response determines which rooms to update.
+///
+/// This only returns if an error occurs.
+async fn sync_with_classic_sync(
+    client: &Client,
+    all_known_rooms: &mut Vector<RoomListServiceRoomInfo>,
+) -> Result<()> {
+    enqueue_rooms_list_update(RoomsListUpdate::NotLoaded);
+    // Show the rooms that are already known from previous syncs before the first new sync completes.
+    update_rooms_from_classic_sync(client, all_known_rooms, None).await?;
+
+    let sync_settings = SyncSettings::default().timeout(CLASSIC_SYNC_TIMEOUT);
+    let mut is_initial_sync = true;
+    let mut is_paused = CLASSIC_SYNC_PAUSED.subscribe();
+    Cx::post_action(RoomsListHeaderAction::SetSyncStatus(true));
+    loop {
+        let _ = is_paused.wait_for(|is_paused| !is_paused).await;
+        // Each sync request also tells the homeserver the current user's presence.
+        let presence = PRESENCE_SETTINGS.lock().unwrap().presence_to_send();
+        let sync_result = tokio::select! {
+            sync_result = client.sync_once(sync_settings.clone().set_presence(presence)) => sync_result,
+            _ = is_paused.wait_for(|is_paused| *is_paused) => continue,
+        };
+        match sync_result {
+            Ok(response) => {
+                let updated_room_ids = response.rooms.joined.keys()
+                    .chain(response.rooms.invited.keys())
+                    .chain(response.rooms.knocked.keys())
+                    .chain(response.rooms.left.keys())
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                update_rooms_from_classic_sync(client, all_known_rooms, Some(&updated_room_ids)).await?;
+                if is_initial_sync {
+                    is_initial_sync = false;
+                    Cx::post_action(RoomsListHeaderAction::SetSyncStatus(false));
+                    enqueue_rooms_list_update(RoomsListUpdate::LoadedRooms {
+                        max_rooms: u32::try_from(num_listed_rooms(all_known_rooms)).ok(),
+                    });
                 }
-                VectorDiff::PushBack { value: new_room } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PushBack"); }
+            }
+            Err(e) => {
+                warning!("Classic sync failed, retrying in {CLASSIC_SYNC_RETRY_DELAY:?}: {e}");
+                tokio::time::sleep(CLASSIC_SYNC_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Adds or updates the given client's rooms in the given list of `all_known_rooms`.
+///
+/// If `updated_room_ids` is given, only those rooms are added or updated.
+async fn update_rooms_from_classic_sync(
+    client: &Client,
+    all_known_rooms: &mut Vector<RoomListServiceRoomInfo>,
+    updated_room_ids: Option<&BTreeSet<OwnedRoomId>>,
+) -> Result<()> {
+    let mut known_room_indices: BTreeMap<OwnedRoomId, usize> = all_known_rooms.iter()
+        .enumerate()
+        .map(|(index, known_room)| (known_room.room_id.clone(), index))
+        .collect();
+    for room in client.rooms() {
+        if updated_room_ids.is_some_and(|ids| !ids.contains(room.room_id())) {
+            continue;
+        }
+        let new_room = RoomListServiceRoomInfo::from_room(room).await;
+        match known_room_indices.get(&new_room.room_id) {
+            Some(&index) => {
+                update_room(&all_known_rooms[index], &new_room).await?;
+                all_known_rooms.set(index, new_room);
+            }
+            None => {
+                add_new_room(&new_room).await?;
+                known_room_indices.insert(new_room.room_id.clone(), all_known_rooms.len());
+                all_known_rooms.push_back(new_room);
+            }
+        }
+    }
+    Ok(())
+}
+
+
+/// Handles a batch of diffs received from the room list service,
+/// applying them to the given list of `all_known_rooms`.
+async fn handle_room_list_diffs(
+    batch: Vec<VectorDiff<RoomListItem>>,
+    all_known_rooms: &mut Vector<RoomListServiceRoomInfo>,
+) -> Result<()> {
+    let mut peekable_diffs = batch.into_iter().peekable();
+    while let Some(diff) = peekable_diffs.next() {
+        match diff {
+            VectorDiff::Append { values: new_rooms } => {
+                let _num_new_rooms = new_rooms.len();
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Append {_num_new_rooms}"); }
+                for new_room in new_rooms {
                     let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
-                    add_new_room(&new_room, &room_list_service).await?;
+                    add_new_room(&new_room).await?;
                     all_known_rooms.push_back(new_room);
                 }
-                remove_diff @ VectorDiff::PopFront => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PopFront"); }
-                    if let Some(room) = all_known_rooms.pop_front() {
-                        optimize_remove_then_add_into_update(
-                            remove_diff,
-                            &room,
-                            &mut peekable_diffs,
-                            &mut all_known_rooms,
-                            &room_list_service,
-                        ).await?;
-                    }
+            }
+            VectorDiff::Clear => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Clear"); }
+                all_known_rooms.clear();
+                ALL_JOINED_ROOMS.lock().unwrap().clear();
+                enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
+            }
+            VectorDiff::PushFront { value: new_room } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PushFront"); }
+                let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
+                add_new_room(&new_room).await?;
+                all_known_rooms.push_front(new_room);
+            }
+            VectorDiff::PushBack { value: new_room } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PushBack"); }
+                let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
+                add_new_room(&new_room).await?;
+                all_known_rooms.push_back(new_room);
+            }
+            remove_diff @ VectorDiff::PopFront => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PopFront"); }
+                if let Some(room) = all_known_rooms.pop_front() {
+                    optimize_remove_then_add_into_update(
+                        remove_diff,
+                        &room,
+                        &mut peekable_diffs,
+                        all_known_rooms,
+                    ).await?;
                 }
-                remove_diff @ VectorDiff::PopBack => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PopBack"); }
-                    if let Some(room) = all_known_rooms.pop_back() {
-                        optimize_remove_then_add_into_update(
-                            remove_diff,
-                            &room,
-                            &mut peekable_diffs,
-                            &mut all_known_rooms,
-                            &room_list_service,
-                        ).await?;
-                    }
+            }
+            remove_diff @ VectorDiff::PopBack => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff PopBack"); }
+                if let Some(room) = all_known_rooms.pop_back() {
+                    optimize_remove_then_add_into_update(
+                        remove_diff,
+                        &room,
+                        &mut peekable_diffs,
+                        all_known_rooms,
+                    ).await?;
                 }
-                VectorDiff::Insert { index, value: new_room } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Insert at {index}"); }
-                    let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
-                    add_new_room(&new_room, &room_list_service).await?;
-                    all_known_rooms.insert(index, new_room);
+            }
+            VectorDiff::Insert { index, value: new_room } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Insert at {index}"); }
+                let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
+                add_new_room(&new_room).await?;
+                all_known_rooms.insert(index, new_room);
+            }
+            VectorDiff::Set { index, value: changed_room } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Set at {index}"); }
+                let changed_room = RoomListServiceRoomInfo::from_room(changed_room.into_inner()).await;
+                if let Some(old_room) = all_known_rooms.get(index) {
+                    update_room(old_room, &changed_room).await?;
+                } else {
+                    error!("BUG: room list diff: Set index {index} was out of bounds.");
                 }
-                VectorDiff::Set { index, value: changed_room } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Set at {index}"); }
-                    let changed_room = RoomListServiceRoomInfo::from_room(changed_room.into_inner()).await;
-                    if let Some(old_room) = all_known_rooms.get(index) {
-                        update_room(old_room, &changed_room, &room_list_service).await?;
-                    } else {
-                        error!("BUG: room list diff: Set index {index} was out of bounds.");
-                    }
-                    all_known_rooms.set(index, changed_room);
+                all_known_rooms.set(index, changed_room);
+            }
+            remove_diff @ VectorDiff::Remove { index: remove_index } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Remove at {remove_index}"); }
+                if remove_index < all_known_rooms.len() {
+                    let room = all_known_rooms.remove(remove_index);
+                    optimize_remove_then_add_into_update(
+                        remove_diff,
+                        &room,
+                        &mut peekable_diffs,
+                        all_known_rooms,
+                    ).await?;
+                } else {
+                    error!("BUG: room_list: diff Remove index {remove_index} out of bounds, len {}", all_known_rooms.len());
                 }
-                remove_diff @ VectorDiff::Remove { index: remove_index } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Remove at {remove_index}"); }
-                    if remove_index < all_known_rooms.len() {
-                        let room = all_known_rooms.remove(remove_index);
-                        optimize_remove_then_add_into_update(
-                            remove_diff,
-                            &room,
-                            &mut peekable_diffs,
-                            &mut all_known_rooms,
-                            &room_list_service,
-                        ).await?;
-                    } else {
-                        error!("BUG: room_list: diff Remove index {remove_index} out of bounds, len {}", all_known_rooms.len());
+            }
+            VectorDiff::Truncate { length } => {
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Truncate to {length}"); }
+                // Iterate manually so we can know which rooms are being removed.
+                while all_known_rooms.len() > length {
+                    if let Some(room) = all_known_rooms.pop_back() {
+                        remove_room(&room);
                     }
                 }
-                VectorDiff::Truncate { length } => {
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Truncate to {length}"); }
-                    // Iterate manually so we can know which rooms are being removed.
-                    while all_known_rooms.len() > length {
-                        if let Some(room) = all_known_rooms.pop_back() {
-                            remove_room(&room);
-                        }
-                    }
-                    all_known_rooms.truncate(length); // sanity check
+                all_known_rooms.truncate(length); // sanity check
+            }
+            VectorDiff::Reset { values: new_rooms } => {
+                // We implement this by clearing all rooms and then adding back the new values.
+                if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Reset, old length {}, new length {}", all_known_rooms.len(), new_rooms.len()); }
+                // Iterate manually so we can know which rooms are being removed.
+                while let Some(room) = all_known_rooms.pop_back() {
+                    remove_room(&room);
                 }
-                VectorDiff::Reset { values: new_rooms } => {
-                    // We implement this by clearing all rooms and then adding back the new values.
-                    if LOG_ROOM_LIST_DIFFS { log!("room_list: diff Reset, old length {}, new length {}", all_known_rooms.len(), new_rooms.len()); }
-                    // Iterate manually so we can know which rooms are being removed.
-                    while let Some(room) = all_known_rooms.pop_back() {
-                        remove_room(&room);
-                    }
-                    // ALL_JOINED_ROOMS should already be empty due to successive calls to `remove_room()`,
-                    // so this is just a sanity check.
-                    ALL_JOINED_ROOMS.lock().unwrap().clear();
-                    enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
-                    for new_room in new_rooms.into_iter() {
-                        let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
-                        add_new_room(&new_room, &room_list_service).await?;
-                        all_known_rooms.push_back(new_room);
-                    }
+                // ALL_JOINED_ROOMS should already be empty due to successive calls to `remove_room()`,
+                // so this is just a sanity check.
+                ALL_JOINED_ROOMS.lock().unwrap().clear();
+                enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
+                for new_room in new_rooms.into_iter() {
+                    let new_room = RoomListServiceRoomInfo::from_room(new_room.into_inner()).await;
+                    add_new_room(&new_room).await?;
+                    all_known_rooms.push_back(new_room);
                 }
             }
         }
     }
-
-    bail!("room list service sync loop ended unexpectedly")
+    Ok(())
 }
 
 
@@ -2065,7 +3432,6 @@ async fn optimize_remove_then_add_into_update(
     room: &RoomListServiceRoomInfo,
     peekable_diffs: &mut Peekable<impl Iterator<Item = VectorDiff<RoomListItem>>>,
     all_known_rooms: &mut Vector<RoomListServiceRoomInfo>,
-    room_list_service: &RoomListService,
 ) -> Result<()> {
     let next_diff_was_handled: bool;
     match peekable_diffs.peek() {
@@ -2076,7 +3442,7 @@ async fn optimize_remove_then_add_into_update(
                 log!("Optimizing {remove_diff:?} + Insert({insert_index}) into Update for room {}", room.room_id);
             }
             let new_room = RoomListServiceRoomInfo::from_room_ref(new_room.deref()).await;
-            update_room(room, &new_room, room_list_service).await?;
+            update_room(room, &new_room).await?;
             all_known_rooms.insert(*insert_index, new_room);
             next_diff_was_handled = true;
         }
@@ -2087,7 +3453,7 @@ async fn optimize_remove_then_add_into_update(
                 log!("Optimizing {remove_diff:?} + PushFront into Update for room {}", room.room_id);
             }
             let new_room = RoomListServiceRoomInfo::from_room_ref(new_room.deref()).await;
-            update_room(room, &new_room, room_list_service).await?;
+            update_room(room, &new_room).await?;
             all_known_rooms.push_front(new_room);
             next_diff_was_handled = true;
         }
@@ -2098,7 +3464,7 @@ async fn optimize_remove_then_add_into_update(
                 log!("Optimizing {remove_diff:?} + PushBack into Update for room {}", room.room_id);
             }
             let new_room = RoomListServiceRoomInfo::from_room_ref(new_room.deref()).await;
-            update_room(room, &new_room, room_list_service).await?;
+            update_room(room, &new_room).await?;
             all_known_rooms.push_back(new_room);
             next_diff_was_handled = true;
         }
@@ -2117,7 +3483,6 @@ async fn optimize_remove_then_add_into_update(
 async fn update_room(
     old_room: &RoomListServiceRoomInfo,
     new_room: &RoomListServiceRoomInfo,
-    room_list_service: &RoomListService,
 ) -> Result<()> {
     let new_room_id = new_room.room_id.clone();
     if old_room.room_id == new_room_id {
@@ -2145,11 +3510,11 @@ async fn update_room(
                 }
                 RoomState::Joined => {
                     log!("update_room(): adding new Joined room: {:?} ({new_room_id})", new_room.display_name);
-                    return add_new_room(new_room, room_list_service).await;
+                    return add_new_room(new_room).await;
                 }
                 RoomState::Invited => {
                     log!("update_room(): adding new Invited room: {:?} ({new_room_id})", new_room.display_name);
-                    return add_new_room(new_room, room_list_service).await;
+                    return add_new_room(new_room).await;
                 }
                 RoomState::Knocked => {
                     // TODO: handle Knocked rooms (e.g., can you re-knock? or cancel a prior knock?)
@@ -2162,8 +3527,14 @@ async fn update_room(
         // including joined, invited, and other rooms.
         // This includes the room name and room avatar.
         if old_room.room_avatar != new_room.room_avatar {
-            log!("Updating room avatar for room {}", new_room_id);
-            spawn_fetch_room_avatar(new_room);
+            // Joined rooms that haven't yet been visible will fetch their avatar later on demand.
+            let should_fetch_avatar = ALL_JOINED_ROOMS.lock().unwrap()
+                .get(&new_room_id)
+                .is_none_or(|jrd| jrd.has_requested_details);
+            if should_fetch_avatar {
+                log!("Updating room avatar for room {}", new_room_id);
+                spawn_fetch_room_avatar(new_room);
+            }
         }
         if old_room.display_name != new_room.display_name {
             log!("Updating room {} name: {:?} --> {:?}", new_room_id, old_room.display_name, new_room.display_name);
@@ -2221,6 +3592,7 @@ async fn update_room(
                 enqueue_rooms_list_update(RoomsListUpdate::UpdateIsDirect {
                     room_id: new_room_id.clone(),
                     is_direct: new_room.is_direct,
+                    direct_user_id: new_room.is_direct.then(|| direct_user_id(&new_room.room)).flatten(),
                 });
             }
 
@@ -2272,7 +3644,7 @@ async fn update_room(
             old_room.room_id, new_room_id,
         );
         remove_room(old_room);
-        add_new_room(new_room, room_list_service).await
+        add_new_room(new_room).await
     }
 }
 
@@ -2292,7 +3664,6 @@ fn remove_room(room: &RoomListServiceRoomInfo) {
 /// Invoked when the room list service has received an update with a brand new room.
 async fn add_new_room(
     new_room: &RoomListServiceRoomInfo,
-    room_list_service: &RoomListService,
 ) -> Result<()> {
     match new_room.state {
         RoomState::Knocked => {
@@ -2331,7 +3702,14 @@ async fn add_new_room(
             let room_name = new_room.display_name.as_ref().map(|n| n.to_string());
             let room_avatar = room_avatar(&new_room.room, room_name.as_deref()).await;
 
-            let inviter_info = if let Some(inviter) = invite_details.and_then(|d| d.inviter) {
+            let inviter = invite_details.and_then(|d| d.inviter);
+            // Don't show invites from ignored users.
+            // These are re-added via `update_invites_from_ignored_users()` if the inviter is unignored.
+            if let Some(inviter) = inviter.as_ref().filter(|i| is_user_ignored(i.user_id())) {
+                log!("Hiding invite to room {} from ignored user {}", new_room.room_id, inviter.user_id());
+                return Ok(());
+            }
+            let inviter_info = if let Some(inviter) = inviter {
                 Some(InviterInfo {
                     user_id: inviter.user_id().to_owned(),
                     display_name: inviter.display_name().map(|n| n.to_string()),
@@ -2363,11 +3741,9 @@ async fn add_new_room(
         RoomState::Joined => { } // Fall through to adding the joined room below.
     }
 
-    // Subscribe to all updates for this room in order to properly receive all of its states,
-    // as well as its latest event (via `Room::new_latest_event_*()` and the `LatestEvents` API).
-    room_list_service.subscribe_to_rooms(&[&new_room.room_id]).await;
-
-
+    // Note: we don't subscribe to this room's updates in the room list service
+    // or fetch its avatar here; that is deferred until the room is first visible
+    // in the RoomsList, at which point it will issue a `MatrixRequest::FetchRoomDetails`.
     let timeline = Arc::new(
         new_room.room.timeline_builder()
             .track_read_marker_and_receipts()
@@ -2401,11 +3777,29 @@ async fn add_new_room(
             timeline_subscriber_handler_task,
             typing_notice_subscriber: None,
             pinned_events_subscriber: None,
+            has_requested_details: false,
         },
     );
     // We need to add the room to the `ALL_JOINED_ROOMS` list before we can
     // send the `AddJoinedRoom` update to the UI, because the UI might immediately
     // issue a `MatrixRequest` that relies on that room being in `ALL_JOINED_ROOMS`.
+    // Newly-joined rooms of a kind that is muted by default must be muted individually.
+    let has_muted_categories = !DEFAULT_MUTE_SETTINGS.lock().unwrap().muted_categories.is_empty();
+    let is_muted_by_default = if let (true, Some(client)) = (has_muted_categories, get_client()) {
+        let is_muted = mute_room_by_default(&client.notification_settings().await, &new_room.room).await;
+        if is_muted {
+            save_default_mute_settings(&client);
+        }
+        is_muted
+    } else {
+        false
+    };
+    let notification_mode = if is_muted_by_default {
+        RoomNotificationMode::Mute
+    } else {
+        room_notification_mode(&new_room.room).await
+    };
+
     let room_name = new_room.display_name.as_ref().map(|n| n.to_string());
     rooms_list::enqueue_rooms_list_update(RoomsListUpdate::AddJoinedRoom(JoinedRoomInfo {
         room_id: new_room.room_id.clone(),
@@ -2413,24 +3807,35 @@ async fn add_new_room(
         tags: new_room.tags.clone().unwrap_or_default(),
         num_unread_messages: new_room.num_unread_messages,
         num_unread_mentions: new_room.num_unread_mentions,
-        // start with a basic text avatar; the avatar image will be fetched asynchronously below.
+        notification_mode,
+        // start with a basic text avatar; the avatar image will be fetched
+        // asynchronously once this room is visible in the RoomsList.
         avatar: avatar_from_room_name(room_name.as_deref()),
         room_name,
         canonical_alias: new_room.room.canonical_alias(),
         alt_aliases: new_room.room.alt_aliases(),
         has_been_paginated: false,
+        has_requested_details: false,
         is_selected: false,
         is_direct: new_room.is_direct,
+        direct_user_id: new_room.is_direct.then(|| direct_user_id(&new_room.room)).flatten(),
         is_tombstoned: new_room.is_tombstoned,
     }));
 
     Cx::post_action(AppStateAction::RoomLoadedSuccessfully(new_room.room_id.clone()));
-    spawn_fetch_room_avatar(new_room);
 
     Ok(())
 }
 
-#[allow(unused)]
+/// Returns the ID of the other user in the given direct room, if there is exactly one.
+fn direct_user_id(room: &Room) -> Option<OwnedUserId> {
+    let mut direct_user_ids = room.direct_targets()
+        .into_iter()
+        .filter_map(|target| target.as_user_id().map(ToOwned::to_owned));
+    let user_id = direct_user_ids.next()?;
+    direct_user_ids.next().is_none().then_some(user_id)
+}
+
 async fn current_ignore_user_list(client: &Client) -> Option<BTreeSet<OwnedUserId>> {
     use matrix_sdk::ruma::events::ignored_user_list::IgnoredUserListEventContent;
     let ignored_users = client.account()
@@ -2449,7 +3854,14 @@ async fn current_ignore_user_list(client: &Client) -> Option<BTreeSet<OwnedUserI
 fn handle_ignore_user_list_subscriber(client: Client) {
     let mut subscriber = client.subscribe_to_ignore_user_list_changes();
     log!("Initial ignored-user list is: {:?}", subscriber.get());
-    Handle::current().spawn(async move {
+    spawn_bound_task(async move {
+        // Load the ignored-user list from the locally-stored account data upon boot,
+        // since the subscriber only yields an update once the list changes.
+        if let Some(ignored_users) = current_ignore_user_list(&client).await {
+            *IGNORED_USERS.lock().unwrap() = ignored_users.clone();
+            Cx::post_action(IgnoredUsersAction::Updated(ignored_users));
+        }
+
         let mut first_update = true;
         while let Some(ignore_list) = subscriber.next().await {
             log!("Received an updated ignored-user list: {ignore_list:?}");
@@ -2458,28 +3870,580 @@ fn handle_ignore_user_list_subscriber(client: Client) {
                 .filter_map(|u| OwnedUserId::try_from(u).ok())
                 .collect::<BTreeSet<_>>();
 
-            // TODO: when we support persistent state, don't forget to update `IGNORED_USERS` upon app boot.
-            let mut ignored_users_old = IGNORED_USERS.lock().unwrap();
-            let has_changed = *ignored_users_old != ignored_users_new;
-            *ignored_users_old = ignored_users_new;
+            let ignored_users_old = std::mem::replace(
+                &mut *IGNORED_USERS.lock().unwrap(),
+                ignored_users_new.clone(),
+            );
+            let has_changed = ignored_users_old != ignored_users_new;
+            if has_changed {
+                Cx::post_action(IgnoredUsersAction::Updated(ignored_users_new.clone()));
+            }
+
+            if has_changed && !first_update {
+                // After successfully (un)ignoring a user, all timelines are fully cleared by the Matrix SDK.
+                // Therefore, we need to re-fetch all timelines for all rooms,
+                // and currently the only way to actually accomplish this is via pagination.
+                // See: <https://github.com/matrix-org/matrix-rust-sdk/issues/1703#issuecomment-2250297923>
+                for joined_room in client.joined_rooms() {
+                    submit_async_request(MatrixRequest::PaginateRoomTimeline {
+                        room_id: joined_room.room_id().to_owned(),
+                        num_events: 50,
+                        direction: PaginationDirection::Backwards,
+                    });
+                }
+            }
+            if has_changed {
+                update_invites_from_ignored_users(&client, &ignored_users_old, &ignored_users_new).await;
+            }
+
+            first_update = false;
+        }
+    });
+}
+
+/// Hides invites from newly-ignored users and re-shows invites from newly-unignored users.
+async fn update_invites_from_ignored_users(
+    client: &Client,
+    ignored_users_old: &BTreeSet<OwnedUserId>,
+    ignored_users_new: &BTreeSet<OwnedUserId>,
+) {
+    for invited_room in client.invited_rooms() {
+        let Some(inviter) = invited_room.invite_details().await.ok().and_then(|d| d.inviter) else { continue };
+        let inviter_id = inviter.user_id();
+        match (ignored_users_old.contains(inviter_id), ignored_users_new.contains(inviter_id)) {
+            (false, true) => {
+                log!("Hiding invite to room {} from newly-ignored user {inviter_id}", invited_room.room_id());
+                enqueue_rooms_list_update(RoomsListUpdate::RemoveRoom {
+                    room_id: invited_room.room_id().to_owned(),
+                    new_state: RoomState::Invited,
+                });
+            }
+            (true, false) => {
+                log!("Re-showing invite to room {} from unignored user {inviter_id}", invited_room.room_id());
+                let room_info = RoomListServiceRoomInfo::from_room(invited_room).await;
+                if let Err(e) = add_new_room(&room_info).await {
+                    error!("Failed to re-add invited room {}: {e:?}", room_info.room_id);
+                }
+            }
+            _ => { }
+        }
+    }
+}
+
+/// How often the current user's presence is re-sent to the homeserver,
+/// which otherwise marks them as unavailable after a few minutes.
+const PRESENCE_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// Listens for other users' presence, and keeps the current user's presence up to date.
+///
+/// Presence events are only received via classic sync; with native sliding sync,
+/// other users' presence is fetched on demand by the presence cache.
+/// Likewise, native sliding sync requests don't update the current user's presence,
+/// so it is periodically re-sent to the homeserver.
+fn handle_presence(client: Client) {
+    bind_event_handler(client.add_event_handler(|event: PresenceEvent| async move {
+        let content = event.content;
+        enqueue_presence_update(PresenceUpdate {
+            user_id: event.sender,
+            presence: UserPresence::new(
+                content.presence,
+                content.status_msg,
+                content.currently_active,
+                content.last_active_ago.map(|ago| Duration::from_millis(ago.into())),
+            ),
+        });
+    }));
+
+    spawn_bound_task(async move {
+        let mut is_first_update = true;
+        // Stop once the user has logged out.
+        while let Some(client) = get_client() {
+            let presence_settings = presence_settings();
+            // If the user doesn't share their presence, it only needs to be set to offline once.
+            if presence_settings.share_presence || is_first_update {
+                if let Err(e) = send_own_presence(&client, &presence_settings).await {
+                    warning!("Failed to send presence: {e}");
+                }
+            }
+            is_first_update = false;
+            tokio::time::sleep(PRESENCE_KEEPALIVE_INTERVAL).await;
+        }
+    });
+}
+
+/// Saves the given presence settings for the current user,
+/// and then sends the resulting presence and status message to the homeserver.
+async fn update_presence_settings(client: &Client, presence_settings: PresenceSettings) {
+    let Some(user_id) = client.user_id() else { return };
+    if let Err(e) = persistence::save_presence_settings(user_id, &presence_settings) {
+        error!("Failed to save presence settings: {e:?}");
+    }
+    if let Err(e) = send_own_presence(client, &presence_settings).await {
+        error!("Failed to update presence: {e:?}");
+        enqueue_popup_notification(PopupItem {
+            message: format!("Failed to update your presence: {e}"),
+            kind: PopupKind::Error,
+            auto_dismissal_duration: None,
+        });
+    }
+}
+
+/// Sends the current user's presence and status message to the homeserver,
+/// as determined by the given presence settings.
+async fn send_own_presence(client: &Client, presence_settings: &PresenceSettings) -> Result<()> {
+    let user_id = client.user_id().ok_or_else(|| anyhow!("Not logged in"))?.to_owned();
+    let presence = presence_settings.presence_to_send();
+    // Don't share a status message if the user doesn't share their presence.
+    let status_msg = presence_settings.status_msg.clone()
+        .filter(|_| presence_settings.share_presence);
+    let mut request = set_presence::v3::Request::new(user_id.clone(), presence.clone());
+    request.status_msg = status_msg.clone();
+    client.send(request).await?;
+    // Show our own new presence right away, rather than waiting for it to be re-fetched.
+    enqueue_presence_update(PresenceUpdate {
+        user_id,
+        presence: UserPresence::new(presence, status_msg, Some(presence_settings.share_presence), None),
+    });
+    Ok(())
+}
+
+/// Listens for changes to the user's push rules, which define their notification settings.
+///
+/// Upon each change, this re-sends the notification mode of every joined room to the RoomsList
+/// and the default notification modes to the settings screen, because a single change
+/// to a default push rule can affect the effective mode of many rooms at once.
+/// It also updates the list of notification keywords that are highlighted in messages.
+fn handle_notification_settings_subscriber(client: Client) {
+    spawn_bound_task(async move {
+        let notification_settings = client.notification_settings().await;
+        let mut changes = notification_settings.subscribe_to_changes();
+        *NOTIFICATION_KEYWORDS.lock().unwrap() = keyword_settings(&notification_settings).await.keywords;
+        loop {
+            match changes.recv().await {
+                Ok(()) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => { }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+            log!("Received an update to the notification settings.");
+            let joined_room_ids: Vec<OwnedRoomId> = ALL_JOINED_ROOMS.lock().unwrap().keys().cloned().collect();
+            for room_id in joined_room_ids {
+                let Some(room) = client.get_room(&room_id) else { continue };
+                enqueue_rooms_list_update(RoomsListUpdate::UpdateNotificationMode {
+                    room_id,
+                    mode: room_notification_mode(&room).await,
+                });
+            }
+            Cx::post_action(NotificationSettingsAction::DefaultModesFetched(
+                default_notification_modes(&notification_settings).await
+            ));
+            let keyword_settings = keyword_settings(&notification_settings).await;
+            *NOTIFICATION_KEYWORDS.lock().unwrap() = keyword_settings.keywords.clone();
+            Cx::post_action(NotificationSettingsAction::KeywordSettingsFetched(keyword_settings));
+        }
+    });
+}
+
+/// Returns the effective notification mode for the given room,
+/// which is either the mode the user set for this room or the default mode for this kind of room.
+pub async fn room_notification_mode(room: &Room) -> RoomNotificationMode {
+    room.notification_mode().await.unwrap_or(RoomNotificationMode::AllMessages)
+}
+
+/// Fetches the list of the user's devices, including their verification status.
+///
+/// The current device is listed first, followed by the others from most to least recently seen.
+async fn fetch_devices(client: &Client) -> Result<Vec<DeviceInfo>> {
+    let user_id = client.user_id().ok_or_else(|| anyhow!("Not logged in"))?.to_owned();
+    let current_device_id = client.device_id().map(ToOwned::to_owned);
+    let response = client.devices().await?;
+    let mut devices = Vec::with_capacity(response.devices.len());
+    for device in response.devices {
+        let is_verified = match client.encryption().get_device(&user_id, &device.device_id).await {
+            Ok(Some(crypto_device)) => crypto_device.is_verified(),
+            _ => false,
+        };
+        devices.push(DeviceInfo {
+            is_current: current_device_id.as_ref() == Some(&device.device_id),
+            device_id: device.device_id,
+            display_name: device.display_name,
+            last_seen_ip: device.last_seen_ip,
+            last_seen_ts: device.last_seen_ts,
+            is_verified,
+        });
+    }
+    devices.sort_by(|a, b| b.is_current.cmp(&a.is_current).then(b.last_seen_ts.cmp(&a.last_seen_ts)));
+    Ok(devices)
+}
+
+/// Signs out (deletes) the given devices, using the given password
+/// to complete user-interactive authentication if the homeserver requires it.
+async fn delete_devices(client: &Client, device_ids: &[OwnedDeviceId], password: Option<String>) -> Result<()> {
+    let Err(e) = client.delete_devices(device_ids, None).await else { return Ok(()) };
+    let Some(uiaa_info) = e.as_uiaa_response() else { return Err(e.into()) };
+    let Some(password) = password else {
+        bail!("Your homeserver requires your password to sign out of other sessions.");
+    };
+    let auth = password_auth_data(client, uiaa_info, password)?;
+    client.delete_devices(device_ids, Some(auth)).await?;
+    Ok(())
+}
+
+/// Returns the auth data needed to complete the given user-interactive auth session
+/// using the current user's password.
+fn password_auth_data(client: &Client, uiaa_info: &uiaa::UiaaInfo, password: String) -> Result<uiaa::AuthData> {
+    let user_id = client.user_id().ok_or_else(|| anyhow!("Not logged in"))?;
+    let mut auth = uiaa::Password::new(UserIdentifier::UserIdOrLocalpart(user_id.to_string()), password);
+    auth.session = uiaa_info.session.clone();
+    Ok(uiaa::AuthData::Password(auth))
+}
+
+/// Changes the current user's password, completing user-interactive auth with their current password.
+async fn change_password(
+    client: &Client,
+    current_password: String,
+    new_password: String,
+    sign_out_other_devices: bool,
+) -> Result<()> {
+    let mut request = change_password::v3::Request::new(new_password);
+    request.logout_devices = sign_out_other_devices;
+    let Err(e) = client.send(request.clone()).await else { return Ok(()) };
+    let Some(uiaa_info) = e.as_uiaa_response() else { return Err(e.into()) };
+    request.auth = Some(password_auth_data(client, uiaa_info, current_password)?);
+    client.send(request).await?;
+    Ok(())
+}
+
+/// Asks the homeserver to send a validation email or SMS for the given new 3PID.
+async fn request_threepid_token(client: &Client, threepid: NewThreepid) -> Result<ThreepidValidation> {
+    let client_secret = OwnedClientSecret::try_from(utils::random_alphanumeric_string(32))?;
+    // We always start a new validation session, so this is always the first attempt.
+    let send_attempt = UInt::from(1u32);
+    let account = client.account();
+    let validation = match threepid {
+        NewThreepid::Email(email) => {
+            let response = account.request_3pid_email_token(&client_secret, &email, send_attempt).await?;
+            ThreepidValidation {
+                medium: Medium::Email,
+                address: email,
+                client_secret,
+                session_id: response.sid,
+                submit_url: response.submit_url,
+            }
+        }
+        NewThreepid::PhoneNumber { country, number } => {
+            let response = account.request_3pid_msisdn_token(&client_secret, &country, &number, send_attempt).await?;
+            ThreepidValidation {
+                medium: Medium::Msisdn,
+                address: number,
+                client_secret,
+                session_id: response.sid,
+                submit_url: response.submit_url,
+            }
+        }
+    };
+    Ok(validation)
+}
+
+/// Adds the given validated 3PID to the current user's account.
+///
+/// For phone numbers, the `verification_code` is first submitted to the validation session's submit URL.
+async fn add_threepid(
+    client: &Client,
+    validation: &ThreepidValidation,
+    verification_code: Option<String>,
+    password: Option<String>,
+) -> Result<()> {
+    if let Some(verification_code) = verification_code {
+        let Some(submit_url) = validation.submit_url.as_deref() else {
+            bail!("Your homeserver doesn't support verifying phone numbers.");
+        };
+        submit_msisdn_token(submit_url, validation, &verification_code).await?;
+    }
+    let account = client.account();
+    let Err(e) = account.add_3pid(&validation.client_secret, &validation.session_id, None).await else { return Ok(()) };
+    let Some(uiaa_info) = e.as_uiaa_response() else { return Err(e.into()) };
+    let Some(password) = password else {
+        bail!("Your homeserver requires your password to add an email address or phone number.");
+    };
+    let auth = password_auth_data(client, uiaa_info, password)?;
+    account.add_3pid(&validation.client_secret, &validation.session_id, Some(auth)).await?;
+    Ok(())
+}
+
+/// Submits the verification code that was sent to a phone number to the given `submit_url`.
+async fn submit_msisdn_token(submit_url: &str, validation: &ThreepidValidation, verification_code: &str) -> Result<()> {
+    let http_client = active_proxy_settings()
+        .apply_to_reqwest(reqwest::ClientBuilder::new())?
+        .build()?;
+    let response = http_client.post(submit_url)
+        .json(&serde_json::json!({
+            "sid": validation.session_id,
+            "client_secret": validation.client_secret,
+            "token": verification_code,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    let body: serde_json::Value = response.json().await?;
+    if body.get("success").and_then(serde_json::Value::as_bool) != Some(true) {
+        bail!("The verification code was incorrect.");
+    }
+    Ok(())
+}
+
+/// The number of room keys imported at once by [`import_room_keys()`].
+const ROOM_KEY_IMPORT_CHUNK_SIZE: usize = 500;
+
+/// Imports room keys from the given passphrase-encrypted file,
+/// posting `RoomKeysAction::ImportProgress` actions along the way.
+///
+/// The SDK can only import an entire key file at once, without reporting its progress.
+/// Thus, we decrypt the file ourselves and then import its keys in chunks,
+/// each of which is re-encrypted into a temporary file with a random passphrase.
+/// Afterwards, the open timelines of the affected rooms retry decrypting their events.
+///
+/// Returns the number of newly-imported sessions, the total number of sessions in the file,
+/// and the set of rooms that those sessions belong to.
+async fn import_room_keys(
+    client: &Client,
+    path: PathBuf,
+    passphrase: String,
+) -> Result<(usize, usize, BTreeSet<OwnedRoomId>)> {
+    let keys = tokio::task::spawn_blocking(move || -> Result<Vec<ExportedRoomKey>> {
+        let file = std::fs::File::open(&path)?;
+        Ok(decrypt_room_key_export(file, &passphrase)?)
+    }).await??;
+    let total = keys.len();
+    Cx::post_action(RoomKeysAction::ImportProgress { processed: 0, total });
+
+    let chunk_passphrase = utils::random_alphanumeric_string(32);
+    let chunk_path = crate::temp_storage::get_temp_dir_path()
+        .join(format!("room_keys_import_{}.txt", std::process::id()));
+    let (mut imported_count, mut total_count) = (0, 0);
+    let mut sessions_by_room: BTreeMap<OwnedRoomId, Vec<String>> = BTreeMap::new();
+    let mut processed = 0;
+    for chunk in keys.chunks(ROOM_KEY_IMPORT_CHUNK_SIZE) {
+        // The temporary file is only readable with the random passphrase, which is never stored,
+        // so a single round of key derivation suffices here.
+        let exported = encrypt_room_key_export(chunk, &chunk_passphrase, 1)?;
+        tokio::fs::write(&chunk_path, exported).await?;
+        let result = client.encryption().import_room_keys(chunk_path.clone(), &chunk_passphrase).await;
+        let _ = tokio::fs::remove_file(&chunk_path).await;
+        let result = result?;
+
+        imported_count += result.imported_count;
+        total_count += result.total_count;
+        for (room_id, sessions_by_sender) in result.keys {
+            sessions_by_room.entry(room_id).or_default()
+                .extend(sessions_by_sender.into_values().flatten());
+        }
+        processed += chunk.len();
+        Cx::post_action(RoomKeysAction::ImportProgress { processed, total });
+    }
+
+    let mut rooms = BTreeSet::new();
+    for (room_id, session_ids) in sessions_by_room {
+        let timeline = ALL_JOINED_ROOMS.lock().unwrap()
+            .get(&room_id)
+            .map(|room| room.timeline.clone());
+        if let Some(timeline) = timeline {
+            timeline.retry_decryption(session_ids).await;
+        }
+        rooms.insert(room_id);
+    }
+    Ok((imported_count, total_count, rooms))
+}
+
+/// Fetches the current status of the user's key backup and recovery.
+pub async fn key_backup_status(client: &Client) -> KeyBackupStatus {
+    let encryption = client.encryption();
+    let backups = encryption.backups();
+    KeyBackupStatus {
+        recovery_state: encryption.recovery().state(),
+        backup_enabled: backups.are_enabled().await,
+        backup_exists_on_server: backups.fetch_exists_on_server().await.unwrap_or_else(|e| {
+            warning!("Failed to check whether a key backup exists on the server: {e}");
+            false
+        }),
+    }
+}
+
+/// Sets up key backup and recovery, returning the newly-generated recovery key.
+///
+/// This waits for all existing room keys to be uploaded to the new backup,
+/// posting `KeyBackupAction::Progress` actions along the way.
+async fn enable_key_backup(client: &Client, passphrase: Option<&str>, reset: bool) -> Result<String> {
+    let recovery = client.encryption().recovery();
+    if reset {
+        log!("Deleting the existing key backup and recovery key...");
+        recovery.disable().await?;
+    }
+    let mut enable = recovery.enable().wait_for_backups_to_upload();
+    if let Some(passphrase) = passphrase {
+        enable = enable.with_passphrase(passphrase);
+    }
+    let mut progress_stream = enable.subscribe_to_progress();
+    let report_progress = async move {
+        while let Some(progress) = progress_stream.next().await {
+            let progress = match progress {
+                Ok(EnableProgress::CreatingBackup) => KeyBackupProgress::CreatingBackup,
+                Ok(EnableProgress::CreatingRecoveryKey) => KeyBackupProgress::CreatingRecoveryKey,
+                Ok(EnableProgress::BackingUp(counts)) => KeyBackupProgress::BackingUp {
+                    backed_up: counts.backed_up,
+                    total: counts.total,
+                },
+                Ok(EnableProgress::RoomKeyUploadError) => KeyBackupProgress::UploadError,
+                _ => continue,
+            };
+            Cx::post_action(KeyBackupAction::Progress(progress));
+        }
+    };
+    tokio::select! {
+        recovery_key = std::future::IntoFuture::into_future(enable) => Ok(recovery_key?),
+        _ = report_progress => bail!("Key backup progress stream ended unexpectedly"),
+    }
+}
+
+/// Returns the user's default notification mode for each kind of room.
+async fn default_notification_modes(notification_settings: &NotificationSettings) -> DefaultNotificationModes {
+    let mut modes = DefaultNotificationModes {
+        // The encrypted and unencrypted direct room rules are kept in sync,
+        // so we only need to check one of them.
+        direct_rooms: notification_settings.get_default_room_notification_mode(IsEncrypted::Yes, IsOneToOne::Yes).await,
+        group_rooms: notification_settings.get_default_room_notification_mode(IsEncrypted::No, IsOneToOne::No).await,
+        encrypted_group_rooms: notification_settings.get_default_room_notification_mode(IsEncrypted::Yes, IsOneToOne::No).await,
+    };
+    // Muting by default isn't expressed in the push rules, so it overrides them.
+    let default_mute_settings = DEFAULT_MUTE_SETTINGS.lock().unwrap();
+    for category in DefaultNotificationCategory::ALL {
+        if default_mute_settings.is_muted(category) {
+            *modes.get_mut(category) = RoomNotificationMode::Mute;
+        }
+    }
+    modes
+}
+
+/// Saves the current user's default mute settings, logging any error.
+fn save_default_mute_settings(client: &Client) {
+    let Some(user_id) = client.user_id() else { return };
+    let default_mute_settings = DEFAULT_MUTE_SETTINGS.lock().unwrap().clone();
+    if let Err(e) = persistence::save_default_mute_settings(user_id, &default_mute_settings) {
+        error!("Failed to save default mute settings for {user_id}: {e}");
+    }
+}
+
+/// Returns the kind of room that determines the given room's default notification mode.
+async fn room_notification_category(room: &Room) -> Option<DefaultNotificationCategory> {
+    let encryption_state = room.latest_encryption_state().await.ok()?;
+    Some(DefaultNotificationCategory::of_room(room.joined_members_count(), encryption_state.is_encrypted()))
+}
+
+/// Mutes the given room if its kind of room is muted by default
+/// and the user hasn't chosen a notification mode for this room.
+///
+/// Returns whether the room was muted.
+async fn mute_room_by_default(notification_settings: &NotificationSettings, room: &Room) -> bool {
+    let Some(category) = room_notification_category(room).await else { return false };
+    let is_category_muted = DEFAULT_MUTE_SETTINGS.lock().unwrap().is_muted(category);
+    if !is_category_muted
+        || notification_settings.get_user_defined_room_notification_mode(room.room_id()).await.is_some()
+    {
+        return false;
+    }
+    if let Err(e) = notification_settings.set_room_notification_mode(room.room_id(), RoomNotificationMode::Mute).await {
+        error!("Failed to mute room {} by default: {e:?}", room.room_id());
+        return false;
+    }
+    DEFAULT_MUTE_SETTINGS.lock().unwrap().rooms_muted_by_default.insert(room.room_id().to_owned());
+    true
+}
+
+/// Mutes or un-mutes all of the user's joined rooms of the given kind
+/// according to whether that kind of room should now be muted by default.
+///
+/// Rooms that the user gave their own notification mode are left untouched.
+async fn apply_default_mute(
+    client: &Client,
+    notification_settings: &NotificationSettings,
+    category: DefaultNotificationCategory,
+    mute: bool,
+) {
+    {
+        let mut default_mute_settings = DEFAULT_MUTE_SETTINGS.lock().unwrap();
+        default_mute_settings.muted_categories.retain(|c| *c != category);
+        if mute {
+            default_mute_settings.muted_categories.push(category);
+        }
+    }
 
-            if has_changed && !first_update {
-                // After successfully (un)ignoring a user, all timelines are fully cleared by the Matrix SDK.
-                // Therefore, we need to re-fetch all timelines for all rooms,
-                // and currently the only way to actually accomplish this is via pagination.
-                // See: <https://github.com/matrix-org/matrix-rust-sdk/issues/1703#issuecomment-2250297923>
-                for joined_room in client.joined_rooms() {
-                    submit_async_request(MatrixRequest::PaginateRoomTimeline {
-                        room_id: joined_room.room_id().to_owned(),
-                        num_events: 50,
-                        direction: PaginationDirection::Backwards,
-                    });
+    if mute {
+        for room in client.joined_rooms() {
+            if mute_room_by_default(notification_settings, &room).await {
+                enqueue_rooms_list_update(RoomsListUpdate::UpdateNotificationMode {
+                    room_id: room.room_id().to_owned(),
+                    mode: RoomNotificationMode::Mute,
+                });
+            }
+        }
+    } else {
+        let rooms_muted_by_default = DEFAULT_MUTE_SETTINGS.lock().unwrap().rooms_muted_by_default.clone();
+        for room_id in rooms_muted_by_default {
+            let Some(room) = client.get_room(&room_id) else { continue };
+            // Skip rooms whose kind is still muted by default, e.g., other kinds of rooms.
+            let room_category = room_notification_category(&room).await;
+            if room_category.is_some_and(|c| DEFAULT_MUTE_SETTINGS.lock().unwrap().is_muted(c)) {
+                continue;
+            }
+            // Only un-mute the room if it is still muted, i.e., the user hasn't changed its mode since.
+            if notification_settings.get_user_defined_room_notification_mode(&room_id).await == Some(RoomNotificationMode::Mute) {
+                if let Err(e) = notification_settings.delete_user_defined_room_rules(&room_id).await {
+                    error!("Failed to un-mute room {room_id} after changing its default mode: {e:?}");
+                    continue;
                 }
             }
+            DEFAULT_MUTE_SETTINGS.lock().unwrap().rooms_muted_by_default.remove(&room_id);
+            enqueue_rooms_list_update(RoomsListUpdate::UpdateNotificationMode {
+                mode: room_notification_mode(&room).await,
+                room_id,
+            });
+        }
+    }
+    save_default_mute_settings(client);
+}
 
-            first_update = false;
+/// Returns the predefined push rules that correspond to the given kind of mention.
+fn mention_push_rules(rule: MentionRule) -> Vec<(RuleKind, String)> {
+    match rule {
+        MentionRule::DisplayName => vec![
+            (RuleKind::Override, PredefinedOverrideRuleId::ContainsDisplayName.to_string()),
+        ],
+        // Both intentional mentions and legacy plaintext mentions of the user ID.
+        MentionRule::UserId => vec![
+            (RuleKind::Override, PredefinedOverrideRuleId::IsUserMention.to_string()),
+            (RuleKind::Content, PredefinedContentRuleId::ContainsUserName.to_string()),
+        ],
+    }
+}
+
+/// Returns whether all of the push rules for the given kind of mention are enabled.
+async fn is_mention_rule_enabled(notification_settings: &NotificationSettings, rule: MentionRule) -> bool {
+    for (kind, rule_id) in mention_push_rules(rule) {
+        // Push rules that don't exist on the homeserver are considered to be enabled.
+        if !notification_settings.is_push_rule_enabled(kind, &rule_id).await.unwrap_or(true) {
+            return false;
         }
-    });
+    }
+    true
+}
+
+/// Returns the user's notification keywords and whether each kind of mention is enabled.
+async fn keyword_settings(notification_settings: &NotificationSettings) -> KeywordSettings {
+    let mut keywords: Vec<String> = notification_settings.enabled_keywords().await.into_iter().collect();
+    keywords.sort_unstable();
+    KeywordSettings {
+        keywords,
+        display_name_mentions: is_mention_rule_enabled(notification_settings, MentionRule::DisplayName).await,
+        user_id_mentions: is_mention_rule_enabled(notification_settings, MentionRule::UserId).await,
+    }
 }
 
 /// Asynchronously loads and restores the app state from persistent storage for the given user.
@@ -2489,6 +4453,23 @@ fn handle_ignore_user_list_subscriber(client: Client) {
 /// with the error message.
 fn handle_load_app_state(user_id: OwnedUserId) {
     Handle::current().spawn(async move {
+        // Load room drafts first, such that they're available before any rooms get restored.
+        match load_room_drafts(&user_id).await {
+            Ok(room_drafts) => {
+                if !room_drafts.drafts.is_empty() {
+                    log!("Loaded {} room drafts from app data directory.", room_drafts.drafts.len());
+                    Cx::post_action(AppStateAction::RestoreRoomDrafts(room_drafts));
+                }
+            }
+            Err(_e) => {
+                log!("Failed to restore room drafts from persistent state: {_e}");
+                enqueue_popup_notification(PopupItem {
+                    message: String::from("Could not restore your unsent message drafts."),
+                    kind: PopupKind::Error,
+                    auto_dismissal_duration: None
+                });
+            }
+        }
         match load_app_state(&user_id).await {
             Ok(app_state) => {
                 if !app_state.saved_dock_state.open_rooms.is_empty()
@@ -2512,7 +4493,7 @@ fn handle_load_app_state(user_id: OwnedUserId) {
 
 fn handle_sync_service_state_subscriber(mut subscriber: Subscriber<sync_service::State>) {
     log!("Initial sync service state is {:?}", subscriber.get());
-    Handle::current().spawn(async move {
+    spawn_bound_task(async move {
         while let Some(state) = subscriber.next().await {
             log!("Received a sync service state update: {state:?}");
             match state {
@@ -2545,7 +4526,7 @@ fn handle_sync_indicator_subscriber(sync_service: &SyncService) {
             SYNC_INDICATOR_HIDE_DELAY
         );
     
-    Handle::current().spawn(async move {
+    spawn_bound_task(async move {
        let mut sync_indicator_stream = std::pin::pin!(sync_indicator_stream);
 
         while let Some(indicator) = sync_indicator_stream.next().await {
@@ -2558,21 +4539,33 @@ fn handle_sync_indicator_subscriber(sync_service: &SyncService) {
     });
 }
 
-fn handle_room_list_service_loading_state(mut loading_state: Subscriber<RoomListLoadingState>) {
-    log!("Initial room list loading state is {:?}", loading_state.get());
-    Handle::current().spawn(async move {
-        while let Some(state) = loading_state.next().await {
-            log!("Received a room list loading state update: {state:?}");
-            match state {
-                RoomListLoadingState::NotLoaded => {
-                    enqueue_rooms_list_update(RoomsListUpdate::NotLoaded);
-                }
-                RoomListLoadingState::Loaded { maximum_number_of_rooms } => {
-                    enqueue_rooms_list_update(RoomsListUpdate::LoadedRooms { max_rooms: maximum_number_of_rooms });
-                }
-            }
-        }
+/// Returns the number of the given rooms that are shown in the RoomsList,
+/// i.e., rooms that the user has joined or been invited to.
+fn num_listed_rooms(all_known_rooms: &Vector<RoomListServiceRoomInfo>) -> usize {
+    all_known_rooms.iter()
+        .filter(|room| matches!(room.state, RoomState::Joined | RoomState::Invited))
+        .count()
+}
+
+/// Tells the RoomsList the maximum number of rooms that it will ever show,
+/// if that number has changed since it was last reported.
+///
+/// The room list service's `max_rooms_in_list` also counts rooms that aren't shown
+/// in the RoomsList (e.g., left or banned rooms), so those that are already known are excluded.
+/// Once all pages of rooms have been loaded, the reported number is exact.
+fn report_max_listed_rooms(
+    max_rooms_in_list: Option<u32>,
+    all_known_rooms: &Vector<RoomListServiceRoomInfo>,
+    reported_max_rooms: &mut Option<Option<u32>>,
+) {
+    let max_rooms = max_rooms_in_list.map(|max| {
+        let num_unlisted_rooms = all_known_rooms.len() - num_listed_rooms(all_known_rooms);
+        max.saturating_sub(u32::try_from(num_unlisted_rooms).unwrap_or(u32::MAX))
     });
+    if *reported_max_rooms != Some(max_rooms) {
+        *reported_max_rooms = Some(max_rooms);
+        enqueue_rooms_list_update(RoomsListUpdate::LoadedRooms { max_rooms });
+    }
 }
 
 /// Returns the timestamp and text preview of the given `latest_event` timeline item.
@@ -2750,6 +4743,8 @@ async fn timeline_subscriber_handler(
             let mut clear_cache = false;
             // whether the changes include items being appended to the end of the timeline
             let mut is_append = false;
+            // the IDs of events that were previously unable to be decrypted but now have been
+            let mut newly_decrypted_events = Vec::new();
             for diff in batch {
                 num_updates += 1;
                 match diff {
@@ -2826,6 +4821,15 @@ async fn timeline_subscriber_handler(
                     VectorDiff::Set { index, value } => {
                         index_of_first_change = min(index_of_first_change, index);
                         index_of_last_change  = max(index_of_last_change, index.saturating_add(1));
+                        // The SDK re-decrypts UTD items in place when their keys arrive later,
+                        // so we track that transition here to inform the RoomScreen.
+                        if timeline_items.get(index).is_some_and(|old| is_unable_to_decrypt(old))
+                            && !is_unable_to_decrypt(&value)
+                        {
+                            if let Some(event_id) = value.as_event().and_then(|ev| ev.event_id()) {
+                                newly_decrypted_events.push(event_id.to_owned());
+                            }
+                        }
                         timeline_items.set(index, value);
                         if LOG_TIMELINE_DIFFS { log!("timeline_subscriber: room {room_id} diff Set at {index}. Changes: {index_of_first_change}..{index_of_last_change}"); }
                     }
@@ -2886,6 +4890,12 @@ async fn timeline_subscriber_handler(
                     is_append,
                 }).expect("Error: timeline update sender couldn't send update with new items!");
 
+                if !newly_decrypted_events.is_empty() {
+                    log!("timeline_subscriber: {} previously-undecryptable events in room {room_id} were decrypted.", newly_decrypted_events.len());
+                    timeline_update_sender.send(TimelineUpdate::EventsDecrypted(newly_decrypted_events))
+                        .expect("Error: timeline update sender couldn't send update with decrypted events!");
+                }
+
                 // We must send this update *after* the actual NewItems update,
                 // otherwise the UI thread (RoomScreen) won't be able to correctly locate the target event.
                 if let Some((index, found_event_id)) = found_target_event_id.take() {
@@ -2913,6 +4923,14 @@ async fn timeline_subscriber_handler(
     error!("Error: unexpectedly ended timeline subscriber for room {room_id}.");
 }
 
+/// Returns `true` if the given timeline item is an event that could not be decrypted.
+fn is_unable_to_decrypt(item: &TimelineItem) -> bool {
+    item.as_event().is_some_and(|ev| matches!(
+        ev.content(),
+        TimelineItemContent::MsgLike(msg_like) if matches!(msg_like.kind, MsgLikeKind::UnableToDecrypt(_))
+    ))
+}
+
 /// Handles the given updated latest event for the given room.
 ///
 /// This function sends a `RoomsListUpdate::UpdateLatestEvent`
@@ -2950,11 +4968,19 @@ async fn update_latest_event(room: &Room) {
         LatestEventValue::None => return,
     };
 
-    let latest_message_text = text_preview_of_timeline_item(
-        &content,
-        &sender_id,
-        &sender_username,
-    ).format_with(&sender_username, true);
+    // Don't show messages from ignored users in the room preview;
+    // instead, show the latest event from a user that isn't ignored.
+    let (timestamp, latest_message_text) = if is_user_ignored(&sender_id) {
+        let Some(preview) = latest_non_ignored_event_preview(room).await else { return };
+        preview
+    } else {
+        let latest_message_text = text_preview_of_timeline_item(
+            &content,
+            &sender_id,
+            &sender_username,
+        ).format_with(&sender_username, true);
+        (timestamp, latest_message_text)
+    };
 
     enqueue_rooms_list_update(RoomsListUpdate::UpdateLatestEvent {
         room_id: room.room_id().to_owned(),
@@ -2963,6 +4989,28 @@ async fn update_latest_event(room: &Room) {
     });
 }
 
+/// Returns the timestamp and preview text of the latest event in the given room's timeline
+/// that wasn't sent by an ignored user, if any.
+async fn latest_non_ignored_event_preview(room: &Room) -> Option<(MilliSecondsSinceUnixEpoch, String)> {
+    let timeline = ALL_JOINED_ROOMS.lock().unwrap()
+        .get(room.room_id())
+        .map(|details| details.timeline.clone())?;
+    let items = timeline.items().await;
+    let event = items.iter().rev()
+        .filter_map(|item| item.as_event())
+        .find(|ev| !is_user_ignored(ev.sender()))?;
+    let sender_username = match event.sender_profile() {
+        TimelineDetails::Ready(profile) => profile.display_name.clone(),
+        _ => None,
+    }.unwrap_or_else(|| event.sender().to_string());
+    let latest_message_text = text_preview_of_timeline_item(
+        event.content(),
+        event.sender(),
+        &sender_username,
+    ).format_with(&sender_username, true);
+    Some((event.timestamp(), latest_message_text))
+}
+
 /// Spawn a new async task to fetch the room's new avatar.
 fn spawn_fetch_room_avatar(room: &RoomListServiceRoomInfo) {
     let room_id = room.room_id.clone();
@@ -3033,13 +5081,15 @@ async fn spawn_sso_server(
         let mut client_and_session = client_and_session_opt;
 
         // If the DEFAULT_SSO_CLIENT is none (meaning it failed to build),
+        // or if it doesn't use the proxy entered on the login screen,
         // or if the homeserver_url is *not* empty and isn't the default,
         // we cannot use the DEFAULT_SSO_CLIENT, so we must build a new one.
         let mut build_client_error = None;
-        if client_and_session.is_none() || (
+        let login_proxy = login_proxy_settings().unwrap_or_default();
+        if client_and_session.as_ref().is_none_or(|(_, session)| session.proxy != login_proxy) || (
             !homeserver_url.is_empty()
-                && homeserver_url != "matrix.org"
-                && Url::parse(&homeserver_url) != Url::parse("https://matrix-client.matrix.org/")
+                && homeserver_url != DEFAULT_HOMESERVER
+                && Url::parse(&homeserver_url) != Url::parse(DEFAULT_HOMESERVER_URL)
                 && Url::parse(&homeserver_url) != Url::parse("https://matrix.org/")
         ) {
             match build_client(
@@ -3137,6 +5187,223 @@ async fn spawn_sso_server(
 }
 
 
+/// Sends the given message to the room of the given timeline, optionally as a reply to another event.
+///
+/// With the `tsp` feature, the message is signed with the default TSP identity if `sign_with_tsp` is `true`.
+/// This enqueues the message into the room's send queue, such that the timeline shows
+/// a local echo of the message until the homeserver has accepted it.
+pub async fn send_message(
+    timeline: &Timeline,
+    message: RoomMessageEventContent,
+    replied_to: Option<Reply>,
+    #[cfg(feature = "tsp")] sign_with_tsp: bool,
+) -> Result<()> {
+    #[cfg(feature = "tsp")]
+    let message = if sign_with_tsp { sign_message_with_tsp(message)? } else { message };
+    match replied_to {
+        Some(replied_to) => { timeline.send_reply(message.into(), replied_to.event_id).await?; }
+        None => { timeline.send(message.into()).await?; }
+    }
+    Ok(())
+}
+
+/// Signs the given message with the default TSP identity, adding the signature to the message.
+#[cfg(feature = "tsp")]
+fn sign_message_with_tsp(mut message: RoomMessageEventContent) -> Result<RoomMessageEventContent> {
+    use matrix_sdk::ruma::serde::Base64;
+    log!("Signing message with TSP...");
+    let message_bytes = serde_json::to_vec(&message)
+        .map_err(|e| anyhow!("Failed to serialize message for TSP signing: {e}"))?;
+    log!("Serialized message to bytes, length {}", message_bytes.len());
+    let signed_msg = crate::tsp::sign_anycast_with_default_vid(&message_bytes)
+        .map_err(|e| anyhow!("Failed to sign message with TSP: {e}"))?;
+    log!("Successfully signed message with TSP, length {}", signed_msg.len());
+    message.tsp_signature = Some(Base64::new(signed_msg));
+    Ok(message)
+}
+
+/// Joins the given room, which may either be known to the client (e.g., an invite)
+/// or not yet known (e.g., a public room found via a link).
+pub async fn join_room(client: &Client, room_id: OwnedRoomId) -> JoinRoomResultAction {
+    log!("Sending request to join room {room_id}...");
+    if let Some(room) = client.get_room(&room_id) {
+        match room.join().await {
+            Ok(()) => {
+                log!("Successfully joined known room {room_id}.");
+                JoinRoomResultAction::Joined { room_id }
+            }
+            Err(e) => {
+                error!("Error joining known room {room_id}: {e:?}");
+                JoinRoomResultAction::Failed { room_id, error: e }
+            }
+        }
+    }
+    else {
+        match client.join_room_by_id(&room_id).await {
+            Ok(_room) => {
+                log!("Successfully joined new unknown room {room_id}.");
+                JoinRoomResultAction::Joined { room_id }
+            }
+            Err(e) => {
+                error!("Error joining new unknown room {room_id}: {e:?}");
+                JoinRoomResultAction::Failed { room_id, error: e }
+            }
+        }
+    }
+}
+
+/// Leaves the given room.
+pub async fn leave_room(client: &Client, room_id: OwnedRoomId) -> LeaveRoomResultAction {
+    log!("Sending request to leave room {room_id}...");
+    if let Some(room) = client.get_room(&room_id) {
+        match room.leave().await {
+            Ok(()) => {
+                log!("Successfully left room {room_id}.");
+                LeaveRoomResultAction::Left { room_id }
+            }
+            Err(e) => {
+                error!("Error leaving room {room_id}: {e:?}");
+                LeaveRoomResultAction::Failed { room_id, error: e }
+            }
+        }
+    } else {
+        error!("BUG: client could not get room with ID {room_id}");
+        LeaveRoomResultAction::Failed {
+            room_id,
+            error: matrix_sdk::Error::UnknownError(
+                String::from("Client couldn't locate room to leave it.").into()
+            ),
+        }
+    }
+}
+
+/// Spawns an async task to log in via OAuth 2.0 with the given homeserver.
+///
+/// The Matrix SDK handles the authorization-code flow with PKCE, dynamically registers Robrix
+/// as a client with the homeserver's authorization server, and refreshes the access token
+/// once it expires (see [`handle_session_changes()`]).
+///
+/// Like [`spawn_sso_server()`], this posts `LoginAction::SsoPending` before and after logging in,
+/// and sends the logged-in `Client` to the login task using the `login_sender`.
+fn spawn_oauth_login(homeserver_url: String, login_sender: Sender<LoginRequest>) {
+    Cx::post_action(LoginAction::SsoPending(true));
+    Handle::current().spawn(async move {
+        match oauth_login(homeserver_url).await {
+            Ok((client, client_session)) => {
+                if let Err(e) = login_sender.send(LoginRequest::LoginBySSOSuccess(client, client_session)).await {
+                    error!("Error sending login request to login_sender: {e:?}");
+                    Cx::post_action(LoginAction::LoginFailure(String::from(
+                        "BUG: failed to send login request to async worker thread."
+                    )));
+                }
+            }
+            Err(e) => {
+                error!("OAuth login failed: {e:?}");
+                Cx::post_action(LoginAction::LoginFailure(format!("OAuth login failed: {e}")));
+            }
+        }
+        Cx::post_action(LoginAction::SsoPending(false));
+    });
+}
+
+/// Logs in via OAuth 2.0 and returns the newly-logged-in client.
+async fn oauth_login(homeserver_url: String) -> Result<(Client, ClientSessionPersisted)> {
+    Cx::post_action(LoginAction::Status {
+        title: "Initializing client...".into(),
+        status: "Please wait while Matrix builds and configures the client object for login.".into(),
+    });
+    let cli = Cli {
+        homeserver: homeserver_url.is_empty().not().then_some(homeserver_url),
+        ..Default::default()
+    };
+    let (client, client_session) = build_client(&cli, app_data_dir()).await
+        .map_err(|e| anyhow!(describe_client_build_error(&cli, &e)))?;
+
+    if let Err(e) = client.oauth().server_metadata().await {
+        bail!("This homeserver does not support OAuth login. Please log in with a password or an SSO provider instead.\n\nError: {e}");
+    }
+
+    let redirect_server = LoopbackRedirectServer::bind().await?;
+    let redirect_uri = redirect_server.redirect_uri().clone();
+    // This allows the login screen's cancel button to shut down the loopback server.
+    Cx::post_action(LoginAction::SsoSetRedirectUrl(redirect_uri.clone()));
+
+    let registration_data = Raw::new(&oauth::client_metadata(redirect_uri.clone()))?.into();
+    let authorization_data = client.oauth()
+        .login(redirect_uri, None, Some(registration_data), None)
+        .build()
+        .await?;
+
+    Cx::post_action(LoginAction::Status {
+        title: "Opening your browser...".into(),
+        status: "Please finish logging in using your browser, and then come back to Robrix.".into(),
+    });
+    Uri::new(authorization_data.url.as_str()).open()
+        .map_err(|e| anyhow!("Unable to open the login page in your browser: {e:?}"))?;
+
+    let callback_url = redirect_server.wait_for_callback().await?;
+    client.oauth().finish_login(UrlOrQuery::Url(callback_url)).await?;
+    log!("Logged in via OAuth as {:?}", client.user_id());
+    enqueue_rooms_list_update(RoomsListUpdate::Status {
+        status: format!("Logged in as {:?}.\n → Loading rooms...", client.user_id()),
+    });
+    Ok((client, client_session))
+}
+
+/// The tasks spawned by [`handle_session_changes()`] for each logged-in account,
+/// which must be aborted once that account is removed such that its client can be dropped.
+static SESSION_CHANGE_TASKS: Mutex<BTreeMap<OwnedUserId, AbortHandle>> = Mutex::new(BTreeMap::new());
+
+/// Listens for changes to the client's session, i.e., its access token being refreshed
+/// or becoming invalid, and persists any refreshed tokens.
+///
+/// If an inactive account's session becomes invalid, that account stops syncing in the background
+/// and the account switcher is notified, such that the user can tell which session expired.
+fn handle_session_changes(client: Client) {
+    let Some(user_id) = client.user_id().map(ToOwned::to_owned) else { return };
+    let mut session_changes = client.subscribe_to_session_changes();
+    let task_user_id = user_id.clone();
+    let task = Handle::current().spawn(async move {
+        let user_id = task_user_id;
+        loop {
+            match session_changes.recv().await {
+                Ok(SessionChange::TokensRefreshed) => {
+                    log!("The access token of {user_id} was refreshed; saving the new session tokens.");
+                    if let Err(e) = persistence::save_session_tokens(&client).await {
+                        error!("Failed to save refreshed session tokens of {user_id}: {e:?}");
+                    }
+                }
+                Ok(SessionChange::UnknownToken { soft_logout }) => {
+                    warning!("The homeserver rejected the access token of {user_id} (soft logout: {soft_logout}).");
+                    if current_user_id().as_ref() == Some(&user_id) {
+                        enqueue_popup_notification(PopupItem {
+                            message: format!("Your login session for {user_id} has expired or was revoked. Please log out and log in again."),
+                            kind: PopupKind::Error,
+                            auto_dismissal_duration: None,
+                        });
+                    } else {
+                        let _ = stop_background_account(&user_id).await;
+                        Cx::post_action(BackgroundAccountAction::SessionExpired { user_id: user_id.clone() });
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => { }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    if let Some(previous_task) = SESSION_CHANGE_TASKS.lock().unwrap().insert(user_id, task.abort_handle()) {
+        previous_task.abort();
+    }
+}
+
+/// Stops listening for changes to the given account's session, e.g., once it has been logged out.
+pub fn stop_handling_session_changes(user_id: &UserId) {
+    if let Some(task) = SESSION_CHANGE_TASKS.lock().unwrap().remove(user_id) {
+        task.abort();
+    }
+}
+
+
 bitflags! {
     /// The powers that a user has in a given room.
     #[derive(Copy, Clone, PartialEq, Eq)]
@@ -3286,6 +5553,276 @@ impl UserPowerLevels {
 }
 
 
+/// A request to switch the UI to a different logged-in account,
+/// sent from the async worker to the main loop.
+struct AccountSwitchRequest {
+    /// The account to switch to, or `None` to log in to an additional account.
+    user_id: Option<OwnedUserId>,
+    is_desktop: bool,
+}
+
+/// The tasks and event handlers that feed the UI with updates from the active account's client.
+///
+/// These are removed when the UI is unbound from that account, e.g., when switching accounts,
+/// whereas the client itself keeps running in the background.
+#[derive(Default)]
+struct ActiveAccountBindings {
+    tasks: Vec<AbortHandle>,
+    event_handlers: Vec<EventHandlerHandle>,
+}
+
+static ACTIVE_ACCOUNT_BINDINGS: Mutex<ActiveAccountBindings> = Mutex::new(ActiveAccountBindings {
+    tasks: Vec::new(),
+    event_handlers: Vec::new(),
+});
+
+/// Spawns a task that feeds the UI with updates from the active account,
+/// which is aborted when the UI is unbound from that account.
+pub fn spawn_bound_task<F>(future: F) -> JoinHandle<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task = Handle::current().spawn(future);
+    ACTIVE_ACCOUNT_BINDINGS.lock().unwrap().tasks.push(task.abort_handle());
+    task
+}
+
+/// Registers an event handler of the active account's client that feeds the UI,
+/// which is removed when the UI is unbound from that account.
+pub fn bind_event_handler(event_handler: EventHandlerHandle) {
+    ACTIVE_ACCOUNT_BINDINGS.lock().unwrap().event_handlers.push(event_handler);
+}
+
+/// A logged-in account that isn't currently shown in the UI, but which keeps syncing
+/// such that the account switcher can show its live unread counts.
+struct BackgroundAccount {
+    client: Client,
+    /// The account's sync service, or `None` if it uses classic sync.
+    sync_service: Option<SyncService>,
+    /// The task that runs classic sync (if needed) and reports the account's unread counts.
+    task: JoinHandle<()>,
+}
+impl Drop for BackgroundAccount {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// All logged-in accounts other than the active account, keyed by their user ID.
+static BACKGROUND_ACCOUNTS: Mutex<BTreeMap<OwnedUserId, BackgroundAccount>> = Mutex::new(BTreeMap::new());
+
+/// How long to wait after a room info update before recalculating a background account's unread counts,
+/// such that a burst of updates (e.g., from a single sync response) only triggers one recalculation.
+const BACKGROUND_UNREAD_COUNTS_DELAY: Duration = Duration::from_secs(1);
+
+/// Restores the sessions of all logged-in accounts other than the active one,
+/// and keeps them syncing in the background.
+fn start_background_accounts(active_user_id: Option<OwnedUserId>) {
+    Handle::current().spawn(async move {
+        for user_id in persistence::logged_in_accounts() {
+            if active_user_id.as_ref() == Some(&user_id)
+                || BACKGROUND_ACCOUNTS.lock().unwrap().contains_key(&user_id)
+            {
+                continue;
+            }
+            match persistence::restore_background_session(&user_id).await {
+                // The user may have switched to this account while its session was being restored.
+                Ok(_client) if current_user_id().as_ref() == Some(&user_id) => { }
+                Ok(client) => {
+                    setup_new_client(&client);
+                    start_background_account(client).await;
+                }
+                Err(e) => error!("Failed to restore the session of {user_id} in the background: {e:?}"),
+            }
+        }
+    });
+}
+
+/// Keeps the given inactive account's client syncing in the background,
+/// reporting its unread counts to the account switcher whenever they change.
+async fn start_background_account(client: Client) {
+    let Some(user_id) = client.user_id().map(ToOwned::to_owned) else { return };
+    let use_classic_sync = SlidingSyncVersion::from(client.sliding_sync_version()) == SlidingSyncVersion::None;
+    let sync_service = if use_classic_sync {
+        None
+    } else {
+        match SyncService::builder(client.clone()).with_offline_mode().build().await {
+            Ok(sync_service) => {
+                sync_service.start().await;
+                Some(sync_service)
+            }
+            Err(e) => {
+                error!("Failed to start syncing {user_id} in the background: {e:?}");
+                None
+            }
+        }
+    };
+    let task = Handle::current().spawn(sync_background_account(client.clone(), use_classic_sync));
+    BACKGROUND_ACCOUNTS.lock().unwrap().insert(
+        user_id,
+        BackgroundAccount { client, sync_service, task },
+    );
+}
+
+/// Stops the given account from syncing in the background, returning its client if it was.
+async fn stop_background_account(user_id: &UserId) -> Option<Client> {
+    let account = BACKGROUND_ACCOUNTS.lock().unwrap().remove(user_id)?;
+    account.task.abort();
+    if let Some(sync_service) = account.sync_service.as_ref() {
+        sync_service.stop().await;
+    }
+    Some(account.client.clone())
+}
+
+/// Syncs the given background account via classic sync if `use_classic_sync` is `true`
+/// (otherwise its sync service does so), and reports its unread counts whenever they change.
+async fn sync_background_account(client: Client, use_classic_sync: bool) {
+    let Some(user_id) = client.user_id().map(ToOwned::to_owned) else { return };
+    let classic_sync_loop = async {
+        if !use_classic_sync {
+            return;
+        }
+        let sync_settings = SyncSettings::default().timeout(CLASSIC_SYNC_TIMEOUT);
+        loop {
+            let presence = persistence::load_presence_settings(&user_id).presence_to_send();
+            if let Err(e) = client.sync_once(sync_settings.clone().set_presence(presence)).await {
+                warning!("Background sync of {user_id} failed, retrying in {CLASSIC_SYNC_RETRY_DELAY:?}: {e}");
+                tokio::time::sleep(CLASSIC_SYNC_RETRY_DELAY).await;
+            }
+        }
+    };
+    let report_unread_counts = async {
+        let mut room_info_updates = client.room_info_notable_update_receiver();
+        let mut reported_counts = None;
+        loop {
+            let counts = account_unread_counts(&client).await;
+            if reported_counts != Some(counts) {
+                reported_counts = Some(counts);
+                Cx::post_action(BackgroundAccountAction::UnreadCountsChanged {
+                    user_id: user_id.clone(),
+                    counts,
+                });
+            }
+            if let Err(broadcast::error::RecvError::Closed) = room_info_updates.recv().await {
+                break;
+            }
+            tokio::time::sleep(BACKGROUND_UNREAD_COUNTS_DELAY).await;
+            room_info_updates = room_info_updates.resubscribe();
+        }
+    };
+    tokio::join!(classic_sync_loop, report_unread_counts);
+}
+
+/// Returns the given account's unread counts as shown in its Home space:
+/// the unread messages and mentions of all non-muted joined rooms, plus one mention per invite.
+async fn account_unread_counts(client: &Client) -> UnreadCounts {
+    let mut counts = UnreadCounts::default();
+    for room in client.rooms() {
+        match room.state() {
+            RoomState::Joined => {
+                if room_notification_mode(&room).await != RoomNotificationMode::Mute {
+                    counts.messages += room.num_unread_messages();
+                    counts.mentions += room.num_unread_mentions();
+                }
+            }
+            RoomState::Invited => counts.mentions += 1,
+            _ => { }
+        }
+    }
+    counts
+}
+
+/// Switches the UI from the given active `client` to the account in the given `switch_request`,
+/// or to a newly logged-in account if it doesn't specify one.
+///
+/// Unlike logging out, the previously-active account stays logged in and keeps syncing
+/// in the background, such that switching back to it later is instant.
+/// Returns the client of the newly-active account.
+async fn switch_active_account(
+    client: Client,
+    switch_request: AccountSwitchRequest,
+    cli: &Cli,
+    login_receiver: &mut Receiver<LoginRequest>,
+) -> Result<Client> {
+    let AccountSwitchRequest { user_id, is_desktop } = switch_request;
+    log!("Switching to account {user_id:?}...");
+    let previous_user_id = client.user_id().map(ToOwned::to_owned);
+    unbind_active_account(is_desktop).await?;
+    start_background_account(client).await;
+
+    let result = match user_id {
+        Some(user_id) => activate_account(&user_id).await,
+        None => match wait_for_login(cli, login_receiver).await {
+            Ok(new_client) => {
+                // The user may have logged in again to an account that was syncing in the background.
+                if let Some(new_user_id) = new_client.user_id() {
+                    let _ = stop_background_account(new_user_id).await;
+                }
+                setup_new_client(&new_client);
+                Ok(new_client)
+            }
+            Err(e) => return Err(e),
+        },
+    };
+    match result {
+        Ok(new_client) => Ok(new_client),
+        Err(e) => {
+            error!("Failed to switch accounts: {e:?}");
+            enqueue_popup_notification(PopupItem {
+                message: format!("Failed to switch accounts: {e}"),
+                kind: PopupKind::Error,
+                auto_dismissal_duration: None,
+            });
+            // Fall back to the previously-active account.
+            let previous_user_id = previous_user_id
+                .ok_or_else(|| anyhow!("BUG: the previously-active client was not logged in"))?;
+            activate_account(&previous_user_id).await
+        }
+    }
+}
+
+/// Returns the client of the given logged-in account such that it can become the active account,
+/// taking it from the background accounts, or restoring its session if it wasn't syncing in the background.
+async fn activate_account(user_id: &UserId) -> Result<Client> {
+    let client = match stop_background_account(user_id).await {
+        Some(client) => {
+            match persistence::load_proxy_settings(user_id).await {
+                Ok(proxy_settings) => set_active_proxy_settings(proxy_settings),
+                Err(e) => warning!("Failed to load the proxy settings of {user_id}: {e}"),
+            }
+            persistence::save_latest_user_id(user_id).await?;
+            client
+        }
+        None => {
+            let (client, _sync_token) = persistence::restore_session(Some(user_id.to_owned())).await?;
+            setup_new_client(&client);
+            client
+        }
+    };
+    Ok(client)
+}
+
+/// Unbinds the UI from the active account, such that it can be bound to a different account.
+///
+/// This stops the active account's sync service, removes its bindings,
+/// and clears all UI state, but the account itself remains logged in.
+/// (Its classic sync loop, if any, has already stopped along with [`run_active_account()`].)
+async fn unbind_active_account(is_desktop: bool) -> Result<()> {
+    let config = LogoutConfig { is_desktop, ..Default::default() };
+    let sync_service = SYNC_SERVICE.lock().unwrap().take();
+    if let Some(sync_service) = sync_service {
+        sync_service.stop().await;
+    }
+
+    if is_desktop {
+        let on_close_all = Arc::new(Notify::new());
+        Cx::post_action(MainDesktopUiAction::CloseAllTabs { on_close_all: on_close_all.clone() });
+        tokio::time::timeout(config.tab_close_timeout, on_close_all.notified()).await
+            .map_err(|_| anyhow!("Timed out waiting for tabs to close"))?;
+    }
+    clear_active_account_state(&config).await
+}
+
 /// Shuts down the current Tokio runtime completely and takes ownership to ensure proper cleanup.
 pub fn shutdown_background_tasks() {
     if let Some(runtime) = TOKIO_RUNTIME.lock().unwrap().take() {
@@ -3294,22 +5831,50 @@ pub fn shutdown_background_tasks() {
 }
 
 pub async fn clean_app_state(config: &LogoutConfig) -> Result<()> {
+    REQUEST_SENDER.lock().unwrap().take();
+    log!("Request sender cleared during logout");
+
+    // The background accounts' clients belong to the runtime that is about to be shut down.
+    BACKGROUND_ACCOUNTS.lock().unwrap().clear();
+    for task in std::mem::take(&mut *SESSION_CHANGE_TASKS.lock().unwrap()).into_values() {
+        task.abort();
+    }
+
+    clear_active_account_state(config).await
+}
+
+/// Clears the active account's client and all UI state derived from it.
+async fn clear_active_account_state(config: &LogoutConfig) -> Result<()> {
+    // Remove the active account's bindings, which requires the client to remove its event handlers.
+    let bindings = std::mem::take(&mut *ACTIVE_ACCOUNT_BINDINGS.lock().unwrap());
+    let client = CLIENT.lock().unwrap().take();
+    let user_id = client.as_ref().and_then(|c| c.user_id().map(ToOwned::to_owned));
+    for task in bindings.tasks {
+        task.abort();
+    }
+    if let Some(client) = client.as_ref() {
+        for event_handler in bindings.event_handlers {
+            client.remove_event_handler(event_handler);
+        }
+    }
     // Clear resources normally, allowing them to be properly dropped
     // This prevents memory leaks when users logout and login again without closing the app
-    CLIENT.lock().unwrap().take();
+    drop(client);
     log!("Client cleared during logout");
     
     SYNC_SERVICE.lock().unwrap().take();
     log!("Sync service cleared during logout");
     
-    REQUEST_SENDER.lock().unwrap().take();
-    log!("Request sender cleared during logout");
-    
     IGNORED_USERS.lock().unwrap().clear();
+    NOTIFICATION_KEYWORDS.lock().unwrap().clear();
+    *PRESENCE_SETTINGS.lock().unwrap() = PresenceSettings::default();
+    *DEFAULT_MUTE_SETTINGS.lock().unwrap() = DefaultMuteSettings::new();
+    notifications::clear_notifications();
     ALL_JOINED_ROOMS.lock().unwrap().clear();
+    enqueue_rooms_list_update(RoomsListUpdate::ClearRooms);
     
     let on_clear_appstate = Arc::new(Notify::new());
-    Cx::post_action(LogoutAction::ClearAppState { on_clear_appstate: on_clear_appstate.clone() });
+    Cx::post_action(LogoutAction::ClearAppState { user_id, on_clear_appstate: on_clear_appstate.clone() });
     
     match tokio::time::timeout(config.app_state_cleanup_timeout, on_clear_appstate.notified()).await {
         Ok(_) => {
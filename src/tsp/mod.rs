@@ -10,7 +10,7 @@ use tokio::{task::JoinHandle, runtime::Handle, sync::mpsc::{unbounded_channel, U
 use tsp_sdk::{definitions::{PublicKeyData, PublicVerificationKeyData, VidEncryptionKeyType, VidSignatureKeyType}, vid::{verify_vid, VidError}, AskarSecureStorage, AsyncSecureStore, OwnedVid, ReceivedTspMessage, SecureStorage, VerifiedVid, Vid};
 use url::Url;
 
-use crate::{persistence::{self, tsp_wallets_dir, SavedTspState}, shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, sliding_sync::current_user_id, tsp::tsp_verification_modal::TspVerificationModalAction, utils::DebugWrapper};
+use crate::{persistence::{self, proxy_settings::active_proxy_settings, tsp_wallets_dir, ProxySettings, SavedTspState}, shared::popup_list::{enqueue_popup_notification, PopupItem, PopupKind}, sliding_sync::current_user_id, tsp::tsp_verification_modal::TspVerificationModalAction, utils::{self, DebugWrapper}};
 
 
 pub mod create_did_modal;
@@ -604,10 +604,12 @@ pub enum TspRequest {
 }
 
 
+/// Creates a `reqwest` client that uses the same proxy settings as the current Matrix client.
 fn create_reqwest_client() -> reqwest::Result<reqwest::Client> {
-    reqwest::ClientBuilder::new()
-        .user_agent(format!("Robrix v{}", env!("CARGO_PKG_VERSION")))
-        .build()
+    active_proxy_settings().apply_to_reqwest(
+        reqwest::ClientBuilder::new()
+            .user_agent(format!("Robrix v{}", env!("CARGO_PKG_VERSION")))
+    )?.build()
 }
 
 
@@ -620,10 +622,20 @@ async fn async_tsp_worker(
 ) -> anyhow::Result<()> {
     log!("Started async_tsp_worker task.");
 
-    // Allow lazy initialization of the reqwest client.
-    let mut __reqwest_client = None;
-    let mut get_reqwest_client = || {
-        __reqwest_client.get_or_insert_with(|| create_reqwest_client().unwrap()).clone()
+    // Allow lazy initialization of the reqwest client,
+    // which must be re-created if the active proxy settings have changed.
+    let mut __reqwest_client: Option<(ProxySettings, reqwest::Client)> = None;
+    let mut get_reqwest_client = || -> anyhow::Result<reqwest::Client> {
+        let proxy_settings = active_proxy_settings();
+        match &__reqwest_client {
+            Some((settings, client)) if *settings == proxy_settings => Ok(client.clone()),
+            _ => {
+                let client = create_reqwest_client()
+                    .map_err(|e| anyhow!("Failed to create an HTTP client with your proxy settings: {e}"))?;
+                __reqwest_client = Some((proxy_settings, client.clone()));
+                Ok(client)
+            }
+        }
     };
 
     while let Some(req) = request_receiver.recv().await { match req {
@@ -774,7 +786,13 @@ async fn async_tsp_worker(
 
         TspRequest::CreateDid { username, alias, server, did_server } => {
             log!("Received TspRequest::CreateDid(username: {username}, alias: {alias:?}, server: {server}, did_server: {did_server})");
-            let client = get_reqwest_client();
+            let client = match get_reqwest_client() {
+                Ok(client) => client,
+                Err(e) => {
+                    Cx::post_action(TspIdentityAction::DidCreationResult(Err(e)));
+                    continue;
+                }
+            };
 
             Handle::current().spawn(async move {
                 let result = create_did_and_add_to_wallet(
@@ -790,7 +808,13 @@ async fn async_tsp_worker(
 
         TspRequest::RepublishDid { did } => {
             log!("Received TspRequest::RepublishDid(did: {did})");
-            let client = get_reqwest_client();
+            let client = match get_reqwest_client() {
+                Ok(client) => client,
+                Err(e) => {
+                    Cx::post_action(TspIdentityAction::DidRepublishResult(Err(e)));
+                    continue;
+                }
+            };
 
             Handle::current().spawn(async move {
                 let result = republish_did(&did, &client).await
@@ -1173,14 +1197,7 @@ async fn associate_did_with_user_id(
         initiating_user_id: our_user_id.clone(),
         responding_vid: did.to_string(),
         responding_user_id: user_id.clone(),
-        random_str: {
-            use rand::{Rng, thread_rng};
-            thread_rng()
-                .sample_iter(rand::distributions::Alphanumeric)
-                .take(32)
-                .map(char::from)
-                .collect()
-        },
+        random_str: utils::random_alphanumeric_string(32),
     };
     tsp_state_ref().lock().unwrap().pending_verification_requests.push(verification_details.clone());
     let request_msg = TspMessage::VerificationRequest(verification_details);
@@ -136,6 +136,17 @@ pub fn load_png_or_jpg(img: &ImageRef, cx: &mut Cx, data: &[u8]) -> Result<(), I
     res
 }
 
+/// Returns a random string of the given length made of ASCII letters and digits,
+/// e.g., for use as a passphrase or a client secret.
+pub fn random_alphanumeric_string(len: usize) -> String {
+    use rand::{distributions::Alphanumeric, Rng};
+    rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
 
 pub fn unix_time_millis_to_datetime(millis: MilliSecondsSinceUnixEpoch) -> Option<DateTime<Local>> {
     let millis: i64 = millis.get().into();
@@ -493,6 +504,140 @@ pub fn linkify(text: &str, is_html: bool) -> Cow<'_, str> {
     linkify_get_urls(text, is_html, None)
 }
 
+/// The background color used to highlight notification keywords in a message body.
+pub const KEYWORD_HIGHLIGHT_BG_COLOR: &str = "#FFE08A";
+
+/// Wraps each occurrence of the given `keywords` within the given `html` in a highlighted `<span>`.
+///
+/// Keywords are matched as whole words, ignoring ASCII case, which mirrors how
+/// the homeserver evaluates keyword push rules.
+/// Only text content is considered: tags and the contents of `<a>`, `<code>`, and `<pre>` elements
+/// are left untouched, and HTML entities are matched by the character they represent,
+/// such that a keyword like `R&D` matches `R&amp;D`.
+///
+/// The given `html` must already be valid HTML, so plaintext must be escaped first.
+/// Returns a borrowed string if no keywords were found.
+pub fn highlight_keywords<'t>(html: &'t str, keywords: &[String]) -> Cow<'t, str> {
+    const SKIPPED_ELEMENTS: [&str; 3] = ["a", "code", "pre"];
+
+    if keywords.iter().all(|k| k.is_empty()) {
+        return Cow::Borrowed(html);
+    }
+
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    // Returns the length of the HTML entity (e.g., `&amp;` or `&#39;`) at the given index, if any.
+    let entity_len_at = |idx: usize| -> Option<usize> {
+        html[idx..].strip_prefix('&')?
+            .find(';')
+            .filter(|&len| len <= 10 && html[idx + 1 .. idx + 1 + len].chars().all(|c| c.is_ascii_alphanumeric() || c == '#'))
+            .map(|len| len + 2)
+    };
+    // Returns the text character at the given index and the length of its HTML representation,
+    // decoding HTML entities. Returns `None` at the end of the text or at the start of a tag.
+    let text_char_at = |idx: usize| -> Option<(char, usize)> {
+        let c = html[idx..].chars().next().filter(|&c| c != '<')?;
+        if c == '&' {
+            if let Some(len) = entity_len_at(idx) {
+                return decode_html_entity(&html[idx + 1 .. idx + len - 1]).map(|decoded| (decoded, len));
+            }
+        }
+        Some((c, c.len_utf8()))
+    };
+    let keyword_len_at = |idx: usize| -> Option<usize> {
+        let preceded_by_word_char = html[..idx].chars().next_back().is_some_and(is_word_char);
+        if preceded_by_word_char {
+            return None;
+        }
+        keywords.iter()
+            .filter(|k| !k.is_empty())
+            .find_map(|k| {
+                let mut end = idx;
+                for keyword_char in k.chars() {
+                    let (c, len) = text_char_at(end)?;
+                    if !c.eq_ignore_ascii_case(&keyword_char) {
+                        return None;
+                    }
+                    end += len;
+                }
+                let followed_by_word_char = text_char_at(end).is_some_and(|(c, _)| is_word_char(c));
+                (!followed_by_word_char).then_some(end - idx)
+            })
+    };
+
+    let mut highlighted = String::new();
+    let mut last_copied_index = 0;
+    let mut skipped_depth: usize = 0;
+    let mut idx = 0;
+    while let Some(c) = html[idx..].chars().next() {
+        match c {
+            '<' => {
+                let tag_end = html[idx..].find('>').map_or(html.len(), |end| idx + end + 1);
+                let tag = &html[idx .. tag_end];
+                let is_closing = tag.starts_with("</");
+                let tag_name: String = tag
+                    .trim_start_matches(['<', '/'])
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric())
+                    .collect::<String>()
+                    .to_ascii_lowercase();
+                if SKIPPED_ELEMENTS.contains(&tag_name.as_str()) {
+                    if is_closing {
+                        skipped_depth = skipped_depth.saturating_sub(1);
+                    } else if !tag.ends_with("/>") {
+                        skipped_depth += 1;
+                    }
+                }
+                idx = tag_end;
+            }
+            _ => {
+                if skipped_depth == 0 {
+                    if let Some(len) = keyword_len_at(idx) {
+                        highlighted.push_str(&html[last_copied_index .. idx]);
+                        highlighted.push_str(&format!(
+                            "<span data-mx-bg-color=\"{KEYWORD_HIGHLIGHT_BG_COLOR}\">{}</span>",
+                            &html[idx .. idx + len],
+                        ));
+                        idx += len;
+                        last_copied_index = idx;
+                        continue;
+                    }
+                }
+                // Skip over entire HTML entities, such that a keyword can't match in the middle of one.
+                idx += if c == '&' { entity_len_at(idx).unwrap_or(1) } else { c.len_utf8() };
+            }
+        }
+    }
+
+    if last_copied_index == 0 {
+        return Cow::Borrowed(html);
+    }
+    highlighted.push_str(&html[last_copied_index..]);
+    Cow::Owned(highlighted)
+}
+
+/// Decodes the given HTML entity name (without its leading `&` and trailing `;`)
+/// into the character it represents, e.g., `amp` into `&` or `#39` into `'`.
+///
+/// Only numeric entities and the named entities used for escaping are supported.
+fn decode_html_entity(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code_point = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code_point)
+        }
+    }
+}
+
 /// Returns true if the given `text` string ends with a valid href attribute opener.
 ///
 /// An href attribute looks like this: `href="http://example.com"`,.
@@ -670,7 +815,7 @@ pub fn build_grapheme_byte_positions(text: &str) -> Vec<usize> {
 }
 
 /// A RON-(de)serializable wrapper around [`OwnedRoomId`].
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct OwnedRoomIdRon(pub OwnedRoomId);
 impl SerRon for OwnedRoomIdRon {
     /// Serialize a `OwnedRoomId` to its string form, using ron.
@@ -1101,3 +1246,56 @@ mod tests_ends_with_href {
         assert!(!ends_with_href(" hrf= "));
     }
 }
+
+#[cfg(test)]
+mod tests_highlight_keywords {
+    use super::*;
+
+    fn keywords(list: &[&str]) -> Vec<String> {
+        list.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_highlight_keywords_none_found() {
+        let html = "Hello, world!";
+        assert!(matches!(highlight_keywords(html, &keywords(&["robrix"])), Cow::Borrowed(_)));
+        assert!(matches!(highlight_keywords(html, &[]), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_highlight_keywords_case_insensitive() {
+        let html = "Have you tried Robrix yet?";
+        let expected = "Have you tried <span data-mx-bg-color=\"#FFE08A\">Robrix</span> yet?";
+        assert_eq!(highlight_keywords(html, &keywords(&["robrix"])).as_ref(), expected);
+    }
+
+    #[test]
+    fn test_highlight_keywords_whole_words_only() {
+        let html = "cargo build &amp;&amp; cargos";
+        let expected = "<span data-mx-bg-color=\"#FFE08A\">cargo</span> build &amp;&amp; cargos";
+        assert_eq!(highlight_keywords(html, &keywords(&["cargo"])).as_ref(), expected);
+        assert!(matches!(highlight_keywords("subcargo", &keywords(&["cargo"])), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_highlight_keywords_skips_tags_and_entities() {
+        let html = "<b class=\"amp\">amp</b> &amp; <a href=\"https://amp.dev\">amp</a> <code>amp</code>";
+        let expected = "<b class=\"amp\"><span data-mx-bg-color=\"#FFE08A\">amp</span></b> &amp; <a href=\"https://amp.dev\">amp</a> <code>amp</code>";
+        assert_eq!(highlight_keywords(html, &keywords(&["amp"])).as_ref(), expected);
+    }
+
+    #[test]
+    fn test_highlight_keywords_containing_escaped_chars() {
+        let html = "The R&amp;D team&#39;s plan for R&#x26;D";
+        let expected = "The <span data-mx-bg-color=\"#FFE08A\">R&amp;D</span> team&#39;s plan for <span data-mx-bg-color=\"#FFE08A\">R&#x26;D</span>";
+        assert_eq!(highlight_keywords(html, &keywords(&["r&d"])).as_ref(), expected);
+        assert!(matches!(highlight_keywords("R&amp;Direct", &keywords(&["R&D"])), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_highlight_keywords_multiple() {
+        let html = "release v2 ships today, the release notes are up";
+        let expected = "<span data-mx-bg-color=\"#FFE08A\">release</span> v2 ships <span data-mx-bg-color=\"#FFE08A\">today</span>, the <span data-mx-bg-color=\"#FFE08A\">release</span> notes are up";
+        assert_eq!(highlight_keywords(html, &keywords(&["release", "today"])).as_ref(), expected);
+    }
+}
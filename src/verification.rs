@@ -1,4 +1,5 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+use anyhow::anyhow;
 use futures_util::StreamExt;
 use makepad_widgets::{log, ActionDefaultRef, Cx, DefaultNone};
 use matrix_sdk::{
@@ -9,11 +10,13 @@ use matrix_sdk::{
         events::{
             key::verification::{request::ToDeviceKeyVerificationRequestEvent, VerificationMethod}, room::message::{MessageType, OriginalSyncRoomMessageEvent}
         },
-        UserId,
+        OwnedDeviceId, OwnedUserId, UserId,
     }, Client
 };
 use tokio::{runtime::Handle, sync::mpsc::{UnboundedReceiver, UnboundedSender}};
 
+use crate::{settings::key_backup_settings::KeyBackupAction, sliding_sync::{bind_event_handler, key_backup_status, spawn_bound_task}};
+
 #[derive(Clone, Debug, DefaultNone)]
 pub enum VerificationStateAction {
     Update(VerificationState),
@@ -23,7 +26,7 @@ pub enum VerificationStateAction {
 pub fn add_verification_event_handlers_and_sync_client(client: Client) {
     let mut verification_state_subscriber = client.encryption().verification_state();
     log!("Initial verification state is {:?}", verification_state_subscriber.get());
-    Handle::current().spawn(async move {
+    spawn_bound_task(async move {
         while let Some(state) = verification_state_subscriber.next().await {
             log!("Received a verification state update: {state:?}");
             Cx::post_action(VerificationStateAction::Update(state));
@@ -33,7 +36,18 @@ pub fn add_verification_event_handlers_and_sync_client(client: Client) {
         }
     });
 
-    client.add_event_handler(
+    // Keep the key backup settings up to date, e.g., once this session is verified
+    // and thereby gains access to the key backup via secret storage.
+    let mut recovery_state_stream = client.encryption().recovery().state_stream();
+    let recovery_client = client.clone();
+    spawn_bound_task(async move {
+        while let Some(state) = recovery_state_stream.next().await {
+            log!("Received a recovery state update: {state:?}");
+            Cx::post_action(KeyBackupAction::StatusFetched(key_backup_status(&recovery_client).await));
+        }
+    });
+
+    bind_event_handler(client.add_event_handler(
         |ev: ToDeviceKeyVerificationRequestEvent, client: Client| async move {
             if let Some(request) = client
                 .encryption()
@@ -48,9 +62,9 @@ pub fn add_verification_event_handlers_and_sync_client(client: Client) {
                 // );
             }
         },
-    );
+    ));
 
-    client.add_event_handler(
+    bind_event_handler(client.add_event_handler(
         |ev: OriginalSyncRoomMessageEvent, client: Client| async move {
             if let MessageType::VerificationRequest(_) = &ev.content.msgtype {
                 if let Some(request) = client
@@ -67,7 +81,7 @@ pub fn add_verification_event_handlers_and_sync_client(client: Client) {
                 }
             }
         }
-    );
+    ));
 }
 
 
@@ -251,11 +265,144 @@ async fn request_verification_handler(client: Client, request: VerificationReque
 }
 
 
+/// How long to wait for the other side to accept an outgoing verification request
+/// before cancelling it.
+const OUTGOING_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The target of an outgoing verification request.
+#[derive(Clone, Debug)]
+pub enum VerificationTarget {
+    /// Another one of the current user's own devices.
+    Device(OwnedDeviceId),
+    /// The current session, which is verified from any of the user's other sessions.
+    OwnSession,
+    /// Another user, who is verified within the direct message room shared with them.
+    User(OwnedUserId),
+}
+
+/// Sends a verification request to the given target,
+/// and then drives the verification from the initiating side.
+pub async fn request_verification(client: Client, target: VerificationTarget) -> anyhow::Result<()> {
+    let own_user_id = client.user_id().ok_or_else(|| anyhow!("Not logged in"))?.to_owned();
+    let request = match &target {
+        VerificationTarget::Device(device_id) => {
+            client.encryption().get_device(&own_user_id, device_id).await?
+                .ok_or_else(|| anyhow!("Unknown session {device_id}"))?
+                .request_verification().await?
+        }
+        VerificationTarget::OwnSession => {
+            client.encryption().get_user_identity(&own_user_id).await?
+                .ok_or_else(|| anyhow!("Your account has no cross-signing identity yet. Set up key backup and recovery first."))?
+                .request_verification().await?
+        }
+        VerificationTarget::User(user_id) => {
+            if client.get_dm_room(user_id).is_none() {
+                anyhow::bail!("You must have a direct message room with {user_id} in order to verify them.");
+            }
+            client.encryption().get_user_identity(user_id).await?
+                .ok_or_else(|| anyhow!("{user_id} has not set up cross-signing, so they cannot be verified."))?
+                .request_verification().await?
+        }
+    };
+    log!("Sent a verification request to {target:?}");
+    Handle::current().spawn(outgoing_verification_handler(client, request));
+    Ok(())
+}
+
+/// Handles a verification request that we sent, starting SAS verification
+/// once the other side has accepted it.
+async fn outgoing_verification_handler(client: Client, request: VerificationRequest) {
+    let (sender, mut response_receiver) = tokio::sync::mpsc::unbounded_channel::<VerificationUserResponse>();
+    Cx::post_action(
+        VerificationAction::RequestSent(
+            VerificationRequestActionState {
+                request: request.clone(),
+                response_sender: sender,
+            }
+        )
+    );
+
+    let mut stream = request.changes();
+    let timeout = tokio::time::sleep(OUTGOING_REQUEST_TIMEOUT);
+    tokio::pin!(timeout);
+    let mut accepted = false;
+    let mut cancel_requested = false;
+    loop {
+        tokio::select! {
+            // SAS verification has its own timeout once it has started,
+            // so we only need to time out the request while waiting for it to be accepted.
+            _ = &mut timeout, if !accepted => {
+                log!("Outgoing verification request was not accepted in time; cancelling it.");
+                let _ = request.cancel().await;
+                Cx::post_action(VerificationAction::RequestTimedOut);
+                return;
+            }
+            response = response_receiver.recv(), if !cancel_requested => match response {
+                // The user can't accept their own request, so any response means to cancel it.
+                Some(_) | None => {
+                    cancel_requested = true;
+                    if let Err(e) = request.cancel().await {
+                        Cx::post_action(VerificationAction::RequestCancelError(Arc::new(e)));
+                        return;
+                    }
+                    // The cancellation will be reported in the stream loop below.
+                }
+            },
+            state = stream.next() => match state {
+                Some(VerificationRequestState::Ready { .. }) => {
+                    accepted = true;
+                    Cx::post_action(VerificationAction::RequestAccepted);
+                    match request.start_sas().await {
+                        Ok(Some(sas)) => {
+                            log!("Started SAS verification as the initiator.");
+                            Handle::current().spawn(sas_verification_handler(client, sas, response_receiver));
+                            return;
+                        }
+                        Ok(None) => { } // The other side started SAS first; wait for the transition.
+                        Err(e) => {
+                            Cx::post_action(VerificationAction::RequestAcceptError(Arc::new(e)));
+                            return;
+                        }
+                    }
+                }
+                Some(VerificationRequestState::Transitioned { verification }) => match verification {
+                    Verification::SasV1(sas) => {
+                        log!("Outgoing verification request transitioned to SAS V1.");
+                        Handle::current().spawn(sas_verification_handler(client, sas, response_receiver));
+                        return;
+                    }
+                    unsupported => {
+                        Cx::post_action(VerificationAction::RequestTransitionedToUnsupportedMethod(unsupported));
+                        return;
+                    }
+                }
+                Some(VerificationRequestState::Cancelled(info)) => {
+                    log!("Outgoing verification request was cancelled, reason: {}", info.reason());
+                    Cx::post_action(VerificationAction::RequestCancelled(info));
+                    return;
+                }
+                Some(VerificationRequestState::Done) => {
+                    Cx::post_action(VerificationAction::RequestCompleted);
+                    return;
+                }
+                Some(VerificationRequestState::Created { .. } | VerificationRequestState::Requested { .. }) => { }
+                None => return,
+            },
+        }
+    }
+}
+
+
 /// Actions related to verification that should be handled by the top-level app context.
 #[derive(Clone, Debug, DefaultNone)]
 pub enum VerificationAction {
     /// Informs the main UI thread that a verification request has been received.
     RequestReceived(VerificationRequestActionState),
+    /// Informs the main UI thread that we sent a verification request to another device or user.
+    RequestSent(VerificationRequestActionState),
+    /// Informs the main UI thread that a verification request we sent was not accepted in time,
+    /// so it was cancelled.
+    RequestTimedOut,
     /// Informs the main UI thread that a verification request was cancelled successfully.
     RequestCancelled(CancelInfo),
     /// Informs the main UI thread that a verification request was accepted successfully.
@@ -190,11 +190,29 @@ impl WidgetMatchEvent for VerificationModal {
                         self.is_final = true;
                     }
 
+                    VerificationAction::RequestTimedOut => {
+                        self.label(id!(prompt)).set_text(
+                            cx,
+                            "The verification request was not accepted in time, so it was cancelled.\n\n\
+                            Please try again."
+                        );
+                        accept_button.set_enabled(cx, true);
+                        accept_button.set_text(cx, "Ok");
+                        cancel_button.set_visible(cx, false);
+                        self.is_final = true;
+                    }
+
                     VerificationAction::RequestAccepted => {
+                        let we_started = self.state.as_ref().is_some_and(|s| s.request.we_started());
                         self.label(id!(prompt)).set_text(
                             cx,
-                            "You successfully accepted the verification request.\n\n\
-                            Waiting for the other device to agree on verification methods..."
+                            if we_started {
+                                "The other device accepted your verification request.\n\n\
+                                Starting verification..."
+                            } else {
+                                "You successfully accepted the verification request.\n\n\
+                                Waiting for the other device to agree on verification methods..."
+                            }
                         );
                         accept_button.set_enabled(cx, false);
                         accept_button.set_text(cx, "Waiting...");
@@ -344,6 +362,30 @@ impl VerificationModal {
     ) {
         log!("Initializing verification modal with state: {:?}", state);
         let request = &state.request;
+        let accept_button = self.button(id!(accept_button));
+        let cancel_button = self.button(id!(cancel_button));
+        cancel_button.set_text(cx, "Cancel");
+        cancel_button.set_enabled(cx, true);
+        cancel_button.set_visible(cx, true);
+
+        // For a request that we sent, there is nothing to accept; we wait for the other side.
+        if request.we_started() {
+            let prompt_text = if request.is_self_verification() {
+                Cow::from("Sent a verification request to your other session.\n\nPlease accept it on that device.")
+            } else {
+                format!("Sent a verification request to {}.\n\nWaiting for them to accept it...",
+                    request.other_user_id(),
+                ).into()
+            };
+            self.label(id!(prompt)).set_text(cx, &prompt_text);
+            accept_button.set_text(cx, "Waiting...");
+            accept_button.set_enabled(cx, false);
+            accept_button.set_visible(cx, true);
+            self.state = Some(state);
+            self.is_final = false;
+            return;
+        }
+
         let prompt_text = if request.is_self_verification() {
             Cow::from("Do you wish to verify your own device?")
         } else {
@@ -360,14 +402,9 @@ impl VerificationModal {
         };
         self.label(id!(prompt)).set_text(cx, &prompt_text);
 
-        let accept_button = self.button(id!(accept_button));
-        let cancel_button = self.button(id!(cancel_button));
         accept_button.set_text(cx, "Yes");
         accept_button.set_enabled(cx, true);
         accept_button.set_visible(cx, true);
-        cancel_button.set_text(cx, "Cancel");
-        cancel_button.set_enabled(cx, true);
-        cancel_button.set_visible(cx, true);
 
         self.state = Some(state);
         self.is_final = false;